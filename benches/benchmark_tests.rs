@@ -1,7 +1,7 @@
 // 在 `benches/benchmark_tests.rs`
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, Criterion};
 use nexusdb::data_processing::workerpool::WorkerPool;
-use nexusdb::storage::nffile::{flush_nffile, NFFile};
+use nexusdb::storage::nffile::NFFile;
 use nexusdb::utils::abs_path::get_absolute_path_for_data_file;
 use std::sync::{Arc, Mutex};
 
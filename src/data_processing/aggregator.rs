@@ -0,0 +1,459 @@
+//! Aggregate functions.
+//!
+//! https://www.sqlite.org/lang_aggfunc.html
+
+use std::cmp::Ordering;
+use std::fmt::Display;
+
+use crate::interpreter::utils::parse_float;
+use crate::interpreter::utils::parse_integer;
+use crate::interpreter::utils::ParseIntegerResult;
+use crate::interpreter::value::Collation;
+use crate::interpreter::value::ConstantValue;
+use crate::interpreter::value::Value;
+use crate::interpreter::value::ValueCmp;
+
+#[derive(Debug)]
+pub enum Error {
+    IntegerOverflow,
+}
+
+impl std::error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IntegerOverflow => {
+                write!(f, "integer overflow")
+            }
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateFunction {
+    /// count(*)
+    CountAll,
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+    Total,
+}
+
+impl AggregateFunction {
+    /// Look up the aggregate function which takes 1 argument by the
+    /// case-insensitive name.
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        match name.to_ascii_lowercase().as_slice() {
+            b"count" => Some(Self::Count),
+            b"sum" => Some(Self::Sum),
+            b"avg" => Some(Self::Avg),
+            b"min" => Some(Self::Min),
+            b"max" => Some(Self::Max),
+            b"total" => Some(Self::Total),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::CountAll | Self::Count => "count",
+            Self::Sum => "sum",
+            Self::Avg => "avg",
+            Self::Min => "min",
+            Self::Max => "max",
+            Self::Total => "total",
+        }
+    }
+}
+
+/// The state of sum(), avg() and total().
+///
+/// This follows SumCtx of func.c in SQLite. Integers are summed exactly
+/// until a non-integer value comes or the sum overflows. After that, values
+/// are summed as real numbers with the Kahan-Babuska-Neumaier algorithm.
+#[derive(Debug, Default)]
+struct SumState {
+    /// The number of non-NULL values.
+    count: i64,
+    integer_sum: i64,
+    real_sum: f64,
+    real_err: f64,
+    /// Whether the sum is a real number.
+    approx: bool,
+    /// Whether the integer sum overflowed.
+    overflow: bool,
+}
+
+/// Integers whose magnitude is at least this can not be represented by f64
+/// exactly.
+const LARGE_INTEGER: i64 = 4503599627370496;
+
+impl SumState {
+    fn step(&mut self, value: Value) {
+        self.count += 1;
+        match numeric_value(value) {
+            Value::Integer(i) => {
+                if self.approx {
+                    self.step_real_integer(i);
+                } else if let Some(sum) = self.integer_sum.checked_add(i) {
+                    self.integer_sum = sum;
+                } else {
+                    self.overflow = true;
+                    self.init_real();
+                    self.step_real_integer(i);
+                }
+            }
+            value => {
+                if !self.approx {
+                    self.init_real();
+                } else {
+                    self.overflow = false;
+                }
+                self.step_real(real_value(&value));
+            }
+        }
+    }
+
+    fn init_real(&mut self) {
+        self.approx = true;
+        let i = self.integer_sum;
+        if i <= -LARGE_INTEGER || i >= LARGE_INTEGER {
+            let small = i % 16384;
+            self.real_sum = (i - small) as f64;
+            self.real_err = small as f64;
+        } else {
+            self.real_sum = i as f64;
+            self.real_err = 0.0;
+        }
+    }
+
+    fn step_real(&mut self, r: f64) {
+        let s = self.real_sum;
+        let t = s + r;
+        if s.abs() > r.abs() {
+            self.real_err += (s - t) + r;
+        } else {
+            self.real_err += (r - t) + s;
+        }
+        self.real_sum = t;
+    }
+
+    fn step_real_integer(&mut self, i: i64) {
+        if i <= -LARGE_INTEGER || i >= LARGE_INTEGER {
+            let small = i % 16384;
+            self.step_real((i - small) as f64);
+            self.step_real(small as f64);
+        } else {
+            self.step_real(i as f64);
+        }
+    }
+
+    fn real_result(&self) -> f64 {
+        if self.approx {
+            if self.real_err.is_finite() {
+                self.real_sum + self.real_err
+            } else {
+                self.real_sum
+            }
+        } else {
+            self.integer_sum as f64
+        }
+    }
+}
+
+/// Convert a text value to a number if the whole text is a well-formed number.
+///
+/// Unlike [Value::apply_numeric_affinity()], a real number is never converted
+/// to an integer. This follows sqlite3_value_numeric_type().
+fn numeric_value(value: Value) -> Value {
+    match value {
+        Value::Text(buf) => match parse_integer(&buf) {
+            (true, ParseIntegerResult::Integer(i)) => Value::Integer(i),
+            _ => {
+                let (valid, _, d) = parse_float(&buf);
+                if valid {
+                    Value::Real(d)
+                } else {
+                    Value::Text(buf)
+                }
+            }
+        },
+        value => value,
+    }
+}
+
+/// sqlite3_value_double() in SQLite.
+fn real_value(value: &Value) -> f64 {
+    match value {
+        Value::Integer(i) => *i as f64,
+        Value::Real(d) => *d,
+        Value::Text(buf) | Value::Blob(buf) => parse_float(buf).2,
+    }
+}
+
+#[derive(Debug)]
+enum State {
+    Count(i64),
+    Sum(SumState),
+    MinMax(Option<ConstantValue>),
+}
+
+/// Accumulates values of a group for an aggregate function.
+#[derive(Debug)]
+pub struct Aggregator {
+    function: AggregateFunction,
+    /// The collation to compare values for min() and max().
+    collation: Collation,
+    state: State,
+}
+
+impl Aggregator {
+    pub fn new(function: AggregateFunction, collation: Collation) -> Self {
+        let state = match function {
+            AggregateFunction::CountAll | AggregateFunction::Count => State::Count(0),
+            AggregateFunction::Sum | AggregateFunction::Avg | AggregateFunction::Total => {
+                State::Sum(SumState::default())
+            }
+            AggregateFunction::Min | AggregateFunction::Max => State::MinMax(None),
+        };
+        Self {
+            function,
+            collation,
+            state,
+        }
+    }
+
+    /// Accumulate the argument value of a row. The value is ignored for
+    /// count(*).
+    ///
+    /// Returns false if the other columns of the row must not be loaded as the
+    /// result of bare columns. min() and max() choose the row which has the
+    /// minimum or maximum value.
+    ///
+    /// https://www.sqlite.org/lang_select.html#bare_columns_in_an_aggregate_query
+    pub fn step(&mut self, value: Option<Value>) -> bool {
+        match &mut self.state {
+            State::Count(count) => {
+                if self.function == AggregateFunction::CountAll || value.is_some() {
+                    *count += 1;
+                }
+                true
+            }
+            State::Sum(sum) => {
+                if let Some(value) = value {
+                    sum.step(value);
+                }
+                true
+            }
+            State::MinMax(best) => {
+                let Some(value) = value else {
+                    // Bare columns are loaded from the NULL row only until a
+                    // non-NULL value comes.
+                    return best.is_none();
+                };
+                let replace = match best {
+                    None => true,
+                    Some(best) => {
+                        let cmp = ValueCmp::new(&value, &self.collation).compare(&best.as_value());
+                        if self.function == AggregateFunction::Min {
+                            cmp == Ordering::Less
+                        } else {
+                            cmp == Ordering::Greater
+                        }
+                    }
+                };
+                if replace {
+                    *best = Some(ConstantValue::copy_from(value));
+                }
+                replace
+            }
+        }
+    }
+
+    /// The result of the aggregate function.
+    pub fn finalize(&self) -> Result<Option<ConstantValue>> {
+        match &self.state {
+            State::Count(count) => Ok(Some(ConstantValue::Integer(*count))),
+            State::Sum(sum) => match self.function {
+                AggregateFunction::Sum => {
+                    if sum.count == 0 {
+                        Ok(None)
+                    } else if !sum.approx {
+                        Ok(Some(ConstantValue::Integer(sum.integer_sum)))
+                    } else if sum.overflow {
+                        Err(Error::IntegerOverflow)
+                    } else {
+                        Ok(Some(ConstantValue::Real(sum.real_result())))
+                    }
+                }
+                AggregateFunction::Avg => {
+                    if sum.count == 0 {
+                        Ok(None)
+                    } else {
+                        Ok(Some(ConstantValue::Real(
+                            sum.real_result() / sum.count as f64,
+                        )))
+                    }
+                }
+                AggregateFunction::Total => Ok(Some(ConstantValue::Real(sum.real_result()))),
+                _ => unreachable!("sum state for {:?}", self.function),
+            },
+            State::MinMax(best) => Ok(best.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aggregate(
+        function: AggregateFunction,
+        values: &[Option<Value>],
+    ) -> Result<Option<ConstantValue>> {
+        let mut aggregator = Aggregator::new(function, Collation::Binary);
+        for value in values {
+            aggregator.step(value.clone());
+        }
+        aggregator.finalize()
+    }
+
+    #[test]
+    fn test_count() {
+        let values = [
+            Some(Value::Integer(1)),
+            None,
+            Some(Value::Text(b"abc".as_slice().into())),
+        ];
+        assert!(matches!(
+            aggregate(AggregateFunction::CountAll, &values).unwrap(),
+            Some(ConstantValue::Integer(3))
+        ));
+        assert!(matches!(
+            aggregate(AggregateFunction::Count, &values).unwrap(),
+            Some(ConstantValue::Integer(2))
+        ));
+        assert!(matches!(
+            aggregate(AggregateFunction::Count, &[]).unwrap(),
+            Some(ConstantValue::Integer(0))
+        ));
+    }
+
+    #[test]
+    fn test_sum() {
+        assert!(aggregate(AggregateFunction::Sum, &[]).unwrap().is_none());
+        assert!(aggregate(AggregateFunction::Sum, &[None, None])
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            aggregate(
+                AggregateFunction::Sum,
+                &[
+                    Some(Value::Integer(1)),
+                    None,
+                    Some(Value::Text(b"2".as_slice().into()))
+                ]
+            )
+            .unwrap(),
+            Some(ConstantValue::Integer(3))
+        ));
+        assert!(matches!(
+            aggregate(
+                AggregateFunction::Sum,
+                &[
+                    Some(Value::Integer(1)),
+                    Some(Value::Real(0.5)),
+                    Some(Value::Text(b"abc".as_slice().into())),
+                    Some(Value::Text(b"2.0".as_slice().into())),
+                ]
+            )
+            .unwrap(),
+            Some(ConstantValue::Real(d)) if d == 3.5
+        ));
+        assert!(matches!(
+            aggregate(
+                AggregateFunction::Sum,
+                &[Some(Value::Integer(i64::MAX)), Some(Value::Integer(1))]
+            ),
+            Err(Error::IntegerOverflow)
+        ));
+        // A real value after overflow clears the overflow.
+        assert!(matches!(
+            aggregate(
+                AggregateFunction::Sum,
+                &[
+                    Some(Value::Integer(i64::MAX)),
+                    Some(Value::Integer(1)),
+                    Some(Value::Real(1.0))
+                ]
+            )
+            .unwrap(),
+            Some(ConstantValue::Real(_))
+        ));
+    }
+
+    #[test]
+    fn test_avg_total() {
+        assert!(aggregate(AggregateFunction::Avg, &[None])
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            aggregate(
+                AggregateFunction::Avg,
+                &[Some(Value::Integer(1)), None, Some(Value::Integer(2))]
+            )
+            .unwrap(),
+            Some(ConstantValue::Real(d)) if d == 1.5
+        ));
+        assert!(matches!(
+            aggregate(AggregateFunction::Total, &[None]).unwrap(),
+            Some(ConstantValue::Real(d)) if d == 0.0
+        ));
+        assert!(matches!(
+            aggregate(
+                AggregateFunction::Total,
+                &[Some(Value::Integer(i64::MAX)), Some(Value::Integer(i64::MAX))]
+            )
+            .unwrap(),
+            Some(ConstantValue::Real(d)) if d == 2.0 * i64::MAX as f64
+        ));
+    }
+
+    #[test]
+    fn test_min_max() {
+        let values = [
+            None,
+            Some(Value::Text(b"b".as_slice().into())),
+            Some(Value::Integer(10)),
+            Some(Value::Real(2.5)),
+            Some(Value::Blob(b"a".as_slice().into())),
+        ];
+        assert!(matches!(
+            aggregate(AggregateFunction::Min, &values).unwrap(),
+            Some(ConstantValue::Real(d)) if d == 2.5
+        ));
+        assert!(matches!(
+            aggregate(AggregateFunction::Max, &values).unwrap(),
+            Some(ConstantValue::Blob(b)) if b == b"a"
+        ));
+        assert!(aggregate(AggregateFunction::Max, &[None])
+            .unwrap()
+            .is_none());
+
+        let mut aggregator = Aggregator::new(AggregateFunction::Max, Collation::NoCase);
+        assert!(aggregator.step(None));
+        assert!(aggregator.step(Some(Value::Text(b"a".as_slice().into()))));
+        assert!(!aggregator.step(None));
+        assert!(!aggregator.step(Some(Value::Text(b"A".as_slice().into()))));
+        assert!(aggregator.step(Some(Value::Text(b"B".as_slice().into()))));
+        assert!(matches!(
+            aggregator.finalize().unwrap(),
+            Some(ConstantValue::Text(t)) if t == b"B"
+        ));
+    }
+}
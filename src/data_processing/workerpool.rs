@@ -8,6 +8,7 @@ type Task = Box<dyn FnOnce() + Send + 'static>;
 
 // Worker 结构体
 pub struct Worker {
+    #[allow(dead_code)]
     id: usize,
     thread: Option<thread::JoinHandle<()>>,
 }
@@ -89,9 +90,6 @@ impl WorkerPool {
         // 设置运行状态为 false，通知所有线程关闭
         let mut running = self.running.lock().unwrap();
         *running = false;
-
-        // 关闭发送端，这样接收端会收到一个错误（`Disconnected`），从而退出循环
-        drop(&self.sender);
     }
 
     pub fn join(&mut self) {
@@ -18,6 +18,12 @@ pub struct IndexManager {
     pub indices: BTreeMap<String, IndexEntry>,
 }
 
+impl Default for IndexManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl IndexManager {
     pub fn new() -> Self {
         IndexManager {
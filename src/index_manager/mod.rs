@@ -1 +1,2 @@
-pub mod index_manager;
\ No newline at end of file
+#[allow(clippy::module_inception)]
+pub mod index_manager;
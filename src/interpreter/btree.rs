@@ -123,10 +123,20 @@ impl BtreeContext {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct BtreePageType(u8);
 
 impl BtreePageType {
+    /// The page type of an empty table btree root page.
+    pub fn table_leaf() -> Self {
+        Self(TABLE_FLAG | LEAF_FLAG)
+    }
+
+    /// The page type of an empty index btree root page.
+    pub fn index_leaf() -> Self {
+        Self(INDEX_FLAG | LEAF_FLAG)
+    }
+
     #[inline]
     pub fn leaf_type(&self) -> Self {
         Self(self.0 | LEAF_FLAG)
@@ -325,6 +335,10 @@ impl<'a> BtreePageHeaderMut<'a> {
         self.0[7] = 0;
     }
 
+    pub fn set_fragmented_free_bytes(&mut self, size: u8) {
+        self.0[7] = size;
+    }
+
     pub fn set_right_page_id(&mut self, page_id: PageId) {
         self.0[BTREE_RIGHT_PAGE_ID_OFFSET..BTREE_RIGHT_PAGE_ID_OFFSET + 4]
             .copy_from_slice(&page_id.get().to_be_bytes());
@@ -771,6 +785,84 @@ pub fn allocate_from_freeblocks(
     None
 }
 
+/// Return the space to the page.
+///
+/// This is the same as freeSpace() in btree.c of SQLite. The freeblock list is
+/// kept sorted by offset. The space is merged with adjacent freeblocks, and a
+/// gap less than 4 bytes between them is taken back from the fragmented free
+/// bytes. If the space is at the head of the cell content area, it is returned
+/// to the unallocated space instead of the freeblock list.
+///
+/// The size must be 4 or more.
+///
+/// Returns an error if the freeblock list does not strictly ascend, overlaps
+/// the space or runs out of the page.
+pub fn free_space(
+    page: &MemPage,
+    buffer: &mut PageBufferMut,
+    offset: usize,
+    size: u16,
+) -> ParseResult<()> {
+    assert!(size >= 4);
+    let first_freeblock_ptr = page.header_offset + BTREE_FIRST_FREEBLOCK_OFFSET.start;
+    let mut start = offset;
+    let mut end = offset + size as usize;
+    check_corrupt!(end <= buffer.len(), "freed space out of page");
+    let get_u16 = |buffer: &PageBufferMut, offset: usize| {
+        u16::from_be_bytes(buffer[offset..offset + 2].try_into().unwrap()) as usize
+    };
+    let mut ptr = first_freeblock_ptr;
+    let mut next_freeblock = get_u16(buffer, ptr);
+    while next_freeblock != 0 && next_freeblock < start {
+        check_corrupt!(next_freeblock > ptr, "freeblock list not ascending");
+        ptr = next_freeblock;
+        next_freeblock = get_u16(buffer, ptr);
+    }
+    check_corrupt!(
+        next_freeblock == 0 || next_freeblock + 4 <= buffer.len(),
+        "freeblock out of page"
+    );
+
+    let mut n_fragments = 0;
+    // Merge the next freeblock.
+    if next_freeblock != 0 && end + 3 >= next_freeblock {
+        check_corrupt!(end <= next_freeblock, "freeblock overlaps freed space");
+        n_fragments += next_freeblock - end;
+        end = next_freeblock + get_u16(buffer, next_freeblock + 2);
+        check_corrupt!(end <= buffer.len(), "freeblock out of page");
+        next_freeblock = get_u16(buffer, next_freeblock);
+    }
+    // Merge the previous freeblock.
+    if ptr != first_freeblock_ptr {
+        let previous_end = ptr + get_u16(buffer, ptr + 2);
+        if previous_end + 3 >= start {
+            check_corrupt!(previous_end <= start, "freeblock overlaps freed space");
+            n_fragments += start - previous_end;
+            start = ptr;
+        }
+    }
+
+    let page_header = BtreePageHeader::from_page_mut(page, buffer);
+    let fragmented_free_bytes = page_header.fragmented_free_bytes();
+    let cell_content_area_offset = page_header.cell_content_area_offset().get() as usize;
+    check_corrupt!(
+        n_fragments <= fragmented_free_bytes as usize,
+        "fragmented free bytes too small"
+    );
+    let mut page_header = BtreePageHeaderMut::from_page(page, buffer);
+    page_header.set_fragmented_free_bytes(fragmented_free_bytes - n_fragments as u8);
+    if start <= cell_content_area_offset {
+        // The space is at the head of the cell content area.
+        page_header.set_first_freeblock_offset(next_freeblock as u16);
+        page_header.set_cell_content_area_offset(non_zero_to_u16(end as u32));
+    } else {
+        set_u16(buffer, ptr, start as u16);
+        set_u16(buffer, start, next_freeblock as u16);
+        set_u16(buffer, start + 2, (end - start) as u16);
+    }
+    Ok(())
+}
+
 /// Allocate a space for idx-th cell from the unallocated space.
 ///
 /// This also update cell pointer. Even if a cell of idx exists, it is
@@ -1183,6 +1275,125 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_free_space() {
+        let pager = create_empty_pager(&[], 4096 * 2, 2 * 4096);
+
+        let (page_id, _) = pager.allocate_page().unwrap();
+        assert_eq!(page_id, PAGE_ID_1);
+        let (page_id, _) = pager.allocate_page().unwrap();
+        assert_eq!(page_id.get(), 2);
+
+        for page_id in [PAGE_ID_1, PageId::new(2).unwrap()] {
+            let page = pager.get_page(page_id).unwrap();
+            let mut buffer = pager.make_page_mut(&page).unwrap();
+
+            let mut header = BtreePageHeaderMut::from_page(&page, &mut buffer);
+            header.set_first_freeblock_offset(0);
+            header.set_cell_content_area_offset(1000);
+            header.set_fragmented_free_bytes(10);
+
+            let freeblocks = |buffer: &PageBufferMut| {
+                FreeblockIterator::new(
+                    BtreePageHeader::from_page_mut(&page, buffer).first_freeblock_offset(),
+                    buffer,
+                )
+                .collect::<Vec<_>>()
+            };
+
+            // Freeblocks are sorted by the offset.
+            free_space(&page, &mut buffer, 1100, 10).unwrap();
+            free_space(&page, &mut buffer, 1050, 10).unwrap();
+            free_space(&page, &mut buffer, 1200, 10).unwrap();
+            assert_eq!(
+                freeblocks(&buffer),
+                vec![(1050, 10), (1100, 10), (1200, 10)]
+            );
+
+            // Merge with the previous freeblock.
+            free_space(&page, &mut buffer, 1060, 10).unwrap();
+            assert_eq!(
+                freeblocks(&buffer),
+                vec![(1050, 20), (1100, 10), (1200, 10)]
+            );
+
+            // Merge with the next freeblock including fragments.
+            free_space(&page, &mut buffer, 1180, 17).unwrap();
+            assert_eq!(
+                freeblocks(&buffer),
+                vec![(1050, 20), (1100, 10), (1180, 30)]
+            );
+            let page_header = BtreePageHeader::from_page_mut(&page, &buffer);
+            assert_eq!(page_header.fragmented_free_bytes(), 7);
+
+            // Merge with both freeblocks.
+            free_space(&page, &mut buffer, 1070, 30).unwrap();
+            assert_eq!(freeblocks(&buffer), vec![(1050, 60), (1180, 30)]);
+
+            // Return to the unallocated space.
+            free_space(&page, &mut buffer, 1000, 48).unwrap();
+            assert_eq!(freeblocks(&buffer), vec![(1180, 30)]);
+            let page_header = BtreePageHeader::from_page_mut(&page, &buffer);
+            assert_eq!(page_header.cell_content_area_offset().get(), 1110);
+            assert_eq!(page_header.fragmented_free_bytes(), 5);
+        }
+    }
+
+    #[test]
+    fn test_free_space_corrupt() {
+        let pager = create_empty_pager(&[], 4096 * 2, 2 * 4096);
+        let (page_id, _) = pager.allocate_page().unwrap();
+        let page = pager.get_page(page_id).unwrap();
+        let mut buffer = pager.make_page_mut(&page).unwrap();
+        let reset = |buffer: &mut PageBufferMut| {
+            let mut header = BtreePageHeaderMut::from_page(&page, buffer);
+            header.set_first_freeblock_offset(1100);
+            header.set_cell_content_area_offset(1000);
+            header.set_fragmented_free_bytes(0);
+            set_u16(buffer, 1100, 1200);
+            set_u16(buffer, 1102, 10);
+            set_u16(buffer, 1200, 0);
+            set_u16(buffer, 1202, 10);
+        };
+
+        // The freed space is out of the page.
+        reset(&mut buffer);
+        let offset = buffer.len() - 5;
+        assert!(free_space(&page, &mut buffer, offset, 10).is_err());
+
+        // The freeblock list goes backward.
+        reset(&mut buffer);
+        set_u16(&mut buffer, 1200, 1050);
+        assert!(free_space(&page, &mut buffer, 1300, 10).is_err());
+
+        // The next freeblock is out of the page.
+        reset(&mut buffer);
+        let offset = buffer.len() as u16 - 2;
+        set_u16(&mut buffer, 1200, offset);
+        assert!(free_space(&page, &mut buffer, 1300, 10).is_err());
+
+        // The next freeblock overlaps the freed space.
+        reset(&mut buffer);
+        assert!(free_space(&page, &mut buffer, 1195, 10).is_err());
+
+        // The previous freeblock overlaps the freed space.
+        reset(&mut buffer);
+        assert!(free_space(&page, &mut buffer, 1105, 10).is_err());
+
+        // The size of the next freeblock runs out of the page.
+        reset(&mut buffer);
+        let size = buffer.len() as u16 - 1199;
+        set_u16(&mut buffer, 1202, size);
+        assert!(free_space(&page, &mut buffer, 1190, 10).is_err());
+
+        // Fragments are not counted in the fragmented free bytes.
+        reset(&mut buffer);
+        assert!(free_space(&page, &mut buffer, 1112, 10).is_err());
+
+        reset(&mut buffer);
+        assert!(free_space(&page, &mut buffer, 1150, 10).is_ok());
+    }
+
     #[test]
     fn test_allocate_from_unallocated_space() {
         let pager = create_empty_pager(&[], 2 * 4096, 2 * 4096);
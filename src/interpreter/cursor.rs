@@ -19,6 +19,7 @@ use crate::interpreter::btree::allocate_from_freeblocks;
 use crate::interpreter::btree::allocate_from_unallocated_space;
 use crate::interpreter::btree::cell_pointer_offset;
 use crate::interpreter::btree::compute_free_size;
+use crate::interpreter::btree::free_space;
 use crate::interpreter::btree::get_cell_offset;
 use crate::interpreter::btree::non_zero_to_u16;
 use crate::interpreter::btree::parse_btree_interior_cell_page_id;
@@ -33,17 +34,14 @@ use crate::interpreter::btree::IndexCellKeyParser;
 use crate::interpreter::btree::OverflowPage;
 use crate::interpreter::btree::PayloadInfo;
 use crate::interpreter::btree::TableCellKeyParser;
-use crate::interpreter::btree::BTREE_FIRST_FREEBLOCK_OFFSET;
 use crate::interpreter::btree::BTREE_OVERFLOW_PAGE_ID_BYTES;
 use crate::interpreter::btree::BTREE_PAGE_CELL_POINTER_SIZE;
-use crate::interpreter::btree::BTREE_RIGHT_PAGE_ID_OFFSET;
 use crate::interpreter::pager::swap_page_buffer;
 use crate::interpreter::pager::Error as PagerError;
 use crate::interpreter::pager::MemPage;
 use crate::interpreter::pager::PageBuffer;
 use crate::interpreter::pager::PageBufferMut;
 use crate::interpreter::pager::PageId;
-use crate::interpreter::pager::PagePayload;
 use crate::interpreter::pager::Pager;
 use crate::interpreter::payload::CopiablePayload;
 use crate::interpreter::payload::LocalPayload;
@@ -109,6 +107,39 @@ impl Display for Error {
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Allocate a new page and initialize it as the root page of an empty btree.
+///
+/// Returns the page id of the new root page.
+pub fn allocate_btree(pager: &Pager, btree_ctx: &BtreeContext, is_table: bool) -> Result<PageId> {
+    let (page_id, page) = pager.allocate_page().map_err(Error::AllocatePage)?;
+    // make_page_mut() must succeed for allocated pages.
+    let mut buffer = pager.make_page_mut(&page).unwrap();
+    let mut page_header = BtreePageHeaderMut::from_page(&page, &mut buffer);
+    page_header.set_page_type(if is_table {
+        BtreePageType::table_leaf()
+    } else {
+        BtreePageType::index_leaf()
+    });
+    page_header.set_first_freeblock_offset(0);
+    page_header.set_n_cells(0);
+    page_header.set_cell_content_area_offset(non_zero_to_u16(btree_ctx.usable_size));
+    page_header.clear_fragmented_free_bytes();
+    Ok(page_id)
+}
+
+/// Delete all the pages of a btree including the root page and overflow pages.
+///
+/// The deleted pages are added to the freelist.
+pub fn drop_btree(pager: &Pager, btree_ctx: &BtreeContext, root_page_id: PageId) -> Result<()> {
+    let mut cursor = BtreeCursor::new(root_page_id, pager, btree_ctx)?;
+    cursor.clear()?;
+    drop(cursor);
+    pager.delete_page(root_page_id).map_err(|e| Error::Pager {
+        page_id: root_page_id,
+        e,
+    })
+}
+
 pub struct BtreePayload<'a> {
     pager: &'a Pager,
     bctx: &'a BtreeContext,
@@ -228,25 +259,6 @@ impl<P: CopiablePayload> CellPayload for LeafCellPayload<'_, P> {
     }
 }
 
-struct PageCellPayload<'a> {
-    header: &'a [u8],
-    payload: PagePayload,
-}
-
-impl CellPayload for PageCellPayload<'_> {
-    fn size(&self) -> u16 {
-        self.header.len() as u16 + CopiablePayload::size(&self.payload).get() as u16
-    }
-
-    fn copy_all(&self, buf: &mut [u8]) {
-        buf[..self.header.len()].copy_from_slice(self.header);
-        assert_eq!(
-            self.payload.copy(0, &mut buf[self.header.len()..]),
-            CopiablePayload::size(&self.payload).get() as usize
-        );
-    }
-}
-
 struct CursorPage {
     /// TODO: This is only for error debug message.
     page_id: PageId,
@@ -273,6 +285,102 @@ impl CursorPage {
     }
 }
 
+/// The cells of a btree page loaded into memory for balancing.
+struct PageCells {
+    page_type: BtreePageType,
+    cells: Vec<Vec<u8>>,
+    right_page_id: Option<PageId>,
+}
+
+/// The size of a cell and its cell pointer in a page.
+fn cell_size(cell: &[u8]) -> u32 {
+    // Cells are allocated at least 4 bytes. See BtreeCursor::insert_cell().
+    cell.len().max(4) as u32 + BTREE_PAGE_CELL_POINTER_SIZE as u32
+}
+
+fn cells_size(cells: &[Vec<u8>]) -> u32 {
+    cells.iter().map(|cell| cell_size(cell)).sum()
+}
+
+/// Whether the page is less than one third full as SQLite balances pages.
+fn is_underfull(btree_ctx: &BtreeContext, free_size: u32) -> bool {
+    free_size * 3 > btree_ctx.usable_size * 2
+}
+
+fn parse_child_page_id(page_id: PageId, cell: &[u8]) -> Result<PageId> {
+    cell.get(..4)
+        .and_then(|buf| PageId::new(u32::from_be_bytes(buf.try_into().unwrap())))
+        .ok_or(Error::FileCorrupt {
+            page_id,
+            e: FileCorrupt::new("child page id"),
+        })
+}
+
+/// The sibling pages before and after [BtreeCursor::balance_siblings()].
+///
+/// The cells of the old siblings (and the dividers between them) are
+/// concatenated and split into the ranges of the new siblings.
+struct SiblingLayout {
+    /// The index of the first sibling in the parent page.
+    first: usize,
+    /// The number of cells in each old sibling.
+    n_old_cells: Vec<usize>,
+    /// The range of the concatenated cells in each new sibling. The cell at
+    /// the end of a range is the divider unless the pages are table leaf pages.
+    bounds: Vec<(usize, usize)>,
+    has_dividers: bool,
+}
+
+impl SiblingLayout {
+    /// Update the path going through the parent page at the depth.
+    fn remap(&self, path: &mut Vec<u16>, depth: usize) {
+        let Some(&idx_cell) = path.get(depth) else {
+            return;
+        };
+        let idx_cell = idx_cell as usize;
+        let n_old = self.n_old_cells.len();
+        let n_new = self.bounds.len();
+        // The path points to a divider cell in the parent page if it ends at
+        // the depth. Otherwise it goes through a child page.
+        let in_parent = path.len() == depth + 1;
+        let n_moved = if in_parent { n_old - 1 } else { n_old };
+        if idx_cell < self.first {
+            return;
+        } else if idx_cell >= self.first + n_moved {
+            path[depth] = (idx_cell + n_new - n_old) as u16;
+            return;
+        }
+
+        let i_sibling = idx_cell - self.first;
+        let offset = self.n_old_cells[..i_sibling]
+            .iter()
+            .map(|n| n + self.has_dividers as usize)
+            .sum::<usize>();
+        let (position, has_child) = if in_parent {
+            (offset + self.n_old_cells[i_sibling], false)
+        } else {
+            (offset + path[depth + 1] as usize, path.len() > depth + 2)
+        };
+        for (k, &(start, end)) in self.bounds.iter().enumerate() {
+            let is_last = k + 1 == n_new;
+            if position > end || (position == end && !is_last && !self.has_dividers) {
+                continue;
+            }
+            path[depth] = (self.first + k) as u16;
+            if position == end && !is_last && !has_child {
+                // The cell is moved to the parent page as the divider.
+                path.truncate(depth + 1);
+            } else if in_parent {
+                path.push((position - start) as u16);
+            } else {
+                path[depth + 1] = (position - start) as u16;
+            }
+            return;
+        }
+        unreachable!("position out of the siblings");
+    }
+}
+
 /// The cursor of btree.
 ///
 /// [BtreeCursor::insert()] may fail to get a writable buffer from the pager if
@@ -362,6 +470,21 @@ impl<'a> BtreeCursor<'a> {
         }
     }
 
+    /// Move to the btree table cell with the smallest key which is equal to
+    /// or greater than the key.
+    ///
+    /// Unlike [Self::table_move_to()], this moves to the next leaf page if all
+    /// keys in the leaf page are less than the key.
+    pub fn table_move_to_lower_bound(&mut self, key: i64) -> Result<()> {
+        self.table_move_to(key)?;
+        if self.current_page.n_cells > 0 && self.current_page.idx_cell == self.current_page.n_cells
+        {
+            self.current_page.idx_cell -= 1;
+            self.move_next()?;
+        }
+        Ok(())
+    }
+
     /// Move to the specified btree index cell with the key.
     ///
     /// Returns `true` if exact key is found.
@@ -381,6 +504,69 @@ impl<'a> BtreeCursor<'a> {
         Ok(found)
     }
 
+    /// Move to the last btree index cell of which the leading values are less
+    /// than the comparators, or equal to them if inclusive.
+    ///
+    /// The cursor is completed if no such cell exists.
+    pub fn index_move_to_last(
+        &mut self,
+        comparators: &[Option<ValueCmp>],
+        inclusive: bool,
+    ) -> Result<()> {
+        self.move_to_root();
+        loop {
+            if !self.current_page.page_type.is_index() {
+                return Err(Error::NotIndex);
+            }
+            let mut i_min = 0;
+            let mut i_max = self.current_page.n_cells as usize;
+            let buffer = self.current_page.mem.buffer();
+            let cell_key_parser =
+                IndexCellKeyParser::new(self.btree_ctx, &self.current_page.mem, &buffer);
+
+            // Find the first cell which is greater than the comparators (or
+            // equal to them if not inclusive).
+            while i_min < i_max {
+                let i_mid = (i_min + i_max) / 2;
+                let payload_info =
+                    cell_key_parser
+                        .get_cell_key(i_mid as u16)
+                        .map_err(|e| Error::FileCorrupt {
+                            page_id: self.current_page.page_id,
+                            e,
+                        })?;
+                let key_payload = BtreePayload {
+                    pager: self.pager,
+                    bctx: self.btree_ctx,
+                    local_page_id: self.current_page.page_id,
+                    local_payload_buffer: self.current_page.mem.buffer(),
+                    payload_info,
+                };
+                match compare_record(comparators, &key_payload).map_err(Error::Record)? {
+                    Ordering::Less => {
+                        i_max = i_mid;
+                    }
+                    Ordering::Equal if !inclusive => {
+                        i_max = i_mid;
+                    }
+                    Ordering::Equal | Ordering::Greater => {
+                        i_min = i_mid + 1;
+                    }
+                }
+            }
+            self.current_page.idx_cell = i_min as u16;
+            if self.current_page.page_type.is_leaf() {
+                break;
+            }
+
+            drop(buffer);
+            assert!(self.move_to_current_child()?);
+        }
+        self.initialized = true;
+        // The cursor points to the cell next to the target.
+        self.move_prev()
+    }
+
     /// Move to the specified btree index cell with the key without adjustment.
     ///
     /// Returns `true` if exact key is found.
@@ -507,6 +693,52 @@ impl<'a> BtreeCursor<'a> {
         Ok(())
     }
 
+    /// Move to the previous cell.
+    ///
+    /// If the cursor points to the tail of a leaf page (e.g. after
+    /// [Self::table_move_to()] misses), this moves to the last cell of the leaf
+    /// page. The cursor is completed after it moves back from the first cell.
+    pub fn move_prev(&mut self) -> Result<()> {
+        if !self.initialized {
+            return Err(Error::NotInitialized);
+        } else if self.parent_pages.is_empty()
+            && (self.current_page.idx_cell == self.current_page.n_cells + 1
+                || self.current_page.n_cells == 0)
+        {
+            // The cursor is completed.
+            return Ok(());
+        }
+
+        if !self.current_page.page_type.is_leaf() {
+            // The previous cell of an interior index cell is the last cell of
+            // its left child.
+            assert!(self.current_page.page_type.is_index());
+            assert!(self.move_to_right_most()?);
+            self.current_page.idx_cell -= 1;
+            return Ok(());
+        } else if self.current_page.idx_cell > 0 {
+            self.current_page.idx_cell -= 1;
+            return Ok(());
+        }
+
+        loop {
+            if !self.back_to_parent() {
+                self.current_page.idx_cell = self.current_page.n_cells + 1;
+                return Ok(()); // The cursor is completed.
+            }
+            if self.current_page.idx_cell > 0 {
+                break;
+            }
+        }
+        self.current_page.idx_cell -= 1;
+        if self.current_page.page_type.is_table() {
+            // The last cell of the left sibling.
+            assert!(self.move_to_right_most()?);
+            self.current_page.idx_cell -= 1;
+        }
+        Ok(())
+    }
+
     /// Insert a new key to index.
     ///
     /// This fails if the key already exists. If you need to update the key for
@@ -551,11 +783,10 @@ impl<'a> BtreeCursor<'a> {
         assert!(self.current_page.page_type.is_table());
         assert!(self.current_page.page_type.is_leaf());
 
-        if let Some(current_cell_key) = current_cell_key {
-            if current_cell_key == key {
-                // TODO: Update the payload
-                todo!("update the payload");
-            }
+        if current_cell_key == Some(key) {
+            // TODO: Overwrite the cell in place if the size of the cell does not change.
+            self.delete()?;
+            self.table_move_to(key)?;
         }
 
         let mut cell_header_buf = [0; 18];
@@ -1003,9 +1234,6 @@ impl<'a> BtreeCursor<'a> {
                 let n_new_cells = n_moved_cells
                     + (!split_into_3) as u16
                     + (!split_into_3 && sub_interior_cell_len.is_some()) as u16;
-                let mut first_freeblock_offset =
-                    BtreePageHeader::from_page_mut(&current_page.mem, &buffer)
-                        .first_freeblock_offset();
                 // TODO: Does this assertion avoid boundary check of cells[i as usize]?
                 assert!(idx_cells.end as usize <= cells.len());
 
@@ -1033,11 +1261,13 @@ impl<'a> BtreeCursor<'a> {
                     cell_content_area_offset = new_cell_content_area_offset;
                     i_new += 1;
 
-                    // TODO: Merge freeblock to unallocated space if possible.
                     // Put the cell to the freeblock list.
-                    set_u16(&mut buffer, offset, first_freeblock_offset);
-                    set_u16(&mut buffer, offset + 2, cell_size);
-                    first_freeblock_offset = offset as u16;
+                    free_space(&current_page.mem, &mut buffer, offset, cell_size.max(4)).map_err(
+                        |e| Error::FileCorrupt {
+                            page_id: current_page.page_id,
+                            e,
+                        },
+                    )?;
                 }
 
                 // Insert the new cell.
@@ -1124,7 +1354,6 @@ impl<'a> BtreeCursor<'a> {
                 };
 
                 let mut page_header = BtreePageHeaderMut::from_page(&current_page.mem, &mut buffer);
-                page_header.set_first_freeblock_offset(first_freeblock_offset);
                 let n_current_cells = current_page.n_cells - n_moved_cells;
                 page_header.set_n_cells(n_current_cells);
 
@@ -1230,7 +1459,23 @@ impl<'a> BtreeCursor<'a> {
                 let is_table_leaf = current_page.page_type.is_table_leaf();
                 if !is_table_leaf {
                     // Remove the cell at the tail.
-                    // TODO: add the cell to freeblocks list or try not to copy the cell.
+                    // TODO: try not to copy the cell.
+                    let cell_size = compute_cell_size(self.btree_ctx, &left_buffer, cell_offset)
+                        .map_err(|e| Error::FileCorrupt {
+                            page_id: current_page.page_id,
+                            e,
+                        })?;
+                    // Both pages are not page 1 because they are not the root page.
+                    free_space(
+                        &current_page.mem,
+                        &mut left_buffer,
+                        cell_offset,
+                        cell_size.max(4),
+                    )
+                    .map_err(|e| Error::FileCorrupt {
+                        page_id: current_page.page_id,
+                        e,
+                    })?;
                     BtreePageHeaderMut::from_page(&current_page.mem, &mut left_buffer)
                         .set_n_cells(n_left_cells - 1);
                 }
@@ -1329,6 +1574,10 @@ impl<'a> BtreeCursor<'a> {
     }
 
     /// Delete the current entry.
+    ///
+    /// The cursor moves to the next entry. If the page becomes underfull, its
+    /// cells are redistributed with the sibling pages like balance_nonroot()
+    /// of SQLite, and emptied pages are added to the freelist.
     pub fn delete(&mut self) -> Result<()> {
         if !self.initialized {
             return Err(Error::NotInitialized);
@@ -1365,274 +1614,575 @@ impl<'a> BtreeCursor<'a> {
             drop(buffer);
         }
 
+        let target = self.current_path();
         let delete_interior_cell = !self.current_page.page_type.is_leaf();
-        if delete_interior_cell {
+        let interior_page = if delete_interior_cell {
             assert!(self.current_page.page_type.is_index());
-            assert!(self.current_page.idx_cell < self.current_page.n_cells);
+            // Replace the interior cell with the right most cell in its left child
+            // page. The right most cell is removed from the leaf page below.
+            let mut interior_page =
+                self.load_cells(self.current_page.page_id, &self.current_page.mem)?;
+            let idx_cell = self.current_page.idx_cell as usize;
+            assert!(self.move_to_right_most()?);
+            self.current_page.idx_cell -= 1;
+            let cell = &mut interior_page.cells[idx_cell];
+            cell.truncate(4);
+            cell.extend_from_slice(&self.load_current_cell()?);
+            Some(interior_page)
+        } else {
+            None
+        };
 
-            let depth_interior_page = self.parent_pages.len();
+        self.drop_current_cell()?;
 
-            self.move_to_right_most()?;
-            self.current_page.idx_cell -= 1;
+        // The leaf page is balanced after the interior page because balancing the
+        // leaf page may rewrite the interior page from the file.
+        let mut paths = [target, self.current_path()];
+        if let Some(interior_page) = interior_page {
+            self.move_to_path(&paths[0])?;
+            self.balance(Some(interior_page), &mut paths)?;
+            self.move_to_path(&paths[1])?;
+        }
+        self.balance(None, &mut paths)?;
 
-            // Remove the cell from the interior page.
-            let page_id_buf = {
-                let current_page = &mut self.parent_pages[depth_interior_page];
-                let header_size = current_page.page_type.header_size();
-                let mut buffer =
-                    self.pager
-                        .make_page_mut(&current_page.mem)
-                        .map_err(|e| Error::Pager {
-                            page_id: current_page.page_id,
-                            e,
-                        })?;
-                let cell_offset = get_cell_offset(
-                    &current_page.mem,
-                    &buffer,
-                    current_page.idx_cell,
-                    header_size,
-                )
-                .map_err(|e| Error::FileCorrupt {
-                    page_id: current_page.page_id,
-                    e,
-                })?;
-                let cell_size = current_page.page_type.compute_cell_size_fn()(
-                    self.btree_ctx,
-                    &buffer,
-                    cell_offset,
-                )
-                .map_err(|e| Error::FileCorrupt {
-                    page_id: current_page.page_id,
-                    e,
-                })?;
-                let cell_pointers_tail_offset =
-                    cell_pointer_offset(&current_page.mem, current_page.n_cells, header_size);
-                let cell_pointer_offset =
-                    cell_pointer_offset(&current_page.mem, current_page.idx_cell, header_size);
-                // Move the cell pointers.
-                buffer.copy_within(
-                    cell_pointer_offset + 2..cell_pointers_tail_offset,
-                    cell_pointer_offset,
-                );
-                let page_id_buf: [u8; 4] = buffer[cell_offset..cell_offset + 4].try_into().unwrap();
-                Self::free_cell(&current_page.mem, &mut buffer, cell_offset, cell_size);
+        let [target, _] = paths;
+        self.move_to_path(&target)?;
+        if delete_interior_cell {
+            // The cursor points to the cell which replaced the deleted cell.
+            self.move_next()?;
+        } else if self.current_page.idx_cell == self.current_page.n_cells
+            && self.current_page.n_cells > 0
+        {
+            // The deleted cell was the tail of the leaf page.
+            self.current_page.idx_cell -= 1;
+            self.move_next()?;
+        }
+        Ok(())
+    }
 
-                // Reduce the n_cells for insert_cell(). The header value is updated in
-                // insert_cell().
-                current_page.n_cells -= 1;
+    /// Remove the current cell from the current leaf page.
+    fn drop_current_cell(&mut self) -> Result<()> {
+        assert!(self.current_page.page_type.is_leaf());
+        let header_size = self.current_page.page_type.header_size();
+        let mut buffer = self
+            .pager
+            .make_page_mut(&self.current_page.mem)
+            .map_err(|e| Error::Pager {
+                page_id: self.current_page.page_id,
+                e,
+            })?;
+        let cell_offset = get_cell_offset(
+            &self.current_page.mem,
+            &buffer,
+            self.current_page.idx_cell,
+            header_size,
+        )
+        .map_err(|e| Error::FileCorrupt {
+            page_id: self.current_page.page_id,
+            e,
+        })?;
+        let cell_size = self.current_page.page_type.compute_cell_size_fn()(
+            self.btree_ctx,
+            &buffer,
+            cell_offset,
+        )
+        .map_err(|e| Error::FileCorrupt {
+            page_id: self.current_page.page_id,
+            e,
+        })?;
 
-                page_id_buf
-            };
+        // Move the cell pointers.
+        let cell_pointers_tail_offset = cell_pointer_offset(
+            &self.current_page.mem,
+            self.current_page.n_cells,
+            header_size,
+        );
+        let cell_pointer_offset = cell_pointer_offset(
+            &self.current_page.mem,
+            self.current_page.idx_cell,
+            header_size,
+        );
+        buffer.copy_within(
+            cell_pointer_offset + 2..cell_pointers_tail_offset,
+            cell_pointer_offset,
+        );
 
-            // Copy the right most cell in the child page to the interior page.
-            {
-                let buffer = self.current_page.mem.buffer();
-                let cell_offset = get_cell_offset(
-                    &self.current_page.mem,
-                    &buffer,
-                    self.current_page.idx_cell,
-                    self.current_page.page_type.header_size(),
-                )
-                .map_err(|e| Error::FileCorrupt {
-                    page_id: self.current_page.page_id,
-                    e,
-                })?;
-                let cell_size = self.current_page.page_type.compute_cell_size_fn()(
-                    self.btree_ctx,
-                    &buffer,
-                    cell_offset,
-                )
-                .map_err(|e| Error::FileCorrupt {
+        self.current_page.n_cells -= 1;
+        if self.current_page.n_cells == 0 {
+            // Initialize the page instead of leaving a freeblock.
+            let mut page_header =
+                BtreePageHeaderMut::from_page(&self.current_page.mem, &mut buffer);
+            page_header.set_first_freeblock_offset(0);
+            page_header.set_cell_content_area_offset(non_zero_to_u16(self.btree_ctx.usable_size));
+            page_header.clear_fragmented_free_bytes();
+        } else {
+            Self::free_cell(&self.current_page.mem, &mut buffer, cell_offset, cell_size).map_err(
+                |e| Error::FileCorrupt {
                     page_id: self.current_page.page_id,
                     e,
-                })?;
-                assert!(cell_size > 0);
-                let payload = PagePayload::new(
+                },
+            )?;
+        }
+        BtreePageHeaderMut::from_page(&self.current_page.mem, &mut buffer)
+            .set_n_cells(self.current_page.n_cells);
+        Ok(())
+    }
+
+    /// Balance the current page and its ancestors.
+    ///
+    /// `page` is the new content of the current page if it is not written yet.
+    /// It may not fit in the page. An overflowing or underfull page is
+    /// balanced with its siblings, which changes the parent page in turn. The
+    /// root page moves its cells to a new child page if it overflows and is
+    /// replaced by its only child page if it has no cells.
+    ///
+    /// `paths` are updated to point to the same cells after balancing. See
+    /// [Self::current_path()].
+    fn balance(&mut self, mut page: Option<PageCells>, paths: &mut [Vec<u16>]) -> Result<()> {
+        loop {
+            let is_dirty = page.is_some();
+            let current = match page.take() {
+                Some(page) => page,
+                None => {
+                    if !self.parent_pages.is_empty() && !self.is_current_page_underfull()? {
+                        return Ok(());
+                    }
+                    self.load_cells(self.current_page.page_id, &self.current_page.mem)?
+                }
+            };
+            let capacity = self.page_capacity(&self.current_page.mem, current.page_type);
+            let used_size = cells_size(&current.cells);
+
+            if self.parent_pages.is_empty() {
+                if used_size > capacity {
+                    // Move all cells to a new child page and split it.
+                    let (child_page_id, child_page) =
+                        self.pager.allocate_page().map_err(Error::AllocatePage)?;
+                    let root_page_type = current.page_type.interior_type();
+                    self.write_cells(
+                        self.current_page.page_id,
+                        &self.current_page.mem,
+                        root_page_type,
+                        &[],
+                        Some(child_page_id),
+                    )?;
+                    self.current_page.page_type = root_page_type;
+                    self.current_page.n_cells = 0;
+                    self.current_page.idx_cell = 0;
+                    let child = CursorPage {
+                        page_id: child_page_id,
+                        mem: child_page,
+                        idx_cell: 0,
+                        n_cells: current.cells.len() as u16,
+                        page_type: current.page_type,
+                    };
+                    let root = std::mem::replace(&mut self.current_page, child);
+                    self.parent_pages.push(root);
+                    for path in paths.iter_mut() {
+                        path.insert(0, 0);
+                    }
+                    page = Some(current);
+                    continue;
+                } else if !current.page_type.is_leaf() && current.cells.is_empty() {
+                    // Shrink the tree by copying the only child page to the root page.
+                    let child_page_id = current.right_page_id.unwrap();
+                    let child_page =
+                        self.pager
+                            .get_page(child_page_id)
+                            .map_err(|e| Error::Pager {
+                                page_id: child_page_id,
+                                e,
+                            })?;
+                    let child = self.load_cells(child_page_id, &child_page)?;
+                    drop(child_page);
+                    if cells_size(&child.cells)
+                        <= self.page_capacity(&self.current_page.mem, child.page_type)
+                    {
+                        self.write_cells(
+                            self.current_page.page_id,
+                            &self.current_page.mem,
+                            child.page_type,
+                            &child.cells,
+                            child.right_page_id,
+                        )?;
+                        self.current_page.page_type = child.page_type;
+                        self.current_page.n_cells = child.cells.len() as u16;
+                        self.current_page.idx_cell = 0;
+                        self.pager
+                            .delete_page(child_page_id)
+                            .map_err(|e| Error::Pager {
+                                page_id: child_page_id,
+                                e,
+                            })?;
+                        for path in paths.iter_mut() {
+                            assert_eq!(path.remove(0), 0);
+                        }
+                        continue;
+                    }
+                }
+            } else if used_size > capacity || is_underfull(self.btree_ctx, capacity - used_size) {
+                page = Some(self.balance_siblings(current, paths)?);
+                continue;
+            }
+
+            if is_dirty {
+                self.write_cells(
+                    self.current_page.page_id,
                     &self.current_page.mem,
-                    cell_offset,
-                    (cell_size as u64).try_into().unwrap(),
-                );
-                drop(buffer);
-                self.insert_cell(
-                    depth_interior_page,
-                    &PageCellPayload {
-                        header: &page_id_buf,
-                        payload,
-                    },
+                    current.page_type,
+                    &current.cells,
+                    current.right_page_id,
                 )?;
+                self.current_page.page_type = current.page_type;
+                self.current_page.n_cells = current.cells.len() as u16;
             }
-
-            // Remove the moved right most cell in the child page.
+            return Ok(());
         }
+    }
 
-        loop {
-            if self.current_page.n_cells > self.current_page.page_type.is_leaf() as u16 {
-                let header_size = self.current_page.page_type.header_size();
-
-                let mut buffer = self
+    /// Redistribute the cells of the current page and at most 2 siblings.
+    ///
+    /// The cells are packed into as few pages as possible, and then moved to
+    /// right pages to even out the sizes. The cursor moves to the parent page,
+    /// and the new content of the parent page is returned.
+    fn balance_siblings(&mut self, page: PageCells, paths: &mut [Vec<u16>]) -> Result<PageCells> {
+        let depth = self.parent_pages.len() - 1;
+        let parent = &self.parent_pages[depth];
+        let parent_page_id = parent.page_id;
+        let idx_child = parent.idx_cell as usize;
+        let mut parent_cells = self.load_cells(parent_page_id, &parent.mem)?;
+        let n_children = parent_cells.cells.len() + 1;
+        let first = idx_child
+            .saturating_sub(1)
+            .min(n_children.saturating_sub(3));
+        let last = (first + 3).min(n_children);
+
+        let page_type = page.page_type;
+        // Except table leaf pages, the cells between siblings are stored in the
+        // parent page as dividers.
+        let has_dividers = !page_type.is_table_leaf();
+        let mut page = Some(page);
+        let mut old_page_ids = Vec::with_capacity(last - first);
+        let mut n_old_cells = Vec::with_capacity(last - first);
+        let mut cells = Vec::new();
+        let mut right_page_id = None;
+        for i in first..last {
+            let page_id = match parent_cells.cells.get(i) {
+                Some(cell) => parse_child_page_id(parent_page_id, cell)?,
+                None => parent_cells.right_page_id.unwrap(),
+            };
+            let sibling = if i == idx_child {
+                page.take().unwrap()
+            } else {
+                let mem = self
                     .pager
-                    .make_page_mut(&self.current_page.mem)
-                    .map_err(|e| Error::Pager {
-                        page_id: self.current_page.page_id,
-                        e,
-                    })?;
-
-                let remove_right_page = self.current_page.idx_cell == self.current_page.n_cells;
-                let cell_offset = if remove_right_page {
-                    assert!(!self.current_page.page_type.is_leaf());
-                    self.current_page.idx_cell -= 1;
+                    .get_page(page_id)
+                    .map_err(|e| Error::Pager { page_id, e })?;
+                self.load_cells(page_id, &mem)?
+            };
+            if sibling.page_type != page_type {
+                return Err(Error::FileCorrupt {
+                    page_id,
+                    e: FileCorrupt::new("sibling page type"),
+                });
+            }
+            old_page_ids.push(page_id);
+            n_old_cells.push(sibling.cells.len());
+            cells.extend(sibling.cells);
+            if i + 1 == last {
+                right_page_id = sibling.right_page_id;
+            } else if has_dividers {
+                // The child page id of an interior divider is the right page of the
+                // sibling.
+                let mut cell = Vec::new();
+                if let Some(right_page_id) = sibling.right_page_id {
+                    cell.extend_from_slice(&right_page_id.get().to_be_bytes());
+                }
+                cell.extend_from_slice(&parent_cells.cells[i][4..]);
+                cells.push(cell);
+            }
+        }
 
-                    let cell_offset = get_cell_offset(
-                        &self.current_page.mem,
-                        &buffer,
-                        self.current_page.idx_cell,
-                        header_size,
-                    )
-                    .map_err(|e| Error::FileCorrupt {
-                        page_id: self.current_page.page_id,
-                        e,
-                    })?;
+        // Pack the cells from the left page.
+        let capacity = self.btree_ctx.usable_size - page_type.header_size() as u32;
+        let sizes = cells.iter().map(|cell| cell_size(cell)).collect::<Vec<_>>();
+        let total_size = |start: usize, end: usize| sizes[start..end].iter().sum::<u32>();
+        let mut bounds = Vec::new();
+        let mut start = 0;
+        let mut size = 0;
+        let mut i = 0;
+        while i < cells.len() {
+            if size + sizes[i] > capacity {
+                bounds.push((start, i));
+                start = i + has_dividers as usize;
+                i = start;
+                size = 0;
+            } else {
+                size += sizes[i];
+                i += 1;
+            }
+        }
+        bounds.push((start, cells.len()));
+        // Move cells to the right pages while the right page is smaller.
+        for k in (1..bounds.len()).rev() {
+            loop {
+                let (left_start, left_end) = bounds[k - 1];
+                let (right_start, right_end) = bounds[k];
+                if left_end - left_start <= 1 {
+                    break;
+                }
+                let left_size = total_size(left_start, left_end - 1);
+                let right_size = total_size(right_start - 1, right_end);
+                if right_size > capacity || (right_start < right_end && right_size > left_size) {
+                    break;
+                }
+                bounds[k - 1].1 -= 1;
+                bounds[k].0 -= 1;
+            }
+        }
 
-                    buffer.copy_within(cell_offset..cell_offset + 4, BTREE_RIGHT_PAGE_ID_OFFSET);
-                    cell_offset
+        let mut new_pages = Vec::with_capacity(bounds.len());
+        for k in 0..bounds.len() {
+            if let Some(&page_id) = old_page_ids.get(k) {
+                let mem = self
+                    .pager
+                    .get_page(page_id)
+                    .map_err(|e| Error::Pager { page_id, e })?;
+                new_pages.push((page_id, mem));
+            } else {
+                new_pages.push(self.pager.allocate_page().map_err(Error::AllocatePage)?);
+            }
+        }
+        let mut dividers = Vec::with_capacity(bounds.len() - 1);
+        for (k, ((page_id, mem), &(start, end))) in new_pages.iter().zip(bounds.iter()).enumerate()
+        {
+            let is_last = k + 1 == bounds.len();
+            let page_right_page_id = if page_type.is_leaf() {
+                None
+            } else if is_last {
+                right_page_id
+            } else {
+                Some(parse_child_page_id(*page_id, &cells[end])?)
+            };
+            self.write_cells(
+                *page_id,
+                mem,
+                page_type,
+                &cells[start..end],
+                page_right_page_id,
+            )?;
+            if !is_last {
+                let mut divider = page_id.get().to_be_bytes().to_vec();
+                if page_type.is_table_leaf() {
+                    // The divider key is the rowid of the last cell in the left page.
+                    let last_cell = &cells[end - 1];
+                    let corrupt = || Error::FileCorrupt {
+                        page_id: *page_id,
+                        e: FileCorrupt::new("table leaf cell key"),
+                    };
+                    let key_offset = len_varint_buffer(last_cell).ok_or_else(corrupt)?;
+                    let key_len =
+                        len_varint_buffer(&last_cell[key_offset..]).ok_or_else(corrupt)?;
+                    divider.extend_from_slice(&last_cell[key_offset..key_offset + key_len]);
+                } else if page_type.is_leaf() {
+                    divider.extend_from_slice(&cells[end]);
                 } else {
-                    let cell_offset = get_cell_offset(
-                        &self.current_page.mem,
-                        &buffer,
-                        self.current_page.idx_cell,
-                        header_size,
-                    )
-                    .map_err(|e| Error::FileCorrupt {
-                        page_id: self.current_page.page_id,
-                        e,
-                    })?;
-
-                    let cell_pointers_tail_offset = cell_pointer_offset(
-                        &self.current_page.mem,
-                        self.current_page.n_cells,
-                        header_size,
-                    );
-                    let cell_pointer_offset = cell_pointer_offset(
-                        &self.current_page.mem,
-                        self.current_page.idx_cell,
-                        header_size,
-                    );
+                    divider.extend_from_slice(&cells[end][4..]);
+                }
+                dividers.push(divider);
+            }
+        }
+        let last_page_id = new_pages.last().unwrap().0;
+        drop(new_pages);
+
+        // Replace the dividers and the child page ids in the parent page.
+        let n_old = old_page_ids.len();
+        let mut tail = parent_cells
+            .cells
+            .split_off(first)
+            .into_iter()
+            .skip(n_old - 1);
+        parent_cells.cells.extend(dividers);
+        if let Some(mut cell) = tail.next() {
+            cell[..4].copy_from_slice(&last_page_id.get().to_be_bytes());
+            parent_cells.cells.push(cell);
+            parent_cells.cells.extend(tail);
+        } else {
+            parent_cells.right_page_id = Some(last_page_id);
+        }
 
-                    // Move the cell pointers.
-                    buffer.copy_within(
-                        cell_pointer_offset + 2..cell_pointers_tail_offset,
-                        cell_pointer_offset,
-                    );
+        let layout = SiblingLayout {
+            first,
+            n_old_cells,
+            bounds,
+            has_dividers,
+        };
+        let prefix = self.parent_pages[..depth]
+            .iter()
+            .map(|page| page.idx_cell)
+            .collect::<Vec<_>>();
+        for path in paths.iter_mut() {
+            if path.starts_with(&prefix) {
+                layout.remap(path, depth);
+            }
+        }
 
-                    cell_offset
-                };
+        assert!(self.back_to_parent());
+        for &page_id in old_page_ids.iter().skip(layout.bounds.len()) {
+            self.pager
+                .delete_page(page_id)
+                .map_err(|e| Error::Pager { page_id, e })?;
+        }
+        Ok(parent_cells)
+    }
 
-                let cell_size = self.current_page.page_type.compute_cell_size_fn()(
-                    self.btree_ctx,
-                    &buffer,
-                    cell_offset,
-                )
-                .map_err(|e| Error::FileCorrupt {
+    fn is_current_page_underfull(&self) -> Result<bool> {
+        let buffer = self
+            .pager
+            .make_page_mut(&self.current_page.mem)
+            .map_err(|e| Error::Pager {
+                page_id: self.current_page.page_id,
+                e,
+            })?;
+        let free_size =
+            compute_free_size(&self.current_page.mem, &buffer, self.current_page.n_cells).map_err(
+                |e| Error::FileCorrupt {
                     page_id: self.current_page.page_id,
                     e,
-                })?;
+                },
+            )?;
+        Ok(is_underfull(self.btree_ctx, free_size as u32))
+    }
 
-                Self::free_cell(&self.current_page.mem, &mut buffer, cell_offset, cell_size);
+    /// The size of the page available for cells and cell pointers.
+    fn page_capacity(&self, page: &MemPage, page_type: BtreePageType) -> u32 {
+        self.btree_ctx.usable_size - page.header_offset as u32 - page_type.header_size() as u32
+    }
 
-                // Update page header.
-                self.current_page.n_cells -= 1;
-                let mut page_header =
-                    BtreePageHeaderMut::from_page(&self.current_page.mem, &mut buffer);
-                page_header.set_n_cells(self.current_page.n_cells);
-                drop(buffer);
-                if self.current_page.page_type.is_leaf() {
-                    if self.current_page.idx_cell == self.current_page.n_cells {
-                        // self.current_page.n_cells was greater than 1 before decremented because
-                        // is_leaf() is true. It is safe to decreament idx_cell.
-                        assert!(self.current_page.idx_cell >= 1);
-                        self.current_page.idx_cell -= 1;
-                        self.move_next()?;
-                    }
-                } else if self.current_page.page_type.is_index() {
-                    let payload_size =
-                        (cell_size as u64 - 4)
-                            .try_into()
-                            .map_err(|_| Error::FileCorrupt {
-                                page_id: self.current_page.page_id,
-                                e: FileCorrupt::new("cell size"),
-                            })?;
-                    // The payload of the freed cell is not broken because freeblocks only
-                    // overwrites the first 4 bytes of the cell.
-                    // Using PagePayload pointing the freed cell for insert_cell() is safe.
-                    // insert_cell() first copies the payload to the leaf page. Even if the
-                    // new cell is inserted into the parent interior page as the divider, it
-                    // is copied into interior_cell_buf.
-                    // TODO: This can save memory copy. But it is not safe because the freed
-                    // cell may be overwritten by future change for insert_cell().
-                    let payload =
-                        PagePayload::new(&self.current_page.mem, cell_offset + 4, payload_size);
-                    if remove_right_page {
-                        assert!(self.move_to_right_most()?);
-                    } else {
-                        assert!(self.move_to_left_most()?);
-                    }
-                    // Insert the payload of the removed cell to child page.
-                    self.insert_cell(
-                        self.parent_pages.len(),
-                        &PageCellPayload {
-                            header: &[],
-                            payload,
-                        },
-                    )?;
-                } else {
-                    assert!(self.move_to_left_most()?);
-                }
-                break;
-            } else {
-                let page_id = self.current_page.page_id;
-                if self.back_to_parent() {
-                    // Remove the child page.
-                    self.pager
-                        .delete_page(page_id)
-                        .map_err(|e| Error::Pager { page_id, e })?;
+    fn load_cells(&self, page_id: PageId, page: &MemPage) -> Result<PageCells> {
+        let buffer = page.buffer();
+        let page_header = BtreePageHeader::from_page(page, &buffer);
+        let page_type = page_header.page_type();
+        let n_cells = page_header.n_cells();
+        let right_page_id = if page_type.is_leaf() {
+            None
+        } else {
+            Some(
+                page_header
+                    .right_page_id()
+                    .map_err(|e| Error::FileCorrupt { page_id, e })?,
+            )
+        };
+        let header_size = page_type.header_size();
+        let compute_cell_size = page_type.compute_cell_size_fn();
+        let mut cells = Vec::with_capacity(n_cells as usize);
+        for i in 0..n_cells {
+            let offset = get_cell_offset(page, &buffer, i, header_size)
+                .map_err(|e| Error::FileCorrupt { page_id, e })?;
+            let cell_size = compute_cell_size(self.btree_ctx, &buffer, offset)
+                .map_err(|e| Error::FileCorrupt { page_id, e })?;
+            cells.push(buffer[offset..offset + cell_size as usize].to_vec());
+        }
+        Ok(PageCells {
+            page_type,
+            cells,
+            right_page_id,
+        })
+    }
 
-                    assert!(!self.current_page.page_type.is_leaf());
-                    if self.current_page.page_type.is_table()
-                        && self.current_page.idx_cell != self.current_page.n_cells
-                        && self.current_page.n_cells == 1
-                    {
-                        // TODO: Optimization: shrink the page level by
-                        // replacing the interior page with the right child
-                        // page.
-                    }
-                } else {
-                    // Initialize the root page.
-                    self.current_page.page_type = self.current_page.page_type.leaf_type();
-                    self.current_page.idx_cell = 0;
-                    self.current_page.n_cells = 0;
-                    let mut buffer =
-                        self.pager
-                            .make_page_mut(&self.current_page.mem)
-                            .map_err(|e| Error::Pager {
-                                page_id: self.current_page.page_id,
-                                e,
-                            })?;
-                    let mut page_header =
-                        BtreePageHeaderMut::from_page(&self.current_page.mem, &mut buffer);
-                    page_header.set_page_type(self.current_page.page_type);
-                    page_header.set_first_freeblock_offset(0);
-                    page_header.set_n_cells(0);
-                    page_header
-                        .set_cell_content_area_offset(non_zero_to_u16(self.btree_ctx.usable_size));
-                    page_header.clear_fragmented_free_bytes();
-                    break;
-                }
-            }
+    fn load_current_cell(&self) -> Result<Vec<u8>> {
+        let buffer = self.current_page.mem.buffer();
+        let cell_offset = get_cell_offset(
+            &self.current_page.mem,
+            &buffer,
+            self.current_page.idx_cell,
+            self.current_page.page_type.header_size(),
+        )
+        .map_err(|e| Error::FileCorrupt {
+            page_id: self.current_page.page_id,
+            e,
+        })?;
+        let cell_size = self.current_page.page_type.compute_cell_size_fn()(
+            self.btree_ctx,
+            &buffer,
+            cell_offset,
+        )
+        .map_err(|e| Error::FileCorrupt {
+            page_id: self.current_page.page_id,
+            e,
+        })?;
+        Ok(buffer[cell_offset..cell_offset + cell_size as usize].to_vec())
+    }
+
+    /// Rebuild the page with the cells.
+    ///
+    /// The cells must fit in the page.
+    fn write_cells(
+        &self,
+        page_id: PageId,
+        page: &MemPage,
+        page_type: BtreePageType,
+        cells: &[Vec<u8>],
+        right_page_id: Option<PageId>,
+    ) -> Result<()> {
+        let mut buffer = self
+            .pager
+            .make_page_mut(page)
+            .map_err(|e| Error::Pager { page_id, e })?;
+        let header_size = page_type.header_size();
+        let usable_size = self.btree_ctx.usable_size as usize;
+        buffer[page.header_offset..usable_size].fill(0);
+        let mut cell_content_area_offset = usable_size;
+        for (i, cell) in cells.iter().enumerate() {
+            cell_content_area_offset = allocate_from_unallocated_space(
+                page,
+                &mut buffer,
+                header_size,
+                cell_content_area_offset,
+                i as u16,
+                (cell.len() as u16).max(4),
+            );
+            buffer[cell_content_area_offset..cell_content_area_offset + cell.len()]
+                .copy_from_slice(cell);
+        }
+        let mut page_header = BtreePageHeaderMut::from_page(page, &mut buffer);
+        page_header.set_page_type(page_type);
+        page_header.set_first_freeblock_offset(0);
+        page_header.set_n_cells(cells.len() as u16);
+        page_header.set_cell_content_area_offset(non_zero_to_u16(cell_content_area_offset as u32));
+        page_header.clear_fragmented_free_bytes();
+        if let Some(right_page_id) = right_page_id {
+            page_header.set_right_page_id(right_page_id);
         }
+        Ok(())
+    }
 
-        if delete_interior_cell {
-            self.move_next()?;
+    /// The indexes of the cells from the root page to the current page.
+    fn current_path(&self) -> Vec<u16> {
+        self.parent_pages
+            .iter()
+            .chain(std::iter::once(&self.current_page))
+            .map(|page| page.idx_cell)
+            .collect()
+    }
+
+    /// Move to the position returned by [Self::current_path()].
+    fn move_to_path(&mut self, path: &[u16]) -> Result<()> {
+        self.move_to_root();
+        // Reload the root page because balancing may change its page type.
+        let page_id = self.current_page.page_id;
+        let mem = self
+            .pager
+            .get_page(page_id)
+            .map_err(|e| Error::Pager { page_id, e })?;
+        self.current_page = CursorPage::new(page_id, mem);
+        for (i, &idx_cell) in path.iter().enumerate() {
+            self.current_page.idx_cell = idx_cell;
+            if i + 1 < path.len() {
+                assert!(self.move_to_current_child()?);
+            }
         }
+        self.initialized = true;
         Ok(())
     }
 
@@ -1666,15 +2216,14 @@ impl<'a> BtreeCursor<'a> {
         Ok(())
     }
 
-    fn free_cell(page: &MemPage, buffer: &mut PageBufferMut, offset: usize, cell_size: u16) {
+    fn free_cell(
+        page: &MemPage,
+        buffer: &mut PageBufferMut,
+        offset: usize,
+        cell_size: u16,
+    ) -> std::result::Result<(), FileCorrupt> {
         let cell_size = if cell_size < 4 { 4 } else { cell_size };
-        buffer.copy_within(BTREE_FIRST_FREEBLOCK_OFFSET, offset);
-        set_u16(buffer, offset + 2, cell_size);
-        let mut page_header = BtreePageHeaderMut::from_page(page, buffer);
-        page_header.set_first_freeblock_offset(offset as u16);
-
-        // TODO: Merge freeblocks.
-        // TODO: Merge freeblock to unallocated space if possible.
+        free_space(page, buffer, offset, cell_size)
     }
 
     pub fn get_table_key(&self) -> Result<Option<i64>> {
@@ -1697,7 +2246,7 @@ impl<'a> BtreeCursor<'a> {
         Ok(Some(key))
     }
 
-    pub fn get_table_payload(&self) -> Result<Option<(i64, BtreePayload)>> {
+    pub fn get_table_payload(&self) -> Result<Option<(i64, BtreePayload<'_>)>> {
         if !self.initialized {
             return Err(Error::NotInitialized);
         } else if !self.current_page.page_type.is_table() {
@@ -1729,7 +2278,7 @@ impl<'a> BtreeCursor<'a> {
         )))
     }
 
-    pub fn get_index_payload(&self) -> Result<Option<BtreePayload>> {
+    pub fn get_index_payload(&self) -> Result<Option<BtreePayload<'_>>> {
         if !self.initialized {
             return Err(Error::NotInitialized);
         } else if !self.current_page.page_type.is_index() {
@@ -2420,37 +2969,184 @@ mod tests {
                 2 * i + 1
             ));
         }
-        let mut queries = vec!["CREATE TABLE example(col);"];
-        queries.extend(inserts.iter().map(|s| s.as_str()));
-        let file = create_sqlite_database(&queries);
+        let mut queries = vec!["CREATE TABLE example(col);"];
+        queries.extend(inserts.iter().map(|s| s.as_str()));
+        let file = create_sqlite_database(&queries);
+        let pager = create_pager(file.as_file().try_clone().unwrap()).unwrap();
+        let bctx = load_btree_context(file.as_file()).unwrap();
+        let page_id = find_table_page_id("example", file.path());
+
+        let mut cursor = BtreeCursor::new(page_id, &pager, &bctx).unwrap();
+
+        for i in 0..2000 {
+            let cell_key = cursor.table_move_to(2 * i).unwrap();
+            assert!(cell_key.is_some());
+            assert_eq!(cell_key.unwrap(), 2 * i + 1);
+            let payload = cursor.get_table_payload().unwrap();
+            assert!(payload.is_some());
+            let (key, _) = payload.unwrap();
+            assert_eq!(key, 2 * i + 1);
+
+            let cell_key = cursor.table_move_to(2 * i + 1).unwrap();
+            assert!(cell_key.is_some());
+            assert_eq!(cell_key.unwrap(), 2 * i + 1);
+            let payload = cursor.get_table_payload().unwrap();
+            assert!(payload.is_some());
+            let (key, _) = payload.unwrap();
+            assert_eq!(key, 2 * i + 1);
+        }
+
+        let cell_key = cursor.table_move_to(40002).unwrap();
+        assert!(cell_key.is_none());
+        let payload = cursor.get_table_payload().unwrap();
+        assert!(payload.is_none());
+    }
+
+    #[test]
+    fn test_table_move_to_lower_bound() {
+        let hex = buffer_to_hex(&[0; 500]);
+        let mut queries = vec!["CREATE TABLE example(col);".to_string()];
+        for i in 1..=300 {
+            queries.push(format!(
+                "INSERT INTO example(rowid, col) VALUES ({i}, X'{hex}');"
+            ));
+        }
+        // Deleting rows leaves interior keys which are greater than the last
+        // key of the left leaf page.
+        queries.push("DELETE FROM example WHERE rowid % 7 IN (0, 1, 2);".to_string());
+        let file = create_sqlite_database(&queries.iter().map(|s| s.as_str()).collect::<Vec<_>>());
+        let pager = create_pager(file.as_file().try_clone().unwrap()).unwrap();
+        let bctx = load_btree_context(file.as_file()).unwrap();
+        let page_id = find_table_page_id("example", file.path());
+
+        let mut cursor = BtreeCursor::new(page_id, &pager, &bctx).unwrap();
+
+        for key in 0..=302 {
+            cursor.table_move_to_lower_bound(key).unwrap();
+            let expected = (key.max(1)..=300).find(|i| i % 7 > 2);
+            let payload = cursor.get_table_payload().unwrap();
+            assert_eq!(payload.map(|(k, _)| k), expected, "key: {key}");
+        }
+
+        let file = create_sqlite_database(&["CREATE TABLE example(col);"]);
+        let pager = create_pager(file.as_file().try_clone().unwrap()).unwrap();
+        let bctx = load_btree_context(file.as_file()).unwrap();
+        let page_id = find_table_page_id("example", file.path());
+        let mut cursor = BtreeCursor::new(page_id, &pager, &bctx).unwrap();
+        cursor.table_move_to_lower_bound(1).unwrap();
+        assert!(cursor.get_table_payload().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_move_prev_table() {
+        let hex = buffer_to_hex(&[0; 500]);
+        let mut queries = vec!["CREATE TABLE example(col);".to_string()];
+        for i in 1..=300 {
+            queries.push(format!(
+                "INSERT INTO example(rowid, col) VALUES ({i}, X'{hex}');"
+            ));
+        }
+        queries.push("DELETE FROM example WHERE rowid % 7 IN (0, 1, 2);".to_string());
+        let file = create_sqlite_database(&queries.iter().map(|s| s.as_str()).collect::<Vec<_>>());
         let pager = create_pager(file.as_file().try_clone().unwrap()).unwrap();
         let bctx = load_btree_context(file.as_file()).unwrap();
         let page_id = find_table_page_id("example", file.path());
 
         let mut cursor = BtreeCursor::new(page_id, &pager, &bctx).unwrap();
+        assert!(matches!(cursor.move_prev(), Err(Error::NotInitialized)));
+
+        cursor.move_to_last().unwrap();
+        let mut keys = Vec::new();
+        while let Some(key) = cursor.get_table_key().unwrap() {
+            keys.push(key);
+            cursor.move_prev().unwrap();
+        }
+        let expected = (1..=300).rev().filter(|i| i % 7 > 2).collect::<Vec<_>>();
+        assert_eq!(keys, expected);
+        // The cursor stays completed.
+        cursor.move_prev().unwrap();
+        assert!(cursor.get_table_key().unwrap().is_none());
+
+        for key in 0..=302 {
+            let found = cursor.table_move_to(key).unwrap();
+            if found != Some(key) {
+                cursor.move_prev().unwrap();
+            }
+            let expected = (1..=key.min(300)).rev().find(|i| i % 7 > 2);
+            assert_eq!(cursor.get_table_key().unwrap(), expected, "key: {key}");
+        }
+
+        let file = create_sqlite_database(&["CREATE TABLE example(col);"]);
+        let pager = create_pager(file.as_file().try_clone().unwrap()).unwrap();
+        let bctx = load_btree_context(file.as_file()).unwrap();
+        let page_id = find_table_page_id("example", file.path());
+        let mut cursor = BtreeCursor::new(page_id, &pager, &bctx).unwrap();
+        cursor.move_to_last().unwrap();
+        cursor.move_prev().unwrap();
+        assert!(cursor.get_table_key().unwrap().is_none());
+    }
 
+    #[test]
+    fn test_move_prev_index() {
+        let mut queries = vec![
+            "CREATE TABLE example(col1, col2);".to_string(),
+            "CREATE INDEX index1 ON example(col1, col2);".to_string(),
+        ];
         for i in 0..2000 {
-            let cell_key = cursor.table_move_to(2 * i).unwrap();
-            assert!(cell_key.is_some());
-            assert_eq!(cell_key.unwrap(), 2 * i + 1);
-            let payload = cursor.get_table_payload().unwrap();
-            assert!(payload.is_some());
-            let (key, _) = payload.unwrap();
-            assert_eq!(key, 2 * i + 1);
+            queries.push(format!(
+                "INSERT INTO example(rowid, col1, col2) VALUES ({i}, {}, '{:0100}');",
+                i % 50,
+                i
+            ));
+        }
+        queries.push("DELETE FROM example WHERE rowid % 11 = 0;".to_string());
+        let file = create_sqlite_database(&queries.iter().map(|s| s.as_str()).collect::<Vec<_>>());
+        let pager = create_pager(file.as_file().try_clone().unwrap()).unwrap();
+        let bctx = load_btree_context(file.as_file()).unwrap();
+        let page_id = find_index_page_id("index1", file.path());
+        let mut expected = (0..2000).filter(|i| i % 11 != 0).collect::<Vec<i64>>();
+        expected.sort_by_key(|i| (i % 50, *i));
+
+        let get_rowid = |cursor: &BtreeCursor| {
+            cursor.get_index_payload().unwrap().map(|payload| {
+                let mut record = parse_record(&payload).unwrap();
+                let Some(Value::Integer(rowid)) = record.get(2).unwrap() else {
+                    panic!("rowid is not integer");
+                };
+                rowid
+            })
+        };
 
-            let cell_key = cursor.table_move_to(2 * i + 1).unwrap();
-            assert!(cell_key.is_some());
-            assert_eq!(cell_key.unwrap(), 2 * i + 1);
-            let payload = cursor.get_table_payload().unwrap();
-            assert!(payload.is_some());
-            let (key, _) = payload.unwrap();
-            assert_eq!(key, 2 * i + 1);
+        let mut cursor = BtreeCursor::new(page_id, &pager, &bctx).unwrap();
+        cursor.move_to_last().unwrap();
+        let mut rowids = Vec::new();
+        while let Some(rowid) = get_rowid(&cursor) {
+            rowids.push(rowid);
+            cursor.move_prev().unwrap();
         }
+        rowids.reverse();
+        assert_eq!(rowids, expected);
 
-        let cell_key = cursor.table_move_to(40002).unwrap();
-        assert!(cell_key.is_none());
-        let payload = cursor.get_table_payload().unwrap();
-        assert!(payload.is_none());
+        // Moving forward and backward.
+        cursor.move_to_first().unwrap();
+        for _ in 0..1000 {
+            cursor.move_next().unwrap();
+        }
+        cursor.move_prev().unwrap();
+        assert_eq!(get_rowid(&cursor), Some(expected[999]));
+
+        for col1 in [-1, 0, 7, 49, 50] {
+            let key = Value::Integer(col1);
+            let comparators = [Some(ValueCmp::new(&key, &Collation::Binary))];
+
+            cursor.index_move_to_last(&comparators, true).unwrap();
+            let expected_rowid = expected.iter().rev().find(|i| *i % 50 <= col1).copied();
+            assert_eq!(get_rowid(&cursor), expected_rowid, "col1 <= {col1}");
+
+            cursor.index_move_to_last(&comparators, false).unwrap();
+            let expected_rowid = expected.iter().rev().find(|i| *i % 50 < col1).copied();
+            assert_eq!(get_rowid(&cursor), expected_rowid, "col1 < {col1}");
+        }
     }
 
     #[test]
@@ -3154,6 +3850,63 @@ mod tests {
         drop(payload);
     }
 
+    #[test]
+    fn test_insert_table_update() {
+        let file = create_sqlite_database(&[
+            "CREATE TABLE example(col);",
+            "INSERT INTO example(col) VALUES (1);", // rowid = 1
+            "INSERT INTO example(col) VALUES (2);", // rowid = 2
+            "INSERT INTO example(col) VALUES (3);", // rowid = 3
+        ]);
+        let pager = create_pager(file.as_file().try_clone().unwrap()).unwrap();
+        let bctx = load_btree_context(file.as_file()).unwrap();
+        let table_page_id = find_table_page_id("example", file.path());
+        let data = vec![2; bctx.usable_size as usize * 2];
+
+        let mut cursor = BtreeCursor::new(table_page_id, &pager, &bctx).unwrap();
+
+        cursor
+            .table_insert(2, &SlicePayload::new(&[20]).unwrap())
+            .unwrap();
+        cursor.table_move_to(2).unwrap();
+        let (key, payload) = cursor.get_table_payload().unwrap().unwrap();
+        assert_eq!(key, 2);
+        assert_eq!(payload.buf(), &[20]);
+        drop(payload);
+
+        // Update with an overflowing payload.
+        cursor
+            .table_insert(2, &SlicePayload::new(&data).unwrap())
+            .unwrap();
+        cursor.table_move_to(2).unwrap();
+        let (key, payload) = cursor.get_table_payload().unwrap().unwrap();
+        assert_eq!(key, 2);
+        assert_eq!(payload.size().get() as usize, data.len());
+        drop(payload);
+        assert_eq!(pager.num_free_pages(), 0);
+
+        // The overflow pages are freed.
+        cursor
+            .table_insert(2, &SlicePayload::new(&[21]).unwrap())
+            .unwrap();
+        assert!(pager.num_free_pages() > 0);
+
+        cursor.move_to_first().unwrap();
+        let mut keys = Vec::new();
+        loop {
+            let Some((key, payload)) = cursor.get_table_payload().unwrap() else {
+                break;
+            };
+            keys.push(key);
+            if key == 2 {
+                assert_eq!(payload.buf(), &[21]);
+            }
+            drop(payload);
+            cursor.move_next().unwrap();
+        }
+        assert_eq!(keys, vec![1, 2, 3]);
+    }
+
     #[test]
     fn test_insert_table_max_page_size() {
         let file =
@@ -3283,7 +4036,7 @@ mod tests {
             let p_size = max_local + i;
             let p_size = (p_size as u64).try_into().unwrap();
             let n_local = bctx.n_local(true, p_size) as usize;
-            if (p_size.get() as usize - n_local) % (bctx.usable_size as usize - 4) == 0 {
+            if (p_size.get() as usize - n_local).is_multiple_of(bctx.usable_size as usize - 4) {
                 size_exact_overflow_page_size = Some(p_size);
                 break;
             }
@@ -4290,7 +5043,7 @@ mod tests {
             let p_size = max_local + i;
             let p_size = (p_size as u64).try_into().unwrap();
             let n_local = bctx.n_local(false, p_size) as usize;
-            if (p_size.get() as usize - n_local) % (bctx.usable_size as usize - 4) == 0 {
+            if (p_size.get() as usize - n_local).is_multiple_of(bctx.usable_size as usize - 4) {
                 size_exact_overflow_page_size = Some(p_size);
                 break;
             }
@@ -4611,6 +5364,56 @@ mod tests {
         assert!(cursor.get_index_payload().unwrap().is_none());
     }
 
+    #[test]
+    fn test_allocate_btree() {
+        let file = create_sqlite_database(&["CREATE TABLE example(col);"]);
+        let pager = create_pager(file.as_file().try_clone().unwrap()).unwrap();
+        let bctx = load_btree_context(file.as_file()).unwrap();
+
+        let table_page_id = allocate_btree(&pager, &bctx, true).unwrap();
+        let index_page_id = allocate_btree(&pager, &bctx, false).unwrap();
+        assert_eq!(table_page_id.get(), 3);
+        assert_eq!(index_page_id.get(), 4);
+
+        let mut cursor = BtreeCursor::new(table_page_id, &pager, &bctx).unwrap();
+        cursor.move_to_first().unwrap();
+        assert!(cursor.get_table_payload().unwrap().is_none());
+        cursor
+            .table_insert(1, &SlicePayload::new(&[2, 1, 2]).unwrap())
+            .unwrap();
+        cursor.move_to_first().unwrap();
+        assert_eq!(cursor.get_table_key().unwrap(), Some(1));
+
+        let mut cursor = BtreeCursor::new(index_page_id, &pager, &bctx).unwrap();
+        cursor.move_to_first().unwrap();
+        assert!(cursor.get_index_payload().unwrap().is_none());
+        assert!(matches!(cursor.get_table_payload(), Err(Error::NotTable)));
+    }
+
+    #[test]
+    fn test_drop_btree() {
+        let mut stmts = vec!["PRAGMA page_size = 512;", "CREATE TABLE example(col);"];
+        // The payloads overflow to overflow pages.
+        let insert_stmt = format!(
+            "INSERT INTO example(col) VALUES (x'{}');",
+            "11".repeat(1000)
+        );
+        for _ in 0..100 {
+            stmts.push(&insert_stmt);
+        }
+        let file = create_sqlite_database(&stmts);
+        let pager = create_pager(file.as_file().try_clone().unwrap()).unwrap();
+        let bctx = load_btree_context(file.as_file()).unwrap();
+        let table_page_id = find_table_page_id("example", file.path());
+        assert_eq!(pager.num_free_pages(), 0);
+        let n_pages = pager.num_pages();
+
+        drop_btree(&pager, &bctx, table_page_id).unwrap();
+
+        // All pages except page 1 are in the freelist.
+        assert_eq!(pager.num_free_pages(), n_pages - 1);
+    }
+
     #[test]
     fn test_clear() {
         let mut stmts = vec![
@@ -4910,17 +5713,19 @@ mod tests {
         while cursor.current_page.idx_cell != cursor.current_page.n_cells - 1 {
             cursor.move_next().unwrap();
         }
-        assert_eq!(cursor.get_table_key().unwrap().unwrap(), 2);
+        let tail_key = cursor.get_table_key().unwrap().unwrap();
+        assert!(tail_key < 7);
         cursor.delete().unwrap();
-        assert_eq!(cursor.get_table_key().unwrap().unwrap(), 3);
+        assert_eq!(cursor.get_table_key().unwrap().unwrap(), tail_key + 1);
 
-        // Delete the first leaf page.
-        for i in 0..2 {
-            cursor.table_move_to(i).unwrap();
+        // Delete the first entries.
+        let mut keys = (0..8).filter(|&i| i != tail_key).collect::<Vec<_>>();
+        for _ in 0..2 {
+            cursor.table_move_to(keys.remove(0)).unwrap();
             cursor.delete().unwrap();
         }
 
-        for i in 3..8 {
+        for &i in keys.iter() {
             let (key, payload) = cursor.get_table_payload().unwrap().unwrap();
             assert_eq!(key, i);
             assert_eq!(payload.buf(), &[i as u8; 100]);
@@ -4930,7 +5735,7 @@ mod tests {
         assert!(cursor.get_table_payload().unwrap().is_none());
 
         cursor.move_to_first().unwrap();
-        for _ in 0..5 {
+        for _ in 0..keys.len() {
             cursor.delete().unwrap();
         }
 
@@ -5248,55 +6053,37 @@ mod tests {
         }
         assert!(cursor.get_index_payload().unwrap().is_none());
 
+        let mut remaining = (0..20).collect::<Vec<_>>();
+
         // Delete the tail entry in a leaf page.
         cursor.move_to_first().unwrap();
         while cursor.current_page.idx_cell != cursor.current_page.n_cells - 1 {
             cursor.move_next().unwrap();
         }
-        assert_eq!(
-            cursor.get_index_payload().unwrap().unwrap().buf(),
-            payloads[1]
-        );
-        cursor.delete().unwrap();
-        assert_eq!(
-            cursor.get_index_payload().unwrap().unwrap().buf(),
-            payloads[2]
-        );
+        delete_index_entry(&mut cursor, &payloads, &mut remaining);
+        assert_index_entries(&mut cursor, &payloads, &remaining);
 
-        // Delete the first leaf page.
+        // Delete the first entry.
         cursor.move_to_first().unwrap();
-        cursor.delete().unwrap();
-
-        for expected in payloads.iter().take(20).skip(2) {
-            let payload = cursor.get_index_payload().unwrap().unwrap();
-            assert_eq!(payload.buf(), expected);
-            drop(payload);
-            cursor.move_next().unwrap();
-        }
-        assert!(cursor.get_index_payload().unwrap().is_none());
+        delete_index_entry(&mut cursor, &payloads, &mut remaining);
+        assert_index_entries(&mut cursor, &payloads, &remaining);
 
         // Delete the tail entry in a leaf page. Also the leaf page is the tail in the
         // upper interior page.
         cursor.move_to_first().unwrap();
-        while cursor.parent_pages.len() != 2
+        while cursor.parent_pages.is_empty()
             || cursor.current_page.idx_cell != cursor.current_page.n_cells - 1
-            || cursor.parent_pages[1].idx_cell != cursor.parent_pages[1].n_cells
+            || cursor.parent_pages.last().unwrap().idx_cell
+                != cursor.parent_pages.last().unwrap().n_cells
         {
             cursor.move_next().unwrap();
         }
-        assert_eq!(
-            cursor.get_index_payload().unwrap().unwrap().buf(),
-            payloads[7]
-        );
-        cursor.delete().unwrap();
-        assert_eq!(
-            cursor.get_index_payload().unwrap().unwrap().buf(),
-            payloads[8]
-        );
+        delete_index_entry(&mut cursor, &payloads, &mut remaining);
+        assert_index_entries(&mut cursor, &payloads, &remaining);
 
         cursor.move_to_first().unwrap();
-        for _ in 0..17 {
-            cursor.delete().unwrap();
+        while !remaining.is_empty() {
+            delete_index_entry(&mut cursor, &payloads, &mut remaining);
         }
 
         cursor.move_to_first().unwrap();
@@ -5305,6 +6092,96 @@ mod tests {
         assert_eq!(pager.num_pages() - pager.num_free_pages(), initial_pages);
     }
 
+    #[test]
+    fn test_delete_merge_pages() {
+        let file =
+            create_sqlite_database(&["PRAGMA page_size = 512;", "CREATE TABLE example(col);"]);
+        let pager = create_pager(file.as_file().try_clone().unwrap()).unwrap();
+        let bctx = load_btree_context(file.as_file()).unwrap();
+        let table_page_id = find_table_page_id("example", file.path());
+
+        let initial_pages = pager.num_pages();
+        let mut cursor = BtreeCursor::new(table_page_id, &pager, &bctx).unwrap();
+        for i in 0..400 {
+            cursor
+                .table_insert(i, &SlicePayload::new(&[i as u8; 100]).unwrap())
+                .unwrap();
+        }
+        cursor.move_to_first().unwrap();
+        assert_eq!(cursor.parent_pages.len(), 2);
+        let used_pages = pager.num_pages() - pager.num_free_pages();
+        assert!(used_pages > initial_pages + 100);
+
+        // Delete 9 of 10 entries.
+        for i in (0..400).filter(|i| i % 10 != 0) {
+            cursor.table_move_to(i).unwrap();
+            cursor.delete().unwrap();
+            if i + 1 < 400 {
+                assert_eq!(cursor.get_table_key().unwrap(), Some(i + 1));
+            }
+        }
+
+        cursor.move_to_first().unwrap();
+        for i in (0..400).step_by(10) {
+            let (key, payload) = cursor.get_table_payload().unwrap().unwrap();
+            assert_eq!(key, i);
+            assert_eq!(payload.buf(), &[i as u8; 100]);
+            drop(payload);
+            cursor.move_next().unwrap();
+        }
+        assert!(cursor.get_table_payload().unwrap().is_none());
+
+        // The emptied pages are freed and the tree gets shallower.
+        cursor.move_to_first().unwrap();
+        assert_eq!(cursor.parent_pages.len(), 1);
+        let used_pages = pager.num_pages() - pager.num_free_pages();
+        assert!(used_pages < initial_pages + 20, "{used_pages}");
+
+        // The root page gets a leaf page again.
+        for i in (0..390).step_by(10) {
+            cursor.table_move_to(i).unwrap();
+            cursor.delete().unwrap();
+        }
+        cursor.move_to_first().unwrap();
+        assert!(cursor.parent_pages.is_empty());
+        assert_eq!(cursor.get_table_key().unwrap(), Some(390));
+        assert_eq!(pager.num_pages() - pager.num_free_pages(), initial_pages);
+    }
+
+    /// Delete the current entry and check the cursor moves to the next entry.
+    fn delete_index_entry(
+        cursor: &mut BtreeCursor,
+        payloads: &[Vec<u8>],
+        remaining: &mut Vec<usize>,
+    ) {
+        let payload = cursor.get_index_payload().unwrap().unwrap().buf().to_vec();
+        let i = remaining
+            .iter()
+            .position(|&i| payloads[i] == payload)
+            .unwrap();
+        cursor.delete().unwrap();
+        remaining.remove(i);
+        assert_eq!(
+            cursor
+                .get_index_payload()
+                .unwrap()
+                .map(|payload| payload.buf().to_vec()),
+            remaining.get(i).map(|&i| payloads[i].clone())
+        );
+    }
+
+    fn assert_index_entries(cursor: &mut BtreeCursor, payloads: &[Vec<u8>], remaining: &[usize]) {
+        cursor.move_to_first().unwrap();
+        for &i in remaining {
+            assert_eq!(
+                cursor.get_index_payload().unwrap().unwrap().buf(),
+                &payloads[i]
+            );
+            cursor.move_next().unwrap();
+        }
+        assert!(cursor.get_index_payload().unwrap().is_none());
+    }
+
     #[test]
     fn test_delete_index_interior() {
         // Deleting entries from interior pages.
@@ -5347,93 +6224,47 @@ mod tests {
             payloads.push(payload);
         }
 
+        let mut remaining = (0..24).collect::<Vec<_>>();
+
         // Delete cell in root page.
         cursor.move_to_first().unwrap();
         assert!(cursor.parent_pages.len() >= 2);
         while !cursor.parent_pages.is_empty() {
             cursor.move_next().unwrap();
         }
-        let payload = cursor.get_index_payload().unwrap().unwrap();
-        assert_eq!(payload.buf(), &payloads[8]);
-        drop(payload);
-
-        cursor.delete().unwrap();
-        let payload = cursor.get_index_payload().unwrap().unwrap();
-        assert_eq!(payload.buf(), &payloads[9]);
-        drop(payload);
-
-        cursor.move_to_first().unwrap();
-        for expected in payloads.iter().take(8) {
-            assert_eq!(cursor.get_index_payload().unwrap().unwrap().buf(), expected);
-            cursor.move_next().unwrap();
-        }
-        for expected in payloads.iter().take(24).skip(9) {
-            assert_eq!(cursor.get_index_payload().unwrap().unwrap().buf(), expected);
-            cursor.move_next().unwrap();
-        }
-        assert!(cursor.get_index_payload().unwrap().is_none());
+        assert_eq!(
+            cursor.get_index_payload().unwrap().unwrap().buf(),
+            &payloads[8]
+        );
+        delete_index_entry(&mut cursor, &payloads, &mut remaining);
+        assert_index_entries(&mut cursor, &payloads, &remaining);
 
         // Delete cell in the second level interior page.
         cursor.move_to_first().unwrap();
         assert!(cursor.parent_pages.len() >= 2);
-        while cursor.parent_pages.len() != 1 {
-            cursor.move_next().unwrap();
-        }
-        let payload = cursor.get_index_payload().unwrap().unwrap();
-        assert_eq!(payload.buf(), &payloads[2]);
-        drop(payload);
-
-        cursor.delete().unwrap();
-        let payload = cursor.get_index_payload().unwrap().unwrap();
-        assert_eq!(payload.buf(), &payloads[3]);
-        drop(payload);
-
-        cursor.move_to_first().unwrap();
-        for expected in payloads.iter().take(2) {
-            assert_eq!(cursor.get_index_payload().unwrap().unwrap().buf(), expected);
-            cursor.move_next().unwrap();
-        }
-        for expected in payloads.iter().take(8).skip(3) {
-            assert_eq!(cursor.get_index_payload().unwrap().unwrap().buf(), expected);
-            cursor.move_next().unwrap();
-        }
-        for expected in payloads.iter().take(24).skip(9) {
-            assert_eq!(cursor.get_index_payload().unwrap().unwrap().buf(), expected);
+        while cursor.parent_pages.len() != 1 || cursor.current_page.page_type.is_leaf() {
             cursor.move_next().unwrap();
         }
-        assert!(cursor.get_index_payload().unwrap().is_none());
+        delete_index_entry(&mut cursor, &payloads, &mut remaining);
+        assert_index_entries(&mut cursor, &payloads, &remaining);
 
-        // Delete cell in the root page.
-        for _ in 0..6 {
+        // Delete cells in interior pages. The tree gets shallower.
+        loop {
             cursor.move_to_first().unwrap();
-            assert!(cursor.parent_pages.len() >= 2);
-            while !cursor.parent_pages.is_empty() {
-                cursor.move_next().unwrap();
+            if cursor.parent_pages.is_empty() {
+                break;
             }
-            cursor.delete().unwrap();
-        }
-        cursor.move_to_first().unwrap();
-        // Root page is empty.
-        assert_eq!(cursor.parent_pages[0].n_cells, 0);
-
-        // Delete cell in the second level page.
-        for _ in 0..12 {
-            cursor.move_to_first().unwrap();
-            assert!(cursor.parent_pages.len() >= 2);
-            while cursor.parent_pages.len() != 1 {
+            while cursor.current_page.page_type.is_leaf() {
                 cursor.move_next().unwrap();
             }
-            cursor.delete().unwrap();
+            delete_index_entry(&mut cursor, &payloads, &mut remaining);
+            assert_index_entries(&mut cursor, &payloads, &remaining);
         }
+        assert!(!remaining.is_empty());
 
         cursor.move_to_first().unwrap();
-        assert_eq!(cursor.parent_pages[0].n_cells, 0);
-        // Second level page is empty.
-        assert_eq!(cursor.parent_pages[1].n_cells, 0);
-
-        for _ in 0..4 {
-            cursor.move_to_first().unwrap();
-            cursor.delete().unwrap();
+        while !remaining.is_empty() {
+            delete_index_entry(&mut cursor, &payloads, &mut remaining);
         }
         assert!(cursor.get_index_payload().unwrap().is_none());
 
@@ -1,14 +1,28 @@
 use std::cmp::Ordering;
 use std::fmt::Display;
+use std::time::SystemTime;
 
+use crate::data_processing::aggregator::AggregateFunction;
+use crate::data_processing::aggregator::Aggregator;
 use crate::interpreter::parser::BinaryOp;
 use crate::interpreter::parser::CompareOp;
+use crate::interpreter::parser::CurrentTime;
 use crate::interpreter::parser::Expr;
+use crate::interpreter::parser::FunctionArgs;
+use crate::interpreter::parser::PatternOp;
 use crate::interpreter::parser::UnaryOp;
 use crate::interpreter::schema::calc_collation;
 use crate::interpreter::schema::calc_type_affinity;
 use crate::interpreter::schema::ColumnNumber;
 use crate::interpreter::schema::Table;
+use crate::interpreter::utils::glob;
+use crate::interpreter::utils::is_space;
+use crate::interpreter::utils::like;
+use crate::interpreter::utils::parse_float;
+use crate::interpreter::utils::parse_integer;
+use crate::interpreter::utils::unix_time_to_utc;
+use crate::interpreter::utils::utf8_read;
+use crate::interpreter::utils::ParseIntegerResult;
 use crate::interpreter::value::Buffer;
 use crate::interpreter::value::Collation;
 use crate::interpreter::value::ConstantValue;
@@ -22,6 +36,10 @@ pub enum Error {
     CollationNotFound,
     ColumnNotFound,
     NoTableContext,
+    NoSuchFunction(String),
+    WrongNumberOfArguments(String),
+    MisuseOfAggregate(&'static str),
+    InvalidEscape,
     FailGetColumn(Box<dyn std::error::Error + Sync + Send>),
 }
 
@@ -31,6 +49,10 @@ impl std::error::Error for Error {
             Self::CollationNotFound => None,
             Self::ColumnNotFound => None,
             Self::NoTableContext => None,
+            Self::NoSuchFunction(_) => None,
+            Self::WrongNumberOfArguments(_) => None,
+            Self::MisuseOfAggregate(_) => None,
+            Self::InvalidEscape => None,
             Self::FailGetColumn(e) => Some(e.as_ref()),
         }
     }
@@ -48,6 +70,18 @@ impl Display for Error {
             Self::NoTableContext => {
                 write!(f, "no table context")
             }
+            Self::NoSuchFunction(name) => {
+                write!(f, "no such function: {}", name)
+            }
+            Self::WrongNumberOfArguments(name) => {
+                write!(f, "wrong number of arguments to function {}()", name)
+            }
+            Self::MisuseOfAggregate(name) => {
+                write!(f, "misuse of aggregate function {}()", name)
+            }
+            Self::InvalidEscape => {
+                write!(f, "ESCAPE expression must be a single character")
+            }
             Self::FailGetColumn(e) => {
                 write!(f, "fail to get column: {}", e)
             }
@@ -77,14 +111,238 @@ fn filter_expression_collation(
     }
 }
 
+/// Select the collation of a binary operator from the collations of the
+/// operands.
+fn select_collation<'a>(
+    left: Option<(&'a Collation, CollateOrigin)>,
+    right: Option<(&'a Collation, CollateOrigin)>,
+) -> Option<(&'a Collation, CollateOrigin)> {
+    match (left, right) {
+        (None, _) => right,
+        (Some((_, CollateOrigin::Column)), Some((_, CollateOrigin::Expression))) => right,
+        _ => left,
+    }
+}
+
+/// Compare 2 non-NULL values after the type conversions prior to comparison.
+fn compare_values(
+    mut left: Value,
+    left_affinity: Option<TypeAffinity>,
+    mut right: Value,
+    right_affinity: Option<TypeAffinity>,
+    collation: Option<(&Collation, CollateOrigin)>,
+) -> Ordering {
+    // Type Conversions Prior To Comparison
+    match (left_affinity, right_affinity) {
+        (
+            Some(TypeAffinity::Integer) | Some(TypeAffinity::Real) | Some(TypeAffinity::Numeric),
+            Some(TypeAffinity::Text) | Some(TypeAffinity::Blob) | None,
+        ) => {
+            right = right.apply_numeric_affinity();
+        }
+        (
+            Some(TypeAffinity::Text) | Some(TypeAffinity::Blob) | None,
+            Some(TypeAffinity::Integer) | Some(TypeAffinity::Real) | Some(TypeAffinity::Numeric),
+        ) => {
+            left = left.apply_numeric_affinity();
+        }
+        (Some(TypeAffinity::Text), None) => {
+            right = right.apply_text_affinity();
+        }
+        (None, Some(TypeAffinity::Text)) => {
+            left = left.apply_text_affinity();
+        }
+        _ => {}
+    }
+    ValueCmp::new(
+        &left,
+        collation.map(|(c, _)| c).unwrap_or(&DEFAULT_COLLATION),
+    )
+    .compare(&right)
+}
+
+/// The three-valued AND of 2 boolean values.
+fn logical_and(left: Option<bool>, right: Option<bool>) -> Option<bool> {
+    match (left, right) {
+        (Some(false), _) | (_, Some(false)) => Some(false),
+        (Some(true), Some(true)) => Some(true),
+        _ => None,
+    }
+}
+
+/// Convert the operand of an arithmetic operator to [Value::Integer] or
+/// [Value::Real].
+///
+/// A text or a blob is converted from its numeric prefix. This comes from
+/// numericType() in vdbe.c of SQLite.
+fn numeric_operand(value: &Value) -> Value<'static> {
+    match value {
+        Value::Integer(i) => Value::Integer(*i),
+        Value::Real(d) => Value::Real(*d),
+        Value::Text(buf) | Value::Blob(buf) => {
+            let mut digits = buf.iter().skip_while(|b| is_space(**b));
+            let mut first = digits.next();
+            if let Some(b'+' | b'-') = first {
+                first = digits.next();
+            }
+            let has_number = match first {
+                Some(b) if b.is_ascii_digit() => true,
+                Some(b'.') => digits.next().is_some_and(|b| b.is_ascii_digit()),
+                _ => false,
+            };
+            if !has_number {
+                return Value::Integer(0);
+            }
+            let (_, pure_integer, d) = parse_float(buf);
+            if pure_integer {
+                if let (_, ParseIntegerResult::Integer(i)) = parse_integer(buf) {
+                    return Value::Integer(i);
+                }
+            }
+            Value::Real(d)
+        }
+    }
+}
+
+/// Evaluate an arithmetic operator for non-NULL operands.
+///
+/// Integer operations which overflow fall back to real operations. The result
+/// is NULL on division by zero. This comes from OP_Add in vdbe.c of SQLite.
+fn arithmetic(operator: BinaryOp, left: &Value, right: &Value) -> Option<Value<'static>> {
+    let left = numeric_operand(left);
+    let right = numeric_operand(right);
+    if let (Value::Integer(l), Value::Integer(r)) = (&left, &right) {
+        let (l, r) = (*l, *r);
+        let result = match operator {
+            BinaryOp::Add => l.checked_add(r),
+            BinaryOp::Sub => l.checked_sub(r),
+            BinaryOp::Mul => l.checked_mul(r),
+            BinaryOp::Div => {
+                if r == 0 {
+                    return None;
+                }
+                l.checked_div(r)
+            }
+            BinaryOp::Rem => {
+                if r == 0 {
+                    return None;
+                }
+                // i64::MIN % -1 overflows.
+                Some(l % if r == -1 { 1 } else { r })
+            }
+            _ => unreachable!("not an arithmetic operator: {:?}", operator),
+        };
+        if let Some(result) = result {
+            return Some(Value::Integer(result));
+        }
+    }
+    let as_real = |v: &Value| match v {
+        Value::Integer(i) => *i as f64,
+        Value::Real(d) => *d,
+        Value::Text(_) | Value::Blob(_) => unreachable!("numeric operand"),
+    };
+    let (l, r) = (as_real(&left), as_real(&right));
+    let result = match operator {
+        BinaryOp::Add => l + r,
+        BinaryOp::Sub => l - r,
+        BinaryOp::Mul => l * r,
+        BinaryOp::Div => {
+            if r == 0.0 {
+                return None;
+            }
+            l / r
+        }
+        BinaryOp::Rem => {
+            let l = left.as_integer();
+            let r = right.as_integer();
+            if r == 0 {
+                return None;
+            }
+            (l % if r == -1 { 1 } else { r }) as f64
+        }
+        _ => unreachable!("not an arithmetic operator: {:?}", operator),
+    };
+    if result.is_nan() {
+        None
+    } else {
+        Some(Value::Real(result))
+    }
+}
+
+/// Evaluate a bitwise operator. This comes from OP_BitAnd in vdbe.c of SQLite.
+fn bitwise(operator: BinaryOp, left: i64, right: i64) -> i64 {
+    match operator {
+        BinaryOp::BitAnd => left & right,
+        BinaryOp::BitOr => left | right,
+        BinaryOp::ShiftLeft | BinaryOp::ShiftRight => {
+            let mut shift_left = operator == BinaryOp::ShiftLeft;
+            let mut n = right;
+            // A negative shift is a shift in the opposite direction.
+            if n < 0 {
+                shift_left = !shift_left;
+                n = if n > -64 { -n } else { 64 };
+            }
+            if n >= 64 {
+                if left >= 0 || shift_left {
+                    0
+                } else {
+                    -1
+                }
+            } else if shift_left {
+                ((left as u64) << n) as i64
+            } else {
+                // Arithmetic shift keeps the sign.
+                left >> n
+            }
+        }
+        _ => unreachable!("not a bitwise operator: {:?}", operator),
+    }
+}
+
 pub trait DataContext {
     fn get_column_value(
         &self,
         column_idx: &ColumnNumber,
-    ) -> std::result::Result<Option<Value>, Box<dyn std::error::Error + Sync + Send>>;
+    ) -> std::result::Result<Option<Value<'_>>, Box<dyn std::error::Error + Sync + Send>>;
+
+    /// The result of the aggregate function of [Expression::Aggregate].
+    fn get_aggregate_value(
+        &self,
+        _aggregate_idx: usize,
+    ) -> std::result::Result<Option<Value<'_>>, Box<dyn std::error::Error + Sync + Send>> {
+        Err("no aggregate context".into())
+    }
+
+    /// The value of the row proposed for insertion of [Expression::Excluded].
+    fn get_excluded_value(
+        &self,
+        _column_idx: &ColumnNumber,
+    ) -> std::result::Result<Option<Value<'_>>, Box<dyn std::error::Error + Sync + Send>> {
+        Err("no excluded row".into())
+    }
 }
 
+/// An aggregate function call in an aggregate query.
 #[derive(Debug, Clone)]
+pub struct AggregateCall {
+    pub function: AggregateFunction,
+    /// None for count(*).
+    pub arg: Option<Expression>,
+}
+
+impl AggregateCall {
+    pub fn aggregator(&self) -> Aggregator {
+        let collation = self
+            .arg
+            .as_ref()
+            .and_then(|arg| arg.collation())
+            .map(|(collation, _)| collation.clone())
+            .unwrap_or(DEFAULT_COLLATION.clone());
+        Aggregator::new(self.function, collation)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
     Column((ColumnNumber, TypeAffinity, Collation)),
     UnaryOperator {
@@ -104,8 +362,49 @@ pub enum Expression {
         expr: Box<Expression>,
         type_affinity: TypeAffinity,
     },
+    Between {
+        expr: Box<Expression>,
+        low: Box<Expression>,
+        high: Box<Expression>,
+    },
+    InList {
+        expr: Box<Expression>,
+        list: Box<[Expression]>,
+    },
+    Like {
+        expr: Box<Expression>,
+        pattern: Box<Expression>,
+        escape: Option<Box<Expression>>,
+    },
+    Glob {
+        expr: Box<Expression>,
+        pattern: Box<Expression>,
+    },
     Null,
     Const(ConstantValue),
+    /// The parameter at the index which is NULL until a value is bound.
+    Parameter {
+        index: u32,
+        value: Option<ConstantValue>,
+    },
+    /// The result of the aggregate function at the index of the aggregate
+    /// calls.
+    Aggregate(usize),
+    /// The column of the row proposed for insertion, which is referred as
+    /// "excluded.column" in the DO UPDATE clause of an upsert.
+    Excluded((ColumnNumber, TypeAffinity, Collation)),
+    /// The text of the time when the expression is executed.
+    CurrentTime(CurrentTime),
+}
+
+/// The names which an expression can refer to in addition to the columns of
+/// the table.
+enum Scope<'a> {
+    Table,
+    /// Aggregate function calls are pushed to the vector.
+    Aggregate(&'a mut Vec<AggregateCall>),
+    /// The row proposed for insertion is referred as "excluded".
+    Upsert,
 }
 
 impl Expression {
@@ -115,21 +414,46 @@ impl Expression {
     }
 
     pub fn from(expr: Expr, table: Option<&Table>) -> Result<Self> {
+        Self::convert(expr, table, &mut Scope::Table)
+    }
+
+    /// Convert the expression of an aggregate query.
+    ///
+    /// Aggregate function calls are replaced with [Expression::Aggregate] and
+    /// pushed to `aggregates`.
+    pub fn from_aggregate(
+        expr: Expr,
+        table: Option<&Table>,
+        aggregates: &mut Vec<AggregateCall>,
+    ) -> Result<Self> {
+        Self::convert(expr, table, &mut Scope::Aggregate(aggregates))
+    }
+
+    /// Convert the expression of the DO UPDATE clause of an upsert.
+    ///
+    /// "excluded.column" is converted to [Expression::Excluded].
+    pub fn from_upsert(expr: Expr, table: &Table) -> Result<Self> {
+        Self::convert(expr, Some(table), &mut Scope::Upsert)
+    }
+
+    fn convert(expr: Expr, table: Option<&Table>, scope: &mut Scope) -> Result<Self> {
         match expr {
             Expr::Null => Ok(Self::Null),
             Expr::Integer(i) => Ok(Self::Const(ConstantValue::Integer(i))),
             Expr::Real(f) => Ok(Self::Const(ConstantValue::Real(f))),
             Expr::Text(text) => Ok(Self::Const(ConstantValue::Text(text.dequote()))),
             Expr::Blob(hex) => Ok(Self::Const(ConstantValue::Blob(hex.decode()))),
+            Expr::Parameter(index) => Ok(Self::Parameter { index, value: None }),
+            Expr::CurrentTime(current_time) => Ok(Self::CurrentTime(current_time)),
             Expr::UnaryOperator { operator, expr } => Ok(Self::UnaryOperator {
                 operator,
-                expr: Box::new(Self::from(*expr, table)?),
+                expr: Box::new(Self::convert(*expr, table, scope)?),
             }),
             Expr::Collate {
                 expr,
                 collation_name,
             } => Ok(Self::Collate {
-                expr: Box::new(Self::from(*expr, table)?),
+                expr: Box::new(Self::convert(*expr, table, scope)?),
                 collation: calc_collation(&collation_name).ok_or(Error::CollationNotFound)?,
             }),
             Expr::BinaryOperator {
@@ -138,8 +462,8 @@ impl Expression {
                 right,
             } => Ok(Self::BinaryOperator {
                 operator,
-                left: Box::new(Self::from(*left, table)?),
-                right: Box::new(Self::from(*right, table)?),
+                left: Box::new(Self::convert(*left, table, scope)?),
+                right: Box::new(Self::convert(*right, table, scope)?),
             }),
             Expr::Column(column_name) => {
                 if let Some(table) = table {
@@ -152,10 +476,254 @@ impl Expression {
                     Err(Error::NoTableContext)
                 }
             }
+            Expr::QualifiedColumn {
+                table_name,
+                column_name,
+            } => match (table, scope) {
+                (Some(table), Scope::Upsert)
+                    if table_name.dequote().eq_ignore_ascii_case(b"excluded") =>
+                {
+                    table
+                        .get_column(&column_name.dequote())
+                        .map(Self::Excluded)
+                        .ok_or(Error::ColumnNotFound)
+                }
+                // TODO: Resolve the columns qualified with the table name.
+                _ => Err(Error::ColumnNotFound),
+            },
             Expr::Cast { expr, type_name } => Ok(Self::Cast {
-                expr: Box::new(Self::from(*expr, table)?),
+                expr: Box::new(Self::convert(*expr, table, scope)?),
                 type_affinity: calc_type_affinity(&type_name),
             }),
+            Expr::Between { expr, low, high } => Ok(Self::Between {
+                expr: Box::new(Self::convert(*expr, table, scope)?),
+                low: Box::new(Self::convert(*low, table, scope)?),
+                high: Box::new(Self::convert(*high, table, scope)?),
+            }),
+            Expr::InList { expr, list } => Ok(Self::InList {
+                expr: Box::new(Self::convert(*expr, table, scope)?),
+                list: list
+                    .into_iter()
+                    .map(|e| Self::convert(e, table, scope))
+                    .collect::<Result<Box<[_]>>>()?,
+            }),
+            Expr::Pattern {
+                operator,
+                expr,
+                pattern,
+                escape,
+            } => {
+                let expr = Box::new(Self::convert(*expr, table, scope)?);
+                let pattern = Box::new(Self::convert(*pattern, table, scope)?);
+                match operator {
+                    PatternOp::Like => Ok(Self::Like {
+                        expr,
+                        pattern,
+                        escape: escape
+                            .map(|e| Self::convert(*e, table, scope).map(Box::new))
+                            .transpose()?,
+                    }),
+                    PatternOp::Glob if escape.is_some() => {
+                        Err(Error::WrongNumberOfArguments("glob".to_string()))
+                    }
+                    PatternOp::Glob => Ok(Self::Glob { expr, pattern }),
+                }
+            }
+            Expr::Function { name, args } => {
+                let name = name.dequote();
+                let function_name = || String::from_utf8_lossy(&name).into_owned();
+                let Some(function) = AggregateFunction::from_name(&name) else {
+                    return Err(Error::NoSuchFunction(function_name()));
+                };
+                // TODO: Support multi-argument min() and max() which are
+                // simple functions.
+                let (function, arg) = match args {
+                    FunctionArgs::Star if function == AggregateFunction::Count => {
+                        (AggregateFunction::CountAll, None)
+                    }
+                    FunctionArgs::Exprs(args)
+                        if args.is_empty() && function == AggregateFunction::Count =>
+                    {
+                        (AggregateFunction::CountAll, None)
+                    }
+                    FunctionArgs::Exprs(mut args) if args.len() == 1 => {
+                        (function, Some(args.pop().unwrap()))
+                    }
+                    _ => return Err(Error::WrongNumberOfArguments(function_name())),
+                };
+                let Scope::Aggregate(aggregates) = scope else {
+                    return Err(Error::MisuseOfAggregate(function.name()));
+                };
+                // Aggregate functions can not be nested.
+                let arg = arg.map(|arg| Self::from(arg, table)).transpose()?;
+                aggregates.push(AggregateCall { function, arg });
+                Ok(Self::Aggregate(aggregates.len() - 1))
+            }
+        }
+    }
+
+    /// The collation of the expression.
+    ///
+    /// This follows the rules of [Expression::execute()] except that the
+    /// collation does not depend on whether values are NULL.
+    pub fn collation(&self) -> Option<(&Collation, CollateOrigin)> {
+        match self {
+            Self::Column((_, _, collation)) | Self::Excluded((_, _, collation)) => {
+                Some((collation, CollateOrigin::Column))
+            }
+            Self::UnaryOperator { expr, .. } => filter_expression_collation(expr.collation()),
+            Self::Collate { collation, .. } => Some((collation, CollateOrigin::Expression)),
+            Self::BinaryOperator { left, right, .. } => {
+                filter_expression_collation(select_collation(left.collation(), right.collation()))
+            }
+            Self::Cast { expr, .. } => expr.collation(),
+            Self::Between { .. }
+            | Self::InList { .. }
+            | Self::Like { .. }
+            | Self::Glob { .. }
+            | Self::Null
+            | Self::Const(_)
+            | Self::Parameter { .. }
+            | Self::Aggregate(_)
+            | Self::CurrentTime(_) => None,
+        }
+    }
+
+    /// Collect the columns referenced by the expression without duplicates.
+    pub fn collect_columns(&self, columns: &mut Vec<ColumnNumber>) {
+        match self {
+            Self::Column((column, _, _)) => {
+                if !columns.contains(column) {
+                    columns.push(*column);
+                }
+            }
+            Self::UnaryOperator { expr, .. }
+            | Self::Collate { expr, .. }
+            | Self::Cast { expr, .. } => expr.collect_columns(columns),
+            Self::BinaryOperator { left, right, .. } => {
+                left.collect_columns(columns);
+                right.collect_columns(columns);
+            }
+            Self::Between { expr, low, high } => {
+                expr.collect_columns(columns);
+                low.collect_columns(columns);
+                high.collect_columns(columns);
+            }
+            Self::InList { expr, list } => {
+                expr.collect_columns(columns);
+                for e in list.iter() {
+                    e.collect_columns(columns);
+                }
+            }
+            Self::Like {
+                expr,
+                pattern,
+                escape,
+            } => {
+                expr.collect_columns(columns);
+                pattern.collect_columns(columns);
+                if let Some(escape) = escape {
+                    escape.collect_columns(columns);
+                }
+            }
+            Self::Glob { expr, pattern } => {
+                expr.collect_columns(columns);
+                pattern.collect_columns(columns);
+            }
+            Self::Null
+            | Self::Const(_)
+            | Self::Parameter { .. }
+            | Self::Aggregate(_)
+            | Self::Excluded(_)
+            | Self::CurrentTime(_) => {}
+        }
+    }
+
+    /// Bind the value to the parameters at the index. None binds NULL.
+    pub fn bind_parameter(&mut self, index: u32, value: &Option<ConstantValue>) {
+        match self {
+            Self::Parameter {
+                index: parameter_index,
+                value: parameter_value,
+            } => {
+                if *parameter_index == index {
+                    *parameter_value = value.clone();
+                }
+            }
+            Self::UnaryOperator { expr, .. }
+            | Self::Collate { expr, .. }
+            | Self::Cast { expr, .. } => expr.bind_parameter(index, value),
+            Self::BinaryOperator { left, right, .. } => {
+                left.bind_parameter(index, value);
+                right.bind_parameter(index, value);
+            }
+            Self::Between { expr, low, high } => {
+                expr.bind_parameter(index, value);
+                low.bind_parameter(index, value);
+                high.bind_parameter(index, value);
+            }
+            Self::InList { expr, list } => {
+                expr.bind_parameter(index, value);
+                for e in list.iter_mut() {
+                    e.bind_parameter(index, value);
+                }
+            }
+            Self::Like {
+                expr,
+                pattern,
+                escape,
+            } => {
+                expr.bind_parameter(index, value);
+                pattern.bind_parameter(index, value);
+                if let Some(escape) = escape {
+                    escape.bind_parameter(index, value);
+                }
+            }
+            Self::Glob { expr, pattern } => {
+                expr.bind_parameter(index, value);
+                pattern.bind_parameter(index, value);
+            }
+            Self::Column(_)
+            | Self::Null
+            | Self::Const(_)
+            | Self::Aggregate(_)
+            | Self::Excluded(_)
+            | Self::CurrentTime(_) => {}
+        }
+    }
+
+    /// Whether the expression contains parameters.
+    pub fn has_parameter(&self) -> bool {
+        match self {
+            Self::Parameter { .. } => true,
+            Self::UnaryOperator { expr, .. }
+            | Self::Collate { expr, .. }
+            | Self::Cast { expr, .. } => expr.has_parameter(),
+            Self::BinaryOperator { left, right, .. } => {
+                left.has_parameter() || right.has_parameter()
+            }
+            Self::Between { expr, low, high } => {
+                expr.has_parameter() || low.has_parameter() || high.has_parameter()
+            }
+            Self::InList { expr, list } => {
+                expr.has_parameter() || list.iter().any(Self::has_parameter)
+            }
+            Self::Like {
+                expr,
+                pattern,
+                escape,
+            } => {
+                expr.has_parameter()
+                    || pattern.has_parameter()
+                    || escape.as_ref().is_some_and(|e| e.has_parameter())
+            }
+            Self::Glob { expr, pattern } => expr.has_parameter() || pattern.has_parameter(),
+            Self::Column(_)
+            | Self::Null
+            | Self::Const(_)
+            | Self::Aggregate(_)
+            | Self::Excluded(_)
+            | Self::CurrentTime(_) => false,
         }
     }
 
@@ -166,8 +734,14 @@ impl Expression {
         match self {
             Self::Column((idx, affinity, collation)) => {
                 if let Some(row) = row {
+                    let mut value = row.get_column_value(idx).map_err(Error::FailGetColumn)?;
+                    // SQLite stores real values without fractional part in
+                    // REAL columns as integers.
+                    if let (TypeAffinity::Real, Some(Value::Integer(i))) = (affinity, &value) {
+                        value = Some(Value::Real(*i as f64));
+                    }
                     Ok((
-                        row.get_column_value(idx).map_err(Error::FailGetColumn)?,
+                        value,
                         Some(*affinity),
                         Some((collation, CollateOrigin::Column)),
                     ))
@@ -179,11 +753,15 @@ impl Expression {
                 let (value, _, collation) = expr.execute(row)?;
                 let value = match operator {
                     UnaryOp::BitNot => value.map(|v| Value::Integer(!v.as_integer())),
-                    UnaryOp::Minus => value.map(|v| match v {
-                        Value::Integer(i) => Value::Integer(-i),
+                    UnaryOp::Minus => value.map(|v| match numeric_operand(&v) {
+                        Value::Integer(i) => match i.checked_neg() {
+                            Some(i) => Value::Integer(i),
+                            None => Value::Real(-(i as f64)),
+                        },
                         Value::Real(d) => Value::Real(-d),
-                        Value::Text(_) | Value::Blob(_) => Value::Integer(0),
+                        Value::Text(_) | Value::Blob(_) => unreachable!("numeric operand"),
                     }),
+                    UnaryOp::Not => value.map(|v| Value::Integer(!v.as_bool() as i64)),
                 };
                 Ok((value, None, filter_expression_collation(collation)))
             }
@@ -196,6 +774,32 @@ impl Expression {
                     Some((collation, CollateOrigin::Expression)),
                 ))
             }
+            Self::BinaryOperator {
+                operator: operator @ (BinaryOp::And | BinaryOp::Or),
+                left,
+                right,
+            } => {
+                // Three-valued logic where NULL is unknown.
+                let (left_value, _, _) = left.execute(row)?;
+                let left_bool = left_value.map(|v| v.as_bool());
+                // The result is determined only by the left value.
+                let determined = *operator == BinaryOp::Or;
+                let result = if left_bool == Some(determined) {
+                    Some(determined)
+                } else {
+                    let (right_value, _, _) = right.execute(row)?;
+                    match (left_bool, right_value.map(|v| v.as_bool())) {
+                        (_, Some(right_bool)) if right_bool == determined => Some(determined),
+                        (Some(_), Some(_)) => Some(!determined),
+                        _ => None,
+                    }
+                };
+                Ok((
+                    result.map(|result| Value::Integer(result as i64)),
+                    None,
+                    None,
+                ))
+            }
             Self::BinaryOperator {
                 operator,
                 left,
@@ -204,55 +808,41 @@ impl Expression {
                 let (left_value, left_affinity, left_collation) = left.execute(row)?;
                 let (right_value, right_affinity, right_collation) = right.execute(row)?;
 
+                let collation = select_collation(left_collation, right_collation);
+                let next_collation = filter_expression_collation(collation);
+
+                if *operator == BinaryOp::Is {
+                    let result = match (left_value, right_value) {
+                        (None, None) => true,
+                        (None, _) | (_, None) => false,
+                        (Some(left_value), Some(right_value)) => {
+                            compare_values(
+                                left_value,
+                                left_affinity,
+                                right_value,
+                                right_affinity,
+                                collation,
+                            ) == Ordering::Equal
+                        }
+                    };
+                    return Ok((Some(Value::Integer(result as i64)), None, next_collation));
+                }
+
                 // TODO: Confirm whether collation is preserved after NULL.
-                let (mut left_value, mut right_value) = match (left_value, right_value) {
+                let (left_value, right_value) = match (left_value, right_value) {
                     (None, _) | (_, None) => return Ok((None, None, None)),
                     (Some(left_value), Some(right_value)) => (left_value, right_value),
                 };
 
-                let collation = match (left_collation, right_collation) {
-                    (None, _) => right_collation,
-                    (Some((_, CollateOrigin::Column)), Some((_, CollateOrigin::Expression))) => {
-                        right_collation
-                    }
-                    _ => left_collation,
-                };
-                let next_collation = filter_expression_collation(collation);
-
                 match operator {
                     BinaryOp::Compare(compare_op) => {
-                        // Type Conversions Prior To Comparison
-                        match (left_affinity, right_affinity) {
-                            (
-                                Some(TypeAffinity::Integer)
-                                | Some(TypeAffinity::Real)
-                                | Some(TypeAffinity::Numeric),
-                                Some(TypeAffinity::Text) | Some(TypeAffinity::Blob) | None,
-                            ) => {
-                                right_value = right_value.apply_numeric_affinity();
-                            }
-                            (
-                                Some(TypeAffinity::Text) | Some(TypeAffinity::Blob) | None,
-                                Some(TypeAffinity::Integer)
-                                | Some(TypeAffinity::Real)
-                                | Some(TypeAffinity::Numeric),
-                            ) => {
-                                left_value = left_value.apply_numeric_affinity();
-                            }
-                            (Some(TypeAffinity::Text), None) => {
-                                right_value = right_value.apply_text_affinity();
-                            }
-                            (None, Some(TypeAffinity::Text)) => {
-                                left_value = left_value.apply_text_affinity();
-                            }
-                            _ => {}
-                        }
-
-                        let cmp = ValueCmp::new(
-                            &left_value,
-                            collation.map(|(c, _)| c).unwrap_or(&DEFAULT_COLLATION),
-                        )
-                        .compare(&right_value);
+                        let cmp = compare_values(
+                            left_value,
+                            left_affinity,
+                            right_value,
+                            right_affinity,
+                            collation,
+                        );
 
                         let result = match compare_op {
                             CompareOp::Eq => cmp == Ordering::Equal,
@@ -288,6 +878,30 @@ impl Expression {
                             next_collation,
                         ))
                     }
+                    BinaryOp::Add
+                    | BinaryOp::Sub
+                    | BinaryOp::Mul
+                    | BinaryOp::Div
+                    | BinaryOp::Rem => Ok((
+                        arithmetic(*operator, &left_value, &right_value),
+                        None,
+                        next_collation,
+                    )),
+                    BinaryOp::BitAnd
+                    | BinaryOp::BitOr
+                    | BinaryOp::ShiftLeft
+                    | BinaryOp::ShiftRight => Ok((
+                        Some(Value::Integer(bitwise(
+                            *operator,
+                            left_value.as_integer(),
+                            right_value.as_integer(),
+                        ))),
+                        None,
+                        next_collation,
+                    )),
+                    BinaryOp::And | BinaryOp::Or | BinaryOp::Is => {
+                        unreachable!("operator with special handling: {:?}", operator)
+                    }
                 }
             }
             Self::Cast {
@@ -301,8 +915,164 @@ impl Expression {
                     collation,
                 ))
             }
+            Self::Between { expr, low, high } => {
+                // "x BETWEEN low AND high" is "x >= low AND x <= high" except
+                // that x is evaluated only once.
+                let (value, affinity, collation) = expr.execute(row)?;
+                let compare = |bound: &'a Expression, expected: Ordering| {
+                    let (bound_value, bound_affinity, bound_collation) = bound.execute(row)?;
+                    Ok(match (&value, bound_value) {
+                        (Some(value), Some(bound_value)) => Some(
+                            compare_values(
+                                value.clone(),
+                                affinity,
+                                bound_value,
+                                bound_affinity,
+                                select_collation(collation, bound_collation),
+                            ) != expected,
+                        ),
+                        _ => None,
+                    })
+                };
+                let ge = compare(low, Ordering::Less)?;
+                let result = if ge == Some(false) {
+                    Some(false)
+                } else {
+                    logical_and(ge, compare(high, Ordering::Greater)?)
+                };
+                Ok((result.map(|v| Value::Integer(v as i64)), None, None))
+            }
+            Self::InList { expr, list } => {
+                // The affinity and the collation of the left operand are used
+                // for all comparisons.
+                let (value, affinity, collation) = expr.execute(row)?;
+                let Some(value) = value else {
+                    let result = if list.is_empty() {
+                        Some(Value::Integer(0))
+                    } else {
+                        None
+                    };
+                    return Ok((result, None, None));
+                };
+                let mut has_null = false;
+                for item in list.iter() {
+                    let (item_value, _, _) = item.execute(row)?;
+                    let Some(item_value) = item_value else {
+                        has_null = true;
+                        continue;
+                    };
+                    if compare_values(value.clone(), affinity, item_value, None, collation)
+                        == Ordering::Equal
+                    {
+                        return Ok((Some(Value::Integer(1)), None, None));
+                    }
+                }
+                if has_null {
+                    Ok((None, None, None))
+                } else {
+                    Ok((Some(Value::Integer(0)), None, None))
+                }
+            }
+            Self::Like {
+                expr,
+                pattern,
+                escape,
+            } => execute_pattern(PatternOp::Like, expr, pattern, escape.as_deref(), row),
+            Self::Glob { expr, pattern } => {
+                execute_pattern(PatternOp::Glob, expr, pattern, None, row)
+            }
             Self::Null => Ok((None, None, None)),
             Self::Const(value) => Ok((Some(value.as_value()), None, None)),
+            Self::Parameter { value, .. } => {
+                Ok((value.as_ref().map(ConstantValue::as_value), None, None))
+            }
+            Self::Aggregate(idx) => {
+                if let Some(row) = row {
+                    Ok((
+                        row.get_aggregate_value(*idx)
+                            .map_err(Error::FailGetColumn)?,
+                        None,
+                        None,
+                    ))
+                } else {
+                    Err(Error::NoTableContext)
+                }
+            }
+            Self::Excluded((idx, affinity, collation)) => {
+                if let Some(row) = row {
+                    Ok((
+                        row.get_excluded_value(idx).map_err(Error::FailGetColumn)?,
+                        Some(*affinity),
+                        Some((collation, CollateOrigin::Column)),
+                    ))
+                } else {
+                    Err(Error::NoTableContext)
+                }
+            }
+            Self::CurrentTime(current_time) => Ok((
+                Some(Value::Text(Buffer::Owned(format_current_time(
+                    *current_time,
+                    SystemTime::now(),
+                )))),
+                None,
+                None,
+            )),
         }
     }
 }
+
+/// Format the time as CURRENT_DATE, CURRENT_TIME or CURRENT_TIMESTAMP of
+/// SQLite.
+fn format_current_time(current_time: CurrentTime, now: SystemTime) -> Vec<u8> {
+    let secs = match now.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    };
+    let (year, month, day, hour, minute, second) = unix_time_to_utc(secs);
+    let text = match current_time {
+        CurrentTime::Date => format!("{year:04}-{month:02}-{day:02}"),
+        CurrentTime::Time => format!("{hour:02}:{minute:02}:{second:02}"),
+        CurrentTime::Timestamp => {
+            format!("{year:04}-{month:02}-{day:02} {hour:02}:{minute:02}:{second:02}")
+        }
+    };
+    text.into_bytes()
+}
+
+/// Execute LIKE or GLOB. This comes from likeFunc() in func.c of SQLite.
+fn execute_pattern<'a, D: DataContext>(
+    operator: PatternOp,
+    expr: &'a Expression,
+    pattern: &'a Expression,
+    escape: Option<&'a Expression>,
+    row: Option<&'a D>,
+) -> ExecutionResult<'a> {
+    let (value, _, _) = expr.execute(row)?;
+    let (pattern_value, _, _) = pattern.execute(row)?;
+    let escape = match escape {
+        Some(escape) => {
+            let (escape_value, _, _) = escape.execute(row)?;
+            let Some(escape_value) = escape_value else {
+                return Ok((None, None, None));
+            };
+            let escape = escape_value.force_text_buffer();
+            let mut pos = 0;
+            let c = utf8_read(&escape, &mut pos);
+            if escape.is_empty() || pos != escape.len() {
+                return Err(Error::InvalidEscape);
+            }
+            Some(c)
+        }
+        None => None,
+    };
+    let (Some(value), Some(pattern_value)) = (value, pattern_value) else {
+        return Ok((None, None, None));
+    };
+    let text = value.force_text_buffer();
+    let pattern_text = pattern_value.force_text_buffer();
+    let result = match operator {
+        PatternOp::Like => like(&pattern_text, &text, escape),
+        PatternOp::Glob => glob(&pattern_text, &text),
+    };
+    Ok((Some(Value::Integer(result as i64)), None, None))
+}
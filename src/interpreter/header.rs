@@ -16,6 +16,8 @@ use crate::interpreter::pager::PageId;
 use crate::interpreter::pager::MAX_PAGE_SIZE;
 
 const MAGIC_HEADER: &[u8; 16] = b"SQLite format 3\0";
+/// The SQLite version written to the header of new databases.
+const SQLITE_VERSION_NUMBER: u32 = 3045000;
 pub const DATABASE_HEADER_SIZE: usize = 100;
 pub struct DatabaseHeader<'a>(&'a [u8; DATABASE_HEADER_SIZE]);
 
@@ -49,10 +51,23 @@ impl<'a> DatabaseHeader<'a> {
         (self.0[16] as u32) << 8 | (self.0[17] as u32) << 16
     }
 
+    /// Whether the database is in the WAL mode.
+    ///
+    /// The file format read version is 2 in the WAL mode.
+    pub fn is_wal_mode(&self) -> bool {
+        self.0[19] == 2
+    }
+
     pub fn reserved(&self) -> u8 {
         self.0[20]
     }
 
+    /// The file change counter is incremented on every commit in the rollback
+    /// journal mode.
+    pub fn change_counter(&self) -> u32 {
+        u32::from_be_bytes(self.0[24..28].try_into().unwrap())
+    }
+
     pub fn n_pages(&self) -> u32 {
         u32::from_be_bytes(self.0[28..32].try_into().unwrap())
     }
@@ -64,6 +79,11 @@ impl<'a> DatabaseHeader<'a> {
     pub fn n_freelist_pages(&self) -> u32 {
         u32::from_be_bytes(self.0[36..40].try_into().unwrap())
     }
+
+    /// The schema cookie is incremented whenever the database schema changes.
+    pub fn schema_cookie(&self) -> u32 {
+        u32::from_be_bytes(self.0[40..44].try_into().unwrap())
+    }
 }
 
 pub struct DatabaseHeaderMut<'a>(&'a mut [u8; DATABASE_HEADER_SIZE]);
@@ -73,6 +93,46 @@ impl<'a> DatabaseHeaderMut<'a> {
         Self(buf)
     }
 
+    /// Initialize the header of a new database with a single page in the same
+    /// way as SQLite.
+    ///
+    /// `pagesize` must be a power of 2 between 512 and 65536.
+    pub fn init(&mut self, pagesize: u32) {
+        self.0.fill(0);
+        self.0[0..16].copy_from_slice(MAGIC_HEADER);
+        // 65536 is stored as 1.
+        self.0[16] = (pagesize >> 8) as u8;
+        self.0[17] = (pagesize >> 16) as u8;
+        self.set_wal_mode(false);
+        // Maximum and minimum embedded payload fractions and leaf payload
+        // fraction.
+        self.0[21] = 64;
+        self.0[22] = 32;
+        self.0[23] = 32;
+        self.set_change_counter(1);
+        self.set_n_pages(1);
+        // Schema format number.
+        self.0[44..48].copy_from_slice(&4_u32.to_be_bytes());
+        // Text encoding: UTF-8.
+        self.0[56..60].copy_from_slice(&1_u32.to_be_bytes());
+        self.0[96..100].copy_from_slice(&SQLITE_VERSION_NUMBER.to_be_bytes());
+    }
+
+    /// Set the file format write and read versions for the WAL mode or the
+    /// rollback journal mode.
+    pub fn set_wal_mode(&mut self, wal: bool) {
+        let version = if wal { 2 } else { 1 };
+        self.0[18] = version;
+        self.0[19] = version;
+    }
+
+    /// Set the file change counter and the version-valid-for number which marks
+    /// the database size in the header valid.
+    pub fn set_change_counter(&mut self, counter: u32) {
+        self.0[24..28].copy_from_slice(&counter.to_be_bytes());
+        self.0[92..96].copy_from_slice(&counter.to_be_bytes());
+    }
+
     pub fn set_n_pages(&mut self, n_pages: u32) {
         self.0[28..32].copy_from_slice(&n_pages.to_be_bytes());
     }
@@ -89,6 +149,10 @@ impl<'a> DatabaseHeaderMut<'a> {
     pub fn set_n_freelist_pages(&mut self, pages: u32) {
         self.0[36..40].copy_from_slice(&pages.to_be_bytes());
     }
+
+    pub fn set_schema_cookie(&mut self, cookie: u32) {
+        self.0[40..44].copy_from_slice(&cookie.to_be_bytes());
+    }
 }
 
 #[cfg(test)]
@@ -146,6 +210,91 @@ mod tests {
         assert_eq!(header.n_pages(), 3);
     }
 
+    #[test]
+    fn schema_cookie() {
+        let file =
+            create_sqlite_database(&["CREATE TABLE example(col);", "CREATE TABLE example2(col);"]);
+        let mut buf = fs::read(file.path()).unwrap();
+
+        let header = DatabaseHeader::from(buf[0..DATABASE_HEADER_SIZE].try_into().unwrap());
+        assert_eq!(header.schema_cookie(), 2);
+
+        let mut_buf = &mut buf[0..DATABASE_HEADER_SIZE];
+        let mut header = DatabaseHeaderMut::from(mut_buf.try_into().unwrap());
+        header.set_schema_cookie(10);
+
+        let header = DatabaseHeader::from(buf[0..DATABASE_HEADER_SIZE].try_into().unwrap());
+        assert_eq!(header.schema_cookie(), 10);
+    }
+
+    #[test]
+    fn change_counter() {
+        let file = create_sqlite_database(&["CREATE TABLE example(col);"]);
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        let buf = fs::read(file.path()).unwrap();
+        let header = DatabaseHeader::from(buf[0..DATABASE_HEADER_SIZE].try_into().unwrap());
+        let counter = header.change_counter();
+        assert_eq!(buf[92..96], counter.to_be_bytes());
+
+        conn.execute("INSERT INTO example(col) VALUES (1);", [])
+            .unwrap();
+        let mut buf = fs::read(file.path()).unwrap();
+        let header = DatabaseHeader::from(buf[0..DATABASE_HEADER_SIZE].try_into().unwrap());
+        assert_eq!(header.change_counter(), counter + 1);
+
+        let mut_buf = &mut buf[0..DATABASE_HEADER_SIZE];
+        let mut header = DatabaseHeaderMut::from(mut_buf.try_into().unwrap());
+        header.set_change_counter(10);
+        assert_eq!(buf[24..28], 10_u32.to_be_bytes());
+        assert_eq!(buf[92..96], 10_u32.to_be_bytes());
+    }
+
+    #[test]
+    fn wal_mode() {
+        let file = create_sqlite_database(&["CREATE TABLE example(col);"]);
+        let mut buf = fs::read(file.path()).unwrap();
+        let header = DatabaseHeader::from(buf[0..DATABASE_HEADER_SIZE].try_into().unwrap());
+        assert!(!header.is_wal_mode());
+
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        conn.execute_batch("PRAGMA journal_mode = WAL;").unwrap();
+        conn.close().unwrap();
+        let wal_buf = fs::read(file.path()).unwrap();
+        let header = DatabaseHeader::from(wal_buf[0..DATABASE_HEADER_SIZE].try_into().unwrap());
+        assert!(header.is_wal_mode());
+
+        let mut_buf = &mut buf[0..DATABASE_HEADER_SIZE];
+        let mut header = DatabaseHeaderMut::from(mut_buf.try_into().unwrap());
+        header.set_wal_mode(true);
+        assert_eq!(buf[18..20], wal_buf[18..20]);
+        let header = DatabaseHeader::from(buf[0..DATABASE_HEADER_SIZE].try_into().unwrap());
+        assert!(header.is_wal_mode());
+
+        let mut_buf = &mut buf[0..DATABASE_HEADER_SIZE];
+        let mut header = DatabaseHeaderMut::from(mut_buf.try_into().unwrap());
+        header.set_wal_mode(false);
+        assert_eq!(buf[18..20], [1, 1]);
+    }
+
+    #[test]
+    fn init() {
+        for pagesize in [512, 4096, 65536] {
+            let mut buf = [0xff; DATABASE_HEADER_SIZE];
+            DatabaseHeaderMut::from(&mut buf).init(pagesize);
+
+            let header = DatabaseHeader::from(&buf);
+            assert!(header.validate().is_ok());
+            assert_eq!(header.pagesize(), pagesize);
+            assert!(!header.is_wal_mode());
+            assert_eq!(header.reserved(), 0);
+            assert_eq!(header.change_counter(), 1);
+            assert_eq!(header.n_pages(), 1);
+            assert_eq!(header.first_freelist_trunk_page_id(), None);
+            assert_eq!(header.n_freelist_pages(), 0);
+            assert_eq!(header.schema_cookie(), 0);
+        }
+    }
+
     #[test]
     fn validate_database_header() {
         let file = create_sqlite_database(&["CREATE TABLE example(col);"]);
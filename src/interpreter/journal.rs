@@ -0,0 +1,495 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The rollback journal compatible with SQLite.
+//!
+//! https://www.sqlite.org/fileformat2.html#the_rollback_journal
+
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::interpreter::pager::PageId;
+
+const JOURNAL_MAGIC: [u8; 8] = [0xd9, 0xd5, 0x05, 0xf9, 0x20, 0xa1, 0x63, 0xd7];
+const JOURNAL_HEADER_SIZE: usize = 28;
+/// The size of the journal header written by this implementation. Page records
+/// start from this offset.
+const SECTOR_SIZE: u32 = 512;
+const MAX_SECTOR_SIZE: u32 = 0x10000;
+/// nRec in the header meaning that the number of records is computed from the
+/// journal size.
+const N_RECORDS_UNKNOWN: u32 = u32::MAX;
+
+/// The path of the rollback journal for the database file.
+pub fn journal_path(db_path: &Path) -> PathBuf {
+    let mut path = OsString::from(db_path.as_os_str());
+    path.push("-journal");
+    PathBuf::from(path)
+}
+
+/// The checksum of a page record.
+///
+/// SQLite samples every 200th byte from the end of the page.
+fn checksum(nonce: u32, data: &[u8]) -> u32 {
+    let mut cksum = nonce;
+    let mut i = data.len() as isize - 200;
+    while i > 0 {
+        cksum = cksum.wrapping_add(data[i as usize] as u32);
+        i -= 200;
+    }
+    cksum
+}
+
+/// The rollback journal of a transaction.
+///
+/// The original content of each page is appended to the journal before the page
+/// is modified for the first time in the transaction. The journal header is
+/// made valid ("hot") only after all records are synced to the disk, and the
+/// database file is written only after that. The transaction is committed when
+/// the journal is deleted.
+pub struct Journal {
+    path: PathBuf,
+    pagesize: u32,
+    file: Option<File>,
+    nonce: u32,
+    n_records: u32,
+    /// The pages saved in the journal.
+    pages: HashSet<PageId>,
+    /// Whether the journal header is made valid. The database file may have
+    /// been modified.
+    is_hot: bool,
+}
+
+impl Journal {
+    pub fn new(path: PathBuf, pagesize: u32) -> Self {
+        Self {
+            path,
+            pagesize,
+            file: None,
+            nonce: 0,
+            n_records: 0,
+            pages: HashSet::new(),
+            is_hot: false,
+        }
+    }
+
+    /// Whether the original content of the page is already in the journal.
+    pub fn contains(&self, page_id: PageId) -> bool {
+        self.pages.contains(&page_id)
+    }
+
+    /// Append the original content of the page.
+    ///
+    /// The journal file is created on the first call. `n_pages` is the size of
+    /// the database before the transaction.
+    pub fn append(&mut self, page_id: PageId, buf: &[u8], n_pages: u32) -> io::Result<()> {
+        assert_eq!(buf.len(), self.pagesize as usize);
+        if self.is_hot {
+            return Err(io::Error::other("hot journal is not rolled back"));
+        }
+        if self.file.is_none() {
+            self.nonce = rand::random();
+            let mut header = [0_u8; SECTOR_SIZE as usize];
+            // The magic number and nRec are zero until the records are synced.
+            header[12..16].copy_from_slice(&self.nonce.to_be_bytes());
+            header[16..20].copy_from_slice(&n_pages.to_be_bytes());
+            header[20..24].copy_from_slice(&SECTOR_SIZE.to_be_bytes());
+            header[24..28].copy_from_slice(&self.pagesize.to_be_bytes());
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&self.path)?;
+            self.file = Some(file);
+            check_crash_point()?;
+            self.file().write_all_at(&header, 0)?;
+        }
+
+        let record_size = self.pagesize as usize + 8;
+        let mut record = Vec::with_capacity(record_size);
+        record.extend_from_slice(&page_id.get().to_be_bytes());
+        record.extend_from_slice(buf);
+        record.extend_from_slice(&checksum(self.nonce, buf).to_be_bytes());
+        let offset = SECTOR_SIZE as u64 + self.n_records as u64 * record_size as u64;
+        check_crash_point()?;
+        self.file().write_all_at(&record, offset)?;
+        self.n_records += 1;
+        self.pages.insert(page_id);
+        Ok(())
+    }
+
+    /// Sync the records and make the journal hot.
+    ///
+    /// This must be called before writing any pages to the database file. This
+    /// is no-op if no page is journaled.
+    pub fn sync(&mut self) -> io::Result<()> {
+        if self.file.is_none() || self.is_hot {
+            return Ok(());
+        }
+        check_crash_point()?;
+        self.file().sync_data()?;
+        let mut header = [0_u8; 12];
+        header[..8].copy_from_slice(&JOURNAL_MAGIC);
+        header[8..12].copy_from_slice(&self.n_records.to_be_bytes());
+        check_crash_point()?;
+        self.file().write_all_at(&header, 0)?;
+        self.is_hot = true;
+        check_crash_point()?;
+        self.file().sync_data()?;
+        // The journal must survive a power loss once the database file is
+        // modified, as SQLite syncs the directory of a new journal.
+        sync_directory(&self.path)
+    }
+
+    /// Whether the journal file exists.
+    pub fn is_open(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// Delete the journal file to commit the transaction.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.file.is_some() {
+            check_crash_point()?;
+            fs::remove_file(&self.path)?;
+            self.reset();
+            return sync_directory(&self.path);
+        }
+        self.reset();
+        Ok(())
+    }
+
+    /// Restore the database file from the journal and delete the journal.
+    ///
+    /// Returns whether the database file was possibly modified. If this fails,
+    /// the journal file is kept to be rolled back later.
+    pub fn rollback(&mut self, db: &File) -> io::Result<bool> {
+        if !self.is_hot {
+            // The database file is not modified yet. The journal which is not hot
+            // is ignored even if it fails to be deleted.
+            let result = self.finish();
+            self.reset();
+            return result.map(|_| false);
+        }
+        playback(db, self.file())?;
+        check_crash_point()?;
+        fs::remove_file(&self.path)?;
+        self.reset();
+        Ok(true)
+    }
+
+    fn file(&self) -> &File {
+        self.file.as_ref().unwrap()
+    }
+
+    fn reset(&mut self) {
+        self.file = None;
+        self.n_records = 0;
+        self.pages.clear();
+        self.is_hot = false;
+    }
+}
+
+/// Sync the directory containing the file to persist its creation or deletion.
+fn sync_directory(path: &Path) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    check_crash_point()?;
+    File::open(dir)?.sync_all()
+}
+
+/// Roll back the hot journal left by a crashed transaction if any.
+///
+/// Returns whether the database file is restored from the journal.
+pub fn rollback_hot_journal(db: &File, path: &Path) -> io::Result<bool> {
+    let journal = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    let mut magic = [0_u8; 8];
+    let is_hot = journal.metadata()?.len() >= JOURNAL_HEADER_SIZE as u64
+        && db.metadata()?.len() > 0
+        && journal.read_exact_at(&mut magic, 0).is_ok()
+        && magic == JOURNAL_MAGIC;
+    if is_hot {
+        playback(db, &journal)?;
+    }
+    check_crash_point()?;
+    fs::remove_file(path)?;
+    Ok(is_hot)
+}
+
+/// Write back the original pages in the journal to the database file and
+/// truncate it to the original size.
+///
+/// The playback stops at the first invalid header or record since they may be
+/// partially written.
+fn playback(db: &File, journal: &File) -> io::Result<()> {
+    let journal_size = journal.metadata()?.len();
+    let mut offset = 0;
+    let mut original_size = None;
+    'segments: while offset + JOURNAL_HEADER_SIZE as u64 <= journal_size {
+        let mut header = [0_u8; JOURNAL_HEADER_SIZE];
+        journal.read_exact_at(&mut header, offset)?;
+        if header[..8] != JOURNAL_MAGIC {
+            break;
+        }
+        let read_u32 = |i: usize| u32::from_be_bytes(header[i..i + 4].try_into().unwrap());
+        let n_records = read_u32(8);
+        let nonce = read_u32(12);
+        let n_pages = read_u32(16);
+        let sector_size = read_u32(20);
+        let pagesize = read_u32(24);
+        if !(512..=65536).contains(&pagesize)
+            || !pagesize.is_power_of_two()
+            || !(32..=MAX_SECTOR_SIZE).contains(&sector_size)
+            || !sector_size.is_power_of_two()
+        {
+            break;
+        }
+        original_size.get_or_insert((n_pages, pagesize));
+
+        offset += sector_size as u64;
+        let record_size = pagesize as u64 + 8;
+        let n_records = if n_records == N_RECORDS_UNKNOWN {
+            journal_size.saturating_sub(offset) / record_size
+        } else {
+            n_records as u64
+        };
+        let mut record = vec![0_u8; record_size as usize];
+        for _ in 0..n_records {
+            if offset + record_size > journal_size {
+                break 'segments;
+            }
+            journal.read_exact_at(&mut record, offset)?;
+            let page_id = u32::from_be_bytes(record[..4].try_into().unwrap());
+            let data = &record[4..4 + pagesize as usize];
+            let cksum = u32::from_be_bytes(record[4 + pagesize as usize..].try_into().unwrap());
+            if page_id == 0 || cksum != checksum(nonce, data) {
+                break 'segments;
+            }
+            check_crash_point()?;
+            db.write_all_at(data, (page_id - 1) as u64 * pagesize as u64)?;
+            offset += record_size;
+        }
+        // The next header starts from the sector boundary.
+        offset = offset.next_multiple_of(sector_size as u64);
+    }
+    if let Some((n_pages, pagesize)) = original_size {
+        check_crash_point()?;
+        db.set_len(n_pages as u64 * pagesize as u64)?;
+    }
+    check_crash_point()?;
+    db.sync_data()?;
+    Ok(())
+}
+
+#[cfg(test)]
+thread_local! {
+    static CRASH_POINT: std::cell::Cell<Option<usize>> = const { std::cell::Cell::new(None) };
+}
+
+/// Simulate a crash after `n` more write operations on the database file and
+/// the journal. All write operations after the crash fail.
+///
+/// This is used by test only.
+#[cfg(test)]
+pub fn set_crash_point(n: Option<usize>) {
+    CRASH_POINT.with(|c| c.set(n));
+}
+
+/// Fails if the simulated crash happened.
+#[cfg(test)]
+pub fn check_crash_point() -> io::Result<()> {
+    CRASH_POINT.with(|c| match c.get() {
+        Some(0) => Err(io::Error::other("simulated crash")),
+        Some(n) => {
+            c.set(Some(n - 1));
+            Ok(())
+        }
+        None => Ok(()),
+    })
+}
+
+#[cfg(not(test))]
+#[inline(always)]
+pub fn check_crash_point() -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page_id(id: u32) -> PageId {
+        PageId::new(id).unwrap()
+    }
+
+    #[test]
+    fn test_journal_path() {
+        assert_eq!(
+            journal_path(Path::new("/tmp/test.db")),
+            PathBuf::from("/tmp/test.db-journal")
+        );
+    }
+
+    #[test]
+    fn test_checksum() {
+        let mut data = vec![0_u8; 1024];
+        assert_eq!(checksum(10, &data), 10);
+        // Sampled bytes: 824, 624, 424, 224, 24.
+        data[824] = 1;
+        data[24] = 2;
+        data[825] = 100;
+        data[0] = 100;
+        assert_eq!(checksum(10, &data), 13);
+        assert_eq!(checksum(u32::MAX, &data), 2);
+    }
+
+    #[test]
+    fn test_journal_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db-journal");
+        let mut journal = Journal::new(path.clone(), 512);
+        assert!(!journal.is_open());
+
+        journal.append(page_id(2), &[2; 512], 3).unwrap();
+        journal.append(page_id(1), &[1; 512], 3).unwrap();
+        assert!(journal.is_open());
+        assert!(journal.contains(page_id(1)));
+        assert!(journal.contains(page_id(2)));
+        assert!(!journal.contains(page_id(3)));
+
+        let content = fs::read(&path).unwrap();
+        assert_eq!(content.len(), 512 + 2 * (512 + 8));
+        // Not hot until synced.
+        assert_eq!(content[..12], [0; 12]);
+        assert_eq!(content[16..20], 3_u32.to_be_bytes());
+        assert_eq!(content[20..24], 512_u32.to_be_bytes());
+        assert_eq!(content[24..28], 512_u32.to_be_bytes());
+        assert_eq!(content[512..516], 2_u32.to_be_bytes());
+        assert_eq!(content[516..1028], [2; 512]);
+        assert_eq!(content[1032..1036], 1_u32.to_be_bytes());
+
+        journal.sync().unwrap();
+        let content = fs::read(&path).unwrap();
+        assert_eq!(content[..8], JOURNAL_MAGIC);
+        assert_eq!(content[8..12], 2_u32.to_be_bytes());
+
+        journal.finish().unwrap();
+        assert!(!path.exists());
+        assert!(!journal.is_open());
+        assert!(!journal.contains(page_id(1)));
+    }
+
+    #[test]
+    fn test_sync_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db-journal");
+        let mut journal = Journal::new(path.clone(), 512);
+        // Creating the journal, and writing the header and the record.
+        journal.append(page_id(1), &[1; 512], 1).unwrap();
+
+        // The directory is synced after the journal is made hot.
+        set_crash_point(Some(3));
+        assert!(journal.sync().is_err());
+        set_crash_point(None);
+        assert!(journal.rollback(&tempfile::tempfile().unwrap()).unwrap());
+
+        journal.append(page_id(1), &[1; 512], 1).unwrap();
+        set_crash_point(Some(4));
+        journal.sync().unwrap();
+        set_crash_point(None);
+
+        // The directory is synced after deleting the journal. The journal is
+        // closed even if the sync fails.
+        set_crash_point(Some(1));
+        assert!(journal.finish().is_err());
+        set_crash_point(None);
+        assert!(!path.exists());
+        assert!(!journal.is_open());
+    }
+
+    #[test]
+    fn test_rollback() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db-journal");
+        let db = tempfile::tempfile().unwrap();
+        db.write_all_at(&[1; 512], 0).unwrap();
+        db.write_all_at(&[2; 512], 512).unwrap();
+
+        // Not synced yet.
+        let mut journal = Journal::new(path.clone(), 512);
+        journal.append(page_id(2), &[2; 512], 2).unwrap();
+        db.write_all_at(&[3; 512], 512).unwrap();
+        assert!(!journal.rollback(&db).unwrap());
+        assert!(!path.exists());
+
+        journal.append(page_id(2), &[2; 512], 2).unwrap();
+        journal.append(page_id(1), &[1; 512], 2).unwrap();
+        journal.sync().unwrap();
+        db.write_all_at(&[4; 512], 0).unwrap();
+        db.write_all_at(&[4; 512], 1024).unwrap();
+        assert!(journal.rollback(&db).unwrap());
+        assert!(!path.exists());
+
+        let mut buf = [0; 1024];
+        db.read_exact_at(&mut buf, 0).unwrap();
+        assert_eq!(buf[..512], [1; 512]);
+        assert_eq!(buf[512..], [2; 512]);
+        assert_eq!(db.metadata().unwrap().len(), 1024);
+    }
+
+    #[test]
+    fn test_rollback_hot_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db-journal");
+        let db = tempfile::tempfile().unwrap();
+        db.write_all_at(&[1; 1024], 0).unwrap();
+        assert!(!rollback_hot_journal(&db, &path).unwrap());
+
+        // Not hot.
+        let mut journal = Journal::new(path.clone(), 512);
+        journal.append(page_id(1), &[5; 512], 2).unwrap();
+        assert!(!rollback_hot_journal(&db, &path).unwrap());
+        assert!(!path.exists());
+        let mut buf = [0; 1024];
+        db.read_exact_at(&mut buf, 0).unwrap();
+        assert_eq!(buf, [1; 1024]);
+
+        let mut journal = Journal::new(path.clone(), 512);
+        journal.append(page_id(1), &[5; 512], 2).unwrap();
+        journal.append(page_id(2), &[6; 512], 2).unwrap();
+        journal.sync().unwrap();
+        // Corrupt the second record.
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        // The checksum samples the byte at 112.
+        file.write_all_at(&[7], 512 + 520 + 4 + 112).unwrap();
+        assert!(rollback_hot_journal(&db, &path).unwrap());
+        assert!(!path.exists());
+        db.read_exact_at(&mut buf, 0).unwrap();
+        assert_eq!(buf[..512], [5; 512]);
+        assert_eq!(buf[512..], [1; 512]);
+    }
+}
@@ -0,0 +1,271 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Advisory locks on the database file compatible with SQLite.
+//!
+//! https://www.sqlite.org/lockingv3.html
+//!
+//! The lock levels are represented by POSIX advisory locks on the bytes at 1GB
+//! offset of the database file, the same as the unix VFS of SQLite.
+//!
+//! On Linux, open file description locks are used so that connections in the
+//! same process also exclude each other. They conflict with the traditional
+//! record locks used by SQLite.
+
+use std::cell::Cell;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+
+const PENDING_BYTE: u64 = 0x40000000;
+const RESERVED_BYTE: u64 = PENDING_BYTE + 1;
+const SHARED_FIRST: u64 = PENDING_BYTE + 2;
+const SHARED_SIZE: u64 = 510;
+
+#[cfg(target_os = "linux")]
+const F_SETLK: libc::c_int = libc::F_OFD_SETLK;
+#[cfg(target_os = "linux")]
+const F_GETLK: libc::c_int = libc::F_OFD_GETLK;
+#[cfg(not(target_os = "linux"))]
+const F_SETLK: libc::c_int = libc::F_SETLK;
+#[cfg(not(target_os = "linux"))]
+const F_GETLK: libc::c_int = libc::F_GETLK;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LockLevel {
+    None,
+    /// Reading the database.
+    Shared,
+    /// Planning to write the database. Other connections can still read.
+    Reserved,
+    /// Waiting for readers to finish to write the database. No new reader is
+    /// allowed.
+    Pending,
+    /// Writing the database file.
+    Exclusive,
+}
+
+fn new_flock(lock_type: libc::c_int, start: u64, len: u64) -> libc::flock {
+    // SAFETY: flock is a plain C struct.
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = lock_type as libc::c_short;
+    lock.l_whence = libc::SEEK_SET as libc::c_short;
+    lock.l_start = start as libc::off_t;
+    lock.l_len = len as libc::off_t;
+    lock
+}
+
+/// The lock of a connection on the database file.
+pub struct FileLock {
+    file: File,
+    level: Cell<LockLevel>,
+}
+
+impl FileLock {
+    /// `file` must share the open file description with the file used to read
+    /// and write the database (e.g. File::try_clone()).
+    pub fn new(file: File) -> Self {
+        Self {
+            file,
+            level: Cell::new(LockLevel::None),
+        }
+    }
+
+    /// The database file.
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    pub fn level(&self) -> LockLevel {
+        self.level.get()
+    }
+
+    /// Upgrade the lock to the level.
+    ///
+    /// Returns false if other connections hold conflicting locks. If acquiring
+    /// the exclusive lock fails, the pending lock is kept to block new readers.
+    pub fn lock(&self, level: LockLevel) -> io::Result<bool> {
+        let current = self.level.get();
+        if current >= level {
+            return Ok(true);
+        }
+        assert!(level != LockLevel::Pending, "pending lock is internal");
+        match level {
+            LockLevel::None | LockLevel::Pending => unreachable!(),
+            LockLevel::Shared => {
+                // A pending lock blocks new readers.
+                if !self.set_lock(libc::F_RDLCK, PENDING_BYTE, 1)? {
+                    return Ok(false);
+                }
+                let result = self.set_lock(libc::F_RDLCK, SHARED_FIRST, SHARED_SIZE);
+                self.set_lock(libc::F_UNLCK, PENDING_BYTE, 1)?;
+                if !result? {
+                    return Ok(false);
+                }
+            }
+            LockLevel::Reserved => {
+                assert_eq!(current, LockLevel::Shared);
+                if !self.set_lock(libc::F_WRLCK, RESERVED_BYTE, 1)? {
+                    return Ok(false);
+                }
+            }
+            LockLevel::Exclusive => {
+                assert!(current >= LockLevel::Shared);
+                if current < LockLevel::Pending {
+                    if !self.set_lock(libc::F_WRLCK, PENDING_BYTE, 1)? {
+                        return Ok(false);
+                    }
+                    self.level.set(LockLevel::Pending);
+                }
+                if !self.set_lock(libc::F_WRLCK, SHARED_FIRST, SHARED_SIZE)? {
+                    return Ok(false);
+                }
+            }
+        }
+        self.level.set(level);
+        Ok(true)
+    }
+
+    /// Downgrade the lock to the shared lock or release the lock.
+    pub fn unlock(&self, level: LockLevel) -> io::Result<()> {
+        assert!(level <= LockLevel::Shared);
+        let current = self.level.get();
+        if current <= level {
+            return Ok(());
+        }
+        if level == LockLevel::Shared {
+            if current == LockLevel::Exclusive {
+                self.set_lock(libc::F_RDLCK, SHARED_FIRST, SHARED_SIZE)?;
+            }
+            // Release the pending and reserved locks.
+            self.set_lock(libc::F_UNLCK, PENDING_BYTE, 2)?;
+        } else {
+            self.set_lock(libc::F_UNLCK, PENDING_BYTE, 2 + SHARED_SIZE)?;
+        }
+        self.level.set(level);
+        Ok(())
+    }
+
+    /// Whether another connection holds the reserved lock or higher.
+    pub fn is_reserved_by_others(&self) -> io::Result<bool> {
+        let mut lock = new_flock(libc::F_WRLCK, RESERVED_BYTE, 1);
+        // SAFETY: lock is a valid flock struct.
+        if unsafe { libc::fcntl(self.file.as_raw_fd(), F_GETLK, &mut lock) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(lock.l_type != libc::F_UNLCK as libc::c_short)
+    }
+
+    /// Returns false if the lock conflicts with locks of other connections.
+    fn set_lock(&self, lock_type: libc::c_int, start: u64, len: u64) -> io::Result<bool> {
+        let lock = new_flock(lock_type, start, len);
+        // SAFETY: lock is a valid flock struct.
+        if unsafe { libc::fcntl(self.file.as_raw_fd(), F_SETLK, &lock) } < 0 {
+            let e = io::Error::last_os_error();
+            match e.raw_os_error() {
+                Some(libc::EAGAIN) | Some(libc::EACCES) => Ok(false),
+                _ => Err(e),
+            }
+        } else {
+            Ok(true)
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = self.unlock(LockLevel::None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_locks() -> (tempfile::NamedTempFile, FileLock, FileLock) {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let lock1 = FileLock::new(file.reopen().unwrap());
+        let lock2 = FileLock::new(file.reopen().unwrap());
+        (file, lock1, lock2)
+    }
+
+    #[test]
+    fn test_shared_lock() {
+        let (_file, lock1, lock2) = new_locks();
+        assert!(lock1.lock(LockLevel::Shared).unwrap());
+        assert!(lock2.lock(LockLevel::Shared).unwrap());
+        assert_eq!(lock1.level(), LockLevel::Shared);
+        assert!(!lock1.lock(LockLevel::Exclusive).unwrap());
+        // The pending lock blocks new readers.
+        assert_eq!(lock1.level(), LockLevel::Pending);
+        lock2.unlock(LockLevel::None).unwrap();
+        assert!(!lock2.lock(LockLevel::Shared).unwrap());
+        assert!(lock1.lock(LockLevel::Exclusive).unwrap());
+        lock1.unlock(LockLevel::Shared).unwrap();
+        assert!(lock2.lock(LockLevel::Shared).unwrap());
+    }
+
+    #[test]
+    fn test_reserved_lock() {
+        let (_file, lock1, lock2) = new_locks();
+        assert!(lock1.lock(LockLevel::Shared).unwrap());
+        assert!(lock2.lock(LockLevel::Shared).unwrap());
+        assert!(!lock1.is_reserved_by_others().unwrap());
+        assert!(lock1.lock(LockLevel::Reserved).unwrap());
+        assert!(!lock1.is_reserved_by_others().unwrap());
+        assert!(lock2.is_reserved_by_others().unwrap());
+        // Only one connection can hold the reserved lock.
+        assert!(!lock2.lock(LockLevel::Reserved).unwrap());
+        assert_eq!(lock2.level(), LockLevel::Shared);
+
+        lock1.unlock(LockLevel::Shared).unwrap();
+        assert!(!lock2.is_reserved_by_others().unwrap());
+        assert!(lock2.lock(LockLevel::Reserved).unwrap());
+    }
+
+    #[test]
+    fn test_exclusive_lock() {
+        let (_file, lock1, lock2) = new_locks();
+        assert!(lock1.lock(LockLevel::Shared).unwrap());
+        assert!(lock1.lock(LockLevel::Exclusive).unwrap());
+        assert!(!lock2.lock(LockLevel::Shared).unwrap());
+        assert_eq!(lock2.level(), LockLevel::None);
+        drop(lock1);
+        assert!(lock2.lock(LockLevel::Shared).unwrap());
+    }
+
+    #[test]
+    fn test_sqlite_compatibility() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        conn.execute("CREATE TABLE example(col);", []).unwrap();
+        let lock = FileLock::new(file.reopen().unwrap());
+
+        conn.execute_batch("BEGIN IMMEDIATE;").unwrap();
+        assert!(lock.lock(LockLevel::Shared).unwrap());
+        assert!(lock.is_reserved_by_others().unwrap());
+        assert!(!lock.lock(LockLevel::Reserved).unwrap());
+        // The exclusive lock of SQLite is blocked by the shared lock.
+        conn.execute("INSERT INTO example(col) VALUES (1);", [])
+            .unwrap();
+        assert!(conn.execute_batch("COMMIT;").is_err());
+        lock.unlock(LockLevel::None).unwrap();
+        conn.execute_batch("COMMIT;").unwrap();
+
+        conn.execute_batch("BEGIN EXCLUSIVE;").unwrap();
+        assert!(!lock.lock(LockLevel::Shared).unwrap());
+        conn.execute_batch("COMMIT;").unwrap();
+        assert!(lock.lock(LockLevel::Shared).unwrap());
+    }
+}
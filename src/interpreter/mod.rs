@@ -2,13 +2,17 @@ pub mod btree;
 pub mod cursor;
 pub mod expression;
 pub mod header;
+pub mod journal;
+pub mod lock;
 pub mod pager;
 pub mod parser;
 pub mod payload;
 pub mod query;
 pub mod record;
 pub mod schema;
+pub mod sorter;
 pub mod token;
 pub mod utils;
 pub mod value;
+pub mod wal;
 pub mod test_utils;
\ No newline at end of file
@@ -27,13 +27,20 @@ use std::num::NonZeroU32;
 use std::ops::Deref;
 use std::ops::DerefMut;
 use std::os::unix::fs::FileExt;
+use std::path::Path;
 use std::rc::Rc;
 
 use crate::interpreter::header::DatabaseHeader;
 use crate::interpreter::header::DatabaseHeaderMut;
 use crate::interpreter::header::DATABASE_HEADER_SIZE;
+use crate::interpreter::journal::check_crash_point;
+use crate::interpreter::journal::Journal;
 use crate::interpreter::payload::CopiablePayload;
 use crate::interpreter::payload::PayloadSize;
+use crate::interpreter::wal::Wal;
+use crate::storage::cache::CacheStats;
+use crate::storage::cache::Eviction;
+use crate::storage::cache::LruCache;
 
 /// Page 1 is special:
 ///
@@ -44,6 +51,20 @@ pub const PAGE_ID_1: PageId = NonZeroU32::MIN;
 pub const MAX_PAGE_SIZE: usize = 65536;
 /// The maximum page id is 4294967294.
 const MAX_PAGE_ID: u32 = u32::MAX - 1;
+/// The number of frames in the WAL to trigger a checkpoint on commit.
+const WAL_AUTO_CHECKPOINT: u32 = 1000;
+/// The same as the default cache_size of SQLite.
+pub const DEFAULT_CACHE_SIZE: CacheSize = CacheSize::Bytes(2000 * 1024);
+
+/// The capacity of the page cache.
+///
+/// Pages in use and pages modified in the transaction are kept in the cache
+/// even if the cache is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheSize {
+    Pages(usize),
+    Bytes(usize),
+}
 
 /// Page id starts from 1.
 pub type PageId = NonZeroU32;
@@ -158,6 +179,32 @@ pub struct Pager {
     first_freelist_trunk_page_id: Cell<Option<PageId>>,
     n_freelist_pages: Cell<u32>,
     usable_size: u32,
+    /// None if the rollback journal is disabled.
+    journal: Option<RefCell<Journal>>,
+    /// Some if the pager is in the WAL mode.
+    wal: Option<RefCell<Wal>>,
+    /// The file change counter in the database header of the cached pages.
+    change_counter: Cell<Option<u32>>,
+    /// The savepoints in the transaction, from the outermost.
+    savepoints: RefCell<Vec<Savepoint>>,
+}
+
+/// The state of the pager when a savepoint is started.
+struct Savepoint {
+    n_pages: u32,
+    /// The content of the pages before they are modified for the first time
+    /// after the savepoint. None if the page was not modified in the
+    /// transaction, i.e. the content is the same as the committed one.
+    pages: HashMap<PageId, Option<Vec<u8>>>,
+}
+
+impl Savepoint {
+    fn new(n_pages: u32) -> Self {
+        Self {
+            n_pages,
+            pages: HashMap::new(),
+        }
+    }
 }
 
 impl Pager {
@@ -180,15 +227,131 @@ impl Pager {
         }
         Ok(Self {
             file,
-            cache: PageCache::new(pagesize),
+            cache: PageCache::new(pagesize, DEFAULT_CACHE_SIZE),
             n_pages: Cell::new(n_pages),
             n_pages_stable: Cell::new(n_pages),
             first_freelist_trunk_page_id: Cell::new(first_freelist_trunk_page_id),
             n_freelist_pages: Cell::new(n_freelist_pages),
             usable_size,
+            journal: None,
+            wal: None,
+            change_counter: Cell::new(None),
+            savepoints: RefCell::new(Vec::new()),
         })
     }
 
+    /// Enable the rollback journal.
+    ///
+    /// If the pager is in the WAL mode, all the frames are checkpointed and the
+    /// WAL file is deleted.
+    pub fn set_journal(&mut self, journal: Journal, wal_path: &Path) -> Result<()> {
+        if let Some(wal) = &self.wal {
+            wal.try_borrow_mut()?.checkpoint(&self.file)?;
+            std::fs::remove_file(wal_path)?;
+            self.wal = None;
+        }
+        self.journal = Some(RefCell::new(journal));
+        Ok(())
+    }
+
+    /// Switch to the WAL mode.
+    ///
+    /// The committed pages in the WAL are loaded.
+    pub fn set_wal(&mut self, wal: Wal) -> Result<()> {
+        self.journal = None;
+        self.wal = Some(RefCell::new(wal));
+        self.reload()
+    }
+
+    /// Whether the pager is in the WAL mode.
+    pub fn is_wal_mode(&self) -> bool {
+        self.wal.is_some()
+    }
+
+    /// Load the pages committed by other connections.
+    ///
+    /// In the rollback journal mode, the changes are detected by the file change
+    /// counter in the database header. This must be called at the beginning of a
+    /// transaction. Returns whether any pages are changed.
+    pub fn refresh(&self) -> Result<bool> {
+        let is_changed = if let Some(wal) = &self.wal {
+            wal.try_borrow_mut()?.refresh()?
+        } else {
+            let mut buf = [0; DATABASE_HEADER_SIZE];
+            self.file.read_exact_at(&mut buf, 0)?;
+            self.change_counter.get() != Some(DatabaseHeader::from(&buf).change_counter())
+        };
+        if is_changed {
+            self.reload()?;
+        }
+        Ok(is_changed)
+    }
+
+    /// Copy all the pages in the WAL back to the database file.
+    ///
+    /// This must not be called in a write transaction. This is no-op if the
+    /// pager is not in the WAL mode.
+    pub fn checkpoint(&self) -> Result<()> {
+        if let Some(wal) = &self.wal {
+            wal.try_borrow_mut()?.checkpoint(&self.file)?;
+        }
+        Ok(())
+    }
+
+    /// Drop all the cached pages and reload the database size and the header.
+    pub fn reload(&self) -> Result<()> {
+        self.cache.map.borrow_mut().clear();
+        let mut buf = [0; DATABASE_HEADER_SIZE];
+        self.file.read_exact_at(&mut buf, 0)?;
+        let header = DatabaseHeader::from(&buf);
+        self.change_counter.set(Some(header.change_counter()));
+        let n_pages = match self.wal.as_ref().map(|wal| wal.borrow().n_pages()) {
+            Some(Some(n_pages)) => n_pages,
+            _ => header.n_pages(),
+        };
+        if n_pages > MAX_PAGE_ID {
+            return Err(Error::InvalidFile);
+        }
+        self.n_pages.set(n_pages);
+        self.n_pages_stable.set(n_pages);
+        // Savepoints started before locking the database have no changes. They
+        // now start from the reloaded database.
+        for savepoint in self.savepoints.borrow_mut().iter_mut() {
+            assert!(savepoint.pages.is_empty());
+            savepoint.n_pages = n_pages;
+        }
+        self.load_header()
+    }
+
+    /// Load the database header fields cached in the pager.
+    fn load_header(&self) -> Result<()> {
+        if self.n_pages.get() > 0 {
+            let page1 = self.get_page(PAGE_ID_1)?;
+            let buffer = page1.buffer();
+            let header = DatabaseHeader::from(buffer[..DATABASE_HEADER_SIZE].try_into().unwrap());
+            self.first_freelist_trunk_page_id
+                .set(header.first_freelist_trunk_page_id());
+            self.n_freelist_pages.set(header.n_freelist_pages());
+        } else {
+            self.first_freelist_trunk_page_id.set(None);
+            self.n_freelist_pages.set(0);
+        }
+        Ok(())
+    }
+
+    /// Read the latest committed content of the page.
+    fn read_page(&self, page_id: PageId, buf: &mut [u8]) -> Result<()> {
+        if let Some(wal) = &self.wal {
+            let wal = wal.try_borrow()?;
+            if let Some(frame) = wal.find_frame(page_id) {
+                wal.read_frame(frame, buf)?;
+                return Ok(());
+            }
+        }
+        self.file.read_exact_at(buf, self.page_offset(page_id))?;
+        Ok(())
+    }
+
     pub fn allocate_page(&self) -> Result<(PageId, MemPage)> {
         let page_id = if let Some(page_id) = self.allocate_from_freelist()? {
             page_id
@@ -201,9 +364,10 @@ impl Pager {
             PageId::new(page_id).unwrap()
         };
 
+        self.journal_page(page_id)?;
+        self.savepoint_page(page_id)?;
         let (page, _) = self.cache.get_page(page_id);
         page.try_borrow_mut()?.is_dirty = true;
-        // TODO: setup journal
 
         let header_offset = if page_id == PAGE_ID_1 {
             DATABASE_HEADER_SIZE
@@ -221,10 +385,12 @@ impl Pager {
 
     fn allocate_from_freelist(&self) -> Result<Option<PageId>> {
         if let Some(first_page_id) = self.first_freelist_trunk_page_id.get() {
+            self.journal_page(PAGE_ID_1)?;
+            self.savepoint_page(PAGE_ID_1)?;
             let (page1, is_new) = self.cache.get_page(PAGE_ID_1);
             let mut page1 = if is_new {
                 let mut page1 = page1.borrow_mut();
-                self.file.read_exact_at(&mut page1.buf, 0)?;
+                self.read_page(PAGE_ID_1, &mut page1.buf)?;
                 page1
             } else {
                 page1.try_borrow_mut()?
@@ -234,21 +400,26 @@ impl Pager {
             let mut header =
                 DatabaseHeaderMut::from((&mut buffer[..DATABASE_HEADER_SIZE]).try_into().unwrap());
 
+            self.savepoint_page(first_page_id)?;
             let (trunk_page, is_new) = self.cache.get_page(first_page_id);
             let mut trunk_page = if is_new {
                 let mut trunk_page = trunk_page.borrow_mut();
-                self.file
-                    .read_exact_at(&mut trunk_page.buf, self.page_offset(first_page_id))?;
+                self.read_page(first_page_id, &mut trunk_page.buf)?;
                 trunk_page
             } else {
                 trunk_page.try_borrow_mut()?
             };
-            let trunk_buffer = &mut trunk_page.buf;
-            let n_pages = u32::from_be_bytes(trunk_buffer[4..8].try_into().unwrap());
+            let n_pages = u32::from_be_bytes(trunk_page.buf[4..8].try_into().unwrap());
 
             if n_pages > self.usable_size / 4 - 2 {
                 return Err(Error::InvalidFile);
             }
+            if n_pages > 0 {
+                // The number of leaf pages in the trunk page is updated.
+                self.journal_page(first_page_id)?;
+                trunk_page.is_dirty = true;
+            }
+            let trunk_buffer = &mut trunk_page.buf;
 
             let page_id = if n_pages == 0 {
                 self.first_freelist_trunk_page_id
@@ -292,8 +463,7 @@ impl Pager {
         let (page, is_new) = self.cache.get_page(page_id);
         if is_new {
             let mut raw_page = page.borrow_mut();
-            self.file
-                .read_exact_at(&mut raw_page.buf, self.page_offset(page_id))?;
+            self.read_page(page_id, &mut raw_page.buf)?;
         }
         let header_offset = if page_id == PAGE_ID_1 {
             DATABASE_HEADER_SIZE
@@ -307,11 +477,12 @@ impl Pager {
     }
 
     pub fn make_page_mut<'a>(&self, page: &'a MemPage) -> Result<PageBufferMut<'a>> {
+        self.savepoint_page(page.page.try_borrow()?.id)?;
         let mut raw_page = page.page.try_borrow_mut()?;
 
         if !raw_page.is_dirty {
+            self.journal_page(raw_page.id)?;
             raw_page.is_dirty = true;
-            // TODO: setup journal
         }
 
         Ok(PageBufferMut(raw_page))
@@ -324,10 +495,13 @@ impl Pager {
         if page_id == PAGE_ID_1 || page_id.get() > self.n_pages.get() {
             return Err(Error::InvalidPageId);
         }
+        self.journal_page(PAGE_ID_1)?;
+        self.savepoint_page(PAGE_ID_1)?;
+        self.savepoint_page(page_id)?;
         let (page1, is_new) = self.cache.get_page(PAGE_ID_1);
         let mut page1 = if is_new {
             let mut page1 = page1.borrow_mut();
-            self.file.read_exact_at(&mut page1.buf, 0)?;
+            self.read_page(PAGE_ID_1, &mut page1.buf)?;
             page1
         } else {
             page1.try_borrow_mut()?
@@ -344,11 +518,11 @@ impl Pager {
             if first_page_id.get() > self.n_pages.get() {
                 return Err(Error::InvalidFile);
             }
+            self.savepoint_page(first_page_id)?;
             let (trunk_page, is_new) = self.cache.get_page(first_page_id);
             let mut trunk_page = if is_new {
                 let mut trunk_page = trunk_page.borrow_mut();
-                self.file
-                    .read_exact_at(&mut trunk_page.buf, self.page_offset(first_page_id))?;
+                self.read_page(first_page_id, &mut trunk_page.buf)?;
                 trunk_page
             } else {
                 trunk_page.try_borrow_mut()?
@@ -364,12 +538,22 @@ impl Pager {
             // is corrupt if there are any page_id in the last 6 entries.
             // https://www.sqlite.org/fileformat2.html#the_freelist
             if n_pages < max_freelist_pages - 6 {
+                self.journal_page(first_page_id)?;
                 // Add the deleted page to the freelist trunk page.
                 buffer[4..8].copy_from_slice(&(n_pages + 1).to_be_bytes());
                 let offset = (n_pages * 4) as usize + 8;
                 buffer[offset..offset + 4].copy_from_slice(&page_id.get().to_be_bytes());
                 trunk_page.is_dirty = true;
-                self.cache.delete_page(page_id);
+                drop(trunk_page);
+                if page_id.get() > self.n_pages_stable.get() {
+                    // The page allocated in this transaction is not in the file yet. Keep it
+                    // dirty to extend the file on commit. Otherwise the file is shorter than
+                    // the number of pages in the database header.
+                    let (page, _) = self.cache.get_page(page_id);
+                    page.try_borrow_mut()?.is_dirty = true;
+                } else {
+                    self.cache.delete_page(page_id);
+                }
                 return Ok(());
             } else if n_pages > max_freelist_pages {
                 return Err(Error::InvalidPageId);
@@ -380,6 +564,7 @@ impl Pager {
         }
 
         // Use the deleted page as a new freelist trunk page.
+        self.journal_page(page_id)?;
         let (deleted_page, _) = self.cache.get_page(page_id);
         let mut deleted_page = deleted_page.try_borrow_mut()?;
         deleted_page.is_dirty = true;
@@ -399,58 +584,278 @@ impl Pager {
         Ok(())
     }
 
+    /// Save the original content of the page to the rollback journal before
+    /// it is modified for the first time in the transaction.
+    ///
+    /// Pages appended in the transaction are not journaled because rollback
+    /// truncates the database file to the original size.
+    fn journal_page(&self, page_id: PageId) -> Result<()> {
+        let Some(journal) = &self.journal else {
+            return Ok(());
+        };
+        if page_id.get() > self.n_pages_stable.get() {
+            return Ok(());
+        }
+        let mut journal = journal.try_borrow_mut()?;
+        if journal.contains(page_id) {
+            return Ok(());
+        }
+        // The cached page may be already modified. Read the original content from
+        // the file.
+        let mut buf = self.allocate_tmp_page();
+        self.file
+            .read_exact_at(&mut buf, self.page_offset(page_id))?;
+        journal.append(page_id, &buf, self.n_pages_stable.get())?;
+        Ok(())
+    }
+
+    /// Save the current content of the page to the innermost savepoint before
+    /// it is modified for the first time after the savepoint.
+    ///
+    /// The page must not be borrowed mutably.
+    fn savepoint_page(&self, page_id: PageId) -> Result<()> {
+        let mut savepoints = self.savepoints.try_borrow_mut()?;
+        let Some(savepoint) = savepoints.last_mut() else {
+            return Ok(());
+        };
+        if let Entry::Vacant(entry) = savepoint.pages.entry(page_id) {
+            // Clean pages are the same as the committed content.
+            let content = match self.cache.map.borrow().peek(&page_id) {
+                Some(page) => {
+                    let page = page.try_borrow()?;
+                    page.is_dirty.then(|| page.buf.clone())
+                }
+                None => None,
+            };
+            entry.insert(content);
+        }
+        Ok(())
+    }
+
+    /// Start a savepoint in the transaction.
+    ///
+    /// Returns the index of the savepoint, which is the number of the savepoints
+    /// started before.
+    pub fn savepoint(&self) -> usize {
+        let mut savepoints = self.savepoints.borrow_mut();
+        savepoints.push(Savepoint::new(self.n_pages.get()));
+        savepoints.len() - 1
+    }
+
+    /// Release the savepoint and the savepoints started after it.
+    ///
+    /// The changes after the savepoint are kept in the transaction.
+    pub fn release_savepoint(&self, index: usize) {
+        let mut savepoints = self.savepoints.borrow_mut();
+        let released = savepoints.split_off(index);
+        if let Some(parent) = savepoints.last_mut() {
+            // The outer savepoint needs the content before the earliest change.
+            for savepoint in released {
+                for (page_id, content) in savepoint.pages {
+                    parent.pages.entry(page_id).or_insert(content);
+                }
+            }
+        }
+    }
+
+    /// Discard the changes after the savepoint.
+    ///
+    /// The savepoints started after it are released. The savepoint itself is
+    /// kept and starts over.
+    ///
+    /// No reference to any pages must be kept.
+    pub fn rollback_to_savepoint(&self, index: usize) -> Result<()> {
+        let mut savepoints = self.savepoints.try_borrow_mut()?;
+        let mut map = self.cache.map.try_borrow_mut()?;
+        let mut n_pages = self.n_pages.get();
+        // Restore the inner savepoints first so that the content saved by the
+        // outer savepoints wins.
+        for savepoint in savepoints.drain(index..).rev() {
+            for (page_id, content) in savepoint.pages {
+                match content {
+                    Some(buf) => {
+                        if let Some(page) = map.peek(&page_id) {
+                            let mut page = page.try_borrow_mut()?;
+                            page.buf = buf;
+                            page.is_dirty = true;
+                        } else {
+                            let page = RawPage {
+                                id: page_id,
+                                buf,
+                                is_dirty: true,
+                            };
+                            map.insert(page_id, Rc::new(RefCell::new(page)));
+                        }
+                    }
+                    None => {
+                        map.remove(&page_id);
+                    }
+                }
+            }
+            n_pages = savepoint.n_pages;
+        }
+        savepoints.push(Savepoint::new(n_pages));
+        self.n_pages.set(n_pages);
+        drop(map);
+        drop(savepoints);
+
+        // Reload the cached database header.
+        self.load_header()
+    }
+
     /// Commit all dirty pages.
     ///
+    /// If the rollback journal is enabled, the journal is synced before
+    /// writing the pages to the database file and deleted after the database
+    /// file is synced. In the WAL mode, the pages are appended to the WAL
+    /// instead.
+    ///
     /// No reference to buffers of any dirty pages must be kept when commiting.
     pub fn commit(&self) -> Result<()> {
+        if let Some(wal) = &self.wal {
+            return self.commit_wal(wal);
+        }
+        let mut journal = self
+            .journal
+            .as_ref()
+            .map(|journal| journal.try_borrow_mut())
+            .transpose()?;
+        if let Some(journal) = journal.as_mut() {
+            journal.sync()?;
+        }
         for (page_id, page) in self.cache.map.borrow().iter() {
             let raw_page = page.try_borrow()?;
             if raw_page.is_dirty {
+                check_crash_point()?;
                 self.file
                     .write_all_at(&raw_page.buf, self.page_offset(*page_id))?;
+                if *page_id == PAGE_ID_1 {
+                    let header = DatabaseHeader::from(
+                        raw_page.buf[..DATABASE_HEADER_SIZE].try_into().unwrap(),
+                    );
+                    self.change_counter.set(Some(header.change_counter()));
+                }
                 drop(raw_page);
                 page.try_borrow_mut()?.is_dirty = false;
             }
         }
+        self.cache.map.borrow_mut().unpin_all();
+        let mut error = None;
+        if let Some(journal) = journal.as_mut() {
+            if journal.is_open() {
+                check_crash_point()?;
+                self.file.sync_data()?;
+            }
+            if let Err(e) = journal.finish() {
+                if journal.is_open() {
+                    return Err(e.into());
+                }
+                // Deleting the journal commits the transaction even if syncing
+                // the directory fails after that.
+                error = Some(e);
+            }
+        }
+        self.n_pages_stable.set(self.n_pages.get());
+        self.savepoints.borrow_mut().clear();
+        match error {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
+    }
+
+    fn commit_wal(&self, wal: &RefCell<Wal>) -> Result<()> {
+        let mut wal = wal.try_borrow_mut()?;
+        let map = self.cache.map.borrow();
+        let mut dirty_pages = Vec::new();
+        for (page_id, page) in map.iter() {
+            let raw_page = page.try_borrow()?;
+            if raw_page.is_dirty {
+                dirty_pages.push((*page_id, raw_page));
+            }
+        }
+        dirty_pages.sort_by_key(|(page_id, _)| *page_id);
+        let pages = dirty_pages
+            .iter()
+            .map(|(page_id, raw_page)| (*page_id, raw_page.buf.as_slice()))
+            .collect::<Vec<_>>();
+        wal.append(&pages, self.n_pages.get())?;
+        drop(pages);
+        for (_, raw_page) in dirty_pages {
+            let page = map.peek(&raw_page.id).unwrap();
+            drop(raw_page);
+            page.try_borrow_mut()?.is_dirty = false;
+        }
+        drop(map);
+        self.cache.map.borrow_mut().unpin_all();
         self.n_pages_stable.set(self.n_pages.get());
+        self.savepoints.borrow_mut().clear();
         Ok(())
     }
 
-    pub fn abort(&self) {
-        self.n_pages.set(self.n_pages_stable.get());
+    /// Whether the WAL is large enough to be checkpointed on commit.
+    pub fn needs_checkpoint(&self) -> bool {
+        self.wal
+            .as_ref()
+            .is_some_and(|wal| wal.borrow().n_frames() >= WAL_AUTO_CHECKPOINT)
+    }
 
-        // Drop all dirty pages.
+    /// Whether any pages are modified in the transaction.
+    pub fn is_dirty(&self) -> bool {
         self.cache
             .map
-            .borrow_mut()
-            .retain(|_, page| !page.borrow().is_dirty);
+            .borrow()
+            .values()
+            .any(|page| page.borrow().is_dirty)
+    }
 
-        // Invalidate cached database header.
-        if self.n_pages.get() > 0 {
-            let (page1, is_new) = self.cache.get_page(PAGE_ID_1);
-            if is_new {
-                let mut page1 = page1.borrow_mut();
-                self.file
-                    .read_exact_at(&mut page1.buf, 0)
-                    .expect("read page 1 must succeed");
-            }
-            let buffer = &page1.borrow().buf;
-            let header = DatabaseHeader::from(buffer[..DATABASE_HEADER_SIZE].try_into().unwrap());
-            self.first_freelist_trunk_page_id
-                .set(header.first_freelist_trunk_page_id());
-            self.n_freelist_pages.set(header.n_freelist_pages());
+    /// Discard all changes in the transaction.
+    ///
+    /// If commit() failed in the middle of writing the database file, the file
+    /// is restored from the rollback journal. If the restoration fails, the hot
+    /// journal is kept and rolled back when the database is opened next time.
+    pub fn abort(&self) {
+        self.n_pages.set(self.n_pages_stable.get());
+        self.savepoints.borrow_mut().clear();
+
+        let is_file_restored = self
+            .journal
+            .as_ref()
+            .map(|journal| journal.borrow_mut().rollback(&self.file).unwrap_or(true))
+            .unwrap_or(false);
+        if is_file_restored {
+            // Clean pages in the cache may be already written to the file before
+            // the restoration.
+            self.cache.map.borrow_mut().clear();
         } else {
-            self.first_freelist_trunk_page_id.set(None);
-            self.n_freelist_pages.set(0);
+            // Drop all dirty pages. Pages written by a failed commit are clean
+            // but may still be pinned.
+            let mut map = self.cache.map.borrow_mut();
+            map.retain(|_, page| !page.borrow().is_dirty);
+            map.unpin_all();
         }
 
-        // TODO: rollback journal
+        // Invalidate cached database header.
+        self.load_header().expect("read page 1 must succeed");
     }
 
     pub fn is_file_size_changed(&self) -> bool {
         self.n_pages.get() != self.n_pages_stable.get()
     }
 
+    pub fn pagesize(&self) -> u32 {
+        self.cache.pagesize
+    }
+
+    /// Change the capacity of the page cache. Clean pages are evicted if the
+    /// cache is over the new capacity.
+    pub fn set_cache_size(&self, size: CacheSize) {
+        self.cache.set_capacity(size);
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.map.borrow().stats()
+    }
+
     pub fn num_pages(&self) -> u32 {
         self.n_pages.get()
     }
@@ -476,7 +881,7 @@ pub struct MemPage {
 
 impl MemPage {
     #[inline(always)]
-    pub fn buffer(&self) -> PageBuffer {
+    pub fn buffer(&self) -> PageBuffer<'_> {
         PageBuffer(self.page.borrow())
     }
 }
@@ -512,13 +917,15 @@ impl CopiablePayload for PagePayload {
 }
 
 struct RawPage {
+    id: PageId,
     buf: Vec<u8>,
     is_dirty: bool,
 }
 
 impl RawPage {
-    fn new(pagesize: u32) -> Self {
+    fn new(id: PageId, pagesize: u32) -> Self {
         Self {
+            id,
             buf: vec![0_u8; pagesize as usize],
             is_dirty: false,
         }
@@ -526,25 +933,59 @@ impl RawPage {
 }
 
 struct PageCache {
-    map: RefCell<HashMap<PageId, Rc<RefCell<RawPage>>>>,
+    map: RefCell<LruCache<PageId, Rc<RefCell<RawPage>>>>,
     pagesize: u32,
+    /// The maximum number of pages.
+    capacity: Cell<usize>,
 }
 
 impl PageCache {
-    fn new(pagesize: u32) -> Self {
-        Self {
-            map: RefCell::new(HashMap::new()),
+    fn new(pagesize: u32, size: CacheSize) -> Self {
+        let cache = Self {
+            map: RefCell::new(LruCache::new()),
             pagesize,
-        }
+            capacity: Cell::new(0),
+        };
+        cache.set_capacity(size);
+        cache
+    }
+
+    fn set_capacity(&self, size: CacheSize) {
+        let n_pages = match size {
+            CacheSize::Pages(n_pages) => n_pages,
+            CacheSize::Bytes(bytes) => bytes / self.pagesize as usize,
+        };
+        self.capacity.set(n_pages.max(1));
+        self.shrink(&mut self.map.borrow_mut());
     }
 
     fn get_page(&self, id: PageId) -> (Rc<RefCell<RawPage>>, bool) {
-        match self.map.borrow_mut().entry(id) {
-            Entry::Occupied(entry) => (entry.get().clone(), false),
-            Entry::Vacant(entry) => {
-                let page = Rc::new(RefCell::new(RawPage::new(self.pagesize)));
-                entry.insert(page.clone());
-                (page, true)
+        let mut map = self.map.borrow_mut();
+        if let Some(page) = map.get(&id) {
+            return (page.clone(), false);
+        }
+        let page = Rc::new(RefCell::new(RawPage::new(id, self.pagesize)));
+        map.insert(id, page.clone());
+        self.shrink(&mut map);
+        (page, true)
+    }
+
+    /// Evict the least recently used pages until the cache fits the capacity.
+    fn shrink(&self, map: &mut LruCache<PageId, Rc<RefCell<RawPage>>>) {
+        while map.len() > self.capacity.get() {
+            // Pages referenced by MemPage are skipped. Pages modified in the
+            // transaction are pinned until commit or abort.
+            let evicted = map.evict(|_, page| {
+                if Rc::strong_count(page) > 1 {
+                    Eviction::Skip
+                } else if page.borrow().is_dirty {
+                    Eviction::Pin
+                } else {
+                    Eviction::Evict
+                }
+            });
+            if evicted.is_none() {
+                break;
             }
         }
     }
@@ -1145,4 +1586,196 @@ mod tests {
         assert_eq!(page_id.get(), 301);
         assert_eq!(pager.num_pages(), 301);
     }
+
+    #[test]
+    fn test_cache_eviction() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        for i in 0..10 {
+            file.as_file()
+                .write_all_at(&[i as u8; 512], i * 512)
+                .unwrap();
+        }
+        let pager = Pager::new(file.reopen().unwrap(), 10, 512, 512, None, 0).unwrap();
+        pager.set_cache_size(CacheSize::Pages(3));
+        let page_id = |i: u32| PageId::new(i).unwrap();
+
+        for i in 1..=10 {
+            let page = pager.get_page(page_id(i)).unwrap();
+            assert_eq!(page.buffer()[0], i as u8 - 1);
+        }
+        assert_eq!(pager.cache.map.borrow().len(), 3);
+        // Page 8 is the least recently used.
+        pager.get_page(page_id(9)).unwrap();
+        pager.get_page(page_id(7)).unwrap();
+        pager.get_page(page_id(10)).unwrap();
+        assert!(pager.cache.map.borrow().peek(&page_id(8)).is_none());
+        assert_eq!(
+            pager.cache_stats(),
+            CacheStats {
+                hits: 2,
+                misses: 11,
+                evictions: 8,
+            }
+        );
+
+        // Pages in use are pinned.
+        let pages = (1..=5)
+            .map(|i| pager.get_page(page_id(i)).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(pager.cache.map.borrow().len(), 5);
+        drop(pages);
+        pager.get_page(page_id(6)).unwrap();
+        assert_eq!(pager.cache.map.borrow().len(), 3);
+
+        // Dirty pages are pinned until commit.
+        for i in 1..=5 {
+            let page = pager.get_page(page_id(i)).unwrap();
+            pager.make_page_mut(&page).unwrap().fill(100 + i as u8);
+        }
+        for i in 6..=10 {
+            pager.get_page(page_id(i)).unwrap();
+        }
+        assert_eq!(pager.cache.map.borrow().len(), 6);
+        pager.commit().unwrap();
+        pager.get_page(page_id(10)).unwrap();
+        assert_eq!(pager.cache.map.borrow().len(), 6);
+        pager.get_page(page_id(9)).unwrap();
+        assert_eq!(pager.cache.map.borrow().len(), 3);
+        for i in 1..=10 {
+            let page = pager.get_page(page_id(i)).unwrap();
+            let expected = if i <= 5 { 100 + i as u8 } else { i as u8 - 1 };
+            assert_eq!(page.buffer()[0], expected);
+        }
+
+        // Shrinking the cache evicts clean pages.
+        pager.set_cache_size(CacheSize::Bytes(4096));
+        assert_eq!(pager.cache.map.borrow().len(), 3);
+        pager.set_cache_size(CacheSize::Bytes(1024));
+        assert_eq!(pager.cache.map.borrow().len(), 2);
+    }
+
+    #[test]
+    fn test_cache_eviction_many_dirty_pages() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        file.as_file().write_all_at(&[0; 512], 0).unwrap();
+        let pager = Pager::new(file.reopen().unwrap(), 1, 512, 512, None, 0).unwrap();
+        pager.set_cache_size(CacheSize::Pages(3));
+
+        for i in 2..=1000 {
+            let (page_id, page) = pager.allocate_page().unwrap();
+            assert_eq!(page_id.get(), i);
+            pager.make_page_mut(&page).unwrap().fill(i as u8);
+        }
+        // All the dirty pages are kept.
+        assert_eq!(pager.cache.map.borrow().len(), 999);
+        pager.commit().unwrap();
+
+        // Committed pages are evicted again.
+        let evictions = pager.cache_stats().evictions;
+        pager.get_page(PAGE_ID_1).unwrap();
+        assert_eq!(pager.cache.map.borrow().len(), 3);
+        assert_eq!(pager.cache_stats().evictions, evictions + 997);
+        let page = pager.get_page(PageId::new(2).unwrap()).unwrap();
+        assert_eq!(page.buffer()[0], 2);
+    }
+
+    #[test]
+    fn test_cache_eviction_abort() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        for i in 0..4 {
+            file.as_file()
+                .write_all_at(&[i as u8; 512], i * 512)
+                .unwrap();
+        }
+        let pager = Pager::new(file.reopen().unwrap(), 4, 512, 512, None, 0).unwrap();
+        pager.set_cache_size(CacheSize::Pages(1));
+        let page_id = |i: u32| PageId::new(i).unwrap();
+
+        let page = pager.get_page(page_id(2)).unwrap();
+        pager.make_page_mut(&page).unwrap().fill(10);
+        drop(page);
+        for i in 1..=4 {
+            pager.get_page(page_id(i)).unwrap();
+        }
+        assert_eq!(pager.get_page(page_id(2)).unwrap().buffer()[0], 10);
+        pager.abort();
+        assert_eq!(pager.get_page(page_id(2)).unwrap().buffer()[0], 1);
+    }
+
+    #[test]
+    fn test_savepoint() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        for i in 0..3 {
+            file.as_file()
+                .write_all_at(&[i as u8; 512], i * 512)
+                .unwrap();
+        }
+        let pager = Pager::new(file.reopen().unwrap(), 3, 512, 512, None, 0).unwrap();
+        let page_id = |i: u32| PageId::new(i).unwrap();
+        let fill = |i: u32, v: u8| {
+            let page = pager.get_page(page_id(i)).unwrap();
+            pager.make_page_mut(&page).unwrap().fill(v);
+        };
+        let content = |i: u32| pager.get_page(page_id(i)).unwrap().buffer()[0];
+
+        fill(2, 20);
+        let savepoint0 = pager.savepoint();
+        assert_eq!(savepoint0, 0);
+        fill(2, 21);
+        fill(3, 31);
+        let (_, page) = pager.allocate_page().unwrap();
+        pager.make_page_mut(&page).unwrap().fill(41);
+        drop(page);
+
+        let savepoint1 = pager.savepoint();
+        assert_eq!(savepoint1, 1);
+        fill(2, 22);
+        let (page_id5, _) = pager.allocate_page().unwrap();
+        assert_eq!(page_id5, page_id(5));
+
+        pager.rollback_to_savepoint(savepoint1).unwrap();
+        assert_eq!(pager.num_pages(), 4);
+        assert_eq!(content(2), 21);
+        assert_eq!(content(3), 31);
+        assert_eq!(content(4), 41);
+
+        // The changes after the released savepoint belong to the outer one.
+        fill(3, 32);
+        pager.release_savepoint(savepoint1);
+        pager.rollback_to_savepoint(savepoint0).unwrap();
+        assert_eq!(pager.num_pages(), 3);
+        assert!(pager.get_page(page_id(4)).is_err());
+        assert_eq!(content(2), 20);
+        assert_eq!(content(3), 2);
+
+        fill(3, 33);
+        pager.release_savepoint(savepoint0);
+        pager.commit().unwrap();
+        let mut buf = [0; 512];
+        file.as_file().read_exact_at(&mut buf, 512).unwrap();
+        assert_eq!(buf, [20; 512]);
+        file.as_file().read_exact_at(&mut buf, 1024).unwrap();
+        assert_eq!(buf, [33; 512]);
+        assert_eq!(file.as_file().metadata().unwrap().len(), 3 * 512);
+    }
+
+    #[test]
+    fn test_savepoint_delete_page() {
+        let pager = create_empty_pager(&[0; 3 * 4096], 4096, 4096);
+        let page_id = |i: u32| PageId::new(i).unwrap();
+        let page = pager.get_page(page_id(3)).unwrap();
+        pager.make_page_mut(&page).unwrap().fill(3);
+        drop(page);
+
+        let savepoint = pager.savepoint();
+        pager.delete_page(page_id(2)).unwrap();
+        pager.delete_page(page_id(3)).unwrap();
+        assert_freelist_header(&pager, Some(page_id(2)), 2);
+        assert_eq!(pager.allocate_page().unwrap().0, page_id(3));
+
+        pager.rollback_to_savepoint(savepoint).unwrap();
+        assert_freelist_header(&pager, None, 0);
+        assert_eq!(pager.get_page(page_id(3)).unwrap().buffer()[0], 3);
+        assert_eq!(pager.allocate_page().unwrap().0, page_id(4));
+    }
 }
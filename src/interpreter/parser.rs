@@ -73,12 +73,79 @@ pub type Result<'a, T> = std::result::Result<T, Error<'a>>;
 
 static NULL_BYTES: &[u8] = b"null";
 
+/// The maximum index of parameters. This is the default of
+/// SQLITE_MAX_VARIABLE_NUMBER.
+pub const MAX_PARAMETER_INDEX: u32 = 32766;
+
+/// The parameters appearing in a statement.
+///
+/// Parameters are numbered from 1 as SQLite does. "?" takes the index after
+/// the largest index so far, "?NNN" takes NNN and a named parameter reuses the
+/// index of the same name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Parameters {
+    /// The largest index of the parameters.
+    n: u32,
+    /// The names including the prefix and the indexes of the named
+    /// parameters.
+    names: Vec<(Vec<u8>, u32)>,
+}
+
+impl Parameters {
+    /// The number of parameters, which is the largest index.
+    pub fn len(&self) -> usize {
+        self.n as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// The index of the named parameter. The name includes the prefix (e.g.
+    /// ":name").
+    pub fn index(&self, name: &[u8]) -> Option<u32> {
+        self.names
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, index)| *index)
+    }
+
+    /// Assign the index to the variable token.
+    ///
+    /// Returns None if the index is out of range.
+    fn add(&mut self, variable: &[u8]) -> Option<u32> {
+        let index = if variable == b"?" {
+            self.n + 1
+        } else if variable[0] == b'?' {
+            match parse_integer(&variable[1..]) {
+                (true, ParseIntegerResult::Integer(index)) if index >= 1 => {
+                    u32::try_from(index).ok()?
+                }
+                _ => return None,
+            }
+        } else if let Some(index) = self.index(variable) {
+            index
+        } else {
+            self.n + 1
+        };
+        if index > MAX_PARAMETER_INDEX {
+            return None;
+        }
+        if variable.len() > 1 && self.index(variable).is_none() {
+            self.names.push((variable.to_vec(), index));
+        }
+        self.n = self.n.max(index);
+        Some(index)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Parser<'a> {
     input: &'a [u8],
     cursor: usize,
     token: Option<Token<'a>>,
     token_size: usize,
+    parameters: Parameters,
 }
 
 impl<'a> Parser<'a> {
@@ -88,6 +155,7 @@ impl<'a> Parser<'a> {
             cursor: 0,
             token: None,
             token_size: 0,
+            parameters: Parameters::default(),
         };
         parser.next();
         parser
@@ -101,6 +169,11 @@ impl<'a> Parser<'a> {
         self.cursor
     }
 
+    /// The parameters parsed so far.
+    pub fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+
     fn next<'b>(&'b mut self) -> Option<&'b Token<'a>> {
         self.cursor += self.token_size;
         if let Some((n, token)) = get_token(&self.input[self.cursor..]) {
@@ -132,13 +205,54 @@ impl<'a> Parser<'a> {
 }
 
 pub enum Stmt<'a> {
+    CreateTable(CreateTable<'a>),
+    CreateIndex(CreateIndex<'a>),
+    DropTable(DropTable<'a>),
+    DropIndex(DropIndex<'a>),
     Select(Select<'a>),
     Insert(Insert<'a>),
+    Update(Update<'a>),
     Delete(Delete<'a>),
+    Analyze(Analyze<'a>),
+    Begin(Begin),
+    Commit,
+    Rollback(Rollback<'a>),
+    Savepoint(Savepoint<'a>),
+    Release(Release<'a>),
+    ExplainQueryPlan(Box<Stmt<'a>>),
 }
 
 pub fn parse_sql<'a>(p: &mut Parser<'a>) -> Result<'a, Stmt<'a>> {
     match p.peek() {
+        Some(Token::Create) => {
+            // Look ahead the token after CREATE to determine the statement.
+            let mut lookahead = p.clone();
+            match lookahead.next() {
+                Some(Token::Table) => {
+                    let create_table = parse_create_table(p)?;
+                    Ok(Stmt::CreateTable(create_table))
+                }
+                Some(Token::Index) | Some(Token::Unique) => {
+                    let create_index = parse_create_index(p)?;
+                    Ok(Stmt::CreateIndex(create_index))
+                }
+                _ => Err(lookahead.error("no table or index after create")),
+            }
+        }
+        Some(Token::Drop) => {
+            let mut lookahead = p.clone();
+            match lookahead.next() {
+                Some(Token::Table) => {
+                    let drop_table = parse_drop_table(p)?;
+                    Ok(Stmt::DropTable(drop_table))
+                }
+                Some(Token::Index) => {
+                    let drop_index = parse_drop_index(p)?;
+                    Ok(Stmt::DropIndex(drop_index))
+                }
+                _ => Err(lookahead.error("no table or index after drop")),
+            }
+        }
         Some(Token::Select) => {
             let select = parse_select(p)?;
             Ok(Stmt::Select(select))
@@ -147,14 +261,71 @@ pub fn parse_sql<'a>(p: &mut Parser<'a>) -> Result<'a, Stmt<'a>> {
             let select = parse_insert(p)?;
             Ok(Stmt::Insert(select))
         }
+        Some(Token::Update) => {
+            let update = parse_update(p)?;
+            Ok(Stmt::Update(update))
+        }
         Some(Token::Delete) => {
             let delete = parse_delete(p)?;
             Ok(Stmt::Delete(delete))
         }
+        Some(Token::Analyze) => {
+            let analyze = parse_analyze(p)?;
+            Ok(Stmt::Analyze(analyze))
+        }
+        Some(Token::Begin) => {
+            let begin = parse_begin(p)?;
+            Ok(Stmt::Begin(begin))
+        }
+        Some(Token::Commit) => {
+            parse_commit(p)?;
+            Ok(Stmt::Commit)
+        }
+        Some(Token::Rollback) => {
+            let rollback = parse_rollback(p)?;
+            Ok(Stmt::Rollback(rollback))
+        }
+        Some(Token::Savepoint) => {
+            let savepoint = parse_savepoint(p)?;
+            Ok(Stmt::Savepoint(savepoint))
+        }
+        Some(Token::Release) => {
+            let release = parse_release(p)?;
+            Ok(Stmt::Release(release))
+        }
+        Some(Token::Explain) => parse_explain(p),
         _ => Err(p.error("no statement")),
     }
 }
 
+/// Parse EXPLAIN QUERY PLAN followed by the statement.
+///
+/// EXPLAIN without QUERY PLAN, which shows the bytecode in SQLite, is not
+/// supported. QUERY and PLAN are not keywords so that they can still be used
+/// as identifiers.
+fn parse_explain<'a>(p: &mut Parser<'a>) -> Result<'a, Stmt<'a>> {
+    p.next();
+    for keyword in [b"query".as_slice(), b"plan"] {
+        if !is_keyword(p.peek(), keyword) {
+            return Err(p.error("no query plan after explain"));
+        }
+        p.next();
+    }
+    if let Some(Token::Explain) = p.peek() {
+        return Err(p.error("nested explain"));
+    }
+    let stmt = parse_sql(p)?;
+    Ok(Stmt::ExplainQueryPlan(Box::new(stmt)))
+}
+
+/// Whether the token is the identifier which equals to the keyword ignoring
+/// case.
+///
+/// This matches the keywords which SQLite allows as identifiers.
+fn is_keyword(token: Option<&Token>, keyword: &[u8]) -> bool {
+    matches!(token, Some(Token::Identifier(id)) if id.dequote().eq_ignore_ascii_case(keyword))
+}
+
 /// Assert that the next token is a semicolon.
 pub fn expect_semicolon<'a>(p: &mut Parser<'a>) -> Result<'a, ()> {
     match p.peek() {
@@ -176,17 +347,24 @@ pub fn expect_no_more_token<'a>(p: &Parser<'a>) -> Result<'a, ()> {
 }
 
 /// CREATE TABLE statement.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub struct CreateTable<'a> {
+    pub if_not_exists: bool,
     pub table_name: MaybeQuotedBytes<'a>,
     pub columns: Vec<ColumnDef<'a>>,
+    /// The original text from the table name to the last right paren.
+    ///
+    /// sqlite_schema stores "CREATE TABLE " followed by this text. "IF NOT
+    /// EXISTS" is not included.
+    pub definition: &'a [u8],
 }
 
 /// Constraint of a column in a table.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub enum ColumnConstraint<'a> {
     Collate(MaybeQuotedBytes<'a>),
     PrinaryKey,
+    Default(Expr<'a>),
 }
 
 /// https://www.sqlite.org/syntax/column-constraint.html
@@ -204,15 +382,58 @@ fn parse_column_constraint<'a>(p: &mut Parser<'a>) -> Result<'a, Option<ColumnCo
             let Some(Token::Key) = p.next() else {
                 return Err(p.error("no key after primary"));
             };
-            p.next();
+            match p.next() {
+                Some(Token::Asc) => {
+                    p.next();
+                }
+                // The index for a descending PRIMARY KEY is in descending
+                // order, which indexes do not support yet.
+                Some(Token::Desc) => return Err(p.error("descending primary key")),
+                _ => {}
+            }
             Ok(Some(ColumnConstraint::PrinaryKey))
         }
+        Some(Token::Default) => match p.next() {
+            // A signed number, a literal value or an expression in parentheses.
+            Some(
+                Token::Plus
+                | Token::Minus
+                | Token::Integer(_)
+                | Token::Float(_)
+                | Token::String(_)
+                | Token::Blob(_)
+                | Token::Null
+                | Token::LeftParen,
+            ) => Ok(Some(ColumnConstraint::Default(parse_expr_unary(p)?))),
+            Some(Token::Identifier(id)) => {
+                let id = *id;
+                p.next();
+                // Unquoted keywords are literal values. SQLite takes other
+                // identifiers as strings.
+                let keyword = id.raw();
+                let expr = if keyword.eq_ignore_ascii_case(b"current_date") {
+                    Expr::CurrentTime(CurrentTime::Date)
+                } else if keyword.eq_ignore_ascii_case(b"current_time") {
+                    Expr::CurrentTime(CurrentTime::Time)
+                } else if keyword.eq_ignore_ascii_case(b"current_timestamp") {
+                    Expr::CurrentTime(CurrentTime::Timestamp)
+                } else if keyword.eq_ignore_ascii_case(b"true") {
+                    Expr::Integer(1)
+                } else if keyword.eq_ignore_ascii_case(b"false") {
+                    Expr::Integer(0)
+                } else {
+                    Expr::Text(id)
+                };
+                Ok(Some(ColumnConstraint::Default(expr)))
+            }
+            _ => Err(p.error("no default value")),
+        },
         _ => Ok(None),
     }
 }
 
 /// Definition of a column in a table.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub struct ColumnDef<'a> {
     pub name: MaybeQuotedBytes<'a>,
     pub type_name: Vec<MaybeQuotedBytes<'a>>,
@@ -292,7 +513,10 @@ pub fn parse_create_table<'a>(p: &mut Parser<'a>) -> Result<'a, CreateTable<'a>>
         return Err(p.error("no table"));
     };
 
-    let Some(Token::Identifier(table_name)) = p.next() else {
+    let if_not_exists = parse_if_not_exists(p)?;
+
+    let definition_start = p.cursor;
+    let Some(Token::Identifier(table_name)) = p.peek() else {
         return Err(p.error("no table_name"));
     };
     let table_name = *table_name;
@@ -330,20 +554,48 @@ pub fn parse_create_table<'a>(p: &mut Parser<'a>) -> Result<'a, CreateTable<'a>>
             _ => return Err(p.error("no right paren")),
         }
     }
+    let definition = &p.input[definition_start..p.cursor + p.token_size];
     p.next();
 
     Ok(CreateTable {
+        if_not_exists,
         table_name,
         columns,
+        definition,
     })
 }
 
+/// Parse optional "IF NOT EXISTS".
+///
+/// The parser must point to the token just before "IF". On return the parser
+/// points to the first token which is not a part of "IF NOT EXISTS".
+fn parse_if_not_exists<'a>(p: &mut Parser<'a>) -> Result<'a, bool> {
+    let Some(Token::If) = p.next() else {
+        return Ok(false);
+    };
+    let Some(Token::Not) = p.next() else {
+        return Err(p.error("no not after if"));
+    };
+    let Some(Token::Exists) = p.next() else {
+        return Err(p.error("no exists after not"));
+    };
+    p.next();
+    Ok(true)
+}
+
 /// CREATE INDEX statement.
 #[derive(Debug, PartialEq, Eq)]
 pub struct CreateIndex<'a> {
+    pub unique: bool,
+    pub if_not_exists: bool,
     pub index_name: MaybeQuotedBytes<'a>,
     pub table_name: MaybeQuotedBytes<'a>,
     pub columns: Vec<IndexedColumn<'a>>,
+    /// The original text from the index name to the last right paren.
+    ///
+    /// sqlite_schema stores "CREATE [UNIQUE] INDEX " followed by this text.
+    /// "IF NOT EXISTS" is not included.
+    pub definition: &'a [u8],
 }
 
 /// Definition of a column in a index.
@@ -360,11 +612,21 @@ pub fn parse_create_index<'a>(p: &mut Parser<'a>) -> Result<'a, CreateIndex<'a>>
         return Err(p.error("no create"));
     };
 
-    let Some(Token::Index) = p.next() else {
+    let unique = if let Some(Token::Unique) = p.next() {
+        p.next();
+        true
+    } else {
+        false
+    };
+
+    let Some(Token::Index) = p.peek() else {
         return Err(p.error("no index"));
     };
 
-    let Some(Token::Identifier(index_name)) = p.next() else {
+    let if_not_exists = parse_if_not_exists(p)?;
+
+    let definition_start = p.cursor;
+    let Some(Token::Identifier(index_name)) = p.peek() else {
         return Err(p.error("no index_name"));
     };
     let index_name = *index_name;
@@ -397,20 +659,272 @@ pub fn parse_create_index<'a>(p: &mut Parser<'a>) -> Result<'a, CreateIndex<'a>>
             _ => return Err(p.error("no right paren")),
         }
     }
+    let definition = &p.input[definition_start..p.cursor + p.token_size];
     p.next();
 
     Ok(CreateIndex {
+        unique,
+        if_not_exists,
         index_name,
         table_name,
         columns,
+        definition,
     })
 }
 
-#[derive(Debug)]
+/// Parse optional "IF EXISTS".
+///
+/// The parser must point to the token just before "IF". On return the parser
+/// points to the first token which is not a part of "IF EXISTS".
+fn parse_if_exists<'a>(p: &mut Parser<'a>) -> Result<'a, bool> {
+    let Some(Token::If) = p.next() else {
+        return Ok(false);
+    };
+    let Some(Token::Exists) = p.next() else {
+        return Err(p.error("no exists after if"));
+    };
+    p.next();
+    Ok(true)
+}
+
+/// DROP TABLE statement.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DropTable<'a> {
+    pub if_exists: bool,
+    pub table_name: MaybeQuotedBytes<'a>,
+}
+
+/// Parse DROP TABLE statement.
+///
+/// https://www.sqlite.org/lang_droptable.html
+pub fn parse_drop_table<'a>(p: &mut Parser<'a>) -> Result<'a, DropTable<'a>> {
+    let Some(Token::Drop) = p.peek() else {
+        return Err(p.error("no drop"));
+    };
+    let Some(Token::Table) = p.next() else {
+        return Err(p.error("no table"));
+    };
+    let if_exists = parse_if_exists(p)?;
+    let Some(Token::Identifier(table_name)) = p.peek() else {
+        return Err(p.error("no table_name"));
+    };
+    let table_name = *table_name;
+    p.next();
+
+    Ok(DropTable {
+        if_exists,
+        table_name,
+    })
+}
+
+/// DROP INDEX statement.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DropIndex<'a> {
+    pub if_exists: bool,
+    pub index_name: MaybeQuotedBytes<'a>,
+}
+
+/// Parse DROP INDEX statement.
+///
+/// https://www.sqlite.org/lang_dropindex.html
+pub fn parse_drop_index<'a>(p: &mut Parser<'a>) -> Result<'a, DropIndex<'a>> {
+    let Some(Token::Drop) = p.peek() else {
+        return Err(p.error("no drop"));
+    };
+    let Some(Token::Index) = p.next() else {
+        return Err(p.error("no index"));
+    };
+    let if_exists = parse_if_exists(p)?;
+    let Some(Token::Identifier(index_name)) = p.peek() else {
+        return Err(p.error("no index_name"));
+    };
+    let index_name = *index_name;
+    p.next();
+
+    Ok(DropIndex {
+        if_exists,
+        index_name,
+    })
+}
+
+/// ANALYZE statement.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Analyze<'a> {
+    /// The name of the table or the index. All tables if None.
+    pub name: Option<MaybeQuotedBytes<'a>>,
+}
+
+/// Parse ANALYZE statement.
+///
+/// https://www.sqlite.org/lang_analyze.html
+pub fn parse_analyze<'a>(p: &mut Parser<'a>) -> Result<'a, Analyze<'a>> {
+    let Some(Token::Analyze) = p.peek() else {
+        return Err(p.error("no analyze"));
+    };
+    let name = if let Some(Token::Identifier(name)) = p.next() {
+        let name = *name;
+        p.next();
+        Some(name)
+    } else {
+        None
+    };
+
+    Ok(Analyze { name })
+}
+
+/// The locking behavior of BEGIN statement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionType {
+    /// Locks are acquired when the database is read or written first.
+    Deferred,
+    /// The reserved lock is acquired immediately.
+    Immediate,
+    /// The exclusive lock is acquired immediately.
+    Exclusive,
+}
+
+/// BEGIN statement.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Begin {
+    pub transaction_type: TransactionType,
+}
+
+/// Parse BEGIN statement.
+///
+/// https://www.sqlite.org/lang_transaction.html
+pub fn parse_begin<'a>(p: &mut Parser<'a>) -> Result<'a, Begin> {
+    let Some(Token::Begin) = p.peek() else {
+        return Err(p.error("no begin"));
+    };
+    let transaction_type = match p.next() {
+        Some(Token::Deferred) => TransactionType::Deferred,
+        Some(Token::Immediate) => TransactionType::Immediate,
+        Some(Token::Exclusive) => TransactionType::Exclusive,
+        _ => {
+            parse_optional_transaction(p);
+            return Ok(Begin {
+                transaction_type: TransactionType::Deferred,
+            });
+        }
+    };
+    p.next();
+    parse_optional_transaction(p);
+
+    Ok(Begin { transaction_type })
+}
+
+/// Parse COMMIT statement.
+///
+/// https://www.sqlite.org/lang_transaction.html
+pub fn parse_commit<'a>(p: &mut Parser<'a>) -> Result<'a, ()> {
+    let Some(Token::Commit) = p.peek() else {
+        return Err(p.error("no commit"));
+    };
+    p.next();
+    parse_optional_transaction(p);
+    Ok(())
+}
+
+/// ROLLBACK statement.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Rollback<'a> {
+    /// The savepoint to roll back to. The whole transaction is rolled back if
+    /// None.
+    pub savepoint_name: Option<MaybeQuotedBytes<'a>>,
+}
+
+/// Parse ROLLBACK statement.
+///
+/// https://www.sqlite.org/lang_transaction.html
+pub fn parse_rollback<'a>(p: &mut Parser<'a>) -> Result<'a, Rollback<'a>> {
+    let Some(Token::Rollback) = p.peek() else {
+        return Err(p.error("no rollback"));
+    };
+    p.next();
+    parse_optional_transaction(p);
+    let Some(Token::To) = p.peek() else {
+        return Ok(Rollback {
+            savepoint_name: None,
+        });
+    };
+    if let Some(Token::Savepoint) = p.next() {
+        p.next();
+    }
+    let Some(Token::Identifier(name)) = p.peek() else {
+        return Err(p.error("no savepoint name"));
+    };
+    let name = *name;
+    p.next();
+
+    Ok(Rollback {
+        savepoint_name: Some(name),
+    })
+}
+
+/// SAVEPOINT statement.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Savepoint<'a> {
+    pub name: MaybeQuotedBytes<'a>,
+}
+
+/// Parse SAVEPOINT statement.
+///
+/// https://www.sqlite.org/lang_savepoint.html
+pub fn parse_savepoint<'a>(p: &mut Parser<'a>) -> Result<'a, Savepoint<'a>> {
+    let Some(Token::Savepoint) = p.peek() else {
+        return Err(p.error("no savepoint"));
+    };
+    let Some(Token::Identifier(name)) = p.next() else {
+        return Err(p.error("no savepoint name"));
+    };
+    let name = *name;
+    p.next();
+
+    Ok(Savepoint { name })
+}
+
+/// RELEASE statement.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Release<'a> {
+    pub name: MaybeQuotedBytes<'a>,
+}
+
+/// Parse RELEASE statement.
+///
+/// https://www.sqlite.org/lang_savepoint.html
+pub fn parse_release<'a>(p: &mut Parser<'a>) -> Result<'a, Release<'a>> {
+    let Some(Token::Release) = p.peek() else {
+        return Err(p.error("no release"));
+    };
+    if let Some(Token::Savepoint) = p.next() {
+        p.next();
+    }
+    let Some(Token::Identifier(name)) = p.peek() else {
+        return Err(p.error("no savepoint name"));
+    };
+    let name = *name;
+    p.next();
+
+    Ok(Release { name })
+}
+
+/// Skip optional "TRANSACTION".
+fn parse_optional_transaction(p: &mut Parser) {
+    if let Some(Token::Transaction) = p.peek() {
+        p.next();
+    }
+}
+
+#[derive(Debug, PartialEq)]
 pub struct Select<'a> {
     pub table_name: MaybeQuotedBytes<'a>,
     pub columns: Vec<ResultColumn<'a>>,
     pub filter: Option<Expr<'a>>,
+    pub group_by: Vec<Expr<'a>>,
+    pub having: Option<Expr<'a>>,
+    pub order_by: Vec<OrderingTerm<'a>>,
+    pub limit: Option<Expr<'a>>,
+    pub offset: Option<Expr<'a>>,
 }
 
 // Parse SELECT statement.
@@ -451,13 +965,105 @@ pub fn parse_select<'a>(p: &mut Parser<'a>) -> Result<'a, Select<'a>> {
         None
     };
 
+    let group_by = if let Some(Token::Group) = p.peek() {
+        let Some(Token::By) = p.next() else {
+            return Err(p.error("no by after group"));
+        };
+        let mut group_by = Vec::new();
+        loop {
+            p.next();
+            group_by.push(parse_expr(p)?);
+            if let Some(Token::Comma) = p.peek() {
+                continue;
+            }
+            break;
+        }
+        group_by
+    } else {
+        Vec::new()
+    };
+
+    let having = if let Some(Token::Having) = p.peek() {
+        p.next();
+        Some(parse_expr(p)?)
+    } else {
+        None
+    };
+
+    let order_by = if let Some(Token::Order) = p.peek() {
+        let Some(Token::By) = p.next() else {
+            return Err(p.error("no by after order"));
+        };
+        let mut order_by = Vec::new();
+        loop {
+            p.next();
+            order_by.push(parse_ordering_term(p)?);
+            if let Some(Token::Comma) = p.peek() {
+                continue;
+            }
+            break;
+        }
+        order_by
+    } else {
+        Vec::new()
+    };
+
+    let (limit, offset) = if let Some(Token::Limit) = p.peek() {
+        p.next();
+        let expr = parse_expr(p)?;
+        match p.peek() {
+            Some(Token::Offset) => {
+                p.next();
+                (Some(expr), Some(parse_expr(p)?))
+            }
+            // "LIMIT <offset>, <limit>"
+            Some(Token::Comma) => {
+                p.next();
+                (Some(parse_expr(p)?), Some(expr))
+            }
+            _ => (Some(expr), None),
+        }
+    } else {
+        (None, None)
+    };
+
     Ok(Select {
         table_name,
         columns,
         filter,
+        group_by,
+        having,
+        order_by,
+        limit,
+        offset,
     })
 }
 
+#[derive(Debug, PartialEq)]
+pub struct OrderingTerm<'a> {
+    pub expr: Expr<'a>,
+    pub desc: bool,
+}
+
+/// Parse ordering term.
+///
+/// https://www.sqlite.org/syntax/ordering-term.html
+fn parse_ordering_term<'a>(p: &mut Parser<'a>) -> Result<'a, OrderingTerm<'a>> {
+    let expr = parse_expr(p)?;
+    let desc = match p.peek() {
+        Some(Token::Asc) => {
+            p.next();
+            false
+        }
+        Some(Token::Desc) => {
+            p.next();
+            true
+        }
+        _ => false,
+    };
+    Ok(OrderingTerm { expr, desc })
+}
+
 #[derive(Debug, PartialEq)]
 pub enum ResultColumn<'a> {
     All,
@@ -511,8 +1117,48 @@ fn parse_result_column<'a>(p: &mut Parser<'a>) -> Result<'a, ResultColumn<'a>> {
 #[derive(Debug, PartialEq)]
 pub struct Insert<'a> {
     pub table_name: MaybeQuotedBytes<'a>,
+    /// Empty if the column list is omitted, which means all the columns.
     pub columns: Vec<MaybeQuotedBytes<'a>>,
-    pub values: Vec<Vec<Expr<'a>>>,
+    pub source: InsertSource<'a>,
+    /// The algorithm of INSERT OR ...
+    pub conflict: ConflictResolution,
+    pub upsert: Option<Upsert<'a>>,
+}
+
+/// The rows to insert.
+#[derive(Debug, PartialEq)]
+pub enum InsertSource<'a> {
+    Values(Vec<Vec<Expr<'a>>>),
+    Select(Box<Select<'a>>),
+    DefaultValues,
+}
+
+/// https://www.sqlite.org/lang_conflict.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictResolution {
+    Abort,
+    Ignore,
+    Replace,
+}
+
+/// ON CONFLICT clause of INSERT statement.
+///
+/// https://www.sqlite.org/lang_upsert.html
+#[derive(Debug, PartialEq)]
+pub struct Upsert<'a> {
+    /// The columns of the unique index or the primary key. Empty if the
+    /// conflict target is omitted, which means any constraint.
+    pub target: Vec<MaybeQuotedBytes<'a>>,
+    pub action: UpsertAction<'a>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum UpsertAction<'a> {
+    Nothing,
+    Update {
+        assignments: Vec<(MaybeQuotedBytes<'a>, Expr<'a>)>,
+        filter: Option<Expr<'a>>,
+    },
 }
 
 // Parse INSERT statement.
@@ -522,7 +1168,23 @@ pub fn parse_insert<'a>(p: &mut Parser<'a>) -> Result<'a, Insert<'a>> {
     let Some(Token::Insert) = p.peek() else {
         return Err(p.error("no insert"));
     };
-    let Some(Token::Into) = p.next() else {
+    let conflict = if let Some(Token::Or) = p.next() {
+        let token = p.next();
+        let conflict = if is_keyword(token, b"abort") {
+            ConflictResolution::Abort
+        } else if is_keyword(token, b"ignore") {
+            ConflictResolution::Ignore
+        } else if is_keyword(token, b"replace") {
+            ConflictResolution::Replace
+        } else {
+            return Err(p.error("no conflict resolution"));
+        };
+        p.next();
+        conflict
+    } else {
+        ConflictResolution::Abort
+    };
+    let Some(Token::Into) = p.peek() else {
         return Err(p.error("no into"));
     };
     let Some(Token::Identifier(table_name)) = p.next() else {
@@ -530,26 +1192,102 @@ pub fn parse_insert<'a>(p: &mut Parser<'a>) -> Result<'a, Insert<'a>> {
     };
     let table_name = *table_name;
 
-    let Some(Token::LeftParen) = p.next() else {
-        return Err(p.error("no left paren"));
-    };
     let mut columns = Vec::new();
+    if let Some(Token::LeftParen) = p.next() {
+        loop {
+            let Some(Token::Identifier(column_name)) = p.next() else {
+                return Err(p.error("no column_name"));
+            };
+            columns.push(*column_name);
+            match p.next() {
+                Some(Token::Comma) => continue,
+                Some(Token::RightParen) => break,
+                _ => return Err(p.error("no right paren")),
+            }
+        }
+        p.next();
+    }
 
-    loop {
-        let Some(Token::Identifier(column_name)) = p.next() else {
-            return Err(p.error("no column_name"));
-        };
-        columns.push(*column_name);
-        match p.next() {
-            Some(Token::Comma) => continue,
-            Some(Token::RightParen) => break,
-            _ => return Err(p.error("no right paren")),
+    let source = match p.peek() {
+        Some(Token::Values) => InsertSource::Values(parse_values(p)?),
+        Some(Token::Select) => InsertSource::Select(Box::new(parse_select(p)?)),
+        Some(Token::Default) if columns.is_empty() => {
+            let Some(Token::Values) = p.next() else {
+                return Err(p.error("no values"));
+            };
+            p.next();
+            InsertSource::DefaultValues
+        }
+        _ => return Err(p.error("no values")),
+    };
+
+    let upsert = match (&source, p.peek()) {
+        (InsertSource::Values(_) | InsertSource::Select(_), Some(Token::On)) => {
+            Some(parse_upsert(p)?)
         }
+        _ => None,
+    };
+
+    Ok(Insert {
+        table_name,
+        columns,
+        source,
+        conflict,
+        upsert,
+    })
+}
+
+/// Parse ON CONFLICT clause. The parser must point to ON.
+fn parse_upsert<'a>(p: &mut Parser<'a>) -> Result<'a, Upsert<'a>> {
+    if !is_keyword(p.next(), b"conflict") {
+        return Err(p.error("no conflict after on"));
     }
-    let Some(Token::Values) = p.next() else {
-        return Err(p.error("no values"));
+    let mut target = Vec::new();
+    if let Some(Token::LeftParen) = p.next() {
+        loop {
+            let Some(Token::Identifier(column_name)) = p.next() else {
+                return Err(p.error("no column_name"));
+            };
+            target.push(*column_name);
+            match p.next() {
+                Some(Token::Comma) => continue,
+                Some(Token::RightParen) => break,
+                _ => return Err(p.error("no right paren")),
+            }
+        }
+        p.next();
+    }
+    if !is_keyword(p.peek(), b"do") {
+        return Err(p.error("no do"));
+    }
+    let action = match p.next() {
+        token if is_keyword(token, b"nothing") => {
+            p.next();
+            UpsertAction::Nothing
+        }
+        Some(Token::Update) => {
+            let Some(Token::Set) = p.next() else {
+                return Err(p.error("no set"));
+            };
+            let assignments = parse_assignments(p)?;
+            let filter = if let Some(Token::Where) = p.peek() {
+                p.next();
+                Some(parse_expr(p)?)
+            } else {
+                None
+            };
+            UpsertAction::Update {
+                assignments,
+                filter,
+            }
+        }
+        _ => return Err(p.error("no nothing or update")),
     };
+    Ok(Upsert { target, action })
+}
 
+/// Parse the rows of VALUES. The parser must point to VALUES.
+fn parse_values<'a>(p: &mut Parser<'a>) -> Result<'a, Vec<Vec<Expr<'a>>>> {
     let mut values = Vec::new();
     loop {
         let Some(Token::LeftParen) = p.next() else {
@@ -576,14 +1314,69 @@ pub fn parse_insert<'a>(p: &mut Parser<'a>) -> Result<'a, Insert<'a>> {
             break;
         };
     }
+    Ok(values)
+}
 
-    Ok(Insert {
+#[derive(Debug)]
+pub struct Update<'a> {
+    pub table_name: MaybeQuotedBytes<'a>,
+    pub assignments: Vec<(MaybeQuotedBytes<'a>, Expr<'a>)>,
+    pub filter: Option<Expr<'a>>,
+}
+
+// Parse UPDATE statement.
+//
+// https://www.sqlite.org/lang_update.html
+pub fn parse_update<'a>(p: &mut Parser<'a>) -> Result<'a, Update<'a>> {
+    let Some(Token::Update) = p.peek() else {
+        return Err(p.error("no update"));
+    };
+    let Some(Token::Identifier(table_name)) = p.next() else {
+        return Err(p.error("no table_name"));
+    };
+    let table_name = *table_name;
+    let Some(Token::Set) = p.next() else {
+        return Err(p.error("no set"));
+    };
+    let assignments = parse_assignments(p)?;
+
+    let filter = if let Some(Token::Where) = p.peek() {
+        p.next();
+        let expr = parse_expr(p)?;
+        Some(expr)
+    } else {
+        None
+    };
+
+    Ok(Update {
         table_name,
-        columns,
-        values,
+        assignments,
+        filter,
     })
 }
 
+/// Parse the assignments of SET. The parser must point to SET.
+fn parse_assignments<'a>(p: &mut Parser<'a>) -> Result<'a, Vec<(MaybeQuotedBytes<'a>, Expr<'a>)>> {
+    let mut assignments = Vec::new();
+    loop {
+        let Some(Token::Identifier(column_name)) = p.next() else {
+            return Err(p.error("no column name"));
+        };
+        let column_name = *column_name;
+        let Some(Token::Eq) = p.next() else {
+            return Err(p.error("no eq"));
+        };
+        p.next();
+        let expr = parse_expr(p)?;
+        assignments.push((column_name, expr));
+        if let Some(Token::Comma) = p.peek() {
+            continue;
+        }
+        break;
+    }
+    Ok(assignments)
+}
+
 #[derive(Debug)]
 pub struct Delete<'a> {
     pub table_name: MaybeQuotedBytes<'a>,
@@ -620,13 +1413,26 @@ pub fn parse_delete<'a>(p: &mut Parser<'a>) -> Result<'a, Delete<'a>> {
 pub enum UnaryOp {
     BitNot,
     Minus,
+    Not,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum BinaryOp {
     Compare(CompareOp),
     Concat,
-    // TODO: BitOr
+    And,
+    Or,
+    /// IS. IS NOT is represented as NOT of IS.
+    Is,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    BitAnd,
+    BitOr,
+    ShiftLeft,
+    ShiftRight,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -648,6 +1454,11 @@ pub enum CompareOp {
 #[derive(Debug, PartialEq)]
 pub enum Expr<'a> {
     Column(MaybeQuotedBytes<'a>),
+    /// The column qualified with the table name (e.g. "excluded.col").
+    QualifiedColumn {
+        table_name: MaybeQuotedBytes<'a>,
+        column_name: MaybeQuotedBytes<'a>,
+    },
     UnaryOperator {
         operator: UnaryOp,
         expr: Box<Expr<'a>>,
@@ -665,30 +1476,313 @@ pub enum Expr<'a> {
         expr: Box<Expr<'a>>,
         type_name: Vec<MaybeQuotedBytes<'a>>,
     },
+    Function {
+        name: MaybeQuotedBytes<'a>,
+        args: FunctionArgs<'a>,
+    },
+    Between {
+        expr: Box<Expr<'a>>,
+        low: Box<Expr<'a>>,
+        high: Box<Expr<'a>>,
+    },
+    InList {
+        expr: Box<Expr<'a>>,
+        list: Vec<Expr<'a>>,
+    },
+    Pattern {
+        operator: PatternOp,
+        expr: Box<Expr<'a>>,
+        pattern: Box<Expr<'a>>,
+        escape: Option<Box<Expr<'a>>>,
+    },
     Null,
     Integer(i64),
     Real(f64),
     Text(MaybeQuotedBytes<'a>),
     Blob(HexedBytes<'a>),
+    /// The index of the parameter starting from 1.
+    Parameter(u32),
+    CurrentTime(CurrentTime),
+}
+
+/// CURRENT_DATE, CURRENT_TIME or CURRENT_TIMESTAMP in UTC.
+///
+/// This is only supported as the DEFAULT value of a column.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CurrentTime {
+    Date,
+    Time,
+    Timestamp,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PatternOp {
+    Like,
+    Glob,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FunctionArgs<'a> {
+    /// "*" as in "count(*)".
+    Star,
+    Exprs(Vec<Expr<'a>>),
+}
+
+/// Parse expression.
+///
+/// https://www.sqlite.org/syntax/expr.html
+fn parse_expr<'a>(p: &mut Parser<'a>) -> Result<'a, Expr<'a>> {
+    parse_expr_or(p)
+}
+
+fn parse_expr_or<'a>(p: &mut Parser<'a>) -> Result<'a, Expr<'a>> {
+    let mut expr = parse_expr_and(p)?;
+    while let Some(Token::Or) = p.peek() {
+        p.next();
+        let right = parse_expr_and(p)?;
+        expr = Expr::BinaryOperator {
+            operator: BinaryOp::Or,
+            left: Box::new(expr),
+            right: Box::new(right),
+        };
+    }
+    Ok(expr)
+}
+
+fn parse_expr_and<'a>(p: &mut Parser<'a>) -> Result<'a, Expr<'a>> {
+    let mut expr = parse_expr_not(p)?;
+    while let Some(Token::And) = p.peek() {
+        p.next();
+        let right = parse_expr_not(p)?;
+        expr = Expr::BinaryOperator {
+            operator: BinaryOp::And,
+            left: Box::new(expr),
+            right: Box::new(right),
+        };
+    }
+    Ok(expr)
+}
+
+fn parse_expr_not<'a>(p: &mut Parser<'a>) -> Result<'a, Expr<'a>> {
+    if let Some(Token::Not) = p.peek() {
+        p.next();
+        let expr = parse_expr_not(p)?;
+        Ok(Expr::UnaryOperator {
+            operator: UnaryOp::Not,
+            expr: Box::new(expr),
+        })
+    } else {
+        parse_expr_eq(p)
+    }
+}
+
+fn parse_expr_eq<'a>(p: &mut Parser<'a>) -> Result<'a, Expr<'a>> {
+    let mut expr = parse_expr_compare(p)?;
+    loop {
+        let operator = match p.peek() {
+            Some(Token::Eq) => BinaryOp::Compare(CompareOp::Eq),
+            Some(Token::Ne) => BinaryOp::Compare(CompareOp::Ne),
+            Some(Token::Is) => {
+                let not = if let Some(Token::Not) = p.next() {
+                    p.next();
+                    true
+                } else {
+                    false
+                };
+                let right = parse_expr_compare(p)?;
+                expr = negate_if(
+                    not,
+                    Expr::BinaryOperator {
+                        operator: BinaryOp::Is,
+                        left: Box::new(expr),
+                        right: Box::new(right),
+                    },
+                );
+                continue;
+            }
+            Some(Token::Isnull) => {
+                p.next();
+                expr = is_null(expr, false);
+                continue;
+            }
+            Some(Token::Notnull) => {
+                p.next();
+                expr = is_null(expr, true);
+                continue;
+            }
+            Some(Token::Not) => {
+                let mut cloned_parser = p.clone();
+                match cloned_parser.next() {
+                    Some(Token::Null) => {
+                        cloned_parser.next();
+                        *p = cloned_parser;
+                        expr = is_null(expr, true);
+                    }
+                    Some(Token::Between | Token::In | Token::Like | Token::Glob) => {
+                        *p = cloned_parser;
+                        expr = negate_if(true, parse_predicate(p, expr)?);
+                    }
+                    _ => break,
+                }
+                continue;
+            }
+            Some(Token::Between | Token::In | Token::Like | Token::Glob) => {
+                expr = parse_predicate(p, expr)?;
+                continue;
+            }
+            _ => break,
+        };
+        p.next();
+        let right = parse_expr_compare(p)?;
+        expr = Expr::BinaryOperator {
+            operator,
+            left: Box::new(expr),
+            right: Box::new(right),
+        };
+    }
+    Ok(expr)
+}
+
+fn negate_if(not: bool, expr: Expr) -> Expr {
+    if not {
+        Expr::UnaryOperator {
+            operator: UnaryOp::Not,
+            expr: Box::new(expr),
+        }
+    } else {
+        expr
+    }
+}
+
+/// "expr ISNULL" or "expr NOTNULL".
+fn is_null(expr: Expr, not: bool) -> Expr {
+    negate_if(
+        not,
+        Expr::BinaryOperator {
+            operator: BinaryOp::Is,
+            left: Box::new(expr),
+            right: Box::new(Expr::Null),
+        },
+    )
+}
+
+/// Parse BETWEEN, IN, LIKE or GLOB following the left operand. The parser
+/// must point to the operator keyword.
+fn parse_predicate<'a>(p: &mut Parser<'a>, expr: Expr<'a>) -> Result<'a, Expr<'a>> {
+    match p.peek() {
+        Some(Token::Between) => {
+            p.next();
+            let low = parse_expr_compare(p)?;
+            let Some(Token::And) = p.peek() else {
+                return Err(p.error("no and after between"));
+            };
+            p.next();
+            let high = parse_expr_compare(p)?;
+            Ok(Expr::Between {
+                expr: Box::new(expr),
+                low: Box::new(low),
+                high: Box::new(high),
+            })
+        }
+        Some(Token::In) => {
+            let Some(Token::LeftParen) = p.next() else {
+                return Err(p.error("no left paren after in"));
+            };
+            let mut list = Vec::new();
+            if let Some(Token::RightParen) = p.next() {
+            } else {
+                list.push(parse_expr(p)?);
+                while let Some(Token::Comma) = p.peek() {
+                    p.next();
+                    list.push(parse_expr(p)?);
+                }
+            }
+            let Some(Token::RightParen) = p.peek() else {
+                return Err(p.error("no in right paren"));
+            };
+            p.next();
+            Ok(Expr::InList {
+                expr: Box::new(expr),
+                list,
+            })
+        }
+        Some(Token::Like | Token::Glob) => {
+            let operator = if let Some(Token::Like) = p.peek() {
+                PatternOp::Like
+            } else {
+                PatternOp::Glob
+            };
+            p.next();
+            let pattern = parse_expr_compare(p)?;
+            let escape = if let Some(Token::Escape) = p.peek() {
+                p.next();
+                Some(Box::new(parse_expr_bit(p)?))
+            } else {
+                None
+            };
+            Ok(Expr::Pattern {
+                operator,
+                expr: Box::new(expr),
+                pattern: Box::new(pattern),
+                escape,
+            })
+        }
+        _ => Err(p.error("no predicate")),
+    }
+}
+
+fn parse_expr_compare<'a>(p: &mut Parser<'a>) -> Result<'a, Expr<'a>> {
+    let mut expr = parse_expr_bit(p)?;
+    loop {
+        let operator = match p.peek() {
+            Some(Token::Gt) => BinaryOp::Compare(CompareOp::Gt),
+            Some(Token::Ge) => BinaryOp::Compare(CompareOp::Ge),
+            Some(Token::Lt) => BinaryOp::Compare(CompareOp::Lt),
+            Some(Token::Le) => BinaryOp::Compare(CompareOp::Le),
+            _ => break,
+        };
+        p.next();
+        let right = parse_expr_bit(p)?;
+        expr = Expr::BinaryOperator {
+            operator,
+            left: Box::new(expr),
+            right: Box::new(right),
+        };
+    }
+    Ok(expr)
 }
 
-/// Parse expression.
-///
-/// https://www.sqlite.org/syntax/expr.html
-fn parse_expr<'a>(p: &mut Parser<'a>) -> Result<'a, Expr<'a>> {
-    parse_expr_eq(p)
+fn parse_expr_bit<'a>(p: &mut Parser<'a>) -> Result<'a, Expr<'a>> {
+    let mut expr = parse_expr_additive(p)?;
+    loop {
+        let operator = match p.peek() {
+            Some(Token::BitAnd) => BinaryOp::BitAnd,
+            Some(Token::BitOr) => BinaryOp::BitOr,
+            Some(Token::ShiftLeft) => BinaryOp::ShiftLeft,
+            Some(Token::ShiftRight) => BinaryOp::ShiftRight,
+            _ => break,
+        };
+        p.next();
+        let right = parse_expr_additive(p)?;
+        expr = Expr::BinaryOperator {
+            operator,
+            left: Box::new(expr),
+            right: Box::new(right),
+        };
+    }
+    Ok(expr)
 }
 
-fn parse_expr_eq<'a>(p: &mut Parser<'a>) -> Result<'a, Expr<'a>> {
-    let mut expr = parse_expr_compare(p)?;
+fn parse_expr_additive<'a>(p: &mut Parser<'a>) -> Result<'a, Expr<'a>> {
+    let mut expr = parse_expr_multiplicative(p)?;
     loop {
         let operator = match p.peek() {
-            Some(Token::Eq) => BinaryOp::Compare(CompareOp::Eq),
-            Some(Token::Ne) => BinaryOp::Compare(CompareOp::Ne),
+            Some(Token::Plus) => BinaryOp::Add,
+            Some(Token::Minus) => BinaryOp::Sub,
             _ => break,
         };
         p.next();
-        let right = parse_expr_compare(p)?;
+        let right = parse_expr_multiplicative(p)?;
         expr = Expr::BinaryOperator {
             operator,
             left: Box::new(expr),
@@ -698,14 +1792,13 @@ fn parse_expr_eq<'a>(p: &mut Parser<'a>) -> Result<'a, Expr<'a>> {
     Ok(expr)
 }
 
-fn parse_expr_compare<'a>(p: &mut Parser<'a>) -> Result<'a, Expr<'a>> {
+fn parse_expr_multiplicative<'a>(p: &mut Parser<'a>) -> Result<'a, Expr<'a>> {
     let mut expr = parse_expr_concat(p)?;
     loop {
         let operator = match p.peek() {
-            Some(Token::Gt) => BinaryOp::Compare(CompareOp::Gt),
-            Some(Token::Ge) => BinaryOp::Compare(CompareOp::Ge),
-            Some(Token::Lt) => BinaryOp::Compare(CompareOp::Lt),
-            Some(Token::Le) => BinaryOp::Compare(CompareOp::Le),
+            Some(Token::Asterisk) => BinaryOp::Mul,
+            Some(Token::Slash) => BinaryOp::Div,
+            Some(Token::Percent) => BinaryOp::Rem,
             _ => break,
         };
         p.next();
@@ -761,6 +1854,9 @@ fn parse_expr_collate<'a>(p: &mut Parser<'a>) -> Result<'a, Expr<'a>> {
 
 fn parse_expr_unary<'a>(p: &mut Parser<'a>) -> Result<'a, Expr<'a>> {
     match p.peek() {
+        // NOT as an operand (e.g. "1 = NOT 0") has the lower precedence than
+        // the following binary operators as SQLite does.
+        Some(Token::Not) => parse_expr_not(p),
         Some(Token::Tilda) => {
             p.next();
             let expr = parse_expr_unary(p)?;
@@ -816,7 +1912,28 @@ fn parse_expr_unary<'a>(p: &mut Parser<'a>) -> Result<'a, Expr<'a>> {
 
 fn parse_expr_primitive<'a>(p: &mut Parser<'a>) -> Result<'a, Expr<'a>> {
     let expr = match p.peek() {
-        Some(Token::Identifier(id)) => Expr::Column(*id),
+        Some(Token::Identifier(id)) => {
+            let id = *id;
+            let mut cloned_parser = p.clone();
+            match cloned_parser.next() {
+                Some(Token::LeftParen) => {
+                    *p = cloned_parser;
+                    return parse_function_call(p, id);
+                }
+                Some(Token::Dot) => {
+                    let Some(Token::Identifier(column_name)) = cloned_parser.next() else {
+                        return Err(cloned_parser.error("no column name"));
+                    };
+                    let column_name = *column_name;
+                    *p = cloned_parser;
+                    Expr::QualifiedColumn {
+                        table_name: id,
+                        column_name,
+                    }
+                }
+                _ => Expr::Column(id),
+            }
+        }
         Some(Token::Cast) => {
             let Some(Token::LeftParen) = p.next() else {
                 return Err(p.error("no cast left paren"));
@@ -841,6 +1958,14 @@ fn parse_expr_primitive<'a>(p: &mut Parser<'a>) -> Result<'a, Expr<'a>> {
                 type_name,
             }
         }
+        Some(Token::LeftParen) => {
+            p.next();
+            let expr = parse_expr(p)?;
+            let Some(Token::RightParen) = p.peek() else {
+                return Err(p.error("no right paren"));
+            };
+            expr
+        }
         Some(Token::Null) => Expr::Null,
         Some(Token::Integer(buf)) => {
             let (valid, parsed_int) = parse_integer(buf);
@@ -866,6 +1991,13 @@ fn parse_expr_primitive<'a>(p: &mut Parser<'a>) -> Result<'a, Expr<'a>> {
         }
         Some(Token::String(text)) => Expr::Text(*text),
         Some(Token::Blob(hex)) => Expr::Blob(*hex),
+        Some(Token::Variable(variable)) => {
+            let variable = *variable;
+            let Some(index) = p.parameters.add(variable) else {
+                return Err(p.error("parameter index out of range"));
+            };
+            Expr::Parameter(index)
+        }
         _ => return Err(p.error("no expr")),
     };
     p.next();
@@ -873,6 +2005,31 @@ fn parse_expr_primitive<'a>(p: &mut Parser<'a>) -> Result<'a, Expr<'a>> {
     Ok(expr)
 }
 
+/// Parse the arguments of a function call. The parser must point to the left
+/// parenthesis following the function name.
+fn parse_function_call<'a>(p: &mut Parser<'a>, name: MaybeQuotedBytes<'a>) -> Result<'a, Expr<'a>> {
+    let args = match p.next() {
+        Some(Token::Asterisk) => {
+            p.next();
+            FunctionArgs::Star
+        }
+        Some(Token::RightParen) => FunctionArgs::Exprs(Vec::new()),
+        _ => {
+            let mut args = vec![parse_expr(p)?];
+            while let Some(Token::Comma) = p.peek() {
+                p.next();
+                args.push(parse_expr(p)?);
+            }
+            FunctionArgs::Exprs(args)
+        }
+    };
+    let Some(Token::RightParen) = p.peek() else {
+        return Err(p.error("no function right paren"));
+    };
+    p.next();
+    Ok(Expr::Function { name, args })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1022,6 +2179,80 @@ mod tests {
             create_table.columns[2].constraints,
             vec![ColumnConstraint::Collate(b"\"RTRIM\"".as_slice().into()),]
         );
+
+        let input = b"create table foo (col1 primary key asc collate nocase)";
+        let mut parser = Parser::new(input);
+        let create_table = parse_create_table(&mut parser).unwrap();
+        assert_eq!(parser.n_consumed(), input.len());
+        assert_eq!(
+            create_table.columns[0].constraints,
+            vec![
+                ColumnConstraint::PrinaryKey,
+                ColumnConstraint::Collate(b"nocase".as_slice().into())
+            ]
+        );
+        let mut parser = Parser::new(b"create table foo (col1 integer primary key desc)");
+        assert!(parse_create_table(&mut parser).is_err());
+
+        let input = b"create table foo (col1 default -1, col2 default 'abc' collate nocase, col3 default (1 + 2), col4 default null)";
+        let mut parser = Parser::new(input);
+        let create_table = parse_create_table(&mut parser).unwrap();
+        assert_eq!(parser.n_consumed(), input.len());
+        assert_eq!(
+            create_table.columns[0].constraints,
+            vec![ColumnConstraint::Default(Expr::Integer(-1))]
+        );
+        assert_eq!(
+            create_table.columns[1].constraints,
+            vec![
+                ColumnConstraint::Default(Expr::Text(b"'abc'".as_slice().into())),
+                ColumnConstraint::Collate(b"nocase".as_slice().into())
+            ]
+        );
+        assert_eq!(
+            create_table.columns[2].constraints,
+            vec![ColumnConstraint::Default(Expr::BinaryOperator {
+                operator: BinaryOp::Add,
+                left: Box::new(Expr::Integer(1)),
+                right: Box::new(Expr::Integer(2)),
+            })]
+        );
+        assert_eq!(
+            create_table.columns[3].constraints,
+            vec![ColumnConstraint::Default(Expr::Null)]
+        );
+
+        let input = b"create table foo (col1 default current_timestamp, col2 default CURRENT_DATE, col3 default Current_Time, col4 default true, col5 default FALSE, col6 default abc, col7 default \"true\")";
+        let mut parser = Parser::new(input);
+        let create_table = parse_create_table(&mut parser).unwrap();
+        assert_eq!(parser.n_consumed(), input.len());
+        let defaults = create_table
+            .columns
+            .into_iter()
+            .map(|column| column.constraints)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            defaults,
+            vec![
+                vec![ColumnConstraint::Default(Expr::CurrentTime(
+                    CurrentTime::Timestamp
+                ))],
+                vec![ColumnConstraint::Default(Expr::CurrentTime(
+                    CurrentTime::Date
+                ))],
+                vec![ColumnConstraint::Default(Expr::CurrentTime(
+                    CurrentTime::Time
+                ))],
+                vec![ColumnConstraint::Default(Expr::Integer(1))],
+                vec![ColumnConstraint::Default(Expr::Integer(0))],
+                vec![ColumnConstraint::Default(Expr::Text(
+                    b"abc".as_slice().into()
+                ))],
+                vec![ColumnConstraint::Default(Expr::Text(
+                    b"\"true\"".as_slice().into()
+                ))],
+            ]
+        );
     }
 
     #[test]
@@ -1048,6 +2279,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_create_table_if_not_exists() {
+        let input = b"create table if not exists [foo] (id, name text) ";
+        let mut parser = Parser::new(input);
+        let create_table = parse_create_table(&mut parser).unwrap();
+        assert_eq!(parser.n_consumed(), input.len());
+        assert!(create_table.if_not_exists);
+        assert_eq!(create_table.table_name, b"foo".as_slice().into());
+        assert_eq!(create_table.columns.len(), 2);
+        assert_eq!(create_table.definition, b"[foo] (id, name text)");
+
+        let input = b"create table foo(id)";
+        let mut parser = Parser::new(input);
+        let create_table = parse_create_table(&mut parser).unwrap();
+        assert!(!create_table.if_not_exists);
+        assert_eq!(create_table.definition, b"foo(id)");
+    }
+
     #[test]
     fn test_parse_create_table_fail() {
         // no column def.
@@ -1062,10 +2311,21 @@ mod tests {
         let r = parse_create_table(&mut Parser::new(b"create table foo (id primary, name)"));
         assert!(r.is_err());
         assert_eq!(r.unwrap_err().cursor(), 28);
+        // default without value.
+        let r = parse_create_table(&mut Parser::new(b"create table foo (id default, name)"));
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().cursor(), 28);
         // key without primary.
         let r = parse_create_table(&mut Parser::new(b"create table foo (id key, name)"));
         assert!(r.is_err());
         assert_eq!(r.unwrap_err().cursor(), 21);
+        // if without not exists.
+        let r = parse_create_table(&mut Parser::new(b"create table if exists foo (id)"));
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().cursor(), 16);
+        let r = parse_create_table(&mut Parser::new(b"create table if not foo (id)"));
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().cursor(), 20);
     }
 
     #[test]
@@ -1113,12 +2373,258 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_create_index_unique_if_not_exists() {
+        let input = b"create unique index if not exists foo on bar (col1, col2) ";
+        let mut parser = Parser::new(input);
+        let create_index = parse_create_index(&mut parser).unwrap();
+        assert_eq!(parser.n_consumed(), input.len());
+        assert!(create_index.unique);
+        assert!(create_index.if_not_exists);
+        assert_eq!(create_index.index_name, b"foo".as_slice().into());
+        assert_eq!(create_index.table_name, b"bar".as_slice().into());
+        assert_eq!(create_index.columns.len(), 2);
+        assert_eq!(create_index.definition, b"foo on bar (col1, col2)");
+
+        let input = b"create index foo on bar(col1)";
+        let mut parser = Parser::new(input);
+        let create_index = parse_create_index(&mut parser).unwrap();
+        assert!(!create_index.unique);
+        assert!(!create_index.if_not_exists);
+        assert_eq!(create_index.definition, b"foo on bar(col1)");
+    }
+
     #[test]
     fn test_parse_create_index_fail() {
         // no right paren.
         let r = parse_create_index(&mut Parser::new(b"create index foo on bar (id, name "));
         assert!(r.is_err());
         assert_eq!(r.unwrap_err().cursor(), 34);
+        // unique without index.
+        let r = parse_create_index(&mut Parser::new(b"create unique foo on bar (id)"));
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().cursor(), 14);
+    }
+
+    #[test]
+    fn test_parse_sql_create() {
+        let mut parser = Parser::new(b"create table foo (id)");
+        assert!(matches!(parse_sql(&mut parser), Ok(Stmt::CreateTable(_))));
+        let mut parser = Parser::new(b"create index foo on bar (id)");
+        assert!(matches!(parse_sql(&mut parser), Ok(Stmt::CreateIndex(_))));
+        let mut parser = Parser::new(b"create unique index foo on bar (id)");
+        assert!(matches!(parse_sql(&mut parser), Ok(Stmt::CreateIndex(_))));
+        let mut parser = Parser::new(b"create foo");
+        let r = parse_sql(&mut parser);
+        assert!(r.is_err());
+        assert_eq!(r.err().unwrap().cursor(), 7);
+    }
+
+    #[test]
+    fn test_parse_drop_table() {
+        let input = b"drop table foo";
+        let mut parser = Parser::new(input);
+        let drop_table = parse_drop_table(&mut parser).unwrap();
+        assert_eq!(parser.n_consumed(), input.len());
+        assert!(!drop_table.if_exists);
+        assert_eq!(drop_table.table_name, b"foo".as_slice().into());
+
+        let input = b"DROP TABLE IF EXISTS \"Foo\";";
+        let mut parser = Parser::new(input);
+        let drop_table = parse_drop_table(&mut parser).unwrap();
+        assert_eq!(parser.n_consumed(), input.len() - 1);
+        assert!(drop_table.if_exists);
+        assert_eq!(drop_table.table_name, b"\"Foo\"".as_slice().into());
+    }
+
+    #[test]
+    fn test_parse_drop_index() {
+        let input = b"drop index if exists foo ";
+        let mut parser = Parser::new(input);
+        let drop_index = parse_drop_index(&mut parser).unwrap();
+        assert_eq!(parser.n_consumed(), input.len());
+        assert!(drop_index.if_exists);
+        assert_eq!(drop_index.index_name, b"foo".as_slice().into());
+    }
+
+    #[test]
+    fn test_parse_drop_fail() {
+        // no table name.
+        let r = parse_drop_table(&mut Parser::new(b"drop table "));
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().cursor(), 11);
+        // no exists after if.
+        let r = parse_drop_index(&mut Parser::new(b"drop index if foo"));
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().cursor(), 14);
+        // neither table nor index.
+        let r = parse_sql(&mut Parser::new(b"drop foo"));
+        assert!(r.is_err());
+        assert_eq!(r.err().unwrap().cursor(), 5);
+
+        let mut parser = Parser::new(b"drop table foo");
+        assert!(matches!(parse_sql(&mut parser), Ok(Stmt::DropTable(_))));
+        let mut parser = Parser::new(b"drop index foo");
+        assert!(matches!(parse_sql(&mut parser), Ok(Stmt::DropIndex(_))));
+    }
+
+    #[test]
+    fn test_parse_analyze() {
+        let input = b"analyze";
+        let mut parser = Parser::new(input);
+        let analyze = parse_analyze(&mut parser).unwrap();
+        assert_eq!(parser.n_consumed(), input.len());
+        assert_eq!(analyze.name, None);
+
+        let input = b"analyze foo ";
+        let mut parser = Parser::new(input);
+        let analyze = parse_analyze(&mut parser).unwrap();
+        assert_eq!(parser.n_consumed(), input.len());
+        assert_eq!(analyze.name, Some(b"foo".as_slice().into()));
+
+        let mut parser = Parser::new(b"analyze \"foo\";");
+        assert!(matches!(
+            parse_sql(&mut parser),
+            Ok(Stmt::Analyze(Analyze { name: Some(_) }))
+        ));
+        assert!(expect_semicolon(&mut parser).is_ok());
+    }
+
+    #[test]
+    fn test_parse_begin() {
+        for (input, transaction_type) in [
+            ("begin", TransactionType::Deferred),
+            ("begin transaction", TransactionType::Deferred),
+            ("BEGIN DEFERRED", TransactionType::Deferred),
+            ("begin immediate", TransactionType::Immediate),
+            ("begin exclusive transaction", TransactionType::Exclusive),
+        ] {
+            let mut parser = Parser::new(input.as_bytes());
+            let begin = parse_begin(&mut parser).unwrap();
+            assert_eq!(parser.n_consumed(), input.len(), "{input}");
+            assert_eq!(begin.transaction_type, transaction_type, "{input}");
+        }
+
+        let mut parser = Parser::new(b"begin immediate;");
+        assert!(matches!(
+            parse_sql(&mut parser),
+            Ok(Stmt::Begin(Begin {
+                transaction_type: TransactionType::Immediate
+            }))
+        ));
+        assert!(expect_semicolon(&mut parser).is_ok());
+
+        let mut parser = Parser::new(b"begin foo;");
+        assert!(parse_sql(&mut parser).is_ok());
+        assert!(expect_semicolon(&mut parser).is_err());
+    }
+
+    #[test]
+    fn test_parse_commit_and_rollback() {
+        for input in ["commit", "commit transaction"] {
+            let mut parser = Parser::new(input.as_bytes());
+            parse_commit(&mut parser).unwrap();
+            assert_eq!(parser.n_consumed(), input.len(), "{input}");
+        }
+
+        for input in ["rollback", "rollback transaction"] {
+            let mut parser = Parser::new(input.as_bytes());
+            let rollback = parse_rollback(&mut parser).unwrap();
+            assert_eq!(parser.n_consumed(), input.len(), "{input}");
+            assert_eq!(rollback.savepoint_name, None);
+        }
+
+        for input in [
+            "rollback to foo",
+            "rollback to savepoint foo",
+            "rollback transaction to savepoint foo",
+        ] {
+            let mut parser = Parser::new(input.as_bytes());
+            let rollback = parse_rollback(&mut parser).unwrap();
+            assert_eq!(parser.n_consumed(), input.len(), "{input}");
+            assert_eq!(rollback.savepoint_name, Some(b"foo".as_slice().into()));
+        }
+
+        let mut parser = Parser::new(b"commit;");
+        assert!(matches!(parse_sql(&mut parser), Ok(Stmt::Commit)));
+        assert!(expect_semicolon(&mut parser).is_ok());
+        let mut parser = Parser::new(b"rollback;");
+        assert!(matches!(
+            parse_sql(&mut parser),
+            Ok(Stmt::Rollback(Rollback {
+                savepoint_name: None
+            }))
+        ));
+        assert!(expect_semicolon(&mut parser).is_ok());
+
+        let mut parser = Parser::new(b"rollback to;");
+        assert!(parse_sql(&mut parser).is_err());
+        let mut parser = Parser::new(b"rollback to savepoint;");
+        assert!(parse_sql(&mut parser).is_err());
+    }
+
+    #[test]
+    fn test_parse_savepoint_and_release() {
+        let input = b"savepoint foo";
+        let mut parser = Parser::new(input);
+        let savepoint = parse_savepoint(&mut parser).unwrap();
+        assert_eq!(parser.n_consumed(), input.len());
+        assert_eq!(savepoint.name, b"foo".as_slice().into());
+
+        for input in ["release foo", "release savepoint foo"] {
+            let mut parser = Parser::new(input.as_bytes());
+            let release = parse_release(&mut parser).unwrap();
+            assert_eq!(parser.n_consumed(), input.len(), "{input}");
+            assert_eq!(release.name, b"foo".as_slice().into());
+        }
+
+        let mut parser = Parser::new(b"savepoint \"foo\";");
+        assert!(matches!(
+            parse_sql(&mut parser),
+            Ok(Stmt::Savepoint(Savepoint { .. }))
+        ));
+        assert!(expect_semicolon(&mut parser).is_ok());
+        let mut parser = Parser::new(b"release foo;");
+        assert!(matches!(
+            parse_sql(&mut parser),
+            Ok(Stmt::Release(Release { .. }))
+        ));
+        assert!(expect_semicolon(&mut parser).is_ok());
+
+        let mut parser = Parser::new(b"savepoint;");
+        assert!(parse_sql(&mut parser).is_err());
+        let mut parser = Parser::new(b"release savepoint;");
+        assert!(parse_sql(&mut parser).is_err());
+    }
+
+    #[test]
+    fn test_parse_explain() {
+        let mut parser = Parser::new(b"explain query plan select * from foo;");
+        let Ok(Stmt::ExplainQueryPlan(stmt)) = parse_sql(&mut parser) else {
+            unreachable!()
+        };
+        assert!(matches!(*stmt, Stmt::Select(Select { .. })));
+        assert!(expect_semicolon(&mut parser).is_ok());
+
+        let mut parser = Parser::new(b"EXPLAIN QUERY PLAN DELETE FROM foo;");
+        let Ok(Stmt::ExplainQueryPlan(stmt)) = parse_sql(&mut parser) else {
+            unreachable!()
+        };
+        assert!(matches!(*stmt, Stmt::Delete(Delete { .. })));
+
+        // QUERY and PLAN are not keywords.
+        let mut parser = Parser::new(b"select query, plan from plan;");
+        assert!(matches!(parse_sql(&mut parser), Ok(Stmt::Select(_))));
+
+        for input in [
+            "explain select * from foo;",
+            "explain query select * from foo;",
+            "explain query plan;",
+            "explain query plan explain query plan select * from foo;",
+        ] {
+            let mut parser = Parser::new(input.as_bytes());
+            assert!(parse_sql(&mut parser).is_err(), "{input}");
+        }
     }
 
     #[test]
@@ -1185,28 +2691,140 @@ mod tests {
         assert_eq!(parser.n_consumed(), input.len());
         assert_eq!(select.table_name, b"foo".as_slice().into());
         assert_eq!(
-            select.columns,
-            vec![ResultColumn::AllOfTable(b"bar".as_slice().into()),]
+            select.columns,
+            vec![ResultColumn::AllOfTable(b"bar".as_slice().into()),]
+        );
+    }
+
+    #[test]
+    fn test_parse_select_where() {
+        let input = b"select * from foo where id = 5";
+        let mut parser = Parser::new(input);
+        let select = parse_select(&mut parser).unwrap();
+        assert_eq!(parser.n_consumed(), input.len());
+        assert_eq!(select.table_name, b"foo".as_slice().into());
+        assert_eq!(select.columns, vec![ResultColumn::All,]);
+        assert!(select.filter.is_some());
+        assert_eq!(
+            select.filter.unwrap(),
+            Expr::BinaryOperator {
+                operator: BinaryOp::Compare(CompareOp::Eq),
+                left: Box::new(Expr::Column(b"id".as_slice().into())),
+                right: Box::new(Expr::Integer(5)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_select_order_by_limit() {
+        let input = b"select * from foo where id = 1 order by col1, col2 collate nocase desc, col3 asc limit 10 offset 5";
+        let mut parser = Parser::new(input);
+        let select = parse_select(&mut parser).unwrap();
+        assert_eq!(parser.n_consumed(), input.len());
+        assert!(select.filter.is_some());
+        assert_eq!(
+            select.order_by,
+            vec![
+                OrderingTerm {
+                    expr: Expr::Column(b"col1".as_slice().into()),
+                    desc: false,
+                },
+                OrderingTerm {
+                    expr: Expr::Collate {
+                        expr: Box::new(Expr::Column(b"col2".as_slice().into())),
+                        collation_name: b"nocase".as_slice().into(),
+                    },
+                    desc: true,
+                },
+                OrderingTerm {
+                    expr: Expr::Column(b"col3".as_slice().into()),
+                    desc: false,
+                },
+            ]
+        );
+        assert_eq!(select.limit, Some(Expr::Integer(10)));
+        assert_eq!(select.offset, Some(Expr::Integer(5)));
+
+        // "LIMIT <offset>, <limit>"
+        let input = b"select * from foo limit 5, 10;";
+        let mut parser = Parser::new(input);
+        let select = parse_select(&mut parser).unwrap();
+        assert_eq!(parser.n_consumed(), input.len() - 1);
+        assert!(select.order_by.is_empty());
+        assert_eq!(select.limit, Some(Expr::Integer(10)));
+        assert_eq!(select.offset, Some(Expr::Integer(5)));
+
+        let input = b"select * from foo order by col limit 1";
+        let mut parser = Parser::new(input);
+        let select = parse_select(&mut parser).unwrap();
+        assert_eq!(parser.n_consumed(), input.len());
+        assert_eq!(select.order_by.len(), 1);
+        assert_eq!(select.limit, Some(Expr::Integer(1)));
+        assert!(select.offset.is_none());
+    }
+
+    #[test]
+    fn test_parse_select_group_by() {
+        let input = b"select col1, count(*), sum(col2) from foo group by col1, col3 having max(col2) > 10 order by 2";
+        let mut parser = Parser::new(input);
+        let select = parse_select(&mut parser).unwrap();
+        assert_eq!(parser.n_consumed(), input.len());
+        assert_eq!(
+            select.columns,
+            vec![
+                ResultColumn::Expr((Expr::Column(b"col1".as_slice().into()), None)),
+                ResultColumn::Expr((
+                    Expr::Function {
+                        name: b"count".as_slice().into(),
+                        args: FunctionArgs::Star,
+                    },
+                    None
+                )),
+                ResultColumn::Expr((
+                    Expr::Function {
+                        name: b"sum".as_slice().into(),
+                        args: FunctionArgs::Exprs(vec![Expr::Column(b"col2".as_slice().into())]),
+                    },
+                    None
+                )),
+            ]
+        );
+        assert_eq!(
+            select.group_by,
+            vec![
+                Expr::Column(b"col1".as_slice().into()),
+                Expr::Column(b"col3".as_slice().into()),
+            ]
         );
-    }
+        assert_eq!(
+            select.having,
+            Some(Expr::BinaryOperator {
+                operator: BinaryOp::Compare(CompareOp::Gt),
+                left: Box::new(Expr::Function {
+                    name: b"max".as_slice().into(),
+                    args: FunctionArgs::Exprs(vec![Expr::Column(b"col2".as_slice().into())]),
+                }),
+                right: Box::new(Expr::Integer(10)),
+            })
+        );
+        assert_eq!(select.order_by.len(), 1);
 
-    #[test]
-    fn test_parse_select_where() {
-        let input = b"select * from foo where id = 5";
+        let input = b"select count() from foo having 1";
         let mut parser = Parser::new(input);
         let select = parse_select(&mut parser).unwrap();
         assert_eq!(parser.n_consumed(), input.len());
-        assert_eq!(select.table_name, b"foo".as_slice().into());
-        assert_eq!(select.columns, vec![ResultColumn::All,]);
-        assert!(select.filter.is_some());
         assert_eq!(
-            select.filter.unwrap(),
-            Expr::BinaryOperator {
-                operator: BinaryOp::Compare(CompareOp::Eq),
-                left: Box::new(Expr::Column(b"id".as_slice().into())),
-                right: Box::new(Expr::Integer(5)),
-            }
+            select.columns,
+            vec![ResultColumn::Expr((
+                Expr::Function {
+                    name: b"count".as_slice().into(),
+                    args: FunctionArgs::Exprs(Vec::new()),
+                },
+                None
+            ))]
         );
+        assert!(select.group_by.is_empty());
+        assert_eq!(select.having, Some(Expr::Integer(1)));
     }
 
     #[test]
@@ -1219,6 +2837,22 @@ mod tests {
         let r = parse_select(&mut Parser::new(b"select col from ;"));
         assert!(r.is_err());
         assert_eq!(r.unwrap_err().cursor(), 16);
+        // no by after order.
+        let r = parse_select(&mut Parser::new(b"select col from foo order col"));
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().cursor(), 26);
+        // no by after group.
+        let r = parse_select(&mut Parser::new(b"select col from foo group col"));
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().cursor(), 26);
+        // no function right paren.
+        let r = parse_select(&mut Parser::new(b"select count(col from foo"));
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().cursor(), 17);
+        // no expr after limit.
+        let r = parse_select(&mut Parser::new(b"select col from foo limit ;"));
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().cursor(), 26);
     }
 
     #[test]
@@ -1230,7 +2864,9 @@ mod tests {
             Insert {
                 table_name: b"example".as_slice().into(),
                 columns: vec![b"col".as_slice().into()],
-                values: vec![vec![Expr::Integer(1)]],
+                source: InsertSource::Values(vec![vec![Expr::Integer(1)]]),
+                conflict: ConflictResolution::Abort,
+                upsert: None,
             }
         );
         assert_parser!(
@@ -1240,7 +2876,9 @@ mod tests {
             Insert {
                 table_name: b"example2".as_slice().into(),
                 columns: vec![b"col".as_slice().into(), b"col2".as_slice().into()],
-                values: vec![vec![Expr::Integer(1), Expr::Integer(2)]],
+                source: InsertSource::Values(vec![vec![Expr::Integer(1), Expr::Integer(2)]]),
+                conflict: ConflictResolution::Abort,
+                upsert: None,
             }
         );
         assert_parser!(
@@ -1250,12 +2888,120 @@ mod tests {
             Insert {
                 table_name: b"example2".as_slice().into(),
                 columns: vec![b"col".as_slice().into(), b"col2".as_slice().into()],
-                values: vec![
+                source: InsertSource::Values(vec![
                     vec![Expr::Integer(1), Expr::Integer(2)],
                     vec![Expr::Integer(3), Expr::Integer(4)]
-                ],
+                ]),
+                conflict: ConflictResolution::Abort,
+                upsert: None,
+            }
+        );
+        assert_parser!(
+            parse_insert,
+            b"insert into example values (1, 2);",
+            33,
+            Insert {
+                table_name: b"example".as_slice().into(),
+                columns: Vec::new(),
+                source: InsertSource::Values(vec![vec![Expr::Integer(1), Expr::Integer(2)]]),
+                conflict: ConflictResolution::Abort,
+                upsert: None,
+            }
+        );
+        assert_parser!(
+            parse_insert,
+            b"insert into example default values;",
+            34,
+            Insert {
+                table_name: b"example".as_slice().into(),
+                columns: Vec::new(),
+                source: InsertSource::DefaultValues,
+                conflict: ConflictResolution::Abort,
+                upsert: None,
             }
         );
+
+        let input = b"insert into example (col) select col2 from example2 where id = 1;";
+        let mut parser = Parser::new(input);
+        let insert = parse_insert(&mut parser).unwrap();
+        assert_eq!(parser.n_consumed(), input.len() - 1);
+        assert_eq!(insert.columns, vec![b"col".as_slice().into()]);
+        let InsertSource::Select(select) = insert.source else {
+            panic!("unexpected source: {:?}", insert.source);
+        };
+        assert_eq!(select.table_name, b"example2".as_slice().into());
+        assert_eq!(
+            select.columns,
+            vec![ResultColumn::Expr((
+                Expr::Column(b"col2".as_slice().into()),
+                None
+            ))]
+        );
+        assert!(select.filter.is_some());
+    }
+
+    #[test]
+    fn test_parse_insert_conflict() {
+        for (input, conflict) in [
+            (
+                b"insert or abort into example values (1)".as_slice(),
+                ConflictResolution::Abort,
+            ),
+            (
+                b"insert or ignore into example values (1)",
+                ConflictResolution::Ignore,
+            ),
+            (
+                b"INSERT OR REPLACE INTO example VALUES (1)",
+                ConflictResolution::Replace,
+            ),
+        ] {
+            let mut parser = Parser::new(input);
+            let insert = parse_insert(&mut parser).unwrap();
+            assert_eq!(parser.n_consumed(), input.len());
+            assert_eq!(insert.conflict, conflict);
+            assert!(insert.upsert.is_none());
+        }
+
+        let input = b"insert into example (id, col) values (1, 2) on conflict (id) do nothing;";
+        let mut parser = Parser::new(input);
+        let insert = parse_insert(&mut parser).unwrap();
+        assert_eq!(parser.n_consumed(), input.len() - 1);
+        assert_eq!(
+            insert.upsert,
+            Some(Upsert {
+                target: vec![b"id".as_slice().into()],
+                action: UpsertAction::Nothing,
+            })
+        );
+
+        let input = b"insert into example select * from example2 where true on conflict do update set col = excluded.col, col2 = 1 where col2 > 0";
+        let mut parser = Parser::new(input);
+        let insert = parse_insert(&mut parser).unwrap();
+        assert_eq!(parser.n_consumed(), input.len());
+        assert_eq!(
+            insert.upsert,
+            Some(Upsert {
+                target: Vec::new(),
+                action: UpsertAction::Update {
+                    assignments: vec![
+                        (
+                            b"col".as_slice().into(),
+                            Expr::QualifiedColumn {
+                                table_name: b"excluded".as_slice().into(),
+                                column_name: b"col".as_slice().into(),
+                            }
+                        ),
+                        (b"col2".as_slice().into(), Expr::Integer(1)),
+                    ],
+                    filter: Some(Expr::BinaryOperator {
+                        operator: BinaryOp::Compare(CompareOp::Gt),
+                        left: Box::new(Expr::Column(b"col2".as_slice().into())),
+                        right: Box::new(Expr::Integer(0)),
+                    }),
+                },
+            })
+        );
     }
 
     #[test]
@@ -1268,6 +3014,98 @@ mod tests {
         let r = parse_insert(&mut Parser::new(b"insert into (col) values (1)"));
         assert!(r.is_err());
         assert_eq!(r.unwrap_err().cursor(), 12);
+        // no values.
+        let r = parse_insert(&mut Parser::new(b"insert into example (col) (1)"));
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().cursor(), 26);
+        // default values with columns.
+        let r = parse_insert(&mut Parser::new(
+            b"insert into example (col) default values",
+        ));
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().cursor(), 26);
+        // no values after default.
+        let r = parse_insert(&mut Parser::new(b"insert into example default"));
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().cursor(), 27);
+        // unknown conflict resolution.
+        let r = parse_insert(&mut Parser::new(
+            b"insert or update into example values (1)",
+        ));
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().cursor(), 10);
+        // no do.
+        let r = parse_insert(&mut Parser::new(
+            b"insert into example values (1) on conflict (id) nothing",
+        ));
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().cursor(), 48);
+        // no set.
+        let r = parse_insert(&mut Parser::new(
+            b"insert into example values (1) on conflict do update col = 1",
+        ));
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().cursor(), 53);
+    }
+
+    #[test]
+    fn test_parse_update() {
+        let input = b"update foo set col = 1";
+        let mut parser = Parser::new(input);
+        let update = parse_update(&mut parser).unwrap();
+        assert_eq!(parser.n_consumed(), input.len());
+        assert_eq!(update.table_name, b"foo".as_slice().into());
+        assert_eq!(
+            update.assignments,
+            vec![(b"col".as_slice().into(), Expr::Integer(1))]
+        );
+        assert!(update.filter.is_none());
+
+        let input = b"update foo set col1 = col2, col2 = 'abc' where id = 5;";
+        let mut parser = Parser::new(input);
+        let update = parse_update(&mut parser).unwrap();
+        assert_eq!(parser.n_consumed(), input.len() - 1);
+        assert_eq!(
+            update.assignments,
+            vec![
+                (
+                    b"col1".as_slice().into(),
+                    Expr::Column(b"col2".as_slice().into())
+                ),
+                (
+                    b"col2".as_slice().into(),
+                    Expr::Text(b"'abc'".as_slice().into())
+                ),
+            ]
+        );
+        assert_eq!(
+            update.filter.unwrap(),
+            Expr::BinaryOperator {
+                operator: BinaryOp::Compare(CompareOp::Eq),
+                left: Box::new(Expr::Column(b"id".as_slice().into())),
+                right: Box::new(Expr::Integer(5)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_update_fail() {
+        // no set.
+        let r = parse_update(&mut Parser::new(b"update foo col = 1"));
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().cursor(), 11);
+        // no assignment.
+        let r = parse_update(&mut Parser::new(b"update foo set where id = 1"));
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().cursor(), 15);
+        // no eq.
+        let r = parse_update(&mut Parser::new(b"update foo set col 1"));
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().cursor(), 19);
+        // trailing comma.
+        let r = parse_update(&mut Parser::new(b"update foo set col = 1,"));
+        assert!(r.is_err());
+        assert_eq!(r.unwrap_err().cursor(), 23);
     }
 
     #[test]
@@ -1383,6 +3221,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_expr_parameter() {
+        assert_parser!(parse_expr, b"?", 1, Expr::Parameter(1));
+        assert_parser!(parse_expr, b"?12", 3, Expr::Parameter(12));
+        assert_parser!(parse_expr, b":name", 5, Expr::Parameter(1));
+
+        let mut parser = Parser::new(b"0 in (?, :a, ?5, ?, @b, :a, ?2, ?)");
+        let Expr::InList { list, .. } = parse_expr(&mut parser).unwrap() else {
+            unreachable!();
+        };
+        assert_eq!(list, [1, 2, 5, 6, 7, 2, 2, 8].map(Expr::Parameter));
+        let parameters = parser.parameters();
+        assert_eq!(parameters.len(), 8);
+        assert_eq!(parameters.index(b":a"), Some(2));
+        assert_eq!(parameters.index(b"?5"), Some(5));
+        assert_eq!(parameters.index(b"@b"), Some(7));
+        assert_eq!(parameters.index(b"?2"), Some(2));
+        assert_eq!(parameters.index(b":b"), None);
+        assert_eq!(parameters.index(b"?"), None);
+
+        assert!(parse_expr(&mut Parser::new(b"?0")).is_err());
+        assert!(parse_expr(&mut Parser::new(b"?32766")).is_ok());
+        assert!(parse_expr(&mut Parser::new(b"?32767")).is_err());
+        assert!(parse_expr(&mut Parser::new(b"?99999999999999999999")).is_err());
+    }
+
     #[test]
     fn test_parse_expr_column() {
         assert_parser!(
@@ -1748,6 +3612,304 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_expr_arithmetic() {
+        assert_parser!(
+            parse_expr,
+            b"1 + 2 * 3 - 4 % 5 / 6",
+            21,
+            Expr::BinaryOperator {
+                operator: BinaryOp::Sub,
+                left: Box::new(Expr::BinaryOperator {
+                    operator: BinaryOp::Add,
+                    left: Box::new(Expr::Integer(1)),
+                    right: Box::new(Expr::BinaryOperator {
+                        operator: BinaryOp::Mul,
+                        left: Box::new(Expr::Integer(2)),
+                        right: Box::new(Expr::Integer(3)),
+                    }),
+                }),
+                right: Box::new(Expr::BinaryOperator {
+                    operator: BinaryOp::Div,
+                    left: Box::new(Expr::BinaryOperator {
+                        operator: BinaryOp::Rem,
+                        left: Box::new(Expr::Integer(4)),
+                        right: Box::new(Expr::Integer(5)),
+                    }),
+                    right: Box::new(Expr::Integer(6)),
+                }),
+            }
+        );
+        assert_parser!(
+            parse_expr,
+            b"1 << 2 + 3 & 4 | 5 >> 6 < 7",
+            27,
+            Expr::BinaryOperator {
+                operator: BinaryOp::Compare(CompareOp::Lt),
+                left: Box::new(Expr::BinaryOperator {
+                    operator: BinaryOp::ShiftRight,
+                    left: Box::new(Expr::BinaryOperator {
+                        operator: BinaryOp::BitOr,
+                        left: Box::new(Expr::BinaryOperator {
+                            operator: BinaryOp::BitAnd,
+                            left: Box::new(Expr::BinaryOperator {
+                                operator: BinaryOp::ShiftLeft,
+                                left: Box::new(Expr::Integer(1)),
+                                right: Box::new(Expr::BinaryOperator {
+                                    operator: BinaryOp::Add,
+                                    left: Box::new(Expr::Integer(2)),
+                                    right: Box::new(Expr::Integer(3)),
+                                }),
+                            }),
+                            right: Box::new(Expr::Integer(4)),
+                        }),
+                        right: Box::new(Expr::Integer(5)),
+                    }),
+                    right: Box::new(Expr::Integer(6)),
+                }),
+                right: Box::new(Expr::Integer(7)),
+            }
+        );
+        assert_parser!(
+            parse_expr,
+            b"2 * 'a' || 'b' - -1",
+            19,
+            Expr::BinaryOperator {
+                operator: BinaryOp::Sub,
+                left: Box::new(Expr::BinaryOperator {
+                    operator: BinaryOp::Mul,
+                    left: Box::new(Expr::Integer(2)),
+                    right: Box::new(Expr::BinaryOperator {
+                        operator: BinaryOp::Concat,
+                        left: Box::new(Expr::Text(b"'a'".as_slice().into())),
+                        right: Box::new(Expr::Text(b"'b'".as_slice().into())),
+                    }),
+                }),
+                right: Box::new(Expr::Integer(-1)),
+            }
+        );
+
+        let mut parser = Parser::new(b"1 +");
+        assert!(parse_expr(&mut parser).is_err());
+    }
+
+    #[test]
+    fn test_parse_expr_logical() {
+        assert_parser!(
+            parse_expr,
+            b"1 OR 2 AND NOT 3 = 4",
+            20,
+            Expr::BinaryOperator {
+                operator: BinaryOp::Or,
+                left: Box::new(Expr::Integer(1)),
+                right: Box::new(Expr::BinaryOperator {
+                    operator: BinaryOp::And,
+                    left: Box::new(Expr::Integer(2)),
+                    right: Box::new(Expr::UnaryOperator {
+                        operator: UnaryOp::Not,
+                        expr: Box::new(Expr::BinaryOperator {
+                            operator: BinaryOp::Compare(CompareOp::Eq),
+                            left: Box::new(Expr::Integer(3)),
+                            right: Box::new(Expr::Integer(4)),
+                        }),
+                    }),
+                }),
+            }
+        );
+        assert_parser!(
+            parse_expr,
+            b"1 and 2 and not not 3 or 4",
+            26,
+            Expr::BinaryOperator {
+                operator: BinaryOp::Or,
+                left: Box::new(Expr::BinaryOperator {
+                    operator: BinaryOp::And,
+                    left: Box::new(Expr::BinaryOperator {
+                        operator: BinaryOp::And,
+                        left: Box::new(Expr::Integer(1)),
+                        right: Box::new(Expr::Integer(2)),
+                    }),
+                    right: Box::new(Expr::UnaryOperator {
+                        operator: UnaryOp::Not,
+                        expr: Box::new(Expr::UnaryOperator {
+                            operator: UnaryOp::Not,
+                            expr: Box::new(Expr::Integer(3)),
+                        }),
+                    }),
+                }),
+                right: Box::new(Expr::Integer(4)),
+            }
+        );
+
+        assert_parser!(
+            parse_expr,
+            b"(1 OR 2) AND 3",
+            14,
+            Expr::BinaryOperator {
+                operator: BinaryOp::And,
+                left: Box::new(Expr::BinaryOperator {
+                    operator: BinaryOp::Or,
+                    left: Box::new(Expr::Integer(1)),
+                    right: Box::new(Expr::Integer(2)),
+                }),
+                right: Box::new(Expr::Integer(3)),
+            }
+        );
+
+        assert_parser!(
+            parse_expr,
+            b"1 = NOT 0 = 1 AND 2",
+            19,
+            Expr::BinaryOperator {
+                operator: BinaryOp::And,
+                left: Box::new(Expr::BinaryOperator {
+                    operator: BinaryOp::Compare(CompareOp::Eq),
+                    left: Box::new(Expr::Integer(1)),
+                    right: Box::new(Expr::UnaryOperator {
+                        operator: UnaryOp::Not,
+                        expr: Box::new(Expr::BinaryOperator {
+                            operator: BinaryOp::Compare(CompareOp::Eq),
+                            left: Box::new(Expr::Integer(0)),
+                            right: Box::new(Expr::Integer(1)),
+                        }),
+                    }),
+                }),
+                right: Box::new(Expr::Integer(2)),
+            }
+        );
+
+        let mut parser = Parser::new(b"1 and");
+        assert!(parse_expr(&mut parser).is_err());
+        let mut parser = Parser::new(b"(1 or 2");
+        assert!(parse_expr(&mut parser).is_err());
+        let mut parser = Parser::new(b"not");
+        assert!(parse_expr(&mut parser).is_err());
+    }
+
+    #[test]
+    fn test_parse_expr_predicates() {
+        assert_parser!(
+            parse_expr,
+            b"x IS NOT NULL",
+            13,
+            Expr::UnaryOperator {
+                operator: UnaryOp::Not,
+                expr: Box::new(Expr::BinaryOperator {
+                    operator: BinaryOp::Is,
+                    left: Box::new(Expr::Column(b"x".as_slice().into())),
+                    right: Box::new(Expr::Null),
+                }),
+            }
+        );
+        assert_parser!(
+            parse_expr,
+            b"x ISNULL = x NOTNULL",
+            20,
+            // Left associative as the other equality operators.
+            Expr::UnaryOperator {
+                operator: UnaryOp::Not,
+                expr: Box::new(Expr::BinaryOperator {
+                    operator: BinaryOp::Is,
+                    left: Box::new(Expr::BinaryOperator {
+                        operator: BinaryOp::Compare(CompareOp::Eq),
+                        left: Box::new(Expr::BinaryOperator {
+                            operator: BinaryOp::Is,
+                            left: Box::new(Expr::Column(b"x".as_slice().into())),
+                            right: Box::new(Expr::Null),
+                        }),
+                        right: Box::new(Expr::Column(b"x".as_slice().into())),
+                    }),
+                    right: Box::new(Expr::Null),
+                }),
+            }
+        );
+        assert_parser!(
+            parse_expr,
+            b"ts NOT BETWEEN 1 AND 2 + 3 AND x",
+            32,
+            Expr::BinaryOperator {
+                operator: BinaryOp::And,
+                left: Box::new(Expr::UnaryOperator {
+                    operator: UnaryOp::Not,
+                    expr: Box::new(Expr::Between {
+                        expr: Box::new(Expr::Column(b"ts".as_slice().into())),
+                        low: Box::new(Expr::Integer(1)),
+                        high: Box::new(Expr::BinaryOperator {
+                            operator: BinaryOp::Add,
+                            left: Box::new(Expr::Integer(2)),
+                            right: Box::new(Expr::Integer(3)),
+                        }),
+                    }),
+                }),
+                right: Box::new(Expr::Column(b"x".as_slice().into())),
+            }
+        );
+        assert_parser!(
+            parse_expr,
+            b"tag IN ('a', x = 1)",
+            19,
+            Expr::InList {
+                expr: Box::new(Expr::Column(b"tag".as_slice().into())),
+                list: vec![
+                    Expr::Text(b"'a'".as_slice().into()),
+                    Expr::BinaryOperator {
+                        operator: BinaryOp::Compare(CompareOp::Eq),
+                        left: Box::new(Expr::Column(b"x".as_slice().into())),
+                        right: Box::new(Expr::Integer(1)),
+                    },
+                ],
+            }
+        );
+        assert_parser!(
+            parse_expr,
+            b"tag NOT IN ()",
+            13,
+            Expr::UnaryOperator {
+                operator: UnaryOp::Not,
+                expr: Box::new(Expr::InList {
+                    expr: Box::new(Expr::Column(b"tag".as_slice().into())),
+                    list: Vec::new(),
+                }),
+            }
+        );
+        assert_parser!(
+            parse_expr,
+            b"name LIKE 'cpu!%%' ESCAPE '!'",
+            29,
+            Expr::Pattern {
+                operator: PatternOp::Like,
+                expr: Box::new(Expr::Column(b"name".as_slice().into())),
+                pattern: Box::new(Expr::Text(b"'cpu!%%'".as_slice().into())),
+                escape: Some(Box::new(Expr::Text(b"'!'".as_slice().into()))),
+            }
+        );
+        assert_parser!(
+            parse_expr,
+            b"name NOT GLOB 'cpu*' = 1",
+            24,
+            Expr::BinaryOperator {
+                operator: BinaryOp::Compare(CompareOp::Eq),
+                left: Box::new(Expr::UnaryOperator {
+                    operator: UnaryOp::Not,
+                    expr: Box::new(Expr::Pattern {
+                        operator: PatternOp::Glob,
+                        expr: Box::new(Expr::Column(b"name".as_slice().into())),
+                        pattern: Box::new(Expr::Text(b"'cpu*'".as_slice().into())),
+                        escape: None,
+                    }),
+                }),
+                right: Box::new(Expr::Integer(1)),
+            }
+        );
+
+        let mut parser = Parser::new(b"x IN 1");
+        assert!(parse_expr(&mut parser).is_err());
+        let mut parser = Parser::new(b"x BETWEEN 1");
+        assert!(parse_expr(&mut parser).is_err());
+        let mut parser = Parser::new(b"x IN (1, 2");
+        assert!(parse_expr(&mut parser).is_err());
+    }
+
     #[test]
     fn test_parse_expr_operators() {
         assert_parser!(
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use std::cmp::Ordering;
+use std::cmp::Reverse;
 use std::fmt::Display;
 
 use crate::interpreter::btree::BtreeContext;
@@ -30,12 +31,16 @@ use crate::interpreter::record::parse_record;
 use crate::interpreter::record::parse_record_header;
 use crate::interpreter::record::SerialType;
 use crate::interpreter::schema::ColumnNumber;
+use crate::interpreter::schema::Index;
+use crate::interpreter::schema::Schema;
 use crate::interpreter::schema::Table;
+use crate::interpreter::sorter::SortOrder;
 use crate::interpreter::value::Collation;
 use crate::interpreter::value::ConstantValue;
 use crate::interpreter::value::TypeAffinity;
 use crate::interpreter::value::Value;
 use crate::interpreter::value::ValueCmp;
+use crate::interpreter::value::DEFAULT_COLLATION;
 
 #[derive(Debug)]
 pub enum Error {
@@ -80,89 +85,809 @@ pub type Result<T> = std::result::Result<T, Error>;
 
 pub enum QueryPlan {
     FullScan,
-    IndexScan(IndexInfo),
+    IndexScan(Box<IndexInfo>),
     RowId(i64),
+    /// Seek each rowid of the list sorted in the ascending order.
+    RowIds {
+        rowids: Vec<i64>,
+        /// Whether to seek in the descending order.
+        reverse: bool,
+    },
+    /// Scan the rowids in the inclusive range.
+    ///
+    /// A full scan in the descending order is a range without bounds.
+    RowIdRange {
+        min: Option<i64>,
+        max: Option<i64>,
+        /// Whether to scan in the descending order.
+        reverse: bool,
+    },
 }
 
 impl QueryPlan {
-    pub fn generate(table: &Table, filter: &Expression) -> Self {
-        let mut plan = Self::FullScan;
+    /// Generate the plan from the terms of the conjunction of the filter.
+    ///
+    /// A rowid equality or IN list is preferred to any index. If the table has
+    /// statistics loaded from sqlite_stat1, the plan with the least estimated
+    /// cost is chosen among a full scan, a rowid range and the indexes.
+    /// Otherwise, among indexes, the one binding the longest prefix of its
+    /// columns is chosen. An index with bound columns is preferred to a rowid
+    /// range, which is preferred to an index range only. The filter is applied
+    /// to every row from the plan, so the other terms are still honored.
+    ///
+    /// `columns` are the columns referenced by the query, or None if the query
+    /// needs the full rows. An index covering them skips the table lookups.
+    pub fn generate(table: &Table, filter: &Expression, columns: Option<&[ColumnNumber]>) -> Self {
+        let mut terms = Vec::new();
+        collect_conjunction_terms(filter, &mut terms);
+        let mut constraints = Vec::new();
+        for term in terms {
+            collect_constraints(term, &mut constraints);
+        }
 
-        if let Expression::BinaryOperator {
-            operator: BinaryOp::Compare(CompareOp::Eq),
-            left,
-            right,
-        } = filter
-        {
-            match (left.as_ref(), right.as_ref()) {
-                (
-                    Expression::Column((ColumnNumber::RowId, _, _)),
-                    Expression::Const(ConstantValue::Integer(value)),
-                )
-                | (
-                    Expression::Const(ConstantValue::Integer(value)),
-                    Expression::Column((ColumnNumber::RowId, _, _)),
-                ) => plan = Self::RowId(*value),
-                (
-                    Expression::Column((column_number, type_affinity, collation)),
-                    Expression::Const(const_value),
-                )
-                | (
-                    Expression::Const(const_value),
-                    Expression::Column((column_number, type_affinity, collation)),
-                ) => {
-                    let mut next_index = table.indexes.as_ref();
-                    while let Some(index) = next_index {
-                        if index.columns[0] == *column_number {
-                            break;
-                        }
-                        next_index = index.next.as_ref();
+        let mut rowid_range = None;
+        let mut rowid_list: Option<Vec<i64>> = None;
+        for constraint in constraints.iter() {
+            if constraint.column_number != ColumnNumber::RowId {
+                continue;
+            }
+            let (min, max) = rowid_range.get_or_insert((None, None));
+            match &constraint.constraint {
+                Constraint::Eq(ConstantValue::Integer(rowid)) => return Self::RowId(*rowid),
+                Constraint::Lower(bound) => {
+                    if let Some(rowid) = bound.rowid(true) {
+                        *min = Some(min.map_or(rowid, |min: i64| min.max(rowid)));
                     }
-                    if let Some(index) = next_index {
-                        let value = match type_affinity {
-                            TypeAffinity::Integer | TypeAffinity::Real | TypeAffinity::Numeric => {
-                                ConstantValue::copy_from(
-                                    const_value.as_value().apply_numeric_affinity(),
-                                )
-                            }
-                            TypeAffinity::Text => ConstantValue::copy_from(
-                                const_value.as_value().apply_text_affinity(),
-                            ),
-                            TypeAffinity::Blob => ConstantValue::copy_from(const_value.as_value()),
-                        };
+                }
+                Constraint::Upper(bound) => {
+                    if let Some(rowid) = bound.rowid(false) {
+                        *max = Some(max.map_or(rowid, |max: i64| max.min(rowid)));
+                    }
+                }
+                Constraint::In(values) => {
+                    // Values other than integers never match a rowid. The
+                    // values are already sorted without duplicates.
+                    let rowids = values
+                        .iter()
+                        .filter_map(|value| match value {
+                            ConstantValue::Integer(rowid) => Some(*rowid),
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>();
+                    if rowid_list
+                        .as_ref()
+                        .is_none_or(|list| rowids.len() < list.len())
+                    {
+                        rowid_list = Some(rowids);
+                    }
+                }
+                Constraint::Eq(_) => {}
+            }
+        }
+        if let Some(rowids) = rowid_list {
+            return Self::RowIds {
+                rowids,
+                reverse: false,
+            };
+        }
+        let rowid_range = rowid_range.filter(|(min, max)| min.is_some() || max.is_some());
+        let is_covering = |index: &Index| columns.is_some_and(|columns| covers(index, columns));
+
+        if let Some(n_rows) = table.n_rows {
+            // An empty table is estimated as 1 row to keep the costs positive.
+            let n_rows = n_rows.max(1) as f64;
+            let mut best_plan = Self::FullScan;
+            let mut best_cost = n_rows;
+            if let Some((min, max)) = rowid_range {
+                let cost =
+                    seek_cost(n_rows) + n_rows * range_selectivity(min.is_some(), max.is_some());
+                if cost < best_cost {
+                    best_plan = Self::RowIdRange {
+                        min,
+                        max,
+                        reverse: false,
+                    };
+                    best_cost = cost;
+                }
+            }
+            let mut next_index = table.indexes.as_ref();
+            while let Some(index) = next_index {
+                if let Some(index_info) = IndexInfo::generate(index, &constraints) {
+                    let cost = index_info.estimate_cost(index, n_rows, is_covering(index));
+                    if cost < best_cost {
+                        best_plan = Self::IndexScan(Box::new(index_info));
+                        best_cost = cost;
+                    }
+                }
+                next_index = index.next.as_ref();
+            }
+            return best_plan;
+        }
 
-                        // TODO: Consider collation of constant value.
-                        plan = Self::IndexScan(IndexInfo {
-                            page_id: index.root_page_id,
-                            keys: vec![(value, collation.clone())],
-                            n_extra: index.columns.len() - 1,
-                        });
+        let mut best_index: Option<(IndexInfo, _)> = None;
+        let mut next_index = table.indexes.as_ref();
+        while let Some(index) = next_index {
+            if let Some(index_info) = IndexInfo::generate(index, &constraints) {
+                let score = index_info.score(is_covering(index));
+                if best_index
+                    .as_ref()
+                    .is_none_or(|(_, best_score)| score > *best_score)
+                {
+                    best_index = Some((index_info, score));
+                }
+            }
+            next_index = index.next.as_ref();
+        }
+        let best_index = best_index.map(|(index_info, _)| index_info);
+
+        match (best_index, rowid_range) {
+            (Some(index_info), _) if index_info.n_keys > 0 => Self::IndexScan(Box::new(index_info)),
+            (_, Some((min, max))) => Self::RowIdRange {
+                min,
+                max,
+                reverse: false,
+            },
+            (Some(index_info), None) => Self::IndexScan(Box::new(index_info)),
+            (None, None) => Self::FullScan,
+        }
+    }
+
+    /// Whether the plan yields rows in the order of the sort keys.
+    pub fn is_sorted_by(&self, table: &Table, sort_keys: &[(Expression, SortOrder)]) -> bool {
+        self.sort_direction(table, sort_keys) == Some(false)
+    }
+
+    /// Whether the plan can yield rows in the order of the sort keys.
+    ///
+    /// If the sort keys are in the descending order of the scan, the plan is
+    /// changed to scan backward. A full scan is changed to scan an index in the
+    /// order of the sort keys if there is such an index.
+    pub fn sort_by(&mut self, table: &Table, sort_keys: &[(Expression, SortOrder)]) -> bool {
+        match self.sort_direction(table, sort_keys) {
+            Some(false) => true,
+            Some(true) => {
+                match self {
+                    Self::FullScan => {
+                        *self = Self::RowIdRange {
+                            min: None,
+                            max: None,
+                            reverse: true,
+                        };
+                    }
+                    Self::RowIds { reverse, .. } | Self::RowIdRange { reverse, .. } => {
+                        *reverse = true
+                    }
+                    Self::IndexScan(index_info) => index_info.reverse = true,
+                    Self::RowId(_) => unreachable!("a single row is sorted in any order"),
+                }
+                true
+            }
+            None => {
+                if !matches!(self, Self::FullScan) {
+                    return false;
+                }
+                let mut next_index = table.indexes.as_ref();
+                while let Some(index) = next_index {
+                    let mut plan = Self::IndexScan(Box::new(IndexInfo::full_scan(index)));
+                    if plan.sort_by(table, sort_keys) {
+                        *self = plan;
+                        return true;
                     }
+                    next_index = index.next.as_ref();
                 }
-                _ => {}
+                false
+            }
+        }
+    }
+
+    /// The direction of the scan which yields rows in the order of the sort
+    /// keys. Some(true) if the scan must be reversed. None if the rows need to
+    /// be sorted.
+    fn sort_direction(&self, table: &Table, sort_keys: &[(Expression, SortOrder)]) -> Option<bool> {
+        let (scan_columns, n_fixed) = match self {
+            // At most 1 row.
+            Self::RowId(_) => return Some(false),
+            Self::FullScan | Self::RowIds { .. } | Self::RowIdRange { .. } => {
+                (vec![(ColumnNumber::RowId, &DEFAULT_COLLATION)], 0)
+            }
+            Self::IndexScan(index_info) => {
+                let index = find_index(table, index_info.page_id)?;
+                let mut scan_columns = index
+                    .columns
+                    .iter()
+                    .map(|column_number| match column_number {
+                        ColumnNumber::RowId => (ColumnNumber::RowId, &DEFAULT_COLLATION),
+                        ColumnNumber::Column(idx) => {
+                            (*column_number, &table.columns[*idx].collation)
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                scan_columns.push((ColumnNumber::RowId, &DEFAULT_COLLATION));
+                // Multiple seeks are in the index order and do not overlap.
+                let n_fixed = match index_info.seeks.as_slice() {
+                    [keys] => keys.len(),
+                    _ => 0,
+                };
+                (scan_columns, n_fixed)
+            }
+        };
+        // The values of the leading columns are fixed by the equality constraints.
+        let (fixed_columns, scan_columns) = scan_columns.split_at(n_fixed);
+        let mut scan_columns = scan_columns.iter();
+        // All the keys except the fixed ones must be in the same direction.
+        let mut desc = None;
+        for (expr, order) in sort_keys {
+            let Expression::Column((column_number, _, _)) = expr else {
+                return None;
             };
+            let column = (*column_number, &order.collation);
+            if fixed_columns.contains(&column) {
+                continue;
+            }
+            if scan_columns.next() != Some(&column) || *desc.get_or_insert(order.desc) != order.desc
+            {
+                return None;
+            }
+            if *column_number == ColumnNumber::RowId {
+                // Rows are unique by rowid. Following keys do not matter.
+                break;
+            }
+        }
+        Some(desc.unwrap_or(false))
+    }
+
+    /// Skip the table lookup of the index scan if the index contains all the
+    /// columns referenced by the query.
+    ///
+    /// The rows are read from the index entries. The rows must not be deleted
+    /// through the query.
+    pub fn use_covering_index(&mut self, table: &Table, columns: &[ColumnNumber]) {
+        let Self::IndexScan(index_info) = self else {
+            return;
         };
-        plan
+        let Some(index) = find_index(table, index_info.page_id) else {
+            return;
+        };
+        if !covers(index, columns) {
+            return;
+        }
+        index_info.column_map = Some(
+            (0..table.columns.len())
+                .map(|column_idx| {
+                    index.columns.iter().position(|column_number| {
+                        *column_number == ColumnNumber::Column(column_idx)
+                    })
+                })
+                .collect(),
+        );
+    }
+
+    /// Whether the rows are read from the index entries without the table lookup.
+    pub fn is_covering(&self) -> bool {
+        matches!(self, Self::IndexScan(index_info) if index_info.column_map.is_some())
     }
 
     pub fn index_page_id(&self) -> Option<PageId> {
         match self {
-            Self::FullScan | Self::RowId(_) => None,
+            Self::FullScan | Self::RowId(_) | Self::RowIds { .. } | Self::RowIdRange { .. } => None,
             Self::IndexScan(index_info) => Some(index_info.page_id),
         }
     }
+
+    /// Describe the plan as the detail of EXPLAIN QUERY PLAN in SQLite (e.g.
+    /// "SEARCH example USING INDEX index1 (col=?)").
+    pub fn explain(&self, table_name: &[u8], table: &Table, schema: &Schema) -> String {
+        let table_name = String::from_utf8_lossy(table_name);
+        match self {
+            // A descending full scan is a rowid range without bounds.
+            Self::FullScan
+            | Self::RowIdRange {
+                min: None,
+                max: None,
+                ..
+            } => format!("SCAN {table_name}"),
+            Self::RowId(_) | Self::RowIds { .. } => {
+                format!("SEARCH {table_name} USING INTEGER PRIMARY KEY (rowid=?)")
+            }
+            Self::RowIdRange { min, max, .. } => {
+                let terms = [min.map(|_| "rowid>?"), max.map(|_| "rowid<?")]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>();
+                format!(
+                    "SEARCH {table_name} USING INTEGER PRIMARY KEY ({})",
+                    terms.join(" AND ")
+                )
+            }
+            Self::IndexScan(index_info) => {
+                let index_name = schema
+                    .indexes()
+                    .find(|(_, index)| index.root_page_id == index_info.page_id)
+                    .map(|(name, _)| String::from_utf8_lossy(name))
+                    .unwrap_or_default();
+                let covering = if index_info.column_map.is_some() {
+                    "COVERING "
+                } else {
+                    ""
+                };
+                let mut terms = Vec::new();
+                if let Some(index) = find_index(table, index_info.page_id) {
+                    let column_name = |i: usize| match index.columns.get(i) {
+                        Some(ColumnNumber::Column(column_idx)) => {
+                            String::from_utf8_lossy(&table.columns[*column_idx].name)
+                        }
+                        Some(ColumnNumber::RowId) | None => "rowid".into(),
+                    };
+                    for i in 0..index_info.n_keys {
+                        terms.push(format!("{}=?", column_name(i)));
+                    }
+                    if let Some(range) = &index_info.range {
+                        let column_name = column_name(index_info.n_keys);
+                        if range.lower.is_some() {
+                            terms.push(format!("{column_name}>?"));
+                        }
+                        if range.upper.is_some() {
+                            terms.push(format!("{column_name}<?"));
+                        }
+                    }
+                }
+                if terms.is_empty() {
+                    format!("SCAN {table_name} USING {covering}INDEX {index_name}")
+                } else {
+                    format!(
+                        "SEARCH {table_name} USING {covering}INDEX {index_name} ({})",
+                        terms.join(" AND ")
+                    )
+                }
+            }
+        }
+    }
+}
+
+fn find_index(table: &Table, page_id: PageId) -> Option<&Index> {
+    let mut next_index = table.indexes.as_ref();
+    while let Some(index) = next_index {
+        if index.root_page_id == page_id {
+            return Some(index);
+        }
+        next_index = index.next.as_ref();
+    }
+    None
+}
+
+/// The estimated cost of moving a cursor from the root of a btree with the
+/// rows.
+fn seek_cost(n_rows: f64) -> f64 {
+    n_rows.log2() + 1.0
+}
+
+/// The estimated ratio of the rows in a range to all the rows.
+///
+/// Each bound is assumed to reduce the rows to 1/4 as sqlite3 does without
+/// sqlite_stat4.
+fn range_selectivity(has_lower: bool, has_upper: bool) -> f64 {
+    let mut selectivity = 1.0;
+    if has_lower {
+        selectivity /= 4.0;
+    }
+    if has_upper {
+        selectivity /= 4.0;
+    }
+    selectivity
+}
+
+/// The average numbers of rows sharing the leading 1, 2, ... columns of an
+/// index without statistics, which sqlite3 also assumes.
+const DEFAULT_AVG_EQ: [u64; 5] = [10, 9, 8, 7, 6];
+
+/// A bound of a range. The value is converted with the affinity of the
+/// column.
+#[derive(Clone)]
+struct RangeBound {
+    value: ConstantValue,
+    inclusive: bool,
+}
+
+impl RangeBound {
+    /// The inclusive rowid bound which covers this bound.
+    ///
+    /// None if the value is not numeric. Numbers are always less than texts
+    /// and blobs, so such a bound does not narrow the rowid range.
+    fn rowid(&self, lower: bool) -> Option<i64> {
+        match &self.value {
+            ConstantValue::Integer(i) if self.inclusive => Some(*i),
+            ConstantValue::Integer(i) if lower => Some(i.saturating_add(1)),
+            ConstantValue::Integer(i) => Some(i.saturating_sub(1)),
+            ConstantValue::Real(d) if lower => Some(d.floor() as i64),
+            ConstantValue::Real(d) => Some(d.ceil() as i64),
+            ConstantValue::Text(_) | ConstantValue::Blob(_) => None,
+        }
+    }
+
+    /// Replace the bound if the other bound is tighter.
+    ///
+    /// `tighter` is the ordering of the value of a tighter bound against the
+    /// current value.
+    fn narrow(bound: &mut Option<Self>, other: &Self, collation: &Collation, tighter: Ordering) {
+        let replace = match bound {
+            None => true,
+            Some(current) => {
+                match ValueCmp::new(&other.value.as_value(), collation)
+                    .compare(&current.value.as_value())
+                {
+                    Ordering::Equal => !other.inclusive,
+                    ordering => ordering == tighter,
+                }
+            }
+        };
+        if replace {
+            *bound = Some(other.clone());
+        }
+    }
+}
+
+enum Constraint {
+    Eq(ConstantValue),
+    /// The values are sorted by the collation of the column without
+    /// duplicates.
+    In(Vec<ConstantValue>),
+    Lower(RangeBound),
+    Upper(RangeBound),
+}
+
+/// A constraint on a column from a term of the filter.
+struct ColumnConstraint<'a> {
+    column_number: ColumnNumber,
+    collation: &'a Collation,
+    constraint: Constraint,
+}
+
+/// Collect the constraints which compare a column with constants.
+fn collect_constraints<'a>(term: &'a Expression, constraints: &mut Vec<ColumnConstraint<'a>>) {
+    match term {
+        Expression::BinaryOperator {
+            operator: BinaryOp::Compare(compare_op),
+            left,
+            right,
+        } => {
+            let ((column_number, type_affinity, collation), const_value, compare_op) =
+                match (left.as_ref(), right.as_ref()) {
+                    (Expression::Column(column), right) => {
+                        let Some(const_value) = constant(right) else {
+                            return;
+                        };
+                        (column, const_value, *compare_op)
+                    }
+                    // Swap the operands so that the column is on the left.
+                    (left, Expression::Column(column)) => {
+                        let Some(const_value) = constant(left) else {
+                            return;
+                        };
+                        let compare_op = match compare_op {
+                            CompareOp::Gt => CompareOp::Lt,
+                            CompareOp::Ge => CompareOp::Le,
+                            CompareOp::Lt => CompareOp::Gt,
+                            CompareOp::Le => CompareOp::Ge,
+                            CompareOp::Eq | CompareOp::Ne => *compare_op,
+                        };
+                        (column, const_value, compare_op)
+                    }
+                    _ => return,
+                };
+            let value = apply_column_affinity(const_value, *type_affinity);
+            let constraint = match compare_op {
+                CompareOp::Eq => Constraint::Eq(value),
+                CompareOp::Ne => return,
+                CompareOp::Gt | CompareOp::Ge => Constraint::Lower(RangeBound {
+                    value,
+                    inclusive: compare_op == CompareOp::Ge,
+                }),
+                CompareOp::Lt | CompareOp::Le => Constraint::Upper(RangeBound {
+                    value,
+                    inclusive: compare_op == CompareOp::Le,
+                }),
+            };
+            constraints.push(ColumnConstraint {
+                column_number: *column_number,
+                collation,
+                constraint,
+            });
+        }
+        Expression::Between { expr, low, high } => {
+            let Expression::Column((column_number, type_affinity, collation)) = expr.as_ref()
+            else {
+                return;
+            };
+            for (bound, lower) in [(low, true), (high, false)] {
+                let Some(const_value) = constant(bound) else {
+                    continue;
+                };
+                let bound = RangeBound {
+                    value: apply_column_affinity(const_value, *type_affinity),
+                    inclusive: true,
+                };
+                constraints.push(ColumnConstraint {
+                    column_number: *column_number,
+                    collation,
+                    constraint: if lower {
+                        Constraint::Lower(bound)
+                    } else {
+                        Constraint::Upper(bound)
+                    },
+                });
+            }
+        }
+        Expression::InList { expr, list } => {
+            let Expression::Column((column_number, type_affinity, collation)) = expr.as_ref()
+            else {
+                return;
+            };
+            let mut values = Vec::with_capacity(list.len());
+            for item in list.iter() {
+                match item {
+                    Expression::Const(const_value)
+                    | Expression::Parameter {
+                        value: Some(const_value),
+                        ..
+                    } => {
+                        values.push(apply_column_affinity(const_value, *type_affinity));
+                    }
+                    // NULL never matches.
+                    Expression::Null | Expression::Parameter { value: None, .. } => {}
+                    _ => return,
+                }
+            }
+            // Seek in the index order without duplicates so that each index
+            // entry is visited at most once.
+            values.sort_by(|a, b| ValueCmp::new(&a.as_value(), collation).compare(&b.as_value()));
+            values.dedup_by(|a, b| {
+                ValueCmp::new(&a.as_value(), collation).compare(&b.as_value()) == Ordering::Equal
+            });
+            constraints.push(ColumnConstraint {
+                column_number: *column_number,
+                collation,
+                constraint: Constraint::In(values),
+            });
+        }
+        _ => {}
+    }
+}
+
+/// The constant value of the expression. Bound parameters are constants for
+/// the query.
+fn constant(expr: &Expression) -> Option<&ConstantValue> {
+    match expr {
+        Expression::Const(const_value)
+        | Expression::Parameter {
+            value: Some(const_value),
+            ..
+        } => Some(const_value),
+        _ => None,
+    }
+}
+
+/// Convert the constant compared with a column as the comparison does.
+fn apply_column_affinity(
+    const_value: &ConstantValue,
+    type_affinity: TypeAffinity,
+) -> ConstantValue {
+    match type_affinity {
+        TypeAffinity::Integer | TypeAffinity::Real | TypeAffinity::Numeric => {
+            ConstantValue::copy_from(const_value.as_value().apply_numeric_affinity())
+        }
+        TypeAffinity::Text => {
+            ConstantValue::copy_from(const_value.as_value().apply_text_affinity())
+        }
+        TypeAffinity::Blob => ConstantValue::copy_from(const_value.as_value()),
+    }
+}
+
+/// Flatten the AND operators of the expression.
+fn collect_conjunction_terms<'a>(expr: &'a Expression, terms: &mut Vec<&'a Expression>) {
+    if let Expression::BinaryOperator {
+        operator: BinaryOp::And,
+        left,
+        right,
+    } = expr
+    {
+        collect_conjunction_terms(left, terms);
+        collect_conjunction_terms(right, terms);
+    } else {
+        terms.push(expr);
+    }
 }
 
 pub struct IndexInfo {
     page_id: PageId,
-    keys: Vec<(ConstantValue, Collation)>,
+    /// The number of the leading index columns bound by the keys.
+    n_keys: usize,
+    /// The keys of the leading index columns for each seek.
+    seeks: Vec<Vec<(ConstantValue, Collation)>>,
+    /// The range of the index column following the keys.
+    range: Option<IndexRange>,
+    /// The number of the index columns following the keys.
     n_extra: usize,
+    /// The position in the index entry of each table column if the index
+    /// covers the query. None if the column is not in the index.
+    column_map: Option<Vec<Option<usize>>>,
+    /// Whether to scan in the descending order.
+    reverse: bool,
+}
+
+struct IndexRange {
+    lower: Option<RangeBound>,
+    upper: Option<RangeBound>,
+    collation: Collation,
+}
+
+impl IndexInfo {
+    /// Generate the index plan which binds as many leading columns of the
+    /// index as possible with equality constraints or IN lists, and the
+    /// following column with a range.
+    ///
+    /// IN lists produce a seek for each combination of the values. None if the
+    /// index is not usable.
+    fn generate(index: &Index, constraints: &[ColumnConstraint]) -> Option<Self> {
+        let mut seeks = vec![Vec::new()];
+        let mut n_keys = 0;
+        for column_number in index.columns.iter() {
+            let mut constraints = constraints
+                .iter()
+                .filter(|constraint| constraint.column_number == *column_number);
+            if let Some((value, collation)) =
+                constraints
+                    .clone()
+                    .find_map(|constraint| match &constraint.constraint {
+                        Constraint::Eq(value) => Some((value, constraint.collation)),
+                        _ => None,
+                    })
+            {
+                for keys in seeks.iter_mut() {
+                    keys.push((value.clone(), collation.clone()));
+                }
+            } else if let Some((values, collation)) =
+                constraints.find_map(|constraint| match &constraint.constraint {
+                    Constraint::In(values) => Some((values, constraint.collation)),
+                    _ => None,
+                })
+            {
+                // The seeks stay in the index order because both the current
+                // seeks and the values are sorted.
+                seeks = seeks
+                    .iter()
+                    .flat_map(|keys| {
+                        values.iter().map(move |value| {
+                            let mut keys = keys.clone();
+                            keys.push((value.clone(), collation.clone()));
+                            keys
+                        })
+                    })
+                    .collect();
+            } else {
+                break;
+            }
+            n_keys += 1;
+        }
+
+        let mut range = None;
+        if let Some(column_number) = index.columns.get(n_keys) {
+            for constraint in constraints
+                .iter()
+                .filter(|constraint| constraint.column_number == *column_number)
+            {
+                let range = range.get_or_insert_with(|| IndexRange {
+                    lower: None,
+                    upper: None,
+                    collation: constraint.collation.clone(),
+                });
+                match &constraint.constraint {
+                    Constraint::Lower(bound) => RangeBound::narrow(
+                        &mut range.lower,
+                        bound,
+                        constraint.collation,
+                        Ordering::Greater,
+                    ),
+                    Constraint::Upper(bound) => RangeBound::narrow(
+                        &mut range.upper,
+                        bound,
+                        constraint.collation,
+                        Ordering::Less,
+                    ),
+                    Constraint::Eq(_) | Constraint::In(_) => {}
+                }
+            }
+        }
+        let range = range.filter(|range| range.lower.is_some() || range.upper.is_some());
+
+        if n_keys == 0 && range.is_none() {
+            return None;
+        }
+        Some(Self {
+            page_id: index.root_page_id,
+            n_keys,
+            seeks,
+            range,
+            n_extra: index.columns.len() - n_keys,
+            column_map: None,
+            reverse: false,
+        })
+    }
+
+    /// The plan scanning all the entries of the index.
+    fn full_scan(index: &Index) -> Self {
+        Self {
+            page_id: index.root_page_id,
+            n_keys: 0,
+            seeks: vec![Vec::new()],
+            range: None,
+            n_extra: index.columns.len(),
+            column_map: None,
+            reverse: false,
+        }
+    }
+
+    /// The preference of the plan. The longer prefix of the index is bound,
+    /// the better. On a tie, equalities are preferred to IN lists, a range is
+    /// preferred to none, a covering index is preferred, and then the index
+    /// with fewer columns is preferred.
+    fn score(&self, is_covering: bool) -> (usize, bool, bool, bool, Reverse<usize>) {
+        (
+            self.n_keys,
+            self.seeks.len() == 1,
+            self.range.is_some(),
+            is_covering,
+            Reverse(self.n_extra),
+        )
+    }
+
+    /// The estimated cost of the plan on the table with n_rows rows.
+    ///
+    /// Each seek moves the index cursor from the root, and each matched entry
+    /// is looked up in the table by the rowid unless the index covers the
+    /// query.
+    fn estimate_cost(&self, index: &Index, n_rows: f64, is_covering: bool) -> f64 {
+        let avg_eq = if self.n_keys == 0 {
+            n_rows
+        } else if let Some(avg_eq) = index
+            .stats
+            .as_ref()
+            .and_then(|stats| stats.avg_eq.get(self.n_keys - 1))
+        {
+            *avg_eq as f64
+        } else if index.unique && self.n_extra == 0 {
+            1.0
+        } else {
+            DEFAULT_AVG_EQ[(self.n_keys - 1).min(DEFAULT_AVG_EQ.len() - 1)] as f64
+        };
+        let mut n_matches = self.seeks.len() as f64 * avg_eq.min(n_rows);
+        if let Some(range) = &self.range {
+            n_matches *= range_selectivity(range.lower.is_some(), range.upper.is_some());
+        }
+        let lookup_cost = if is_covering { 0.0 } else { seek_cost(n_rows) };
+        self.seeks.len() as f64 * seek_cost(n_rows) + n_matches * (1.0 + lookup_cost)
+    }
+}
+
+/// Whether the index contains all the columns. The rowid is in every index.
+fn covers(index: &Index, columns: &[ColumnNumber]) -> bool {
+    columns.iter().all(|column_number| {
+        *column_number == ColumnNumber::RowId || index.columns.contains(column_number)
+    })
 }
 
 enum PlanExecutor<'a> {
     Full,
     Index(IndexCursor<'a>),
     RowId(Option<i64>),
+    RowIds {
+        rowids: &'a [i64],
+        reverse: bool,
+    },
+    RowIdRange {
+        min: Option<i64>,
+        max: Option<i64>,
+        reverse: bool,
+    },
 }
 
 pub struct Query<'a> {
@@ -189,6 +914,15 @@ impl<'a> Query<'a> {
                 index_info,
             )?),
             QueryPlan::RowId(rowid) => PlanExecutor::RowId(Some(*rowid)),
+            QueryPlan::RowIds { rowids, reverse } => PlanExecutor::RowIds {
+                rowids,
+                reverse: *reverse,
+            },
+            QueryPlan::RowIdRange { min, max, reverse } => PlanExecutor::RowIdRange {
+                min: *min,
+                max: *max,
+                reverse: *reverse,
+            },
         };
 
         Ok(Self {
@@ -199,6 +933,7 @@ impl<'a> Query<'a> {
         })
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Result<Option<RowData<'_>>> {
         let mut headers;
         let mut content_offset;
@@ -220,7 +955,9 @@ impl<'a> Query<'a> {
                     let rowid = index_cursor.next(self.deleted)?;
                     self.deleted = false;
                     if let Some(rowid) = rowid {
-                        self.cursor.table_move_to(rowid)?;
+                        if index_cursor.index.column_map.is_none() {
+                            self.cursor.table_move_to(rowid)?;
+                        }
                     } else {
                         return Ok(None);
                     }
@@ -232,11 +969,72 @@ impl<'a> Query<'a> {
                         return Ok(None);
                     }
                 }
+                PlanExecutor::RowIds { rowids, reverse } => {
+                    let next = if *reverse {
+                        rowids.split_last()
+                    } else {
+                        rowids.split_first()
+                    };
+                    let Some((rowid, rest)) = next else {
+                        return Ok(None);
+                    };
+                    *rowids = rest;
+                    self.deleted = false;
+                    // Skip the missing rowid without checking the row next to it.
+                    if self.cursor.table_move_to(*rowid)? != Some(*rowid) {
+                        continue;
+                    }
+                }
+                PlanExecutor::RowIdRange {
+                    min,
+                    reverse: false,
+                    ..
+                } => {
+                    if !self.cursor.is_initialized() {
+                        if let Some(min) = min {
+                            self.cursor.table_move_to_lower_bound(*min)?;
+                        } else {
+                            self.cursor.move_to_first()?;
+                        }
+                    } else if !self.deleted {
+                        self.cursor.move_next()?;
+                    } else {
+                        self.deleted = false;
+                    }
+                }
+                PlanExecutor::RowIdRange {
+                    max, reverse: true, ..
+                } => {
+                    if !self.cursor.is_initialized() {
+                        if let Some(max) = max {
+                            if self.cursor.table_move_to(*max)? != Some(*max) {
+                                self.cursor.move_prev()?;
+                            }
+                        } else {
+                            self.cursor.move_to_last()?;
+                        }
+                    } else {
+                        // The deleted row is replaced with the next row, so
+                        // the previous row is still before the cursor.
+                        self.cursor.move_prev()?;
+                        self.deleted = false;
+                    }
+                }
             }
 
-            let Some((rowid, payload)) = self.cursor.get_table_payload()? else {
+            let Some((rowid, payload)) = self.current_payload()? else {
                 return Ok(None);
             };
+            if let PlanExecutor::RowIdRange { min, max, reverse } = self.plan {
+                let out_of_range = if reverse {
+                    min.is_some_and(|min| rowid < min)
+                } else {
+                    max.is_some_and(|max| rowid > max)
+                };
+                if out_of_range {
+                    return Ok(None);
+                }
+            }
 
             headers = parse_record_header(&payload).map_err(Error::Record)?;
             assert!(!headers.is_empty());
@@ -252,6 +1050,26 @@ impl<'a> Query<'a> {
                 let n = payload.load(content_offset, &mut tmp_buf)?;
                 assert_eq!(n, content_size);
             };
+            if let PlanExecutor::Index(IndexCursor {
+                index:
+                    IndexInfo {
+                        column_map: Some(column_map),
+                        ..
+                    },
+                ..
+            }) = &self.plan
+            {
+                // Arrange the headers of the index entry in the order of the
+                // table columns.
+                headers = column_map
+                    .iter()
+                    .map(|position| {
+                        position.map_or((SerialType::NULL, content_offset), |position| {
+                            headers[position]
+                        })
+                    })
+                    .collect();
+            }
 
             let data = RowData {
                 rowid,
@@ -261,10 +1079,11 @@ impl<'a> Query<'a> {
                 use_local_buffer,
                 content_offset,
             };
-            let skip = matches!(
-                self.filter.execute(Some(&data))?.0,
-                None | Some(Value::Integer(0))
-            );
+            let skip = !self
+                .filter
+                .execute(Some(&data))?
+                .0
+                .is_some_and(|v| v.as_bool());
             RowData {
                 rowid: _,
                 payload: _,
@@ -278,7 +1097,7 @@ impl<'a> Query<'a> {
             }
         }
 
-        let Some((rowid, payload)) = self.cursor.get_table_payload()? else {
+        let Some((rowid, payload)) = self.current_payload()? else {
             unreachable!("cursor must point to a valid row");
         };
 
@@ -292,6 +1111,20 @@ impl<'a> Query<'a> {
         }))
     }
 
+    /// The rowid and the payload of the current row. The payload is the index
+    /// entry if the index covers the query.
+    fn current_payload(&self) -> Result<Option<(i64, BtreePayload<'_>)>> {
+        match &self.plan {
+            PlanExecutor::Index(index_cursor) if index_cursor.index.column_map.is_some() => {
+                Ok(index_cursor
+                    .cursor
+                    .get_index_payload()?
+                    .map(|payload| (index_cursor.rowid, payload)))
+            }
+            _ => Ok(self.cursor.get_table_payload()?),
+        }
+    }
+
     pub fn delete(&mut self) -> Result<()> {
         self.cursor.delete()?;
         if let PlanExecutor::Index(index_cursor) = &mut self.plan {
@@ -305,6 +1138,12 @@ impl<'a> Query<'a> {
 struct IndexCursor<'a> {
     cursor: BtreeCursor<'a>,
     index: &'a IndexInfo,
+    /// The index of the current seek in [IndexInfo::seeks].
+    seek_idx: usize,
+    /// Whether the cursor needs to move to the keys of the current seek.
+    needs_seek: bool,
+    /// The rowid of the current entry.
+    rowid: i64,
 }
 
 impl<'a> IndexCursor<'a> {
@@ -317,33 +1156,98 @@ impl<'a> IndexCursor<'a> {
         Ok(Self {
             cursor: BtreeCursor::new(index_page_id, pager, bctx)?,
             index,
+            seek_idx: 0,
+            needs_seek: true,
+            rowid: 0,
         })
     }
 
-    fn next(&mut self, deleted: bool) -> Result<Option<i64>> {
-        if !self.cursor.is_initialized() {
-            // TODO: IndexInfo should hold ValueCmp instead of ConstantValue.
-            let tmp_keys = self
-                .index
-                .keys
-                .iter()
-                .map(|(v, c)| (v.as_value(), c))
-                .collect::<Vec<_>>();
-            let mut comparators =
-                Vec::with_capacity(self.index.keys.len() + self.index.n_extra + 1);
-            comparators.extend(tmp_keys.iter().map(|(v, c)| Some(ValueCmp::new(v, c))));
-            // +1 for rowid
-            comparators.extend((0..self.index.n_extra + 1).map(|_| None));
-            self.cursor.index_move_to(&comparators)?;
-        } else if !deleted {
-            self.cursor.move_next()?;
+    fn next(&mut self, mut deleted: bool) -> Result<Option<i64>> {
+        loop {
+            // A reverse scan runs the seeks from the last one.
+            let keys = if self.index.reverse {
+                self.index
+                    .seeks
+                    .len()
+                    .checked_sub(self.seek_idx + 1)
+                    .map(|i| &self.index.seeks[i])
+            } else {
+                self.index.seeks.get(self.seek_idx)
+            };
+            let Some(keys) = keys else {
+                return Ok(None);
+            };
+            if self.needs_seek && self.index.reverse {
+                let upper =
+                    self.index.range.as_ref().and_then(|range| {
+                        range.upper.as_ref().map(|bound| (bound, &range.collation))
+                    });
+                let tmp_keys = keys
+                    .iter()
+                    .map(|(v, c)| (v.as_value(), c))
+                    .chain(upper.map(|(bound, c)| (bound.value.as_value(), c)))
+                    .collect::<Vec<_>>();
+                let comparators = tmp_keys
+                    .iter()
+                    .map(|(v, c)| Some(ValueCmp::new(v, c)))
+                    .collect::<Vec<_>>();
+                let inclusive = upper.is_none_or(|(bound, _)| bound.inclusive);
+                self.cursor.index_move_to_last(&comparators, inclusive)?;
+                self.needs_seek = false;
+            } else if self.needs_seek {
+                let lower =
+                    self.index.range.as_ref().and_then(|range| {
+                        range.lower.as_ref().map(|bound| (bound, &range.collation))
+                    });
+                // TODO: IndexInfo should hold ValueCmp instead of ConstantValue.
+                let tmp_keys = keys
+                    .iter()
+                    .map(|(v, c)| (v.as_value(), c))
+                    .chain(lower.map(|(bound, c)| (bound.value.as_value(), c)))
+                    .collect::<Vec<_>>();
+                let mut comparators = Vec::with_capacity(keys.len() + self.index.n_extra + 1);
+                comparators.extend(tmp_keys.iter().map(|(v, c)| Some(ValueCmp::new(v, c))));
+                // +1 for rowid
+                comparators
+                    .extend((tmp_keys.len()..keys.len() + self.index.n_extra + 1).map(|_| None));
+                self.cursor.index_move_to(&comparators)?;
+                self.needs_seek = false;
+            } else if self.index.reverse {
+                // The deleted entry is replaced with the next entry, so the
+                // previous entry is still before the cursor.
+                self.cursor.move_prev()?;
+            } else if !deleted {
+                self.cursor.move_next()?;
+            }
+            deleted = false;
+
+            match self.current_entry(keys)? {
+                IndexEntry::Match(rowid) => {
+                    self.rowid = rowid;
+                    return Ok(Some(rowid));
+                }
+                IndexEntry::Skip => {}
+                IndexEntry::End => {
+                    // The entries for the current seek are exhausted.
+                    self.seek_idx += 1;
+                    self.needs_seek = true;
+                }
+            }
         }
+    }
 
+    /// Check the current entry against the keys and the range.
+    fn current_entry(&self, keys: &[(ConstantValue, Collation)]) -> Result<IndexEntry> {
+        // Entries out of the range are skipped until the scan reaches the range.
+        let (below_range, above_range) = if self.index.reverse {
+            (IndexEntry::End, IndexEntry::Skip)
+        } else {
+            (IndexEntry::Skip, IndexEntry::End)
+        };
         let Some(index_payload) = self.cursor.get_index_payload()? else {
-            return Ok(None);
+            return Ok(IndexEntry::End);
         };
         let mut record = parse_record(&index_payload).map_err(Error::Record)?;
-        let keys = self.index.keys.as_slice();
         if record.len() < keys.len() {
             return Err(Error::Record(anyhow::anyhow!("index payload is too short")));
         }
@@ -353,7 +1257,27 @@ impl<'a> IndexCursor<'a> {
                     continue;
                 }
             }
-            return Ok(None);
+            return Ok(IndexEntry::End);
+        }
+        if let Some(range) = &self.index.range {
+            // NULL is less than any value and never satisfies the range.
+            let Some(value) = record.get(keys.len()).map_err(Error::Record)? else {
+                return Ok(below_range);
+            };
+            if let Some(lower) = &range.lower {
+                match ValueCmp::new(&lower.value.as_value(), &range.collation).compare(&value) {
+                    Ordering::Greater => return Ok(below_range),
+                    Ordering::Equal if !lower.inclusive => return Ok(below_range),
+                    _ => {}
+                }
+            }
+            if let Some(upper) = &range.upper {
+                match ValueCmp::new(&upper.value.as_value(), &range.collation).compare(&value) {
+                    Ordering::Less => return Ok(above_range),
+                    Ordering::Equal if !upper.inclusive => return Ok(above_range),
+                    _ => {}
+                }
+            }
         }
         let Some(Value::Integer(rowid)) = record.get(record.len() - 1).map_err(Error::Record)?
         else {
@@ -362,10 +1286,19 @@ impl<'a> IndexCursor<'a> {
             )));
         };
 
-        Ok(Some(rowid))
+        Ok(IndexEntry::Match(rowid))
     }
 }
 
+#[derive(Clone, Copy)]
+enum IndexEntry {
+    Match(i64),
+    /// The entry does not match but following entries may match.
+    Skip,
+    /// No more entries match for the current seek.
+    End,
+}
+
 pub struct RowData<'a> {
     rowid: i64,
     payload: BtreePayload<'a>,
@@ -379,7 +1312,7 @@ impl<'a> DataContext for RowData<'a> {
     fn get_column_value(
         &self,
         column_idx: &ColumnNumber,
-    ) -> std::result::Result<Option<Value>, Box<dyn std::error::Error + Sync + Send>> {
+    ) -> std::result::Result<Option<Value<'_>>, Box<dyn std::error::Error + Sync + Send>> {
         match column_idx {
             ColumnNumber::Column(idx) => {
                 if let Some((serial_type, offset)) = self.headers.get(*idx) {
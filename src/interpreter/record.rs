@@ -53,9 +53,12 @@ pub fn compare_record(
     Ok(Ordering::Equal)
 }
 
+#[derive(Clone, Copy)]
 pub struct SerialType(u32);
 
 impl SerialType {
+    pub const NULL: Self = Self(0);
+
     pub fn content_size(&self) -> u32 {
         // TODO: use pre-calculated table for first 128 serial types.
         match self.0 {
@@ -146,7 +149,7 @@ pub struct Record<'a, P: LocalPayload<E>, E> {
 #[inline]
 pub fn parse_record<'a>(
     payload: &'a BtreePayload<'a>,
-) -> anyhow::Result<Record<BtreePayload<'a>, crate::interpreter::cursor::Error>> {
+) -> anyhow::Result<Record<'a, BtreePayload<'a>, crate::interpreter::cursor::Error>> {
     Record::parse(payload)
 }
 
@@ -165,6 +168,10 @@ impl<'a, P: LocalPayload<E>, E: Debug> Record<'a, P, E> {
         self.header.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.header.is_empty()
+    }
+
     pub fn get(&mut self, i: usize) -> anyhow::Result<Option<Value<'_>>> {
         let Some((serial_type, offset)) = &self.header.get(i) else {
             bail!("index out of range");
@@ -541,9 +548,9 @@ impl CopiablePayload for RecordPayload<'_> {
 mod tests {
 
     use super::*;
-    use crate::interpreter::test_utils::*;
     use crate::interpreter::cursor::BtreeCursor;
     use crate::interpreter::payload::SlicePayload;
+    use crate::interpreter::test_utils::*;
 
     const ONE: i64 = 1;
 
@@ -26,6 +26,7 @@ use crate::interpreter::parser::parse_create_index;
 use crate::interpreter::parser::parse_create_table;
 use crate::interpreter::parser::ColumnConstraint;
 use crate::interpreter::parser::Parser;
+use crate::interpreter::query::QueryPlan;
 use crate::interpreter::utils::upper_to_lower;
 use crate::interpreter::utils::CaseInsensitiveBytes;
 use crate::interpreter::utils::MaybeQuotedBytes;
@@ -35,6 +36,7 @@ use crate::interpreter::value::TypeAffinity;
 use crate::interpreter::value::Value;
 use crate::interpreter::value::DEFAULT_COLLATION;
 use crate::Columns;
+use crate::Expression;
 use crate::SelectStatement;
 
 struct SchemaRecord<'a> {
@@ -100,44 +102,57 @@ impl Schema {
                     name: b"type".to_vec(),
                     type_affinity: TypeAffinity::Text,
                     primary_key: false,
+                    rowid_alias: false,
                     collation: Collation::Binary,
+                    default: None,
                 },
                 Column {
                     name: b"name".to_vec(),
                     type_affinity: TypeAffinity::Text,
                     primary_key: false,
+                    rowid_alias: false,
                     collation: Collation::Binary,
+                    default: None,
                 },
                 Column {
                     name: b"tbl_name".to_vec(),
                     type_affinity: TypeAffinity::Text,
                     primary_key: false,
+                    rowid_alias: false,
                     collation: Collation::Binary,
+                    default: None,
                 },
                 Column {
                     name: b"rootpage".to_vec(),
                     type_affinity: TypeAffinity::Integer,
                     primary_key: false,
+                    rowid_alias: false,
                     collation: Collation::Binary,
+                    default: None,
                 },
                 Column {
                     name: b"sql".to_vec(),
                     type_affinity: TypeAffinity::Text,
                     primary_key: false,
+                    rowid_alias: false,
                     collation: Collation::Binary,
+                    default: None,
                 },
             ],
             indexes: None,
+            n_rows: None,
         }
     }
 
+    /// Load the tables and the indexes from sqlite_schema, and the statistics
+    /// from sqlite_stat1 if it exists.
     pub fn generate(stmt: SelectStatement, schema_table: Table) -> anyhow::Result<Schema> {
-        let stmt = stmt;
+        let conn = stmt.conn;
         let mut rows = stmt
             .query()
             .map_err(|e| anyhow::anyhow!("query: {:?}", e))?;
         let mut tables = HashMap::new();
-        let mut indexes = HashMap::new();
+        let mut pending_indexes = Vec::new();
         while let Some(row) = rows
             .next_row()
             .map_err(|e| anyhow::anyhow!("next row: {:?}", e))?
@@ -174,12 +189,10 @@ impl Schema {
                     // schema.table_name is the same as the schema.name of the table entry.
                     let mut table_name = schema.table_name.to_vec();
                     upper_to_lower(&mut table_name);
-                    let table = tables
-                        .get_mut(&table_name)
-                        .context("index table not found")?;
+                    let table = tables.get(&table_name).context("index table not found")?;
                     // TODO: validate the schema.table is equal to table.name.
                     if let Some(sql) = schema.sql {
-                        let (mut index_name, parsed_table_name, mut index) =
+                        let (mut index_name, parsed_table_name, index) =
                             Index::parse(sql, schema.root_page_id, table)?;
                         if index_name != schema.name {
                             bail!(
@@ -197,15 +210,16 @@ impl Schema {
                                 parsed_table_name
                             );
                         }
-                        index.next = table.indexes.clone();
-                        let index = Rc::new(index);
-                        table.indexes = Some(index.clone());
-
                         upper_to_lower(&mut index_name);
-                        indexes.insert(index_name, index);
+                        // Indexes are linked to the table after loading the statistics.
+                        pending_indexes.push((table_name, index_name, index));
+                    } else if let Some(index) = Index::autoindex(schema.root_page_id, table) {
+                        let mut index_name = schema.name.to_vec();
+                        upper_to_lower(&mut index_name);
+                        pending_indexes.push((table_name, index_name, index));
                     } else {
-                        // TODO: support autoindex
-                        eprintln!("no sql for index: {:?}", schema.name);
+                        // Writes to the table would not maintain the unknown index.
+                        bail!("unsupported autoindex: {:?}", schema.name);
                     }
                 }
                 b"view" => {
@@ -217,6 +231,40 @@ impl Schema {
                 type_ => bail!("unsupported type: {:?}", type_),
             }
         }
+        drop(rows);
+
+        let mut stats = if let Some(stat_table) = tables.get(b"sqlite_stat1".as_slice()) {
+            load_stats(SelectStatement::new(
+                conn,
+                stat_table.root_page_id,
+                stat_table
+                    .get_all_columns()
+                    .map(Expression::Column)
+                    .collect(),
+                Expression::one(),
+                QueryPlan::FullScan,
+            ))?
+        } else {
+            HashMap::new()
+        };
+        for (table_name, table) in tables.iter_mut() {
+            if let Some(stats) = stats.remove(&(table_name.clone(), None)) {
+                table.n_rows = Some(stats.n_rows);
+            }
+        }
+        let mut indexes = HashMap::new();
+        for (table_name, index_name, mut index) in pending_indexes {
+            let table = tables.get_mut(&table_name).unwrap();
+            index.stats = stats.remove(&(table_name, Some(index_name.clone())));
+            if let Some(stats) = &index.stats {
+                table.n_rows = Some(stats.n_rows);
+            }
+            index.next = table.indexes.clone();
+            let index = Rc::new(index);
+            table.indexes = Some(index.clone());
+            indexes.insert(index_name, index);
+        }
+
         Ok(Self {
             schema_table,
             tables,
@@ -235,7 +283,20 @@ impl Schema {
         }
     }
 
-    #[allow(unused)]
+    /// Iterate the tables except sqlite_schema with the lowercased names.
+    pub fn tables(&self) -> impl Iterator<Item = (&[u8], &Table)> {
+        self.tables
+            .iter()
+            .map(|(name, table)| (name.as_slice(), table))
+    }
+
+    /// Iterate the indexes with the lowercased names.
+    pub fn indexes(&self) -> impl Iterator<Item = (&[u8], &Rc<Index>)> {
+        self.indexes
+            .iter()
+            .map(|(name, index)| (name.as_slice(), index))
+    }
+
     pub fn get_index(&self, index: &[u8]) -> Option<&Rc<Index>> {
         // TODO: use the reference of given index name.
         let mut key = index.to_vec();
@@ -244,15 +305,97 @@ impl Schema {
     }
 }
 
+/// The statistics keyed by the lowercased table name and index name.
+type StatsMap = HashMap<(Vec<u8>, Option<Vec<u8>>), IndexStats>;
+
+/// Load the rows of sqlite_stat1 keyed by the lowercased table name and index
+/// name.
+///
+/// Malformed rows are ignored as sqlite3 does.
+fn load_stats(stmt: SelectStatement) -> anyhow::Result<StatsMap> {
+    let mut rows = stmt
+        .query()
+        .map_err(|e| anyhow::anyhow!("query: {:?}", e))?;
+    let mut stats = HashMap::new();
+    while let Some(row) = rows
+        .next_row()
+        .map_err(|e| anyhow::anyhow!("next row: {:?}", e))?
+    {
+        let columns = row.parse().map_err(|e| anyhow::anyhow!("parse: {:?}", e))?;
+        let (Some(Value::Text(table_name)), Some(Value::Text(stat))) =
+            (columns.get(0), columns.get(2))
+        else {
+            continue;
+        };
+        let index_name = match columns.get(1) {
+            None => None,
+            Some(Value::Text(index_name)) => Some(index_name),
+            _ => continue,
+        };
+        let Some(index_stats) = IndexStats::parse(stat) else {
+            continue;
+        };
+        let mut table_name = table_name.to_vec();
+        upper_to_lower(&mut table_name);
+        let index_name = index_name.map(|index_name| {
+            let mut index_name = index_name.to_vec();
+            upper_to_lower(&mut index_name);
+            index_name
+        });
+        stats.insert((table_name, index_name), index_stats);
+    }
+    Ok(stats)
+}
+
+/// The statistics of an index in sqlite_stat1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexStats {
+    /// The number of rows in the index.
+    pub n_rows: u64,
+    /// The average number of rows sharing the same values of the leading
+    /// columns. The i-th value is for the leading i+1 columns.
+    pub avg_eq: Vec<u64>,
+}
+
+impl IndexStats {
+    /// Parse the stat column of sqlite_stat1.
+    ///
+    /// The format is "nRow a1 a2 ... aN" optionally followed by keywords like
+    /// "unordered", which are ignored.
+    pub fn parse(stat: &[u8]) -> Option<Self> {
+        let mut values = stat
+            .split(|b| *b == b' ')
+            .map_while(|v| std::str::from_utf8(v).ok()?.parse::<u64>().ok());
+        let n_rows = values.next()?;
+        Some(Self {
+            n_rows,
+            avg_eq: values.collect(),
+        })
+    }
+
+    /// Format the statistics in the format of sqlite_stat1.
+    pub fn format(&self) -> Vec<u8> {
+        let mut stat = self.n_rows.to_string();
+        for avg_eq in &self.avg_eq {
+            stat.push(' ');
+            stat.push_str(&avg_eq.to_string());
+        }
+        stat.into_bytes()
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct Index {
     pub root_page_id: PageId,
     pub columns: Vec<ColumnNumber>,
+    pub unique: bool,
+    /// The statistics loaded from sqlite_stat1.
+    pub stats: Option<IndexStats>,
     pub next: Option<Rc<Index>>,
 }
 
 impl Index {
-    fn parse<'a>(
+    pub(crate) fn parse<'a>(
         sql: &'a [u8],
         root_page_id: PageId,
         table: &Table,
@@ -285,18 +428,39 @@ impl Index {
             Self {
                 root_page_id,
                 columns,
+                unique: create_index.unique,
+                stats: None,
                 next: None,
             },
         ))
     }
+
+    /// The index which sqlite3 creates for the PRIMARY KEY of the table.
+    ///
+    /// None if the table has no PRIMARY KEY or it is the rowid.
+    pub(crate) fn autoindex(root_page_id: PageId, table: &Table) -> Option<Self> {
+        Some(Self {
+            root_page_id,
+            columns: table.autoindex_columns()?,
+            unique: true,
+            stats: None,
+            next: None,
+        })
+    }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub struct Column {
     pub name: Vec<u8>,
     pub type_affinity: TypeAffinity,
     pub primary_key: bool,
+    /// Whether the column is an alias of the rowid.
+    ///
+    /// Only a PRIMARY KEY column whose declared type is exactly INTEGER is.
+    pub rowid_alias: bool,
     pub collation: Collation,
+    /// The value of the DEFAULT clause, which is a constant expression.
+    pub default: Option<Expression>,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -357,15 +521,17 @@ pub fn calc_collation(collation_name: &MaybeQuotedBytes) -> Option<Collation> {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub struct Table {
     pub root_page_id: PageId,
     pub columns: Vec<Column>,
     pub indexes: Option<Rc<Index>>,
+    /// The number of rows recorded in sqlite_stat1.
+    pub n_rows: Option<u64>,
 }
 
 impl Table {
-    fn parse(sql: &[u8], root_page_id: PageId) -> anyhow::Result<(Vec<u8>, Self)> {
+    pub(crate) fn parse(sql: &[u8], root_page_id: PageId) -> anyhow::Result<(Vec<u8>, Self)> {
         let mut parser = Parser::new(sql);
         let create_table = parse_create_table(&mut parser)
             .map_err(|e| anyhow::anyhow!("parse create table sql: {:?}", e))?;
@@ -399,18 +565,34 @@ impl Table {
             }
 
             let mut collation = DEFAULT_COLLATION.clone();
-            for constraint in &column_def.constraints {
-                if let ColumnConstraint::Collate(collation_name) = constraint {
-                    collation = calc_collation(collation_name)
-                        .ok_or_else(|| anyhow::anyhow!("collation is not found"))?;
+            let mut default = None;
+            for constraint in column_def.constraints {
+                match constraint {
+                    ColumnConstraint::Collate(collation_name) => {
+                        collation = calc_collation(&collation_name)
+                            .ok_or_else(|| anyhow::anyhow!("collation is not found"))?;
+                    }
+                    ColumnConstraint::Default(expr) => {
+                        let expr = Expression::from(expr, None)
+                            .map_err(|e| anyhow::anyhow!("default value: {:?}", e))?;
+                        if expr.has_parameter() {
+                            bail!("default value of column is not constant");
+                        }
+                        default = Some(expr);
+                    }
+                    ColumnConstraint::PrinaryKey => {}
                 }
             }
 
+            let rowid_alias = primary_key
+                && matches!(column_def.type_name.as_slice(), [type_name] if type_name.dequote().eq_ignore_ascii_case(b"integer"));
             columns.push(Column {
                 name: column_name,
                 type_affinity: calc_type_affinity(&column_def.type_name),
                 primary_key,
+                rowid_alias,
                 collation,
+                default,
             });
         }
         Ok((
@@ -419,6 +601,7 @@ impl Table {
                 root_page_id,
                 columns,
                 indexes: None,
+                n_rows: None,
             },
         ))
     }
@@ -431,12 +614,11 @@ impl Table {
             .enumerate()
             .find(|(_, c)| CaseInsensitiveBytes::from(&c.name) == column)
         {
-            let column_number =
-                if column.primary_key && column.type_affinity == TypeAffinity::Integer {
-                    ColumnNumber::RowId
-                } else {
-                    ColumnNumber::Column(i)
-                };
+            let column_number = if column.rowid_alias {
+                ColumnNumber::RowId
+            } else {
+                ColumnNumber::Column(i)
+            };
             Some((
                 column_number,
                 column.type_affinity,
@@ -453,11 +635,22 @@ impl Table {
         }
     }
 
+    /// The columns of the PRIMARY KEY which is not the rowid.
+    ///
+    /// sqlite3 creates the index "sqlite_autoindex_<table>_1" for them.
+    pub fn autoindex_columns(&self) -> Option<Vec<ColumnNumber>> {
+        let column_idx = self
+            .columns
+            .iter()
+            .position(|column| column.primary_key && !column.rowid_alias)?;
+        Some(vec![ColumnNumber::Column(column_idx)])
+    }
+
     pub fn get_all_columns(
         &self,
     ) -> impl Iterator<Item = (ColumnNumber, TypeAffinity, Collation)> + '_ {
         self.columns.iter().enumerate().map(|(i, column)| {
-            if column.primary_key && column.type_affinity == TypeAffinity::Integer {
+            if column.rowid_alias {
                 (
                     ColumnNumber::RowId,
                     TypeAffinity::Integer,
@@ -479,10 +672,8 @@ mod tests {
     use std::path::Path;
 
     use super::*;
-    use crate::interpreter::query::QueryPlan;
     use crate::interpreter::test_utils::*;
     use crate::Connection;
-    use crate::Expression;
 
     fn generate_schema(filepath: &Path) -> Schema {
         let conn = Connection::open(filepath).unwrap();
@@ -597,43 +788,93 @@ mod tests {
                         name: b"col".to_vec(),
                         type_affinity: TypeAffinity::Blob,
                         primary_key: false,
+                        rowid_alias: false,
                         collation: Collation::Binary,
+                        default: None,
                     },
                     Column {
                         name: b"col1".to_vec(),
                         type_affinity: TypeAffinity::Integer,
                         primary_key: true,
+                        rowid_alias: true,
                         collation: Collation::Binary,
+                        default: None,
                     },
                     Column {
                         name: b"col2".to_vec(),
                         type_affinity: TypeAffinity::Text,
                         primary_key: false,
+                        rowid_alias: false,
                         collation: Collation::Binary,
+                        default: None,
                     },
                     Column {
                         name: b"co`l3".to_vec(),
                         type_affinity: TypeAffinity::Blob,
                         primary_key: false,
+                        rowid_alias: false,
                         collation: Collation::Binary,
+                        default: None,
                     },
                     Column {
                         name: b"col4".to_vec(),
                         type_affinity: TypeAffinity::Real,
                         primary_key: false,
+                        rowid_alias: false,
                         collation: Collation::Binary,
+                        default: None,
                     },
                     Column {
                         name: b"col5".to_vec(),
                         type_affinity: TypeAffinity::Numeric,
                         primary_key: false,
+                        rowid_alias: false,
                         collation: Collation::Binary,
+                        default: None,
                     },
                 ],
                 indexes: None,
+                n_rows: None,
             }
         );
 
+        // Only the declared type INTEGER makes the rowid alias.
+        for (sql, rowid_alias) in [
+            (
+                b"create table example(id INTEGER primary key)".as_slice(),
+                true,
+            ),
+            (
+                b"create table example(id \"Integer\" primary key asc)",
+                true,
+            ),
+            (b"create table example(id int primary key)", false),
+            (b"create table example(id bigint primary key)", false),
+            (
+                b"create table example(id unsigned integer primary key)",
+                false,
+            ),
+        ] {
+            let (_, table) = Table::parse(sql, PAGE_ID_1).unwrap();
+            assert_eq!(table.columns[0].rowid_alias, rowid_alias);
+            assert_eq!(
+                table.get_column(b"id").unwrap().0,
+                if rowid_alias {
+                    ColumnNumber::RowId
+                } else {
+                    ColumnNumber::Column(0)
+                }
+            );
+            assert_eq!(
+                table.autoindex_columns(),
+                if rowid_alias {
+                    None
+                } else {
+                    Some(vec![ColumnNumber::Column(0)])
+                }
+            );
+        }
+
         // multiple primary key
         assert!(Table::parse(
             b"create table example(col, col1 integer primary key, col2 text primary key)",
@@ -701,9 +942,12 @@ mod tests {
                     name: b"col".to_vec(),
                     type_affinity: TypeAffinity::Blob,
                     primary_key: false,
+                    rowid_alias: false,
                     collation: Collation::Binary,
+                    default: None,
                 }],
                 indexes: None,
+                n_rows: None,
             }
         );
         assert_eq!(
@@ -713,13 +957,17 @@ mod tests {
                     name: b"col1".to_vec(),
                     type_affinity: TypeAffinity::Numeric,
                     primary_key: false,
+                    rowid_alias: false,
                     collation: Collation::Binary,
+                    default: None,
                 },
                 Column {
                     name: b"col2".to_vec(),
                     type_affinity: TypeAffinity::Integer,
                     primary_key: false,
+                    rowid_alias: false,
                     collation: Collation::Binary,
+                    default: None,
                 }
             ]
         );
@@ -730,25 +978,33 @@ mod tests {
                     name: b"COL1".to_vec(),
                     type_affinity: TypeAffinity::Real,
                     primary_key: false,
+                    rowid_alias: false,
                     collation: Collation::Binary,
+                    default: None,
                 },
                 Column {
                     name: b"Col2".to_vec(),
                     type_affinity: TypeAffinity::Text,
                     primary_key: true,
+                    rowid_alias: false,
                     collation: Collation::Binary,
+                    default: None,
                 },
                 Column {
                     name: b"cOL3".to_vec(),
                     type_affinity: TypeAffinity::Blob,
                     primary_key: false,
+                    rowid_alias: false,
                     collation: Collation::Binary,
+                    default: None,
                 },
                 Column {
                     name: b"_".to_vec(),
                     type_affinity: TypeAffinity::Blob,
                     primary_key: false,
+                    rowid_alias: false,
                     collation: Collation::Binary,
+                    default: None,
                 }
             ]
         );
@@ -760,37 +1016,49 @@ mod tests {
                     name: b"col".to_vec(),
                     type_affinity: TypeAffinity::Blob,
                     primary_key: false,
+                    rowid_alias: false,
                     collation: Collation::Binary,
+                    default: None,
                 },
                 Column {
                     name: b"col1".to_vec(),
                     type_affinity: TypeAffinity::Integer,
                     primary_key: true,
+                    rowid_alias: true,
                     collation: Collation::Binary,
+                    default: None,
                 },
                 Column {
                     name: b"col2".to_vec(),
                     type_affinity: TypeAffinity::Text,
                     primary_key: false,
+                    rowid_alias: false,
                     collation: Collation::NoCase,
+                    default: None,
                 },
                 Column {
                     name: b"co`l3".to_vec(),
                     type_affinity: TypeAffinity::Blob,
                     primary_key: false,
+                    rowid_alias: false,
                     collation: Collation::RTrim,
+                    default: None,
                 },
                 Column {
                     name: b"col4".to_vec(),
                     type_affinity: TypeAffinity::Real,
                     primary_key: false,
+                    rowid_alias: false,
                     collation: Collation::Binary,
+                    default: None,
                 },
                 Column {
                     name: b"col5".to_vec(),
                     type_affinity: TypeAffinity::Numeric,
                     primary_key: false,
+                    rowid_alias: false,
                     collation: Collation::Binary,
+                    default: None,
                 },
             ]
         );
@@ -1023,11 +1291,15 @@ mod tests {
         let index1 = Rc::new(Index {
             root_page_id: PageId::new(3).unwrap(),
             columns: vec![ColumnNumber::Column(0)],
+            unique: false,
+            stats: None,
             next: None,
         });
         let index2 = Rc::new(Index {
             root_page_id: PageId::new(4).unwrap(),
             columns: vec![ColumnNumber::Column(0), ColumnNumber::Column(1)],
+            unique: false,
+            stats: None,
             next: Some(index1.clone()),
         });
         assert_eq!(schema.get_index(b"index1").unwrap(), &index1);
@@ -1046,11 +1318,15 @@ mod tests {
         let index1 = Rc::new(Index {
             root_page_id: PageId::new(3).unwrap(),
             columns: vec![ColumnNumber::Column(0)],
+            unique: false,
+            stats: None,
             next: None,
         });
         let index2 = Rc::new(Index {
             root_page_id: PageId::new(4).unwrap(),
             columns: vec![ColumnNumber::Column(0), ColumnNumber::Column(1)],
+            unique: false,
+            stats: None,
             next: Some(index1.clone()),
         });
         assert_eq!(schema.get_index(b"index1").unwrap(), &index1);
@@ -1071,11 +1347,15 @@ mod tests {
         let index1 = Rc::new(Index {
             root_page_id: PageId::new(3).unwrap(),
             columns: vec![ColumnNumber::Column(0)],
+            unique: false,
+            stats: None,
             next: None,
         });
         let index2 = Rc::new(Index {
             root_page_id: PageId::new(4).unwrap(),
             columns: vec![ColumnNumber::Column(0), ColumnNumber::Column(1)],
+            unique: false,
+            stats: None,
             next: Some(index1.clone()),
         });
         assert_eq!(table.indexes, Some(index2));
@@ -1131,9 +1411,18 @@ mod tests {
                     ColumnNumber::Column(0),
                     ColumnNumber::Column(2)
                 ],
+                unique: false,
+                stats: None,
                 next: None,
             }
         );
+        let (_, _, index) = Index::parse(
+            b"create unique index index1 on example(col1)",
+            page_id,
+            &table,
+        )
+        .unwrap();
+        assert!(index.unique);
         // unknown column
         assert!(Index::parse(
             b"create index index1 on example(col1, invalid)",
@@ -1146,4 +1435,48 @@ mod tests {
             Index::parse(b"create index index1 on invalid(col1)", page_id, &table).unwrap();
         assert_eq!(table_name, b"invalid".as_slice().into());
     }
+
+    #[test]
+    fn test_index_stats_parse() {
+        let stats = IndexStats::parse(b"100 15 4").unwrap();
+        assert_eq!(stats.n_rows, 100);
+        assert_eq!(stats.avg_eq, vec![15, 4]);
+        assert_eq!(stats.format(), b"100 15 4");
+
+        let stats = IndexStats::parse(b"3").unwrap();
+        assert_eq!(stats.n_rows, 3);
+        assert!(stats.avg_eq.is_empty());
+
+        // Trailing keywords are ignored.
+        let stats = IndexStats::parse(b"100 1 unordered sz=10").unwrap();
+        assert_eq!(stats.avg_eq, vec![1]);
+
+        assert!(IndexStats::parse(b"").is_none());
+        assert!(IndexStats::parse(b"abc 1").is_none());
+    }
+
+    #[test]
+    fn test_generate_with_stats() {
+        let file = create_sqlite_database(&[
+            "CREATE TABLE example(col1, col2);",
+            "CREATE INDEX index1 ON example(col1, col2);",
+            "CREATE INDEX index2 ON example(col2);",
+            "INSERT INTO example VALUES (1, 1), (1, 2), (2, 3), (2, 4);",
+            "ANALYZE;",
+        ]);
+        let schema = generate_schema(file.path());
+
+        let table = schema.get_table(b"example").unwrap();
+        assert_eq!(table.n_rows, Some(4));
+        let index1 = schema.get_index(b"index1").unwrap();
+        assert_eq!(
+            index1.stats,
+            Some(IndexStats {
+                n_rows: 4,
+                avg_eq: vec![2, 1]
+            })
+        );
+        let index2 = schema.get_index(b"index2").unwrap();
+        assert_eq!(index2.stats.as_ref().unwrap().avg_eq, vec![1]);
+    }
 }
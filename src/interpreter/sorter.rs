@@ -0,0 +1,388 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+
+use anyhow::Context;
+
+use crate::interpreter::payload::CopiablePayload;
+use crate::interpreter::payload::SlicePayload;
+use crate::interpreter::record::Record;
+use crate::interpreter::record::RecordPayload;
+use crate::interpreter::value::Collation;
+use crate::interpreter::value::ConstantValue;
+use crate::interpreter::value::ValueCmp;
+
+/// The default maximum size of rows a [Sorter] holds in memory.
+pub const DEFAULT_SORTER_MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// Approximate memory overhead of a value in a row.
+const VALUE_OVERHEAD: usize = std::mem::size_of::<Option<ConstantValue>>();
+
+pub type SortRow = Vec<Option<ConstantValue>>;
+
+/// The order of a sort key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortOrder {
+    pub collation: Collation,
+    pub desc: bool,
+}
+
+/// Compare 2 rows by the leading sort keys.
+///
+/// NULL is smaller than any other value.
+pub fn compare_rows(orders: &[SortOrder], row1: &SortRow, row2: &SortRow) -> Ordering {
+    for (i, order) in orders.iter().enumerate() {
+        let ordering = match (&row1[i], &row2[i]) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (Some(v1), Some(v2)) => {
+                ValueCmp::new(&v1.as_value(), &order.collation).compare(&v2.as_value())
+            }
+        };
+        let ordering = if order.desc {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+fn row_size(row: &SortRow) -> usize {
+    row.iter()
+        .map(|v| {
+            VALUE_OVERHEAD
+                + match v {
+                    Some(ConstantValue::Text(buf)) | Some(ConstantValue::Blob(buf)) => buf.len(),
+                    _ => 0,
+                }
+        })
+        .sum()
+}
+
+/// Sorts rows of which leading values are the sort keys.
+///
+/// Rows are sorted in memory until the size of them exceeds the memory limit.
+/// Then the sorted rows are spilled to a temporary file as a run, and the runs
+/// are merged on [Sorter::finish()]. The sort is stable.
+pub struct Sorter {
+    orders: Vec<SortOrder>,
+    rows: Vec<SortRow>,
+    memory_size: usize,
+    memory_limit: usize,
+    runs: Vec<File>,
+}
+
+impl Sorter {
+    pub fn new(orders: Vec<SortOrder>, memory_limit: usize) -> Self {
+        Self {
+            orders,
+            rows: Vec::new(),
+            memory_size: 0,
+            memory_limit,
+            runs: Vec::new(),
+        }
+    }
+
+    /// Add a row. The row must have at least as many values as the sort keys.
+    pub fn add(&mut self, row: SortRow) -> anyhow::Result<()> {
+        assert!(row.len() >= self.orders.len());
+        self.memory_size += row_size(&row);
+        self.rows.push(row);
+        if self.memory_size > self.memory_limit {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// The number of runs spilled to temporary files.
+    pub fn n_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    fn sort_rows(&mut self) {
+        let orders = &self.orders;
+        self.rows
+            .sort_by(|row1, row2| compare_rows(orders, row1, row2));
+    }
+
+    fn spill(&mut self) -> anyhow::Result<()> {
+        self.sort_rows();
+        let file = tempfile::tempfile().context("create temporary file for sort")?;
+        let mut writer = BufWriter::new(file);
+        let mut buf = Vec::new();
+        for row in self.rows.drain(..) {
+            let values = row
+                .iter()
+                .map(|v| v.as_ref().map(|v| v.as_value()))
+                .collect::<Vec<_>>();
+            let value_refs = values.iter().map(|v| v.as_ref()).collect::<Vec<_>>();
+            let payload = RecordPayload::new(&value_refs)?;
+            buf.resize(payload.size().get() as usize, 0);
+            let n = payload.copy(0, &mut buf);
+            assert_eq!(n, buf.len());
+            writer.write_all(&(buf.len() as u64).to_be_bytes())?;
+            writer.write_all(&buf)?;
+        }
+        let mut file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.seek(SeekFrom::Start(0))?;
+        self.runs.push(file);
+        self.memory_size = 0;
+        Ok(())
+    }
+
+    /// Finish adding rows and start reading the sorted rows.
+    pub fn finish(mut self) -> anyhow::Result<SortedRows> {
+        if !self.runs.is_empty() && !self.rows.is_empty() {
+            self.spill()?;
+        }
+        if self.runs.is_empty() {
+            self.sort_rows();
+            return Ok(SortedRows {
+                orders: self.orders,
+                sources: vec![RowSource::Memory(self.rows.into_iter())],
+                heads: vec![None],
+                initialized: false,
+                current: None,
+            });
+        }
+        let n_runs = self.runs.len();
+        Ok(SortedRows {
+            orders: self.orders,
+            sources: self
+                .runs
+                .into_iter()
+                .map(|file| RowSource::Run(BufReader::new(file)))
+                .collect(),
+            heads: (0..n_runs).map(|_| None).collect(),
+            initialized: false,
+            current: None,
+        })
+    }
+}
+
+enum RowSource {
+    Memory(std::vec::IntoIter<SortRow>),
+    Run(BufReader<File>),
+}
+
+impl RowSource {
+    fn next(&mut self) -> anyhow::Result<Option<SortRow>> {
+        match self {
+            Self::Memory(rows) => Ok(rows.next()),
+            Self::Run(reader) => {
+                let mut len_buf = [0; 8];
+                match reader.read_exact(&mut len_buf) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                    Err(e) => return Err(e.into()),
+                }
+                let mut buf = vec![0; u64::from_be_bytes(len_buf) as usize];
+                reader.read_exact(&mut buf)?;
+                let payload = SlicePayload::new(&buf)?;
+                let mut record = Record::<_, ()>::parse(&payload)?;
+                let mut row = Vec::with_capacity(record.len());
+                for i in 0..record.len() {
+                    row.push(record.get(i)?.map(ConstantValue::copy_from));
+                }
+                Ok(Some(row))
+            }
+        }
+    }
+}
+
+/// Rows sorted by [Sorter].
+pub struct SortedRows {
+    orders: Vec<SortOrder>,
+    sources: Vec<RowSource>,
+    heads: Vec<Option<SortRow>>,
+    initialized: bool,
+    current: Option<SortRow>,
+}
+
+impl SortedRows {
+    /// Move to the next row and return it.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> anyhow::Result<Option<&SortRow>> {
+        if !self.initialized {
+            for (source, head) in self.sources.iter_mut().zip(self.heads.iter_mut()) {
+                *head = source.next()?;
+            }
+            self.initialized = true;
+        }
+        // The number of runs is small. Linear search is enough.
+        let mut min_idx: Option<usize> = None;
+        for (i, head) in self.heads.iter().enumerate() {
+            let Some(row) = head else {
+                continue;
+            };
+            if let Some(idx) = min_idx {
+                // Prefer the earlier run on tie to keep the sort stable.
+                if compare_rows(&self.orders, row, self.heads[idx].as_ref().unwrap())
+                    == Ordering::Less
+                {
+                    min_idx = Some(i);
+                }
+            } else {
+                min_idx = Some(i);
+            }
+        }
+        let Some(idx) = min_idx else {
+            self.current = None;
+            return Ok(None);
+        };
+        let next_head = self.sources[idx].next()?;
+        self.current = std::mem::replace(&mut self.heads[idx], next_head);
+        Ok(self.current.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int_row(values: &[Option<i64>]) -> SortRow {
+        values
+            .iter()
+            .map(|v| v.map(ConstantValue::Integer))
+            .collect()
+    }
+
+    fn collect_ints(sorted: &mut SortedRows, column: usize) -> Vec<Option<i64>> {
+        let mut result = Vec::new();
+        while let Some(row) = sorted.next().unwrap() {
+            result.push(match &row[column] {
+                None => None,
+                Some(ConstantValue::Integer(i)) => Some(*i),
+                v => panic!("unexpected value: {:?}", v),
+            });
+        }
+        result
+    }
+
+    #[test]
+    fn test_sort_in_memory() {
+        let mut sorter = Sorter::new(
+            vec![SortOrder {
+                collation: Collation::Binary,
+                desc: false,
+            }],
+            DEFAULT_SORTER_MEMORY_LIMIT,
+        );
+        for (key, seq) in [(3, 0), (1, 1), (2, 2), (1, 3), (-1, 4)] {
+            sorter.add(int_row(&[Some(key), Some(seq)])).unwrap();
+        }
+        sorter.add(int_row(&[None, Some(5)])).unwrap();
+        assert_eq!(sorter.n_runs(), 0);
+
+        let mut sorted = sorter.finish().unwrap();
+        // NULL first and stable.
+        assert_eq!(
+            collect_ints(&mut sorted, 1),
+            vec![Some(5), Some(4), Some(1), Some(3), Some(2), Some(0)]
+        );
+        assert!(sorted.next().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_sort_multiple_keys() {
+        let mut sorter = Sorter::new(
+            vec![
+                SortOrder {
+                    collation: Collation::NoCase,
+                    desc: false,
+                },
+                SortOrder {
+                    collation: Collation::Binary,
+                    desc: true,
+                },
+            ],
+            DEFAULT_SORTER_MEMORY_LIMIT,
+        );
+        for (text, key, seq) in [("b", 1, 0), ("A", 1, 1), ("a", 2, 2), ("B", 3, 3)] {
+            sorter
+                .add(vec![
+                    Some(ConstantValue::Text(text.as_bytes().to_vec())),
+                    Some(ConstantValue::Integer(key)),
+                    Some(ConstantValue::Integer(seq)),
+                ])
+                .unwrap();
+        }
+        let mut sorted = sorter.finish().unwrap();
+        assert_eq!(
+            collect_ints(&mut sorted, 2),
+            vec![Some(2), Some(1), Some(3), Some(0)]
+        );
+    }
+
+    #[test]
+    fn test_sort_spill() {
+        let orders = vec![SortOrder {
+            collation: Collation::Binary,
+            desc: true,
+        }];
+        let mut sorter = Sorter::new(orders, 1000);
+        let mut expected = Vec::new();
+        for i in 0..1000 {
+            let key = (i * 7919) % 100;
+            sorter
+                .add(vec![
+                    Some(ConstantValue::Integer(key)),
+                    Some(ConstantValue::Integer(i)),
+                    Some(ConstantValue::Text(vec![b'a'; 10])),
+                    None,
+                ])
+                .unwrap();
+            expected.push((key, i));
+        }
+        assert!(sorter.n_runs() > 1);
+        // Descending and stable.
+        expected.sort_by(|(k1, _), (k2, _)| k2.cmp(k1));
+
+        let mut sorted = sorter.finish().unwrap();
+        let mut result = Vec::new();
+        while let Some(row) = sorted.next().unwrap() {
+            let (Some(ConstantValue::Integer(key)), Some(ConstantValue::Integer(i))) =
+                (&row[0], &row[1])
+            else {
+                panic!("unexpected row: {:?}", row);
+            };
+            assert!(matches!(&row[2], Some(ConstantValue::Text(t)) if t == &[b'a'; 10]));
+            assert!(row[3].is_none());
+            result.push((*key, *i));
+        }
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_sort_empty() {
+        let sorter = Sorter::new(Vec::new(), DEFAULT_SORTER_MEMORY_LIMIT);
+        let mut sorted = sorter.finish().unwrap();
+        assert!(sorted.next().unwrap().is_none());
+        assert!(sorted.next().unwrap().is_none());
+    }
+}
@@ -24,6 +24,7 @@ const CHAR_DIGIT: u8 = 0x03;
 const CHAR_DOLLAR: u8 = 0x04;
 const CHAR_QUOTE: u8 = 0x05; // ", ', `
 const CHAR_QUOTE2: u8 = 0x06; // [
+const CHAR_VARIABLE: u8 = 0x07; // ?, :, @
 const CHAR_INVALID: u8 = 0xFF;
 
 static CHAR_LOOKUP_TABLE: [u8; 256] = [
@@ -31,11 +32,11 @@ static CHAR_LOOKUP_TABLE: [u8; 256] = [
     0xFF, b' ', b' ', 0xFF, b' ', b' ', 0xFF, 0xFF, // 0x08 - 0x0F
     0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, // 0x10 - 0x17
     0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, // 0x18 - 0x1F
-    b' ', b'!', 0x05, 0xFF, 0x04, 0xFF, 0xFF, 0x05, // 0x20 - 0x27
-    b'(', b')', b'*', b'+', b',', b'-', b'.', 0xFF, // 0x28 - 0x2F
+    b' ', b'!', 0x05, 0xFF, 0x04, b'%', b'&', 0x05, // 0x20 - 0x27
+    b'(', b')', b'*', b'+', b',', b'-', b'.', b'/', // 0x28 - 0x2F
     0x03, 0x03, 0x03, 0x03, 0x03, 0x03, 0x03, 0x03, // 0x30 - 0x37
-    0x03, 0x03, 0xFF, b';', b'<', b'=', b'>', 0xFF, // 0x38 - 0x3F
-    0xFF, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, // 0x40 - 0x47
+    0x03, 0x03, 0x07, b';', b'<', b'=', b'>', 0x07, // 0x38 - 0x3F
+    0x07, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, // 0x40 - 0x47
     0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, // 0x48 - 0x4F
     0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, // 0x50 - 0x57
     0x00, 0x01, 0x01, 0x06, 0xFF, 0xFF, 0xFF, 0x02, // 0x58 - 0x5F
@@ -64,21 +65,59 @@ static CHAR_LOOKUP_TABLE: [u8; 256] = [
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Token<'a> {
     // Keywords
+    Analyze,
+    And,
     As,
+    Asc,
+    Begin,
+    Between,
+    By,
     Cast,
     Collate,
+    Commit,
     Create,
+    Default,
+    Deferred,
     Delete,
+    Desc,
+    Drop,
+    Escape,
+    Exclusive,
+    Exists,
+    Explain,
     From,
+    Glob,
+    Group,
+    Having,
+    If,
+    Immediate,
+    In,
     Index,
     Insert,
     Into,
+    Is,
+    Isnull,
     Key,
+    Like,
+    Limit,
+    Not,
+    Notnull,
     Null,
+    Offset,
     On,
+    Or,
+    Order,
     Primary,
+    Release,
+    Rollback,
+    Savepoint,
     Select,
+    Set,
     Table,
+    To,
+    Transaction,
+    Unique,
+    Update,
     Values,
     Where,
 
@@ -92,6 +131,8 @@ pub enum Token<'a> {
     Minus,
     Dot,
     Semicolon,
+    Slash,
+    Percent,
     Tilda,
 
     // Operators
@@ -107,7 +148,10 @@ pub enum Token<'a> {
     Lt,
     /// Less than or equal to
     Le,
+    BitAnd,
     BitOr,
+    ShiftLeft,
+    ShiftRight,
     Concat,
 
     // Literals
@@ -117,10 +161,12 @@ pub enum Token<'a> {
     // Only contains 0-9 chars.
     Integer(&'a [u8]),
     Float(&'a [u8]),
+    /// A parameter including the prefix (e.g. "?", "?1", ":name", "@name").
+    Variable(&'a [u8]),
     Illegal,
 }
 
-pub fn get_token(input: &[u8]) -> Option<(usize, Token)> {
+pub fn get_token(input: &[u8]) -> Option<(usize, Token<'_>)> {
     if input.is_empty() {
         return None;
     }
@@ -140,6 +186,8 @@ pub fn get_token(input: &[u8]) -> Option<(usize, Token)> {
                 Some((1, Token::Illegal))
             }
         }
+        b'%' => Some((1, Token::Percent)),
+        b'&' => Some((1, Token::BitAnd)),
         b'(' => Some((1, Token::LeftParen)),
         b')' => Some((1, Token::RightParen)),
         b'*' => Some((1, Token::Asterisk)),
@@ -158,12 +206,14 @@ pub fn get_token(input: &[u8]) -> Option<(usize, Token)> {
                 Some((1, Token::Dot))
             }
         }
+        b'/' => Some((1, Token::Slash)),
         b';' => Some((1, Token::Semicolon)),
         b'<' => {
             if input.len() >= 2 {
                 match input[1] {
                     b'=' => Some((2, Token::Le)),
                     b'>' => Some((2, Token::Ne)),
+                    b'<' => Some((2, Token::ShiftLeft)),
                     _ => Some((1, Token::Lt)),
                 }
             } else {
@@ -178,8 +228,12 @@ pub fn get_token(input: &[u8]) -> Option<(usize, Token)> {
             }
         }
         b'>' => {
-            if input.len() >= 2 && input[1] == b'=' {
-                Some((2, Token::Ge))
+            if input.len() >= 2 {
+                match input[1] {
+                    b'=' => Some((2, Token::Ge)),
+                    b'>' => Some((2, Token::ShiftRight)),
+                    _ => Some((1, Token::Gt)),
+                }
             } else {
                 Some((1, Token::Gt))
             }
@@ -226,30 +280,68 @@ pub fn get_token(input: &[u8]) -> Option<(usize, Token)> {
         CHAR_ALPHABET | CHAR_UNDERSCORE => {
             let len = len_identifier(input);
             let id = &input[..len];
-            const MAX_KEYWORD_LEN: usize = 7;
+            const MAX_KEYWORD_LEN: usize = 11;
             if len <= MAX_KEYWORD_LEN {
                 let mut lower_id = [0; MAX_KEYWORD_LEN];
                 for (i, &byte) in id.iter().take(MAX_KEYWORD_LEN).enumerate() {
                     lower_id[i] = UPPER_TO_LOWER[byte as usize];
                 }
                 match &lower_id {
-                    b"as\0\0\0\0\0" => Some((len, Token::As)),
-                    b"cast\0\0\0" => Some((len, Token::Cast)),
-                    b"collate" => Some((len, Token::Collate)),
-                    b"create\0" => Some((len, Token::Create)),
-                    b"delete\0" => Some((len, Token::Delete)),
-                    b"from\0\0\0" => Some((len, Token::From)),
-                    b"index\0\0" => Some((len, Token::Index)),
-                    b"insert\0" => Some((len, Token::Insert)),
-                    b"into\0\0\0" => Some((len, Token::Into)),
-                    b"key\0\0\0\0" => Some((len, Token::Key)),
-                    b"null\0\0\0" => Some((len, Token::Null)),
-                    b"on\0\0\0\0\0" => Some((len, Token::On)),
-                    b"primary" => Some((len, Token::Primary)),
-                    b"select\0" => Some((len, Token::Select)),
-                    b"table\0\0" => Some((len, Token::Table)),
-                    b"values\0" => Some((len, Token::Values)),
-                    b"where\0\0" => Some((len, Token::Where)),
+                    b"analyze\0\0\0\0" => Some((len, Token::Analyze)),
+                    b"and\0\0\0\0\0\0\0\0" => Some((len, Token::And)),
+                    b"as\0\0\0\0\0\0\0\0\0" => Some((len, Token::As)),
+                    b"asc\0\0\0\0\0\0\0\0" => Some((len, Token::Asc)),
+                    b"begin\0\0\0\0\0\0" => Some((len, Token::Begin)),
+                    b"between\0\0\0\0" => Some((len, Token::Between)),
+                    b"by\0\0\0\0\0\0\0\0\0" => Some((len, Token::By)),
+                    b"cast\0\0\0\0\0\0\0" => Some((len, Token::Cast)),
+                    b"collate\0\0\0\0" => Some((len, Token::Collate)),
+                    b"commit\0\0\0\0\0" => Some((len, Token::Commit)),
+                    b"create\0\0\0\0\0" => Some((len, Token::Create)),
+                    b"default\0\0\0\0" => Some((len, Token::Default)),
+                    b"deferred\0\0\0" => Some((len, Token::Deferred)),
+                    b"delete\0\0\0\0\0" => Some((len, Token::Delete)),
+                    b"desc\0\0\0\0\0\0\0" => Some((len, Token::Desc)),
+                    b"drop\0\0\0\0\0\0\0" => Some((len, Token::Drop)),
+                    b"escape\0\0\0\0\0" => Some((len, Token::Escape)),
+                    b"exclusive\0\0" => Some((len, Token::Exclusive)),
+                    b"exists\0\0\0\0\0" => Some((len, Token::Exists)),
+                    b"explain\0\0\0\0" => Some((len, Token::Explain)),
+                    b"from\0\0\0\0\0\0\0" => Some((len, Token::From)),
+                    b"glob\0\0\0\0\0\0\0" => Some((len, Token::Glob)),
+                    b"group\0\0\0\0\0\0" => Some((len, Token::Group)),
+                    b"having\0\0\0\0\0" => Some((len, Token::Having)),
+                    b"if\0\0\0\0\0\0\0\0\0" => Some((len, Token::If)),
+                    b"immediate\0\0" => Some((len, Token::Immediate)),
+                    b"in\0\0\0\0\0\0\0\0\0" => Some((len, Token::In)),
+                    b"index\0\0\0\0\0\0" => Some((len, Token::Index)),
+                    b"insert\0\0\0\0\0" => Some((len, Token::Insert)),
+                    b"into\0\0\0\0\0\0\0" => Some((len, Token::Into)),
+                    b"is\0\0\0\0\0\0\0\0\0" => Some((len, Token::Is)),
+                    b"isnull\0\0\0\0\0" => Some((len, Token::Isnull)),
+                    b"key\0\0\0\0\0\0\0\0" => Some((len, Token::Key)),
+                    b"like\0\0\0\0\0\0\0" => Some((len, Token::Like)),
+                    b"limit\0\0\0\0\0\0" => Some((len, Token::Limit)),
+                    b"not\0\0\0\0\0\0\0\0" => Some((len, Token::Not)),
+                    b"notnull\0\0\0\0" => Some((len, Token::Notnull)),
+                    b"null\0\0\0\0\0\0\0" => Some((len, Token::Null)),
+                    b"offset\0\0\0\0\0" => Some((len, Token::Offset)),
+                    b"on\0\0\0\0\0\0\0\0\0" => Some((len, Token::On)),
+                    b"or\0\0\0\0\0\0\0\0\0" => Some((len, Token::Or)),
+                    b"order\0\0\0\0\0\0" => Some((len, Token::Order)),
+                    b"primary\0\0\0\0" => Some((len, Token::Primary)),
+                    b"release\0\0\0\0" => Some((len, Token::Release)),
+                    b"rollback\0\0\0" => Some((len, Token::Rollback)),
+                    b"savepoint\0\0" => Some((len, Token::Savepoint)),
+                    b"select\0\0\0\0\0" => Some((len, Token::Select)),
+                    b"set\0\0\0\0\0\0\0\0" => Some((len, Token::Set)),
+                    b"table\0\0\0\0\0\0" => Some((len, Token::Table)),
+                    b"to\0\0\0\0\0\0\0\0\0" => Some((len, Token::To)),
+                    b"transaction" => Some((len, Token::Transaction)),
+                    b"unique\0\0\0\0\0" => Some((len, Token::Unique)),
+                    b"update\0\0\0\0\0" => Some((len, Token::Update)),
+                    b"values\0\0\0\0\0" => Some((len, Token::Values)),
+                    b"where\0\0\0\0\0\0" => Some((len, Token::Where)),
                     _ => Some((len, Token::Identifier(id.into()))),
                 }
             } else {
@@ -305,6 +397,21 @@ pub fn get_token(input: &[u8]) -> Option<(usize, Token)> {
             }
             Some((input.len(), Token::Illegal))
         }
+        CHAR_VARIABLE => {
+            if input[0] == b'?' {
+                let len = 1 + input
+                    .iter()
+                    .skip(1)
+                    .take_while(|byte| byte.is_ascii_digit())
+                    .count();
+                Some((len, Token::Variable(&input[..len])))
+            } else if input.len() >= 2 && CHAR_LOOKUP_TABLE[input[1] as usize] <= CHAR_DOLLAR {
+                let len = 1 + len_identifier(&input[1..]);
+                Some((len, Token::Variable(&input[..len])))
+            } else {
+                Some((1, Token::Illegal))
+            }
+        }
         CHAR_INVALID => Some((1, Token::Illegal)),
         c => {
             unreachable!("unexpected char code: ({}), char: {}", c, input[0]);
@@ -380,6 +487,8 @@ mod tests {
             ('-', Token::Minus),
             ('.', Token::Dot),
             (';', Token::Semicolon),
+            ('/', Token::Slash),
+            ('%', Token::Percent),
             ('~', Token::Tilda),
         ] {
             let input = format!("{c}");
@@ -680,21 +789,59 @@ mod tests {
     #[test]
     fn test_keywords() {
         for (keyword, token) in [
+            ("analyze", Token::Analyze),
+            ("and", Token::And),
             ("as", Token::As),
+            ("asc", Token::Asc),
+            ("begin", Token::Begin),
+            ("between", Token::Between),
+            ("by", Token::By),
             ("cast", Token::Cast),
             ("collate", Token::Collate),
+            ("commit", Token::Commit),
             ("create", Token::Create),
+            ("default", Token::Default),
+            ("deferred", Token::Deferred),
             ("delete", Token::Delete),
+            ("desc", Token::Desc),
+            ("drop", Token::Drop),
+            ("escape", Token::Escape),
+            ("exclusive", Token::Exclusive),
+            ("exists", Token::Exists),
+            ("explain", Token::Explain),
             ("from", Token::From),
+            ("glob", Token::Glob),
+            ("group", Token::Group),
+            ("having", Token::Having),
+            ("if", Token::If),
+            ("immediate", Token::Immediate),
+            ("in", Token::In),
             ("index", Token::Index),
             ("insert", Token::Insert),
             ("into", Token::Into),
+            ("is", Token::Is),
+            ("isnull", Token::Isnull),
             ("key", Token::Key),
+            ("like", Token::Like),
+            ("limit", Token::Limit),
+            ("not", Token::Not),
+            ("notnull", Token::Notnull),
             ("null", Token::Null),
+            ("offset", Token::Offset),
             ("on", Token::On),
+            ("or", Token::Or),
+            ("order", Token::Order),
             ("primary", Token::Primary),
+            ("release", Token::Release),
+            ("rollback", Token::Rollback),
+            ("savepoint", Token::Savepoint),
             ("select", Token::Select),
+            ("set", Token::Set),
             ("table", Token::Table),
+            ("to", Token::To),
+            ("transaction", Token::Transaction),
+            ("unique", Token::Unique),
+            ("update", Token::Update),
             ("values", Token::Values),
             ("where", Token::Where),
         ] {
@@ -791,6 +938,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_variable() {
+        for (input, len) in [
+            ("?", 1),
+            ("? ", 1),
+            ("?1", 2),
+            ("?123,", 4),
+            ("?a", 1),
+            (":a", 2),
+            (":abc_1$ ", 7),
+            (":1", 2),
+            ("@name)", 5),
+            ("@_", 2),
+        ] {
+            assert_eq!(
+                get_token(input.as_bytes()),
+                Some((len, Token::Variable(&input.as_bytes()[..len]))),
+                "{}",
+                input
+            );
+        }
+        assert_eq!(get_token(b":"), Some((1, Token::Illegal)));
+        assert_eq!(get_token(b": a"), Some((1, Token::Illegal)));
+        assert_eq!(get_token(b"@"), Some((1, Token::Illegal)));
+        assert_eq!(get_token(b"@?"), Some((1, Token::Illegal)));
+    }
+
     #[test]
     fn test_binary_operators() {
         for (s, token) in [
@@ -803,6 +977,9 @@ mod tests {
             ("==", Token::Eq),
             (">", Token::Gt),
             (">=", Token::Ge),
+            ("&", Token::BitAnd),
+            ("<<", Token::ShiftLeft),
+            (">>", Token::ShiftRight),
             ("|", Token::BitOr),
             ("||", Token::Concat),
         ] {
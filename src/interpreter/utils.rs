@@ -487,6 +487,249 @@ pub fn upper_to_lower(buf: &mut [u8]) {
     }
 }
 
+/// Read a UTF-8 character from `buf` at `pos` and advance `pos`.
+///
+/// This returns 0 at the end of the buffer. Invalid sequences are decoded
+/// loosely to U+FFFD or to the raw byte as sqlite3Utf8Read() does.
+pub fn utf8_read(buf: &[u8], pos: &mut usize) -> u32 {
+    let Some(&b) = buf.get(*pos) else {
+        return 0;
+    };
+    *pos += 1;
+    if b < 0xc0 {
+        return b as u32;
+    }
+    let mut c = match b {
+        0xc0..=0xdf => b - 0xc0,
+        0xe0..=0xef => b - 0xe0,
+        0xf0..=0xf7 => b - 0xf0,
+        0xf8..=0xfb => b - 0xf8,
+        0xfc..=0xfd => b - 0xfc,
+        _ => 0,
+    } as u32;
+    while let Some(&b) = buf.get(*pos) {
+        if b & 0xc0 != 0x80 {
+            break;
+        }
+        c = (c << 6) + (0x3f & b as u32);
+        *pos += 1;
+    }
+    if c < 0x80 || (c & 0xFFFFF800) == 0xD800 || (c & 0xFFFFFFFE) == 0xFFFE {
+        0xFFFD
+    } else {
+        c
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum PatternMatch {
+    Match,
+    NoMatch,
+    /// No match even if the input is extended. The caller can give up.
+    NoWildcardMatch,
+}
+
+struct PatternInfo {
+    /// "%" or "*". 0 if disabled by the escape character.
+    match_all: u32,
+    /// "_" or "?". 0 if disabled by the escape character.
+    match_one: u32,
+    /// Whether "[...]" is supported.
+    match_set: bool,
+    no_case: bool,
+}
+
+#[inline]
+fn byte_at(buf: &[u8], pos: usize) -> u8 {
+    buf.get(pos).copied().unwrap_or(0)
+}
+
+#[inline]
+fn ascii_to_lower(c: u32) -> u32 {
+    if c < 0x80 {
+        UPPER_TO_LOWER[c as usize] as u32
+    } else {
+        c
+    }
+}
+
+/// Compare the pattern with the input string.
+///
+/// `match_other` is the escape character for LIKE or "[" for GLOB.
+///
+/// This is ported from patternCompare() in func.c of SQLite.
+fn pattern_compare(
+    pattern: &[u8],
+    mut p: usize,
+    string: &[u8],
+    mut s: usize,
+    info: &PatternInfo,
+    match_other: u32,
+) -> PatternMatch {
+    // One past the last escaped pattern character.
+    let mut escaped = None;
+    loop {
+        let mut c = utf8_read(pattern, &mut p);
+        if c == 0 {
+            break;
+        }
+        if c == info.match_all {
+            // Skip over multiple "%" in the pattern. "_" following the "%"
+            // consumes a character of the input each.
+            loop {
+                c = utf8_read(pattern, &mut p);
+                if c == info.match_all {
+                    continue;
+                } else if c == info.match_one && info.match_one != 0 {
+                    if utf8_read(string, &mut s) == 0 {
+                        return PatternMatch::NoWildcardMatch;
+                    }
+                } else {
+                    break;
+                }
+            }
+            if c == 0 {
+                // "%" at the end of the pattern matches.
+                return PatternMatch::Match;
+            } else if c == match_other {
+                if !info.match_set {
+                    c = utf8_read(pattern, &mut p);
+                    if c == 0 {
+                        return PatternMatch::NoWildcardMatch;
+                    }
+                } else {
+                    // "[...]" immediately follows the "*". "[" is a single
+                    // byte character.
+                    while byte_at(string, s) != 0 {
+                        let result = pattern_compare(pattern, p - 1, string, s, info, match_other);
+                        if result != PatternMatch::NoMatch {
+                            return result;
+                        }
+                        utf8_read(string, &mut s);
+                    }
+                    return PatternMatch::NoWildcardMatch;
+                }
+            }
+
+            // Search the input for the character following the "%" and
+            // continue the match recursively from there.
+            loop {
+                let c2 = utf8_read(string, &mut s);
+                if c2 == 0 {
+                    break;
+                }
+                let matched = if info.no_case {
+                    c2 == c || (c < 0x80 && c2 < 0x80 && ascii_to_lower(c2) == ascii_to_lower(c))
+                } else {
+                    c2 == c
+                };
+                if !matched {
+                    continue;
+                }
+                let result = pattern_compare(pattern, p, string, s, info, match_other);
+                if result != PatternMatch::NoMatch {
+                    return result;
+                }
+            }
+            return PatternMatch::NoWildcardMatch;
+        }
+        if c == match_other {
+            if !info.match_set {
+                c = utf8_read(pattern, &mut p);
+                if c == 0 {
+                    return PatternMatch::NoMatch;
+                }
+                escaped = Some(p);
+            } else {
+                let mut prior_c = 0;
+                let mut seen = false;
+                let mut invert = false;
+                c = utf8_read(string, &mut s);
+                if c == 0 {
+                    return PatternMatch::NoMatch;
+                }
+                let mut c2 = utf8_read(pattern, &mut p);
+                if c2 == '^' as u32 {
+                    invert = true;
+                    c2 = utf8_read(pattern, &mut p);
+                }
+                if c2 == ']' as u32 {
+                    if c == ']' as u32 {
+                        seen = true;
+                    }
+                    c2 = utf8_read(pattern, &mut p);
+                }
+                while c2 != 0 && c2 != ']' as u32 {
+                    let next = byte_at(pattern, p);
+                    if c2 == '-' as u32 && next != b']' && next != 0 && prior_c > 0 {
+                        c2 = utf8_read(pattern, &mut p);
+                        if c >= prior_c && c <= c2 {
+                            seen = true;
+                        }
+                        prior_c = 0;
+                    } else {
+                        if c == c2 {
+                            seen = true;
+                        }
+                        prior_c = c2;
+                    }
+                    c2 = utf8_read(pattern, &mut p);
+                }
+                if c2 == 0 || seen == invert {
+                    return PatternMatch::NoMatch;
+                }
+                continue;
+            }
+        }
+        let c2 = utf8_read(string, &mut s);
+        if c == c2 {
+            continue;
+        }
+        if info.no_case && c < 0x80 && c2 < 0x80 && ascii_to_lower(c) == ascii_to_lower(c2) {
+            continue;
+        }
+        if c == info.match_one && escaped != Some(p) && c2 != 0 {
+            continue;
+        }
+        return PatternMatch::NoMatch;
+    }
+    if byte_at(string, s) == 0 {
+        PatternMatch::Match
+    } else {
+        PatternMatch::NoMatch
+    }
+}
+
+/// Whether the text matches the LIKE pattern.
+///
+/// "%" matches any sequence and "_" matches any single character. Only ASCII
+/// characters are compared case insensitively as SQLite does by default.
+/// `escape` is the character which escapes the following character.
+pub fn like(pattern: &[u8], text: &[u8], escape: Option<u32>) -> bool {
+    let escape = escape.unwrap_or(0);
+    let info = PatternInfo {
+        match_all: if escape == '%' as u32 { 0 } else { '%' as u32 },
+        match_one: if escape == '_' as u32 { 0 } else { '_' as u32 },
+        match_set: false,
+        no_case: true,
+    };
+    pattern_compare(pattern, 0, text, 0, &info, escape) == PatternMatch::Match
+}
+
+/// Whether the text matches the GLOB pattern.
+///
+/// "*" matches any sequence, "?" matches any single character and "[...]"
+/// matches a character in the set. The comparison is case sensitive.
+pub fn glob(pattern: &[u8], text: &[u8]) -> bool {
+    let info = PatternInfo {
+        match_all: '*' as u32,
+        match_one: '?' as u32,
+        match_set: true,
+        no_case: false,
+    };
+    pattern_compare(pattern, 0, text, 0, &info, '[' as u32) == PatternMatch::Match
+}
+
 /// A wrapper for bytes to compare each other case insensitively with zero
 /// allocation.
 #[derive(Eq)]
@@ -671,6 +914,34 @@ impl<'a> Iterator for DequotedIter<'a> {
     }
 }
 
+/// Convert the unix time to the date and time in UTC.
+///
+/// Returns (year, month, day, hour, minute, second). The date comes from
+/// civil_from_days() in http://howardhinnant.github.io/date_algorithms.html.
+pub fn unix_time_to_utc(secs: i64) -> (i64, u32, u32, u32, u32, u32) {
+    let days = secs.div_euclid(86400);
+    let secs_of_day = secs.rem_euclid(86400) as u32;
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // The month starting from March.
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+    )
+}
+
 /// Convert 1 byte ascii hexadecimal character to integer.
 ///
 /// The input must be a valid hexadecimal character, i.e. 0-9, a-f, A-F.
@@ -689,7 +960,7 @@ pub struct HexedBytes<'a>(&'a [u8]);
 
 impl HexedBytes<'_> {
     pub fn decode(&self) -> Vec<u8> {
-        assert!(self.0.len() % 2 == 0);
+        assert!(self.0.len().is_multiple_of(2));
         let mut result = Vec::with_capacity(self.0.len() / 2);
         let mut iter = self.0.iter();
         // TODO: Optimization to avoid bounds check.
@@ -704,7 +975,7 @@ impl HexedBytes<'_> {
 
 impl<'a> From<&'a [u8]> for HexedBytes<'a> {
     fn from(bytes: &'a [u8]) -> Self {
-        assert!(bytes.len() % 2 == 0);
+        assert!(bytes.len().is_multiple_of(2));
         Self(bytes)
     }
 }
@@ -1048,6 +1319,77 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_utf8_read() {
+        let buf = "aé日😀".as_bytes();
+        let mut pos = 0;
+        assert_eq!(utf8_read(buf, &mut pos), 'a' as u32);
+        assert_eq!(utf8_read(buf, &mut pos), 'é' as u32);
+        assert_eq!(utf8_read(buf, &mut pos), '日' as u32);
+        assert_eq!(utf8_read(buf, &mut pos), '😀' as u32);
+        assert_eq!(pos, buf.len());
+        assert_eq!(utf8_read(buf, &mut pos), 0);
+        assert_eq!(pos, buf.len());
+
+        // Overlong encoding.
+        let mut pos = 0;
+        assert_eq!(utf8_read(&[0xc1, 0x81], &mut pos), 0xFFFD);
+        assert_eq!(pos, 2);
+    }
+
+    #[test]
+    fn test_like() {
+        assert!(like(b"", b"", None));
+        assert!(like(b"abc", b"ABC", None));
+        assert!(like(b"cpu%", b"cpu_user", None));
+        assert!(like(b"%user", b"cpu_user", None));
+        assert!(like(b"%", b"", None));
+        assert!(like(b"c_u%", b"CPU", None));
+        assert!(like(b"%_%", b"a", None));
+        assert!(!like(b"%_%", b"", None));
+        assert!(!like(b"cpu", b"cpu0", None));
+        assert!(!like(b"cpu_", b"cpu", None));
+        // Only ASCII characters are case insensitive.
+        assert!(like("é%".as_bytes(), "éa".as_bytes(), None));
+        assert!(!like("é".as_bytes(), "É".as_bytes(), None));
+        assert!(like("_".as_bytes(), "日".as_bytes(), None));
+        assert!(like("%日".as_bytes(), "ab日".as_bytes(), None));
+
+        assert!(like(b"10!%", b"10%", Some('!' as u32)));
+        assert!(!like(b"10!%", b"100", Some('!' as u32)));
+        assert!(like(b"a!_c", b"a_c", Some('!' as u32)));
+        assert!(!like(b"a!_c", b"abc", Some('!' as u32)));
+        assert!(like(b"a!!", b"a!", Some('!' as u32)));
+        assert!(like(b"%!%%", b"50% off", Some('!' as u32)));
+        // The escape character disables the wildcard.
+        assert!(like(b"a%%", b"a%", Some('%' as u32)));
+        assert!(!like(b"a%%", b"ab", Some('%' as u32)));
+        // The escape at the end of the pattern does not match.
+        assert!(!like(b"a!", b"a!", Some('!' as u32)));
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(glob(b"abc", b"abc"));
+        assert!(!glob(b"abc", b"ABC"));
+        assert!(glob(b"a*", b"abc"));
+        assert!(glob(b"*c", b"abc"));
+        assert!(glob(b"a?c", b"abc"));
+        assert!(!glob(b"a?c", b"ac"));
+        assert!(glob(b"[abc]x", b"bx"));
+        assert!(!glob(b"[abc]x", b"dx"));
+        assert!(glob(b"[^abc]x", b"dx"));
+        assert!(!glob(b"[^abc]x", b"ax"));
+        assert!(glob(b"[a-z]*", b"cpu"));
+        assert!(!glob(b"[a-z]*", b"CPU"));
+        assert!(glob(b"[]]", b"]"));
+        assert!(glob(b"[a-]", b"-"));
+        assert!(glob(b"*[0-9]", b"cpu0"));
+        assert!(!glob(b"*[0-9]", b"cpu"));
+        assert!(!glob(b"[abc", b"a"));
+        assert!(glob("*日?".as_bytes(), "ab日本".as_bytes()));
+    }
+
     #[test]
     fn test_case_insensitive_bytes() {
         assert!(
@@ -1340,6 +1682,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_unix_time_to_utc() {
+        assert_eq!(unix_time_to_utc(0), (1970, 1, 1, 0, 0, 0));
+        assert_eq!(unix_time_to_utc(-1), (1969, 12, 31, 23, 59, 59));
+        assert_eq!(unix_time_to_utc(951782400 + 3661), (2000, 2, 29, 1, 1, 1));
+        assert_eq!(unix_time_to_utc(951868800), (2000, 3, 1, 0, 0, 0));
+        assert_eq!(unix_time_to_utc(1700000000), (2023, 11, 14, 22, 13, 20));
+        assert_eq!(unix_time_to_utc(4102444799), (2099, 12, 31, 23, 59, 59));
+    }
+
     #[test]
     fn test_hexed_bytes() {
         let mut test_cases = Vec::new();
@@ -200,6 +200,18 @@ impl<'a> Value<'a> {
         }
     }
 
+    /// Convert the value to a boolean. Text and blob values are converted to
+    /// real numbers.
+    ///
+    /// This comes from sqlite3VdbeBooleanValue().
+    pub fn as_bool(&self) -> bool {
+        match self {
+            Value::Integer(i) => *i != 0,
+            Value::Real(d) => *d != 0.0,
+            Value::Text(buf) | Value::Blob(buf) => parse_float(buf).2 != 0.0,
+        }
+    }
+
     /// Convert the value to text and return the [Buffer].
     pub fn force_text_buffer(self) -> Buffer<'a> {
         match self {
@@ -370,7 +382,7 @@ fn cmp_int_real(i: i64, r: f64) -> Ordering {
     Ordering::Equal
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ConstantValue {
     Integer(i64),
     Real(f64),
@@ -388,7 +400,7 @@ impl ConstantValue {
         }
     }
 
-    pub fn as_value(&self) -> Value {
+    pub fn as_value(&self) -> Value<'_> {
         match self {
             Self::Integer(i) => Value::Integer(*i),
             Self::Real(f) => Value::Real(*f),
@@ -0,0 +1,533 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The write-ahead log compatible with SQLite.
+//!
+//! https://www.sqlite.org/fileformat2.html#the_write_ahead_log
+//!
+//! The wal-index (-shm file) is not maintained. SQLite rebuilds it from the WAL
+//! file when it opens the database without other connections.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::interpreter::journal::check_crash_point;
+use crate::interpreter::pager::PageId;
+
+/// The magic number of the WAL header. The least significant bit is 1 if the
+/// checksums use big-endian words.
+const WAL_MAGIC: u32 = 0x377f0682;
+const WAL_VERSION: u32 = 3007000;
+const WAL_HEADER_SIZE: usize = 32;
+const WAL_FRAME_HEADER_SIZE: usize = 24;
+
+/// The path of the WAL file for the database file.
+pub fn wal_path(db_path: &Path) -> PathBuf {
+    let mut path = OsString::from(db_path.as_os_str());
+    path.push("-wal");
+    PathBuf::from(path)
+}
+
+/// The cumulative checksum of the WAL.
+///
+/// `data` is interpreted as an array of 32-bit words.
+fn checksum(big_endian: bool, data: &[u8], mut s: [u32; 2]) -> [u32; 2] {
+    assert_eq!(data.len() % 8, 0);
+    for chunk in data.chunks_exact(8) {
+        let (x0, x1) = if big_endian {
+            (
+                u32::from_be_bytes(chunk[..4].try_into().unwrap()),
+                u32::from_be_bytes(chunk[4..].try_into().unwrap()),
+            )
+        } else {
+            (
+                u32::from_le_bytes(chunk[..4].try_into().unwrap()),
+                u32::from_le_bytes(chunk[4..].try_into().unwrap()),
+            )
+        };
+        s[0] = s[0].wrapping_add(x0).wrapping_add(s[1]);
+        s[1] = s[1].wrapping_add(x1).wrapping_add(s[0]);
+    }
+    s
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct WalHeader {
+    big_endian_checksum: bool,
+    checkpoint_seq: u32,
+    salt: [u8; 8],
+    checksum: [u32; 2],
+}
+
+impl WalHeader {
+    fn parse(buf: &[u8; WAL_HEADER_SIZE], pagesize: u32) -> Option<Self> {
+        let read_u32 = |i: usize| u32::from_be_bytes(buf[i..i + 4].try_into().unwrap());
+        let magic = read_u32(0);
+        if magic & !1 != WAL_MAGIC || read_u32(4) != WAL_VERSION || read_u32(8) != pagesize {
+            return None;
+        }
+        let big_endian_checksum = magic & 1 == 1;
+        let cksum = checksum(big_endian_checksum, &buf[..24], [0, 0]);
+        if cksum != [read_u32(24), read_u32(28)] {
+            return None;
+        }
+        Some(Self {
+            big_endian_checksum,
+            checkpoint_seq: read_u32(12),
+            salt: buf[16..24].try_into().unwrap(),
+            checksum: cksum,
+        })
+    }
+
+    fn new(checkpoint_seq: u32, salt: [u8; 8], pagesize: u32) -> (Self, [u8; WAL_HEADER_SIZE]) {
+        let mut buf = [0_u8; WAL_HEADER_SIZE];
+        buf[..4].copy_from_slice(&(WAL_MAGIC | 1).to_be_bytes());
+        buf[4..8].copy_from_slice(&WAL_VERSION.to_be_bytes());
+        buf[8..12].copy_from_slice(&pagesize.to_be_bytes());
+        buf[12..16].copy_from_slice(&checkpoint_seq.to_be_bytes());
+        buf[16..24].copy_from_slice(&salt);
+        let cksum = checksum(true, &buf[..24], [0, 0]);
+        buf[24..28].copy_from_slice(&cksum[0].to_be_bytes());
+        buf[28..32].copy_from_slice(&cksum[1].to_be_bytes());
+        (
+            Self {
+                big_endian_checksum: true,
+                checkpoint_seq,
+                salt,
+                checksum: cksum,
+            },
+            buf,
+        )
+    }
+}
+
+/// The write-ahead log.
+///
+/// Committed transactions are appended to the WAL file as frames. Only frames
+/// up to the last commit frame are visible. The index keeps the latest
+/// committed frame of each page.
+pub struct Wal {
+    file: File,
+    pagesize: u32,
+    /// The header of the WAL file. If the WAL file is truncated by checkpoint,
+    /// this is the previous header to derive the next salt from.
+    header: Option<WalHeader>,
+    /// The cumulative checksum of the last committed frame.
+    checksum: [u32; 2],
+    /// The number of committed frames.
+    n_frames: u32,
+    /// The latest committed frame number (1-origin) of each page.
+    index: HashMap<PageId, u32>,
+    /// The size of the database in pages after the last commit.
+    n_pages: Option<u32>,
+}
+
+impl Wal {
+    /// Open or create the WAL file and load the committed frames.
+    pub fn open(path: &Path, pagesize: u32) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        Self::load(file, pagesize)
+    }
+
+    /// Open the existing WAL file for reading only.
+    pub fn open_read_only(path: &Path, pagesize: u32) -> io::Result<Self> {
+        Self::load(File::open(path)?, pagesize)
+    }
+
+    fn load(file: File, pagesize: u32) -> io::Result<Self> {
+        let mut wal = Self {
+            file,
+            pagesize,
+            header: None,
+            checksum: [0, 0],
+            n_frames: 0,
+            index: HashMap::new(),
+            n_pages: None,
+        };
+        wal.reload()?;
+        Ok(wal)
+    }
+
+    /// The latest committed frame of the page.
+    pub fn find_frame(&self, page_id: PageId) -> Option<u32> {
+        self.index.get(&page_id).copied()
+    }
+
+    /// The size of the database in pages after the last commit in the WAL.
+    pub fn n_pages(&self) -> Option<u32> {
+        self.n_pages
+    }
+
+    /// The number of committed frames.
+    pub fn n_frames(&self) -> u32 {
+        self.n_frames
+    }
+
+    pub fn read_frame(&self, frame: u32, buf: &mut [u8]) -> io::Result<()> {
+        self.file
+            .read_exact_at(buf, self.frame_offset(frame) + WAL_FRAME_HEADER_SIZE as u64)
+    }
+
+    /// Load the frames committed by other connections.
+    ///
+    /// Returns whether any pages are changed.
+    pub fn refresh(&mut self) -> io::Result<bool> {
+        let header = self.read_header()?;
+        if header.is_none() && self.n_frames == 0 {
+            Ok(false)
+        } else if header.is_some()
+            && header == self.header
+            && self.file.metadata()?.len() >= self.frame_offset(self.n_frames + 1)
+        {
+            let n_frames = self.n_frames;
+            self.load_frames()?;
+            Ok(self.n_frames != n_frames)
+        } else {
+            self.reload()?;
+            Ok(true)
+        }
+    }
+
+    /// Append the pages as a transaction and sync the WAL file.
+    ///
+    /// `n_pages` is the size of the database in pages after the transaction.
+    pub fn append(&mut self, pages: &[(PageId, &[u8])], n_pages: u32) -> io::Result<()> {
+        if pages.is_empty() {
+            return Ok(());
+        }
+        if self.n_frames == 0 {
+            let salt1 = self
+                .header
+                .map(|header| u32::from_be_bytes(header.salt[..4].try_into().unwrap()))
+                .map_or_else(rand::random, |salt1| salt1.wrapping_add(1));
+            let mut salt = [0_u8; 8];
+            salt[..4].copy_from_slice(&salt1.to_be_bytes());
+            salt[4..].copy_from_slice(&rand::random::<u32>().to_be_bytes());
+            let checkpoint_seq = self
+                .header
+                .map_or(0, |header| header.checkpoint_seq.wrapping_add(1));
+            let (header, buf) = WalHeader::new(checkpoint_seq, salt, self.pagesize);
+            check_crash_point()?;
+            self.file.write_all_at(&buf, 0)?;
+            self.header = Some(header);
+            self.checksum = header.checksum;
+        }
+        let header = self.header.unwrap();
+
+        let mut cksum = self.checksum;
+        let mut frame = vec![0_u8; WAL_FRAME_HEADER_SIZE + self.pagesize as usize];
+        for (i, (page_id, data)) in pages.iter().enumerate() {
+            let is_commit = i == pages.len() - 1;
+            frame[..4].copy_from_slice(&page_id.get().to_be_bytes());
+            frame[4..8].copy_from_slice(&(if is_commit { n_pages } else { 0 }).to_be_bytes());
+            frame[8..16].copy_from_slice(&header.salt);
+            cksum = checksum(header.big_endian_checksum, &frame[..8], cksum);
+            cksum = checksum(header.big_endian_checksum, data, cksum);
+            frame[16..20].copy_from_slice(&cksum[0].to_be_bytes());
+            frame[20..24].copy_from_slice(&cksum[1].to_be_bytes());
+            frame[WAL_FRAME_HEADER_SIZE..].copy_from_slice(data);
+            check_crash_point()?;
+            self.file
+                .write_all_at(&frame, self.frame_offset(self.n_frames + 1 + i as u32))?;
+        }
+        check_crash_point()?;
+        self.file.sync_data()?;
+
+        for (i, (page_id, _)) in pages.iter().enumerate() {
+            self.index.insert(*page_id, self.n_frames + 1 + i as u32);
+        }
+        self.n_frames += pages.len() as u32;
+        self.checksum = cksum;
+        self.n_pages = Some(n_pages);
+        Ok(())
+    }
+
+    /// Copy the committed pages back to the database file and truncate the
+    /// frames in the WAL file.
+    ///
+    /// The WAL header is kept so that other connections can detect the
+    /// checkpoint. The next transaction restarts the WAL with a new salt.
+    ///
+    /// If this fails in the middle, the WAL is still valid and the next
+    /// checkpoint writes the pages again.
+    pub fn checkpoint(&mut self, db: &File) -> io::Result<()> {
+        if self.n_frames == 0 {
+            return Ok(());
+        }
+        let mut pages = self.index.iter().collect::<Vec<_>>();
+        pages.sort();
+        let mut buf = vec![0_u8; self.pagesize as usize];
+        for (page_id, frame) in pages {
+            self.read_frame(*frame, &mut buf)?;
+            check_crash_point()?;
+            db.write_all_at(&buf, (page_id.get() - 1) as u64 * self.pagesize as u64)?;
+        }
+        if let Some(n_pages) = self.n_pages {
+            check_crash_point()?;
+            db.set_len(n_pages as u64 * self.pagesize as u64)?;
+        }
+        check_crash_point()?;
+        db.sync_data()?;
+
+        check_crash_point()?;
+        self.file.set_len(WAL_HEADER_SIZE as u64)?;
+        self.n_frames = 0;
+        self.index.clear();
+        self.n_pages = None;
+        Ok(())
+    }
+
+    fn frame_offset(&self, frame: u32) -> u64 {
+        WAL_HEADER_SIZE as u64
+            + (frame - 1) as u64 * (WAL_FRAME_HEADER_SIZE as u64 + self.pagesize as u64)
+    }
+
+    fn read_header(&self) -> io::Result<Option<WalHeader>> {
+        if self.file.metadata()?.len() < WAL_HEADER_SIZE as u64 {
+            return Ok(None);
+        }
+        let mut buf = [0_u8; WAL_HEADER_SIZE];
+        self.file.read_exact_at(&mut buf, 0)?;
+        Ok(WalHeader::parse(&buf, self.pagesize))
+    }
+
+    /// Load all committed frames from the beginning.
+    fn reload(&mut self) -> io::Result<()> {
+        self.n_frames = 0;
+        self.index.clear();
+        self.n_pages = None;
+        let header = self.read_header()?;
+        if let Some(header) = header {
+            self.header = Some(header);
+            self.checksum = header.checksum;
+            self.load_frames()?;
+        }
+        Ok(())
+    }
+
+    /// Load the committed frames after the known frames.
+    ///
+    /// Loading stops at the first invalid frame. Frames after the last commit
+    /// frame are ignored.
+    fn load_frames(&mut self) -> io::Result<()> {
+        let Some(header) = self.header else {
+            return Ok(());
+        };
+        let file_size = self.file.metadata()?.len();
+        let frame_size = WAL_FRAME_HEADER_SIZE + self.pagesize as usize;
+        let mut frame = vec![0_u8; frame_size];
+        let mut cksum = self.checksum;
+        let mut pending = Vec::new();
+        let mut n_frames = self.n_frames;
+        while self.frame_offset(n_frames + 1) + frame_size as u64 <= file_size {
+            n_frames += 1;
+            self.file
+                .read_exact_at(&mut frame, self.frame_offset(n_frames))?;
+            let read_u32 = |i: usize| u32::from_be_bytes(frame[i..i + 4].try_into().unwrap());
+            let Some(page_id) = PageId::new(read_u32(0)) else {
+                break;
+            };
+            if frame[8..16] != header.salt {
+                break;
+            }
+            cksum = checksum(header.big_endian_checksum, &frame[..8], cksum);
+            cksum = checksum(
+                header.big_endian_checksum,
+                &frame[WAL_FRAME_HEADER_SIZE..],
+                cksum,
+            );
+            if cksum != [read_u32(16), read_u32(20)] {
+                break;
+            }
+            pending.push((page_id, n_frames));
+            let n_pages = read_u32(4);
+            if n_pages != 0 {
+                // Commit frame.
+                self.index.extend(pending.drain(..));
+                self.n_frames = n_frames;
+                self.checksum = cksum;
+                self.n_pages = Some(n_pages);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page_id(id: u32) -> PageId {
+        PageId::new(id).unwrap()
+    }
+
+    #[test]
+    fn test_wal_path() {
+        assert_eq!(
+            wal_path(Path::new("/tmp/test.db")),
+            PathBuf::from("/tmp/test.db-wal")
+        );
+    }
+
+    #[test]
+    fn test_checksum() {
+        let data = [0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 4];
+        // s0 = 1, s1 = 2 + 1 = 3, s0 = 1 + 3 + 3 = 7, s1 = 3 + 4 + 7 = 14
+        assert_eq!(checksum(true, &data, [0, 0]), [7, 14]);
+        assert_eq!(checksum(true, &data[8..], [1, 3]), [7, 14]);
+        // Little-endian words.
+        let data = [1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 4, 0, 0, 0];
+        assert_eq!(checksum(false, &data, [0, 0]), [7, 14]);
+    }
+
+    #[test]
+    fn test_append_and_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db-wal");
+        let mut wal = Wal::open(&path, 512).unwrap();
+        assert_eq!(wal.n_frames(), 0);
+        assert_eq!(wal.n_pages(), None);
+
+        wal.append(&[(page_id(1), &[1; 512]), (page_id(3), &[3; 512])], 3)
+            .unwrap();
+        wal.append(&[(page_id(1), &[4; 512])], 4).unwrap();
+        assert_eq!(wal.n_frames(), 3);
+        assert_eq!(wal.n_pages(), Some(4));
+        assert_eq!(wal.find_frame(page_id(1)), Some(3));
+        assert_eq!(wal.find_frame(page_id(2)), None);
+        assert_eq!(wal.find_frame(page_id(3)), Some(2));
+
+        let mut buf = [0; 512];
+        wal.read_frame(3, &mut buf).unwrap();
+        assert_eq!(buf, [4; 512]);
+
+        let wal2 = Wal::open(&path, 512).unwrap();
+        assert_eq!(wal2.n_frames(), 3);
+        assert_eq!(wal2.n_pages(), Some(4));
+        assert_eq!(wal2.find_frame(page_id(1)), Some(3));
+        assert_eq!(wal2.find_frame(page_id(3)), Some(2));
+
+        // The pagesize mismatch invalidates the WAL.
+        let wal2 = Wal::open(&path, 1024).unwrap();
+        assert_eq!(wal2.n_frames(), 0);
+    }
+
+    #[test]
+    fn test_uncommitted_frames() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db-wal");
+        let mut wal = Wal::open(&path, 512).unwrap();
+        wal.append(&[(page_id(1), &[1; 512])], 1).unwrap();
+        wal.append(&[(page_id(1), &[2; 512]), (page_id(2), &[2; 512])], 2)
+            .unwrap();
+
+        // Corrupt the commit frame.
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.write_all_at(&[0xff], wal.frame_offset(3) + 100)
+            .unwrap();
+        let wal2 = Wal::open(&path, 512).unwrap();
+        assert_eq!(wal2.n_frames(), 1);
+        assert_eq!(wal2.n_pages(), Some(1));
+        assert_eq!(wal2.find_frame(page_id(1)), Some(1));
+        assert_eq!(wal2.find_frame(page_id(2)), None);
+
+        // Clear the commit flag of the last frame.
+        let mut wal = Wal::open(&path, 512).unwrap();
+        wal.append(&[(page_id(1), &[2; 512]), (page_id(2), &[2; 512])], 2)
+            .unwrap();
+        file.write_all_at(&[0; 4], wal.frame_offset(3) + 4).unwrap();
+        let wal2 = Wal::open(&path, 512).unwrap();
+        assert_eq!(wal2.n_frames(), 1);
+    }
+
+    #[test]
+    fn test_refresh() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db-wal");
+        let mut wal = Wal::open(&path, 512).unwrap();
+        let mut wal2 = Wal::open(&path, 512).unwrap();
+        assert!(!wal2.refresh().unwrap());
+
+        wal.append(&[(page_id(1), &[1; 512])], 1).unwrap();
+        assert!(wal2.refresh().unwrap());
+        assert_eq!(wal2.find_frame(page_id(1)), Some(1));
+        assert!(!wal2.refresh().unwrap());
+
+        wal.append(&[(page_id(2), &[2; 512])], 2).unwrap();
+        assert!(wal2.refresh().unwrap());
+        assert_eq!(wal2.n_frames(), 2);
+        assert_eq!(wal2.find_frame(page_id(2)), Some(2));
+
+        let db = tempfile::tempfile().unwrap();
+        wal.checkpoint(&db).unwrap();
+        assert!(wal2.refresh().unwrap());
+        assert_eq!(wal2.n_frames(), 0);
+        assert!(!wal2.refresh().unwrap());
+
+        // The new header after checkpoint.
+        wal.append(&[(page_id(2), &[3; 512])], 2).unwrap();
+        assert!(wal2.refresh().unwrap());
+        assert_eq!(wal2.n_frames(), 1);
+        assert_eq!(wal2.find_frame(page_id(2)), Some(1));
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db-wal");
+        let db = tempfile::tempfile().unwrap();
+        db.write_all_at(&[1; 1024], 0).unwrap();
+        let mut wal = Wal::open(&path, 512).unwrap();
+        wal.append(&[(page_id(2), &[2; 512]), (page_id(3), &[3; 512])], 3)
+            .unwrap();
+        wal.append(&[(page_id(3), &[4; 512])], 3).unwrap();
+        let header = wal.header.unwrap();
+
+        wal.checkpoint(&db).unwrap();
+        assert_eq!(wal.n_frames(), 0);
+        assert_eq!(wal.find_frame(page_id(2)), None);
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().len(),
+            WAL_HEADER_SIZE as u64
+        );
+        let mut buf = [0; 512 * 3];
+        db.read_exact_at(&mut buf, 0).unwrap();
+        assert_eq!(buf[..512], [1; 512]);
+        assert_eq!(buf[512..1024], [2; 512]);
+        assert_eq!(buf[1024..], [4; 512]);
+        assert_eq!(db.metadata().unwrap().len(), 512 * 3);
+
+        // The WAL restarts with the new salt.
+        wal.append(&[(page_id(1), &[5; 512])], 3).unwrap();
+        let new_header = wal.header.unwrap();
+        assert_eq!(new_header.checkpoint_seq, header.checkpoint_seq + 1);
+        assert_eq!(
+            u32::from_be_bytes(new_header.salt[..4].try_into().unwrap()),
+            u32::from_be_bytes(header.salt[..4].try_into().unwrap()).wrapping_add(1)
+        );
+        let wal2 = Wal::open(&path, 512).unwrap();
+        assert_eq!(wal2.n_frames(), 1);
+    }
+}
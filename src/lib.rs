@@ -12,48 +12,99 @@ use crate::interpreter::query;
 
 use std::cell::Cell;
 use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::fmt::Display;
+use std::fs::File;
 use std::fs::OpenOptions;
+use std::io;
 use std::os::unix::fs::FileExt;
 use std::path::Path;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Duration;
 
+use crate::data_processing::aggregator::AggregateFunction;
+use crate::data_processing::aggregator::Aggregator;
 use crate::interpreter::btree::BtreeContext;
+use crate::interpreter::cursor::allocate_btree;
+use crate::interpreter::cursor::drop_btree;
 use crate::interpreter::cursor::BtreeCursor;
+use crate::interpreter::expression::AggregateCall;
 use crate::interpreter::expression::DataContext;
 use crate::interpreter::expression::Expression;
 use crate::interpreter::header::DatabaseHeader;
 use crate::interpreter::header::DatabaseHeaderMut;
 use crate::interpreter::header::DATABASE_HEADER_SIZE;
+use crate::interpreter::journal::journal_path;
+use crate::interpreter::journal::rollback_hot_journal;
+use crate::interpreter::journal::Journal;
+use crate::interpreter::lock::FileLock;
+use crate::interpreter::lock::LockLevel;
+pub use crate::interpreter::pager::CacheSize;
 use crate::interpreter::pager::PageId;
 use crate::interpreter::pager::Pager;
+use crate::interpreter::pager::DEFAULT_CACHE_SIZE;
+use crate::interpreter::pager::MAX_PAGE_SIZE;
 use crate::interpreter::pager::PAGE_ID_1;
 use crate::interpreter::parser::expect_no_more_token;
 use crate::interpreter::parser::expect_semicolon;
 use crate::interpreter::parser::parse_sql;
+use crate::interpreter::parser::Analyze;
+use crate::interpreter::parser::Begin;
+use crate::interpreter::parser::ConflictResolution;
+use crate::interpreter::parser::CreateIndex;
+use crate::interpreter::parser::CreateTable;
 use crate::interpreter::parser::Delete;
+use crate::interpreter::parser::DropIndex;
+use crate::interpreter::parser::DropTable;
 use crate::interpreter::parser::Insert;
+use crate::interpreter::parser::InsertSource;
+use crate::interpreter::parser::Parameters;
 use crate::interpreter::parser::Parser;
+use crate::interpreter::parser::Release;
 use crate::interpreter::parser::ResultColumn;
+use crate::interpreter::parser::Rollback;
+use crate::interpreter::parser::Savepoint;
 use crate::interpreter::parser::Select;
 use crate::interpreter::parser::Stmt;
+use crate::interpreter::parser::TransactionType;
+use crate::interpreter::parser::Update;
+use crate::interpreter::parser::UpsertAction;
 use crate::interpreter::query::Query;
 use crate::interpreter::query::QueryPlan;
 use crate::interpreter::query::RowData;
+use crate::interpreter::record::parse_record;
 use crate::interpreter::record::RecordPayload;
 use crate::interpreter::schema::ColumnNumber;
 use crate::interpreter::schema::Index;
+use crate::interpreter::schema::IndexStats;
 use crate::interpreter::schema::Schema;
 use crate::interpreter::schema::Table;
+use crate::interpreter::sorter::compare_rows;
+use crate::interpreter::sorter::SortOrder;
+use crate::interpreter::sorter::SortRow;
+use crate::interpreter::sorter::SortedRows;
+use crate::interpreter::sorter::Sorter;
+use crate::interpreter::sorter::DEFAULT_SORTER_MEMORY_LIMIT;
+use crate::interpreter::utils::upper_to_lower;
 pub use crate::interpreter::value::Buffer;
 use crate::interpreter::value::Collation;
+use crate::interpreter::value::ConstantValue;
 use crate::interpreter::value::TypeAffinity;
 pub use crate::interpreter::value::Value;
 use crate::interpreter::value::ValueCmp;
 use crate::interpreter::value::DEFAULT_COLLATION;
+use crate::interpreter::wal::wal_path;
+use crate::interpreter::wal::Wal;
+pub use crate::storage::cache::CacheStats;
 use anyhow::bail;
 use anyhow::Context;
 
 const MAX_ROWID: i64 = i64::MAX;
+/// SQLite does not wait for locks by default.
+const DEFAULT_BUSY_TIMEOUT: Duration = Duration::ZERO;
+/// The default page size of new databases, the same as SQLite.
+const DEFAULT_PAGE_SIZE: u32 = 4096;
 
 #[derive(Debug)]
 pub enum Error<'a> {
@@ -63,6 +114,10 @@ pub enum Error<'a> {
     Query(query::Error),
     UniqueConstraintViolation,
     DataTypeMismatch,
+    /// The database file is locked by other connections.
+    Busy,
+    /// The connection is opened in the read-only mode.
+    ReadOnly,
     Unsupported(&'static str),
     Other(anyhow::Error),
 }
@@ -97,6 +152,12 @@ impl From<anyhow::Error> for Error<'_> {
     }
 }
 
+impl From<io::Error> for Error<'_> {
+    fn from(e: io::Error) -> Self {
+        Self::Other(e.into())
+    }
+}
+
 impl std::error::Error for Error<'_> {}
 
 impl Display for Error<'_> {
@@ -120,6 +181,12 @@ impl Display for Error<'_> {
             Error::UniqueConstraintViolation => {
                 write!(f, "unique constraint violation")
             }
+            Error::Busy => {
+                write!(f, "database is locked")
+            }
+            Error::ReadOnly => {
+                write!(f, "attempt to write a readonly database")
+            }
             Error::Unsupported(msg) => {
                 write!(f, "unsupported: {}", msg)
             }
@@ -140,16 +207,230 @@ pub struct Connection {
     /// 0   : no read/write
     /// -1  : write running
     ref_count: Cell<i64>,
+    path: PathBuf,
+    lock: FileLock,
+    busy_timeout: Cell<Duration>,
+    read_only: bool,
+    /// The explicit transaction. None in the autocommit mode.
+    transaction: RefCell<Option<Transaction>>,
+}
+
+/// The explicit transaction started by BEGIN or SAVEPOINT.
+struct Transaction {
+    /// The names of the savepoints from the outermost, in lower case. They
+    /// correspond to the savepoints in the pager.
+    savepoints: Vec<Vec<u8>>,
+    /// Whether the transaction is started by SAVEPOINT. Releasing the outermost
+    /// savepoint commits such a transaction.
+    is_savepoint: bool,
+}
+
+/// The journal mode of the database, compatible with journal_mode of SQLite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalMode {
+    /// The rollback journal is deleted on commit.
+    Delete,
+    /// The write-ahead log.
+    Wal,
+}
+
+/// Options to open a database, similar to the flags of sqlite3_open_v2().
+///
+/// ```no_run
+/// # use std::path::Path;
+/// # use nexusdb::{Connection, OpenFlags};
+/// let flags = OpenFlags::create().pagesize(8192);
+/// let conn = Connection::open_with_flags(Path::new("example.db"), &flags).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct OpenFlags {
+    read_only: bool,
+    create: bool,
+    pagesize: u32,
+    busy_timeout: Duration,
+    cache_size: CacheSize,
+}
+
+impl OpenFlags {
+    /// Open an existing database for reading and writing.
+    pub fn read_write() -> Self {
+        Self {
+            read_only: false,
+            create: false,
+            pagesize: DEFAULT_PAGE_SIZE,
+            busy_timeout: DEFAULT_BUSY_TIMEOUT,
+            cache_size: DEFAULT_CACHE_SIZE,
+        }
+    }
+
+    /// Open an existing database for reading only.
+    ///
+    /// Statements modifying the database fail with [Error::ReadOnly]. The WAL
+    /// file is used only if it exists when the database is opened.
+    pub fn read_only() -> Self {
+        Self {
+            read_only: true,
+            ..Self::read_write()
+        }
+    }
+
+    /// Open a database for reading and writing, creating an empty database if
+    /// the file does not exist or is empty.
+    pub fn create() -> Self {
+        Self {
+            create: true,
+            ..Self::read_write()
+        }
+    }
+
+    /// The page size of a newly created database.
+    ///
+    /// This must be a power of 2 between 512 and 65536. This is ignored for
+    /// existing databases.
+    pub fn pagesize(mut self, pagesize: u32) -> Self {
+        self.pagesize = pagesize;
+        self
+    }
+
+    /// The busy timeout of the connection. See [Connection::set_busy_timeout].
+    pub fn busy_timeout(mut self, timeout: Duration) -> Self {
+        self.busy_timeout = timeout;
+        self
+    }
+
+    /// The capacity of the page cache. See [Connection::set_cache_size].
+    pub fn cache_size(mut self, size: CacheSize) -> Self {
+        self.cache_size = size;
+        self
+    }
+}
+
+impl Default for OpenFlags {
+    fn default() -> Self {
+        Self::read_write()
+    }
+}
+
+/// Write the database header and the empty sqlite_schema table to an empty
+/// database file.
+///
+/// The shared lock must be held.
+fn initialize_database(
+    file: &File,
+    lock: &FileLock,
+    pagesize: u32,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    // Other connections may be initializing the database at the same time.
+    wait_for_lock(timeout, || lock.lock(LockLevel::Reserved))?;
+    wait_for_lock(timeout, || lock.lock(LockLevel::Exclusive))?;
+    if file.metadata()?.len() == 0 {
+        let pager = Pager::new(file.try_clone()?, 0, pagesize, pagesize, None, 0)?;
+        let btree_ctx = BtreeContext::new(pagesize);
+        let page_id = allocate_btree(&pager, &btree_ctx, true)?;
+        assert_eq!(page_id, PAGE_ID_1);
+        let page1 = pager.get_page(PAGE_ID_1)?;
+        let mut buffer = pager.make_page_mut(&page1)?;
+        let header_buf = &mut buffer[..DATABASE_HEADER_SIZE];
+        DatabaseHeaderMut::from(header_buf.try_into().unwrap()).init(pagesize);
+        drop(buffer);
+        drop(page1);
+        pager.commit()?;
+        file.sync_all()?;
+    }
+    lock.unlock(LockLevel::Shared)?;
+    Ok(())
+}
+
+/// Whether the latest database header in the pager is in the WAL mode.
+fn is_wal_mode_header(pager: &Pager) -> anyhow::Result<bool> {
+    let page1 = pager.get_page(PAGE_ID_1)?;
+    let buffer = page1.buffer();
+    let header = DatabaseHeader::from(buffer[..DATABASE_HEADER_SIZE].try_into().unwrap());
+    Ok(header.is_wal_mode())
+}
+
+/// Retry acquiring a lock until the timeout in the same way as the default busy
+/// handler of SQLite.
+fn wait_for_lock(
+    timeout: Duration,
+    mut try_lock: impl FnMut() -> io::Result<bool>,
+) -> Result<'static, ()> {
+    const DELAYS_MS: [u64; 12] = [1, 2, 5, 10, 15, 20, 25, 25, 25, 50, 50, 100];
+    let mut waited = Duration::ZERO;
+    for i in 0.. {
+        if try_lock()? {
+            return Ok(());
+        } else if waited >= timeout {
+            break;
+        }
+        let delay = Duration::from_millis(DELAYS_MS[i.min(DELAYS_MS.len() - 1)]);
+        let delay = delay.min(timeout - waited);
+        std::thread::sleep(delay);
+        waited += delay;
+    }
+    Err(Error::Busy)
+}
+
+/// Roll back the hot journal left by a crashed writer.
+///
+/// The shared lock or the reserved lock must be held. Returns whether the
+/// database file is restored.
+fn recover_hot_journal(
+    lock: &FileLock,
+    journal_path: &Path,
+    read_only: bool,
+) -> Result<'static, bool> {
+    // The journal of a live writer holding the reserved lock is not hot.
+    if !journal_path.exists() || lock.is_reserved_by_others()? {
+        return Ok(false);
+    } else if read_only {
+        return Err(Error::ReadOnly);
+    }
+    let level = lock.level();
+    if !lock.lock(LockLevel::Exclusive)? {
+        return Err(Error::Busy);
+    }
+    let result = rollback_hot_journal(lock.file(), journal_path)
+        .with_context(|| format!("failed to roll back journal: {:?}", journal_path));
+    lock.unlock(LockLevel::Shared)?;
+    let is_restored = result?;
+    if level == LockLevel::Reserved && !lock.lock(LockLevel::Reserved)? {
+        return Err(Error::Busy);
+    }
+    Ok(is_restored)
 }
 
 impl Connection {
+    /// Open an existing database for reading and writing.
     pub fn open(filename: &Path) -> anyhow::Result<Self> {
-        // TODO: support read only mode.
+        Self::open_with_flags(filename, &OpenFlags::default())
+    }
+
+    pub fn open_with_flags(filename: &Path, flags: &OpenFlags) -> anyhow::Result<Self> {
+        if flags.create
+            && !((512..=MAX_PAGE_SIZE as u32).contains(&flags.pagesize)
+                && flags.pagesize.is_power_of_two())
+        {
+            bail!("invalid page size: {}", flags.pagesize);
+        }
         let file = OpenOptions::new()
             .read(true)
-            .write(true)
+            .write(!flags.read_only)
+            .create(flags.create)
+            .truncate(false)
             .open(filename)
             .with_context(|| format!("failed to open file: {:?}", filename))?;
+        // The lock shares the open file description with the pager.
+        let lock = FileLock::new(file.try_clone()?);
+        // Read the database header and the WAL under the shared lock. The lock is
+        // released when the connection is returned.
+        wait_for_lock(flags.busy_timeout, || lock.lock(LockLevel::Shared))?;
+        if flags.create && file.metadata()?.len() == 0 {
+            initialize_database(&file, &lock, flags.pagesize, flags.busy_timeout)?;
+        }
+        let journal_path = journal_path(filename);
+        recover_hot_journal(&lock, &journal_path, flags.read_only)?;
         let mut buf = [0; DATABASE_HEADER_SIZE];
         file.read_exact_at(&mut buf, 0)?;
         let header = DatabaseHeader::from(&buf);
@@ -160,7 +441,7 @@ impl Connection {
         // pagesize is bigger than or equal to 512.
         // reserved is smaller than or equal to 255.
         let usable_size = pagesize - header.reserved() as u32;
-        let pager = Pager::new(
+        let mut pager = Pager::new(
             file,
             header.n_pages(),
             pagesize,
@@ -168,28 +449,261 @@ impl Connection {
             header.first_freelist_trunk_page_id(),
             header.n_freelist_pages(),
         )?;
+        pager.set_cache_size(flags.cache_size);
+        let wal_path = wal_path(filename);
+        // SQLite uses the WAL file if it exists even if the database is not in the
+        // WAL mode.
+        if flags.read_only {
+            if wal_path.exists() {
+                let wal = Wal::open_read_only(&wal_path, pagesize)
+                    .with_context(|| format!("failed to open wal: {:?}", wal_path))?;
+                pager.set_wal(wal)?;
+            }
+        } else {
+            if header.is_wal_mode() || wal_path.exists() {
+                let wal = Wal::open(&wal_path, pagesize)
+                    .with_context(|| format!("failed to open wal: {:?}", wal_path))?;
+                pager.set_wal(wal)?;
+            }
+            if !pager.is_wal_mode() || !is_wal_mode_header(&pager)? {
+                pager.set_journal(Journal::new(journal_path, pagesize), &wal_path)?;
+            }
+        }
+        lock.unlock(LockLevel::None)?;
         Ok(Self {
             pager,
             btree_ctx: BtreeContext::new(usable_size),
             schema: RefCell::new(None),
             ref_count: Cell::new(0),
+            path: filename.to_path_buf(),
+            lock,
+            busy_timeout: Cell::new(flags.busy_timeout),
+            read_only: flags.read_only,
+            transaction: RefCell::new(None),
         })
     }
 
+    /// Set how long to wait for locks held by other connections before failing
+    /// with [Error::Busy].
+    ///
+    /// Zero, the default, means no waiting.
+    pub fn set_busy_timeout(&self, timeout: Duration) {
+        self.busy_timeout.set(timeout);
+    }
+
+    pub fn busy_timeout(&self) -> Duration {
+        self.busy_timeout.get()
+    }
+
+    /// Set the capacity of the page cache.
+    ///
+    /// The least recently used pages are evicted when the cache is full. Pages
+    /// modified in the running transaction are kept until it is committed.
+    pub fn set_cache_size(&self, size: CacheSize) {
+        self.pager.set_cache_size(size);
+    }
+
+    /// The hit, miss and eviction counters of the page cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.pager.cache_stats()
+    }
+
+    /// Whether the connection is in the autocommit mode, i.e. no transaction is
+    /// started by BEGIN or SAVEPOINT.
+    pub fn is_autocommit(&self) -> bool {
+        self.transaction.borrow().is_none()
+    }
+
+    pub fn journal_mode(&self) -> JournalMode {
+        if self.pager.is_wal_mode() {
+            JournalMode::Wal
+        } else {
+            JournalMode::Delete
+        }
+    }
+
+    /// Switch the journal mode of the database.
+    ///
+    /// The journal mode is persisted in the database header.
+    pub fn set_journal_mode(&mut self, mode: JournalMode) -> anyhow::Result<()> {
+        if mode == self.journal_mode() {
+            return Ok(());
+        } else if !self.is_autocommit() {
+            bail!("cannot change journal mode within a transaction");
+        }
+        let write_txn = self.start_write()?;
+        let page1 = self.pager.get_page(PAGE_ID_1)?;
+        let mut buffer = self.pager.make_page_mut(&page1)?;
+        let header_buf = &mut buffer[..DATABASE_HEADER_SIZE];
+        let mut header = DatabaseHeaderMut::from(header_buf.try_into().unwrap());
+        header.set_wal_mode(mode == JournalMode::Wal);
+        drop(buffer);
+        drop(page1);
+        write_txn.commit()?;
+
+        // Switching the journal files requires no other connections reading the
+        // database.
+        self.begin(LockLevel::Shared)?;
+        let result = self.switch_journal_mode(mode);
+        self.lock.unlock(LockLevel::None)?;
+        result
+    }
+
+    fn switch_journal_mode(&mut self, mode: JournalMode) -> anyhow::Result<()> {
+        self.wait_for_lock(LockLevel::Exclusive)?;
+        let pagesize = self.pager.pagesize();
+        let wal_path = wal_path(&self.path);
+        match mode {
+            JournalMode::Wal => {
+                let wal = Wal::open(&wal_path, pagesize)
+                    .with_context(|| format!("failed to open wal: {:?}", wal_path))?;
+                self.pager.set_wal(wal)?;
+            }
+            JournalMode::Delete => {
+                let journal = Journal::new(journal_path(&self.path), pagesize);
+                self.pager.set_journal(journal, &wal_path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy the pages in the WAL back to the database file.
+    ///
+    /// This is no-op if the database is not in the WAL mode.
+    pub fn checkpoint(&self) -> anyhow::Result<()> {
+        if self.read_only {
+            return Err(Error::ReadOnly.into());
+        } else if self.ref_count.get() != 0 {
+            bail!("other statments running");
+        } else if !self.is_autocommit() {
+            bail!("cannot checkpoint within a transaction");
+        }
+        self.begin(LockLevel::Shared)?;
+        // Readers may be reading the pages in the WAL.
+        let result = self
+            .wait_for_lock(LockLevel::Exclusive)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| Ok(self.pager.checkpoint()?));
+        self.lock.unlock(LockLevel::None)?;
+        result
+    }
+
     pub fn prepare<'a, 'conn>(&'conn self, sql: &'a str) -> Result<'a, Statement<'conn>> {
         let input = sql.as_bytes();
         let mut parser = Parser::new(input);
         let statement = parse_sql(&mut parser)?;
         expect_semicolon(&mut parser)?;
         expect_no_more_token(&parser)?;
+        let parameters = parser.parameters().clone();
+        if !parameters.is_empty() && !matches!(statement, Stmt::Select(_) | Stmt::Insert(_)) {
+            return Err(Error::Unsupported(
+                "parameters are supported only in SELECT and INSERT",
+            ));
+        }
+        if self.read_only
+            && !matches!(
+                statement,
+                Stmt::Select(_)
+                    | Stmt::Begin(_)
+                    | Stmt::Commit
+                    | Stmt::Rollback(_)
+                    | Stmt::Savepoint(_)
+                    | Stmt::Release(_)
+                    | Stmt::ExplainQueryPlan(_)
+            )
+        {
+            return Err(Error::ReadOnly);
+        }
+        self.prepare_statement(statement, parameters)
+    }
 
+    fn prepare_statement<'a, 'conn>(
+        &'conn self,
+        statement: Stmt<'a>,
+        parameters: Parameters,
+    ) -> Result<'a, Statement<'conn>> {
         match statement {
-            Stmt::Select(select) => Ok(Statement::Query(self.prepare_select(select)?)),
-            Stmt::Insert(insert) => {
-                Ok(Statement::Execution(Box::new(self.prepare_insert(insert)?)))
+            Stmt::CreateTable(create_table) => Ok(Statement::Execution(Box::new(
+                self.prepare_create_table(create_table)?,
+            ))),
+            Stmt::CreateIndex(create_index) => Ok(Statement::Execution(Box::new(
+                self.prepare_create_index(create_index)?,
+            ))),
+            Stmt::DropTable(drop_table) => Ok(Statement::Execution(Box::new(
+                self.prepare_drop_table(drop_table)?,
+            ))),
+            Stmt::DropIndex(drop_index) => Ok(Statement::Execution(Box::new(
+                self.prepare_drop_index(drop_index)?,
+            ))),
+            Stmt::Select(select) => Ok(Statement::Query(Box::new(
+                self.prepare_select(select, parameters)?,
+            ))),
+            Stmt::Insert(insert) => Ok(Statement::Execution(Box::new(
+                self.prepare_insert(insert, parameters)?,
+            ))),
+            Stmt::Update(update) => {
+                Ok(Statement::Execution(Box::new(self.prepare_update(update)?)))
             }
             Stmt::Delete(delete) => Ok(Statement::Execution(self.prepare_delete(delete)?)),
+            Stmt::Analyze(analyze) => Ok(Statement::Execution(Box::new(
+                self.prepare_analyze(analyze),
+            ))),
+            Stmt::Begin(begin) => Ok(Statement::Execution(Box::new(self.prepare_begin(begin)))),
+            Stmt::Commit => Ok(Statement::Execution(Box::new(TransactionStatement {
+                conn: self,
+                control: TransactionControl::Commit,
+            }))),
+            Stmt::Rollback(rollback) => Ok(Statement::Execution(Box::new(
+                self.prepare_rollback(rollback),
+            ))),
+            Stmt::Savepoint(savepoint) => Ok(Statement::Execution(Box::new(
+                self.prepare_savepoint(savepoint),
+            ))),
+            Stmt::Release(release) => Ok(Statement::Execution(Box::new(
+                self.prepare_release(release),
+            ))),
+            Stmt::ExplainQueryPlan(statement) => {
+                let details = match self.prepare_statement(*statement, parameters)? {
+                    Statement::Query(stmt) => stmt.explain()?,
+                    Statement::Execution(stmt) => stmt.explain()?,
+                    Statement::Explain(_) => unreachable!("nested explain"),
+                };
+                Ok(Statement::Explain(ExplainStatement {
+                    rows: details
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, detail)| QueryPlanRow {
+                            id: i as u32 + 1,
+                            parent: 0,
+                            detail,
+                        })
+                        .collect(),
+                }))
+            }
+        }
+    }
+
+    /// Describe the query plan on the table for EXPLAIN QUERY PLAN.
+    fn explain_query_plan(
+        &self,
+        table_page_id: PageId,
+        query_plan: &QueryPlan,
+    ) -> Result<'static, String> {
+        if self.schema.borrow().is_none() {
+            self.load_schema()?;
         }
+        let schema_cell = self.schema.borrow();
+        let schema = schema_cell.as_ref().unwrap();
+        let (table_name, table) = if table_page_id == PAGE_ID_1 {
+            let table_name = b"sqlite_schema".as_slice();
+            (table_name, schema.get_table(table_name).unwrap())
+        } else {
+            schema
+                .tables()
+                .find(|(_, table)| table.root_page_id == table_page_id)
+                .ok_or(anyhow::anyhow!("table not found: {table_page_id}"))?
+        };
+        Ok(query_plan.explain(table_name, table, schema))
     }
 
     fn load_schema(&self) -> anyhow::Result<()> {
@@ -211,7 +725,167 @@ impl Connection {
         Ok(())
     }
 
-    fn prepare_select<'a>(&self, select: Select<'a>) -> Result<'a, SelectStatement> {
+    fn prepare_create_table<'a>(
+        &self,
+        create_table: CreateTable<'a>,
+    ) -> Result<'a, CreateTableStatement<'_>> {
+        let table_name = create_table.table_name.dequote();
+        if is_reserved_name(&table_name) {
+            return Err(Error::Other(anyhow::anyhow!(
+                "object name reserved for internal use: {:?}",
+                std::str::from_utf8(&table_name).unwrap_or_default()
+            )));
+        }
+
+        // sqlite_schema stores the normalized sql without "IF NOT EXISTS".
+        let mut sql = b"CREATE TABLE ".to_vec();
+        sql.extend_from_slice(create_table.definition);
+        // Validate the table definition in the same way as loading the schema.
+        let (_, table) = Table::parse(&sql, PAGE_ID_1)?;
+        let autoindex_name = table.autoindex_columns().map(|_| {
+            let mut name = b"sqlite_autoindex_".to_vec();
+            name.extend_from_slice(&table_name);
+            name.extend_from_slice(b"_1");
+            name
+        });
+
+        Ok(CreateTableStatement {
+            conn: self,
+            table_name,
+            if_not_exists: create_table.if_not_exists,
+            sql,
+            autoindex_name,
+        })
+    }
+
+    fn prepare_create_index<'a>(
+        &self,
+        create_index: CreateIndex<'a>,
+    ) -> Result<'a, CreateIndexStatement<'_>> {
+        let index_name = create_index.index_name.dequote();
+        if is_reserved_name(&index_name) {
+            return Err(Error::Other(anyhow::anyhow!(
+                "object name reserved for internal use: {:?}",
+                std::str::from_utf8(&index_name).unwrap_or_default()
+            )));
+        }
+        let table_name = create_index.table_name.dequote();
+        if is_reserved_name(&table_name) {
+            return Err(Error::Other(anyhow::anyhow!(
+                "table {:?} may not be indexed",
+                std::str::from_utf8(&table_name).unwrap_or_default()
+            )));
+        }
+
+        // sqlite_schema stores the normalized sql without "IF NOT EXISTS".
+        let mut sql = if create_index.unique {
+            b"CREATE UNIQUE INDEX ".to_vec()
+        } else {
+            b"CREATE INDEX ".to_vec()
+        };
+        sql.extend_from_slice(create_index.definition);
+
+        if self.schema.borrow().is_none() {
+            self.load_schema()?;
+        }
+        let schema_cell = self.schema.borrow();
+        let schema = schema_cell.as_ref().unwrap();
+        let table = schema.get_table(&table_name).ok_or(anyhow::anyhow!(
+            "table not found: {:?}",
+            std::str::from_utf8(&table_name).unwrap_or_default()
+        ))?;
+        // Validate the index definition in the same way as loading the schema.
+        Index::parse(&sql, PAGE_ID_1, table)?;
+
+        Ok(CreateIndexStatement {
+            conn: self,
+            index_name,
+            table_name,
+            if_not_exists: create_index.if_not_exists,
+            sql,
+        })
+    }
+
+    fn prepare_drop_table<'a>(
+        &self,
+        drop_table: DropTable<'a>,
+    ) -> Result<'a, DropTableStatement<'_>> {
+        let table_name = drop_table.table_name.dequote();
+        if is_reserved_name(&table_name) && !is_stat_table_name(&table_name) {
+            return Err(Error::Other(anyhow::anyhow!(
+                "table {:?} may not be dropped",
+                std::str::from_utf8(&table_name).unwrap_or_default()
+            )));
+        }
+        Ok(DropTableStatement {
+            conn: self,
+            table_name,
+            if_exists: drop_table.if_exists,
+        })
+    }
+
+    fn prepare_drop_index<'a>(
+        &self,
+        drop_index: DropIndex<'a>,
+    ) -> Result<'a, DropIndexStatement<'_>> {
+        let index_name = drop_index.index_name.dequote();
+        if is_autoindex_name(&index_name) {
+            return Err(Error::Other(anyhow::anyhow!(
+                "index associated with UNIQUE or PRIMARY KEY constraint cannot be dropped: {:?}",
+                std::str::from_utf8(&index_name).unwrap_or_default()
+            )));
+        }
+        Ok(DropIndexStatement {
+            conn: self,
+            index_name,
+            if_exists: drop_index.if_exists,
+        })
+    }
+
+    fn prepare_analyze(&self, analyze: Analyze) -> AnalyzeStatement<'_> {
+        AnalyzeStatement {
+            conn: self,
+            name: analyze.name.map(|name| name.dequote()),
+        }
+    }
+
+    fn prepare_begin(&self, begin: Begin) -> TransactionStatement<'_> {
+        TransactionStatement {
+            conn: self,
+            control: TransactionControl::Begin(begin.transaction_type),
+        }
+    }
+
+    fn prepare_rollback(&self, rollback: Rollback) -> TransactionStatement<'_> {
+        let control = match rollback.savepoint_name {
+            Some(name) => TransactionControl::RollbackTo(savepoint_name(name.dequote())),
+            None => TransactionControl::Rollback,
+        };
+        TransactionStatement {
+            conn: self,
+            control,
+        }
+    }
+
+    fn prepare_savepoint(&self, savepoint: Savepoint) -> TransactionStatement<'_> {
+        TransactionStatement {
+            conn: self,
+            control: TransactionControl::Savepoint(savepoint_name(savepoint.name.dequote())),
+        }
+    }
+
+    fn prepare_release(&self, release: Release) -> TransactionStatement<'_> {
+        TransactionStatement {
+            conn: self,
+            control: TransactionControl::Release(savepoint_name(release.name.dequote())),
+        }
+    }
+
+    fn prepare_select<'a>(
+        &self,
+        select: Select<'a>,
+        parameters: Parameters,
+    ) -> Result<'a, SelectStatement<'_>> {
         if self.schema.borrow().is_none() {
             self.load_schema()?;
         }
@@ -223,6 +897,7 @@ impl Connection {
             std::str::from_utf8(&table_name).unwrap_or_default()
         ))?;
 
+        let mut aggregates = Vec::new();
         let mut columns = Vec::new();
         for column in select.columns {
             match column {
@@ -231,7 +906,11 @@ impl Connection {
                 }
                 ResultColumn::Expr((expr, _alias)) => {
                     // TODO: consider alias.
-                    columns.push(Expression::from(expr, Some(table))?);
+                    columns.push(Expression::from_aggregate(
+                        expr,
+                        Some(table),
+                        &mut aggregates,
+                    )?);
                 }
                 ResultColumn::AllOfTable(_table_name) => {
                     todo!("ResultColumn::AllOfTable");
@@ -245,18 +924,150 @@ impl Connection {
             .transpose()?
             .unwrap_or(Expression::one());
 
-        let query_plan = QueryPlan::generate(table, &filter);
+        let mut group_by = Vec::with_capacity(select.group_by.len());
+        for (i, expr) in select.group_by.into_iter().enumerate() {
+            let expr = if let parser::Expr::Integer(column_idx) = expr {
+                // An integer refers to the result column.
+                if column_idx < 1 || column_idx as usize > columns.len() {
+                    return Err(Error::Other(anyhow::anyhow!(
+                        "GROUP BY term {} out of range - should be between 1 and {}",
+                        i + 1,
+                        columns.len()
+                    )));
+                }
+                columns[column_idx as usize - 1].clone()
+            } else {
+                Expression::from(expr, Some(table))?
+            };
+            let collation = expr
+                .collation()
+                .map(|(collation, _)| collation.clone())
+                .unwrap_or(DEFAULT_COLLATION.clone());
+            group_by.push((
+                expr,
+                SortOrder {
+                    collation,
+                    desc: false,
+                },
+            ));
+        }
+
+        let having = select
+            .having
+            .map(|expr| Expression::from_aggregate(expr, Some(table), &mut aggregates))
+            .transpose()?;
+
+        let mut sort_keys = Vec::with_capacity(select.order_by.len());
+        for (i, term) in select.order_by.into_iter().enumerate() {
+            let expr = if let parser::Expr::Integer(column_idx) = term.expr {
+                // An integer refers to the result column.
+                if column_idx < 1 || column_idx as usize > columns.len() {
+                    return Err(Error::Other(anyhow::anyhow!(
+                        "ORDER BY term {} out of range - should be between 1 and {}",
+                        i + 1,
+                        columns.len()
+                    )));
+                }
+                columns[column_idx as usize - 1].clone()
+            } else {
+                Expression::from_aggregate(term.expr, Some(table), &mut aggregates)?
+            };
+            let collation = expr
+                .collation()
+                .map(|(collation, _)| collation.clone())
+                .unwrap_or(DEFAULT_COLLATION.clone());
+            sort_keys.push((
+                expr,
+                SortOrder {
+                    collation,
+                    desc: term.desc,
+                },
+            ));
+        }
+
+        let mut referenced_columns = Vec::new();
+        for expr in columns
+            .iter()
+            .chain(std::iter::once(&filter))
+            .chain(group_by.iter().map(|(expr, _)| expr))
+            .chain(having.iter())
+            .chain(sort_keys.iter().map(|(expr, _)| expr))
+            .chain(
+                aggregates
+                    .iter()
+                    .filter_map(|aggregate| aggregate.arg.as_ref()),
+            )
+        {
+            expr.collect_columns(&mut referenced_columns);
+        }
+
+        let mut aggregation = if aggregates.is_empty() && group_by.is_empty() && having.is_none() {
+            None
+        } else {
+            let mut bare_columns = Vec::new();
+            for expr in columns
+                .iter()
+                .chain(having.iter())
+                .chain(sort_keys.iter().map(|(expr, _)| expr))
+            {
+                expr.collect_columns(&mut bare_columns);
+            }
+            Some(Box::new(Aggregation {
+                group_by_sorted: false,
+                group_by,
+                aggregates,
+                bare_columns,
+                having,
+            }))
+        };
+
+        // The query is planned again when values are bound to the parameters
+        // in the filter.
+        let plan_inputs = filter.has_parameter().then(|| {
+            Box::new(PlanInputs {
+                table_name: table_name.clone(),
+                referenced_columns: referenced_columns.clone(),
+                order_by: sort_keys.clone(),
+            })
+        });
+        let query_plan = plan_select(
+            table,
+            &filter,
+            &referenced_columns,
+            &mut sort_keys,
+            aggregation.as_deref_mut(),
+        );
+
+        let limit = select
+            .limit
+            .map(|expr| Expression::from(expr, None))
+            .transpose()?;
+        let offset = select
+            .offset
+            .map(|expr| Expression::from(expr, None))
+            .transpose()?;
 
-        Ok(SelectStatement::new(
-            self,
-            table.root_page_id,
+        Ok(SelectStatement {
+            conn: self,
+            table_page_id: table.root_page_id,
             columns,
             filter,
             query_plan,
-        ))
+            aggregation,
+            sort_keys,
+            limit,
+            offset,
+            sorter_memory_limit: DEFAULT_SORTER_MEMORY_LIMIT,
+            parameters,
+            plan_inputs,
+        })
     }
 
-    fn prepare_insert<'a>(&self, insert: Insert<'a>) -> Result<'a, InsertStatement> {
+    fn prepare_insert<'a>(
+        &self,
+        insert: Insert<'a>,
+        parameters: Parameters,
+    ) -> Result<'a, InsertStatement<'_>> {
         if self.schema.borrow().is_none() {
             self.load_schema()?;
         }
@@ -269,7 +1080,7 @@ impl Connection {
         ))?;
 
         let mut columns_idx = Vec::with_capacity(insert.columns.len());
-        for column in insert.columns {
+        for column in insert.columns.iter() {
             let column_name = column.dequote();
             if let Some((column_idx, _, _)) = table.get_column(&column_name) {
                 columns_idx.push(column_idx);
@@ -280,33 +1091,79 @@ impl Connection {
                 )));
             }
         }
-
-        let mut records = Vec::with_capacity(insert.values.len());
-        for column_values in insert.values {
-            let mut columns = table
-                .columns
-                .iter()
-                .map(|column| (Expression::Null, column.type_affinity))
-                .collect::<Vec<_>>();
-            let mut rowid = None;
-            if column_values.len() != columns_idx.len() {
-                return Err(Error::Other(anyhow::anyhow!(
+        if insert.columns.is_empty() {
+            columns_idx.extend(table.get_all_columns().map(|(column_idx, _, _)| column_idx));
+        }
+        let n_values = |n: usize| {
+            if n == columns_idx.len() {
+                Ok(())
+            } else if insert.columns.is_empty() {
+                Err(Error::Other(anyhow::anyhow!(
+                    "table {} has {} columns but {} values were supplied",
+                    std::str::from_utf8(&table_name).unwrap_or_default(),
+                    columns_idx.len(),
+                    n
+                )))
+            } else {
+                Err(Error::Other(anyhow::anyhow!(
                     "{} values for {} columns",
-                    column_values.len(),
+                    n,
                     columns_idx.len()
-                )));
+                )))
             }
-            for (column, expr) in columns_idx.iter().zip(column_values) {
-                match column {
-                    ColumnNumber::RowId => {
-                        rowid = Some(Expression::from(expr, None)?);
-                    }
-                    ColumnNumber::Column(column_idx) => {
-                        columns[*column_idx].0 = Expression::from(expr, None)?;
+        };
+
+        // The columns not in the column list have the default values. The
+        // default value of INTEGER PRIMARY KEY is ignored and the new rowid is
+        // generated.
+        let default_columns = table
+            .columns
+            .iter()
+            .zip(table.get_all_columns())
+            .map(|(column, (column_idx, _, _))| {
+                let expr = match (&column.default, column_idx) {
+                    (Some(default), ColumnNumber::Column(_)) => default.clone(),
+                    _ => Expression::Null,
+                };
+                (expr, column.type_affinity)
+            })
+            .collect::<Vec<_>>();
+
+        let mut records = Vec::new();
+        let mut query = None;
+        match insert.source {
+            InsertSource::Values(values) => {
+                records.reserve(values.len());
+                for column_values in values {
+                    n_values(column_values.len())?;
+                    let mut columns = default_columns.clone();
+                    let mut rowid = None;
+                    for (column, expr) in columns_idx.iter().zip(column_values) {
+                        match column {
+                            ColumnNumber::RowId => {
+                                rowid = Some(Expression::from(expr, None)?);
+                            }
+                            ColumnNumber::Column(column_idx) => {
+                                columns[*column_idx].0 = Expression::from(expr, None)?;
+                            }
+                        }
                     }
+                    records.push(InsertRecord { rowid, columns })
                 }
             }
-            records.push(InsertRecord { rowid, columns })
+            InsertSource::Select(select) => {
+                let select = self.prepare_select(*select, parameters.clone())?;
+                n_values(select.columns.len())?;
+                query = Some(Box::new(InsertQuery {
+                    select,
+                    columns: columns_idx,
+                    default_columns,
+                }));
+            }
+            InsertSource::DefaultValues => records.push(InsertRecord {
+                rowid: None,
+                columns: default_columns,
+            }),
         }
 
         let table_page_id = table.root_page_id;
@@ -316,42 +1173,167 @@ impl Connection {
             indexes.push(IndexSchema::create(table, idx));
             index = idx.next.as_ref();
         }
+
+        let upsert = if let Some(upsert) = insert.upsert {
+            let target = if upsert.target.is_empty() {
+                None
+            } else {
+                let mut target_columns = Vec::with_capacity(upsert.target.len());
+                for column in upsert.target {
+                    let column_name = column.dequote();
+                    let Some((column_idx, _, _)) = table.get_column(&column_name) else {
+                        return Err(Error::Other(anyhow::anyhow!(
+                            "column not found: {:?}",
+                            std::str::from_utf8(&column_name).unwrap_or_default()
+                        )));
+                    };
+                    target_columns.push(column_idx);
+                }
+                let constraint = if target_columns == [ColumnNumber::RowId] {
+                    Some(Constraint::RowId)
+                } else {
+                    indexes
+                        .iter()
+                        .position(|index| index.unique && index.has_key_columns(&target_columns))
+                        .map(Constraint::Index)
+                };
+                if constraint.is_none() {
+                    return Err(Error::Other(anyhow::anyhow!(
+                        "ON CONFLICT clause does not match any PRIMARY KEY or UNIQUE constraint"
+                    )));
+                }
+                constraint
+            };
+            let action = match upsert.action {
+                UpsertAction::Nothing => None,
+                UpsertAction::Update {
+                    assignments,
+                    filter,
+                } => {
+                    let mut update_assignments = Vec::with_capacity(assignments.len());
+                    for (column, expr) in assignments {
+                        let column_name = column.dequote();
+                        let Some((column_idx, type_affinity, _)) = table.get_column(&column_name)
+                        else {
+                            return Err(Error::Other(anyhow::anyhow!(
+                                "column not found: {:?}",
+                                std::str::from_utf8(&column_name).unwrap_or_default()
+                            )));
+                        };
+                        update_assignments.push((
+                            column_idx,
+                            Expression::from_upsert(expr, table)?,
+                            type_affinity,
+                        ));
+                    }
+                    let filter = filter
+                        .map(|expr| Expression::from_upsert(expr, table))
+                        .transpose()?;
+                    Some(UpsertUpdate {
+                        assignments: update_assignments,
+                        filter,
+                    })
+                }
+            };
+            Some(UpsertClause { target, action })
+        } else {
+            None
+        };
+
         Ok(InsertStatement {
             conn: self,
             table_page_id,
+            n_columns: table.columns.len(),
             records,
+            query,
             indexes,
+            conflict: insert.conflict,
+            upsert,
+            parameters,
         })
     }
 
-    fn prepare_delete<'a, 'conn>(
-        &'conn self,
-        delete: Delete<'a>,
-    ) -> Result<'a, Box<dyn ExecutionStatement + 'conn>> {
+    fn prepare_update<'a>(&self, update: Update<'a>) -> Result<'a, UpdateStatement<'_>> {
         if self.schema.borrow().is_none() {
             self.load_schema()?;
         }
         let schema_cell = self.schema.borrow();
         let schema = schema_cell.as_ref().unwrap();
-        let table_name = delete.table_name.dequote();
+        let table_name = update.table_name.dequote();
         let table = schema.get_table(&table_name).ok_or(anyhow::anyhow!(
             "table not found: {:?}",
             std::str::from_utf8(&table_name).unwrap_or_default()
         ))?;
 
-        let filter = delete
+        let mut assignments = Vec::with_capacity(update.assignments.len());
+        for (column, expr) in update.assignments {
+            let column_name = column.dequote();
+            let Some((column_idx, type_affinity, _)) = table.get_column(&column_name) else {
+                return Err(Error::Other(anyhow::anyhow!(
+                    "column not found: {:?}",
+                    std::str::from_utf8(&column_name).unwrap_or_default()
+                )));
+            };
+            assignments.push((
+                column_idx,
+                Expression::from(expr, Some(table))?,
+                type_affinity,
+            ));
+        }
+
+        let filter = update
             .filter
             .map(|expr| Expression::from(expr, Some(table)))
-            .transpose()?;
+            .transpose()?
+            .unwrap_or(Expression::one());
+        let query_plan = QueryPlan::generate(table, &filter, None);
 
-        let table_page_id = table.root_page_id;
-        if let Some(filter) = filter {
-            let query_plan = QueryPlan::generate(table, &filter);
+        let mut indexes = Vec::new();
+        let mut index = table.indexes.as_ref();
+        while let Some(idx) = index {
+            indexes.push(IndexSchema::create(table, idx));
+            index = idx.next.as_ref();
+        }
 
-            let query_index_page_id = query_plan.index_page_id();
-            let mut indexes = Vec::new();
-            let mut index = table.indexes.as_ref();
-            while let Some(idx) = index {
+        Ok(UpdateStatement {
+            conn: self,
+            table_page_id: table.root_page_id,
+            n_columns: table.columns.len(),
+            assignments,
+            indexes,
+            filter,
+            query_plan,
+        })
+    }
+
+    fn prepare_delete<'a, 'conn>(
+        &'conn self,
+        delete: Delete<'a>,
+    ) -> Result<'a, Box<dyn ExecutionStatement + 'conn>> {
+        if self.schema.borrow().is_none() {
+            self.load_schema()?;
+        }
+        let schema_cell = self.schema.borrow();
+        let schema = schema_cell.as_ref().unwrap();
+        let table_name = delete.table_name.dequote();
+        let table = schema.get_table(&table_name).ok_or(anyhow::anyhow!(
+            "table not found: {:?}",
+            std::str::from_utf8(&table_name).unwrap_or_default()
+        ))?;
+
+        let filter = delete
+            .filter
+            .map(|expr| Expression::from(expr, Some(table)))
+            .transpose()?;
+
+        let table_page_id = table.root_page_id;
+        if let Some(filter) = filter {
+            let query_plan = QueryPlan::generate(table, &filter, None);
+
+            let query_index_page_id = query_plan.index_page_id();
+            let mut indexes = Vec::new();
+            let mut index = table.indexes.as_ref();
+            while let Some(idx) = index {
                 if Some(idx.root_page_id) != query_index_page_id {
                     indexes.push(IndexSchema::create(table, idx));
                 }
@@ -380,57 +1362,485 @@ impl Connection {
         }
     }
 
-    fn start_read(&self) -> anyhow::Result<ReadTransaction> {
-        // TODO: Lock across processes
+    /// Insert a new entry to sqlite_schema.
+    ///
+    /// The sql is NULL for autoindexes. This must be called in a write
+    /// transaction.
+    fn insert_schema_record<'a>(
+        &self,
+        type_: &[u8],
+        name: &[u8],
+        table_name: &[u8],
+        root_page_id: PageId,
+        sql: Option<&[u8]>,
+    ) -> Result<'a, ()> {
+        let mut cursor = BtreeCursor::new(PAGE_ID_1, &self.pager, &self.btree_ctx)?;
+        cursor.move_to_last()?;
+        let rowid = cursor.get_table_key()?.unwrap_or(0) + 1;
+        let type_ = Value::Text(Buffer::Ref(type_));
+        let name = Value::Text(Buffer::Ref(name));
+        let table_name = Value::Text(Buffer::Ref(table_name));
+        let root_page_id = Value::Integer(root_page_id.get() as i64);
+        let sql = sql.map(|sql| Value::Text(Buffer::Ref(sql)));
+        cursor.table_insert(
+            rowid,
+            &RecordPayload::new(&[
+                Some(&type_),
+                Some(&name),
+                Some(&table_name),
+                Some(&root_page_id),
+                sql.as_ref(),
+            ])?,
+        )?;
+        Ok(())
+    }
+
+    /// Delete the entries in sqlite_schema of which the column at column_idx
+    /// matches the name case-insensitively.
+    ///
+    /// Returns the root page ids of the deleted entries. This must be called in
+    /// a write transaction.
+    fn delete_schema_records<'a>(&self, column_idx: usize, name: &[u8]) -> Result<'a, Vec<PageId>> {
+        let mut root_page_ids = Vec::new();
+        self.delete_records(PAGE_ID_1, column_idx, name, |data| {
+            // Views and triggers have no btree and their root page is 0.
+            if let Some(Value::Integer(root_page_id)) = data
+                .get_column_value(&ColumnNumber::Column(3))
+                .map_err(expression::Error::FailGetColumn)?
+            {
+                if let Some(root_page_id) = u32::try_from(root_page_id).ok().and_then(PageId::new) {
+                    root_page_ids.push(root_page_id);
+                }
+            }
+            Ok(())
+        })?;
+        Ok(root_page_ids)
+    }
+
+    /// Delete the entries in sqlite_stat1 of which the column at column_idx
+    /// matches the name case-insensitively.
+    ///
+    /// This does nothing if sqlite_stat1 does not exist. This must be called in
+    /// a write transaction.
+    fn delete_stat_records<'a>(
+        &self,
+        stat_page_id: Option<PageId>,
+        column_idx: usize,
+        name: &[u8],
+    ) -> Result<'a, ()> {
+        if let Some(stat_page_id) = stat_page_id {
+            self.delete_records(stat_page_id, column_idx, name, |_| Ok(()))?;
+        }
+        Ok(())
+    }
+
+    /// Delete the rows in the table of which the column at column_idx matches
+    /// the name case-insensitively.
+    ///
+    /// on_delete is called with each row before it is deleted. This must be
+    /// called in a write transaction.
+    fn delete_records<'a>(
+        &self,
+        table_page_id: PageId,
+        column_idx: usize,
+        name: &[u8],
+        mut on_delete: impl FnMut(&RowData) -> Result<'a, ()>,
+    ) -> Result<'a, ()> {
+        let query_plan = QueryPlan::FullScan;
+        let filter = Expression::one();
+        let mut query = Query::new(
+            table_page_id,
+            &self.pager,
+            &self.btree_ctx,
+            &query_plan,
+            &filter,
+        )?;
+        loop {
+            let Some(data) = query.next()? else {
+                break;
+            };
+            let matched = matches!(
+                data.get_column_value(&ColumnNumber::Column(column_idx))
+                    .map_err(expression::Error::FailGetColumn)?,
+                Some(Value::Text(value)) if value.eq_ignore_ascii_case(name)
+            );
+            if !matched {
+                continue;
+            }
+            on_delete(&data)?;
+            drop(data);
+            query.delete()?;
+        }
+        Ok(())
+    }
+
+    /// Increment the schema cookie in the database header.
+    ///
+    /// This must be called in a write transaction which modifies the schema.
+    fn increment_schema_cookie(&self) -> anyhow::Result<()> {
+        let page1 = self.pager.get_page(PAGE_ID_1)?;
+        let mut buffer = self.pager.make_page_mut(&page1)?;
+        let header_buf = &mut buffer[..DATABASE_HEADER_SIZE];
+        let cookie = DatabaseHeader::from(header_buf.as_ref().try_into().unwrap()).schema_cookie();
+        let mut header = DatabaseHeaderMut::from(header_buf.try_into().unwrap());
+        header.set_schema_cookie(cookie.wrapping_add(1));
+        Ok(())
+    }
+
+    /// Load the changes committed by other connections.
+    ///
+    /// This must be called when no statement is running.
+    fn refresh(&self) -> anyhow::Result<()> {
+        if self.pager.refresh()? && self.schema.borrow().is_some() {
+            // The schema may be changed by other connections.
+            self.load_schema()?;
+        }
+        Ok(())
+    }
+
+    /// Wait for the lock with the busy timeout.
+    fn wait_for_lock(&self, level: LockLevel) -> Result<'static, ()> {
+        wait_for_lock(self.busy_timeout.get(), || self.lock.lock(level))
+    }
+
+    /// Lock the database file for a transaction and load the changes by other
+    /// connections.
+    ///
+    /// `level` is either the shared lock for reading or the reserved lock for
+    /// writing. The lock is released on failure.
+    fn begin(&self, level: LockLevel) -> Result<'static, ()> {
+        let result = self.lock_and_refresh(level);
+        if result.is_err() {
+            let _ = self.lock.unlock(LockLevel::None);
+        }
+        result
+    }
+
+    fn lock_and_refresh(&self, level: LockLevel) -> Result<'static, ()> {
+        wait_for_lock(self.busy_timeout.get(), || {
+            if !self.lock.lock(LockLevel::Shared)? {
+                return Ok(false);
+            }
+            if level == LockLevel::Reserved && !self.lock.lock(LockLevel::Reserved)? {
+                // Keeping the shared lock while waiting would block the writer from
+                // committing.
+                self.lock.unlock(LockLevel::None)?;
+                return Ok(false);
+            }
+            Ok(true)
+        })?;
+        if !self.pager.is_wal_mode()
+            && recover_hot_journal(&self.lock, &journal_path(&self.path), self.read_only)?
+        {
+            self.pager.reload().map_err(anyhow::Error::from)?;
+        }
+        // Loading the schema runs a nested read transaction which must not
+        // release the lock.
+        let ref_count = self.ref_count.replace(1);
+        let result = self.refresh();
+        self.ref_count.set(ref_count);
+        Ok(result?)
+    }
+
+    fn start_read(&self) -> Result<'static, ReadTransaction<'_>> {
         let ref_count = self.ref_count.get();
         if ref_count >= 0 {
+            // The explicit transaction keeps the lock between statements.
+            if ref_count == 0 && self.lock.level() == LockLevel::None {
+                self.begin(LockLevel::Shared)?;
+            }
             self.ref_count.set(ref_count + 1);
             Ok(ReadTransaction(self))
         } else {
-            bail!("write statment running");
+            Err(Error::Other(anyhow::anyhow!("write statment running")))
         }
     }
 
-    fn start_write(&self) -> anyhow::Result<WriteTransaction> {
-        // TODO: Lock across processes
-        if self.ref_count.get() == 0 {
+    fn start_write(&self) -> Result<'static, WriteTransaction<'_>> {
+        if self.read_only {
+            Err(Error::ReadOnly)
+        } else if self.ref_count.get() == 0 {
+            let savepoint = if self.is_autocommit() {
+                self.begin(LockLevel::Reserved)?;
+                None
+            } else {
+                self.reserve()?;
+                // A failed statement is rolled back without aborting the explicit
+                // transaction.
+                Some(self.pager.savepoint())
+            };
             self.ref_count.set(-1);
             Ok(WriteTransaction {
                 conn: self,
+                savepoint,
                 do_commit: false,
             })
         } else {
-            bail!("other statments running");
+            Err(Error::Other(anyhow::anyhow!("other statments running")))
+        }
+    }
+
+    /// Acquire the reserved lock for writing in the explicit transaction.
+    fn reserve(&self) -> Result<'static, ()> {
+        match self.lock.level() {
+            LockLevel::None => self.begin(LockLevel::Reserved),
+            LockLevel::Shared => {
+                // Waiting for the reserved lock while holding the shared lock would
+                // deadlock with the writer waiting for the readers to finish.
+                if !self.lock.lock(LockLevel::Reserved)? {
+                    return Err(Error::Busy);
+                }
+                // In the WAL mode, other connections may have committed after the
+                // transaction started reading. Writing on top of the stale snapshot
+                // would lose their changes. The transaction sees the new snapshot
+                // after this fails.
+                if self.pager.refresh().map_err(anyhow::Error::from)? {
+                    *self.schema.borrow_mut() = None;
+                    self.lock.unlock(LockLevel::Shared)?;
+                    return Err(Error::Busy);
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Write the changes in the transaction to the database file or the WAL.
+    ///
+    /// The reserved lock must be held.
+    fn commit_pager(&self) -> Result<'static, ()> {
+        let pager = &self.pager;
+        let is_wal_mode = pager.is_wal_mode();
+        // Other connections detect changes by the file change counter in the
+        // rollback journal mode.
+        let update_change_counter = !is_wal_mode && pager.is_dirty();
+        if pager.is_file_size_changed() || update_change_counter {
+            let page1 = pager.get_page(PAGE_ID_1).map_err(anyhow::Error::from)?;
+            let mut buffer = pager.make_page_mut(&page1).map_err(anyhow::Error::from)?;
+            let header_buf = &mut buffer[..DATABASE_HEADER_SIZE];
+            let counter =
+                DatabaseHeader::from(header_buf.as_ref().try_into().unwrap()).change_counter();
+            let mut header = DatabaseHeaderMut::from(header_buf.try_into().unwrap());
+            header.set_n_pages(pager.num_pages());
+            if update_change_counter {
+                header.set_change_counter(counter.wrapping_add(1));
+            }
+            drop(buffer);
+            drop(page1);
+        }
+
+        if !is_wal_mode {
+            // Wait for the readers to finish before overwriting the database file.
+            self.wait_for_lock(LockLevel::Exclusive)?;
+        }
+        pager.commit().map_err(anyhow::Error::from)?;
+
+        if pager.needs_checkpoint() && matches!(self.lock.lock(LockLevel::Exclusive), Ok(true)) {
+            // The transaction is already committed. The checkpoint is retried on
+            // the next commit if it fails or readers are running.
+            let _ = pager.checkpoint();
+        }
+        Ok(())
+    }
+
+    fn begin_transaction(&self, transaction_type: TransactionType) -> Result<'static, ()> {
+        if !self.is_autocommit() {
+            return Err(Error::Other(anyhow::anyhow!(
+                "cannot start a transaction within a transaction"
+            )));
+        }
+        match transaction_type {
+            TransactionType::Deferred => {}
+            TransactionType::Immediate | TransactionType::Exclusive => {
+                if self.read_only {
+                    return Err(Error::ReadOnly);
+                }
+                self.begin(LockLevel::Reserved)?;
+                // The exclusive lock is not used in the WAL mode, the same as
+                // SQLite.
+                if transaction_type == TransactionType::Exclusive && !self.pager.is_wal_mode() {
+                    if let Err(e) = self.wait_for_lock(LockLevel::Exclusive) {
+                        self.lock.unlock(LockLevel::None)?;
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        *self.transaction.borrow_mut() = Some(Transaction {
+            savepoints: Vec::new(),
+            is_savepoint: false,
+        });
+        Ok(())
+    }
+
+    fn commit_transaction(&self) -> Result<'static, ()> {
+        if self.is_autocommit() {
+            return Err(Error::Other(anyhow::anyhow!(
+                "cannot commit - no transaction is active"
+            )));
+        }
+        if self.pager.is_dirty() {
+            match self.commit_pager() {
+                Ok(()) => {}
+                // COMMIT can be retried after the readers finish.
+                Err(Error::Busy) => return Err(Error::Busy),
+                Err(e) => {
+                    self.abort_transaction();
+                    return Err(e);
+                }
+            }
+        } else if self.lock.level() >= LockLevel::Reserved {
+            // Changes rolled back to savepoints may leave the journal.
+            self.pager.abort();
+        } else {
+            self.pager.release_savepoint(0);
+        }
+        *self.transaction.borrow_mut() = None;
+        self.lock.unlock(LockLevel::None)?;
+        Ok(())
+    }
+
+    fn rollback_transaction(&self) -> Result<'static, ()> {
+        if self.is_autocommit() {
+            return Err(Error::Other(anyhow::anyhow!(
+                "cannot rollback - no transaction is active"
+            )));
+        }
+        self.abort_transaction();
+        Ok(())
+    }
+
+    /// Discard the changes in the explicit transaction and release the lock.
+    fn abort_transaction(&self) {
+        if self.lock.level() >= LockLevel::Reserved {
+            self.pager.abort();
+            // The schema may be changed in the transaction.
+            *self.schema.borrow_mut() = None;
+        } else {
+            self.pager.release_savepoint(0);
+        }
+        *self.transaction.borrow_mut() = None;
+        let _ = self.lock.unlock(LockLevel::None);
+    }
+
+    /// Start a savepoint. This starts a transaction in the autocommit mode.
+    fn savepoint(&self, name: &[u8]) {
+        let mut transaction = self.transaction.borrow_mut();
+        let transaction = transaction.get_or_insert_with(|| Transaction {
+            savepoints: Vec::new(),
+            is_savepoint: true,
+        });
+        let index = self.pager.savepoint();
+        assert_eq!(index, transaction.savepoints.len());
+        transaction.savepoints.push(name.to_vec());
+    }
+
+    /// The index of the innermost savepoint with the name.
+    fn find_savepoint(&self, name: &[u8]) -> Result<'static, usize> {
+        self.transaction
+            .borrow()
+            .as_ref()
+            .and_then(|transaction| {
+                transaction
+                    .savepoints
+                    .iter()
+                    .rposition(|savepoint| savepoint == name)
+            })
+            .ok_or_else(|| {
+                Error::Other(anyhow::anyhow!(
+                    "no such savepoint: {:?}",
+                    std::str::from_utf8(name).unwrap_or_default()
+                ))
+            })
+    }
+
+    fn release_savepoint(&self, name: &[u8]) -> Result<'static, ()> {
+        let index = self.find_savepoint(name)?;
+        let is_savepoint = self.transaction.borrow().as_ref().unwrap().is_savepoint;
+        if index == 0 && is_savepoint {
+            return self.commit_transaction();
+        }
+        self.pager.release_savepoint(index);
+        let mut transaction = self.transaction.borrow_mut();
+        transaction.as_mut().unwrap().savepoints.truncate(index);
+        Ok(())
+    }
+
+    fn rollback_to_savepoint(&self, name: &[u8]) -> Result<'static, ()> {
+        let index = self.find_savepoint(name)?;
+        self.pager
+            .rollback_to_savepoint(index)
+            .map_err(anyhow::Error::from)?;
+        let mut transaction = self.transaction.borrow_mut();
+        transaction.as_mut().unwrap().savepoints.truncate(index + 1);
+        if self.lock.level() >= LockLevel::Reserved {
+            // The schema may be changed after the savepoint.
+            *self.schema.borrow_mut() = None;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if !self.is_autocommit() {
+            self.abort_transaction();
         }
     }
 }
 
+/// Savepoint names are case insensitive.
+fn savepoint_name(mut name: Vec<u8>) -> Vec<u8> {
+    upper_to_lower(&mut name);
+    name
+}
+
+/// The table storing the statistics of the indexes, compatible with sqlite3.
+const STAT1_TABLE_NAME: &[u8] = b"sqlite_stat1";
+const STAT1_TABLE_SQL: &[u8] = b"CREATE TABLE sqlite_stat1(tbl,idx,stat)";
+
+/// Whether the name of a table or an index is reserved by SQLite.
+fn is_reserved_name(name: &[u8]) -> bool {
+    name.len() >= 7 && name[..7].eq_ignore_ascii_case(b"sqlite_")
+}
+
+/// Whether the table is a statistics table, which sqlite3 allows to drop.
+fn is_stat_table_name(name: &[u8]) -> bool {
+    name.len() >= 11 && name[..11].eq_ignore_ascii_case(b"sqlite_stat")
+}
+
+/// Whether the index is created for a UNIQUE or PRIMARY KEY constraint.
+fn is_autoindex_name(name: &[u8]) -> bool {
+    name.len() >= 17 && name[..17].eq_ignore_ascii_case(b"sqlite_autoindex_")
+}
+
 struct ReadTransaction<'a>(&'a Connection);
 
 impl Drop for ReadTransaction<'_> {
     fn drop(&mut self) {
-        self.0.ref_count.set(self.0.ref_count.get() - 1);
+        let ref_count = self.0.ref_count.get() - 1;
+        self.0.ref_count.set(ref_count);
+        if ref_count == 0 && self.0.is_autocommit() {
+            let _ = self.0.lock.unlock(LockLevel::None);
+        }
     }
 }
 
 struct WriteTransaction<'a> {
     conn: &'a Connection,
+    /// The savepoint of the statement in the explicit transaction. The changes
+    /// are committed by COMMIT instead.
+    savepoint: Option<usize>,
     do_commit: bool,
 }
 
 impl WriteTransaction<'_> {
-    fn commit(mut self) -> anyhow::Result<()> {
-        if self.conn.pager.is_file_size_changed() {
-            let page1 = self.conn.pager.get_page(PAGE_ID_1)?;
-            let mut buffer = self.conn.pager.make_page_mut(&page1)?;
-            let header_buf = &mut buffer[..DATABASE_HEADER_SIZE];
-            let mut header = DatabaseHeaderMut::from(header_buf.try_into().unwrap());
-            header.set_n_pages(self.conn.pager.num_pages());
-            drop(buffer);
-            drop(page1);
+    fn commit(mut self) -> Result<'static, ()> {
+        if let Some(savepoint) = self.savepoint {
+            self.conn.pager.release_savepoint(savepoint);
+        } else {
+            self.conn.commit_pager()?;
         }
-
-        self.conn.pager.commit()?;
         self.do_commit = true;
         Ok(())
     }
@@ -438,20 +1848,51 @@ impl WriteTransaction<'_> {
 
 impl Drop for WriteTransaction<'_> {
     fn drop(&mut self) {
-        if !self.do_commit {
-            self.conn.pager.abort();
-        }
         self.conn.ref_count.set(0);
+        if let Some(savepoint) = self.savepoint {
+            if !self.do_commit {
+                if self.conn.pager.rollback_to_savepoint(savepoint).is_ok() {
+                    self.conn.pager.release_savepoint(savepoint);
+                    // The statement may have changed the schema.
+                    *self.conn.schema.borrow_mut() = None;
+                } else {
+                    self.conn.abort_transaction();
+                }
+            }
+        } else {
+            if !self.do_commit {
+                self.conn.pager.abort();
+            }
+            let _ = self.conn.lock.unlock(LockLevel::None);
+        }
     }
 }
 
 pub trait ExecutionStatement {
-    fn execute(&self) -> Result<u64>;
+    fn execute(&self) -> Result<'_, u64>;
+
+    /// Bind the value to the parameter at the index starting from 1. None
+    /// binds NULL.
+    fn bind(&mut self, index: usize, _value: Option<Value>) -> Result<'static, ()> {
+        Err(parameter_out_of_range(index))
+    }
+
+    /// Bind the value to the named parameter (e.g. ":name").
+    fn bind_named(&mut self, name: &str, _value: Option<Value>) -> Result<'static, ()> {
+        Err(parameter_not_found(name))
+    }
+
+    /// The details of EXPLAIN QUERY PLAN for the statement. Empty if the
+    /// statement does not scan any table.
+    fn explain(&self) -> Result<'static, Vec<String>> {
+        Ok(Vec::new())
+    }
 }
 
 pub enum Statement<'conn> {
-    Query(SelectStatement<'conn>),
+    Query(Box<SelectStatement<'conn>>),
     Execution(Box<dyn ExecutionStatement + 'conn>),
+    Explain(ExplainStatement),
 }
 
 impl<'conn> Statement<'conn> {
@@ -459,23 +1900,159 @@ impl<'conn> Statement<'conn> {
         match self {
             Self::Query(stmt) => stmt.query(),
             Self::Execution(_) => bail!("execute statement not support query"),
+            Self::Explain(_) => bail!("explain statement not support query"),
         }
     }
 
-    pub fn execute(&'conn self) -> Result<u64> {
+    pub fn execute(&'conn self) -> Result<'conn, u64> {
         match self {
             Self::Query(_) => Err(Error::Unsupported("select statement not support execute")),
             Self::Execution(stmt) => stmt.execute(),
+            Self::Explain(_) => Err(Error::Unsupported("explain statement not support execute")),
+        }
+    }
+
+    /// Bind the value to the parameter at the index starting from 1. None
+    /// binds NULL.
+    pub fn bind(&mut self, index: usize, value: Option<Value>) -> Result<'static, ()> {
+        match self {
+            Self::Query(stmt) => stmt.bind(index, value),
+            Self::Execution(stmt) => stmt.bind(index, value),
+            Self::Explain(_) => Err(parameter_out_of_range(index)),
+        }
+    }
+
+    /// Bind the value to the named parameter (e.g. ":name").
+    pub fn bind_named(&mut self, name: &str, value: Option<Value>) -> Result<'static, ()> {
+        match self {
+            Self::Query(stmt) => stmt.bind_named(name, value),
+            Self::Execution(stmt) => stmt.bind_named(name, value),
+            Self::Explain(_) => Err(parameter_not_found(name)),
         }
     }
 }
 
+/// A row of EXPLAIN QUERY PLAN. The rows form a tree as in SQLite.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryPlanRow {
+    pub id: u32,
+    /// The id of the parent row. 0 for the top level rows.
+    pub parent: u32,
+    pub detail: String,
+}
+
+/// EXPLAIN QUERY PLAN. The plan is generated on preparing the statement.
+pub struct ExplainStatement {
+    rows: Vec<QueryPlanRow>,
+}
+
+impl ExplainStatement {
+    pub fn rows(&self) -> &[QueryPlanRow] {
+        &self.rows
+    }
+}
+
+fn parameter_out_of_range(index: usize) -> Error<'static> {
+    Error::Other(anyhow::anyhow!("parameter index out of range: {index}"))
+}
+
+fn parameter_not_found(name: &str) -> Error<'static> {
+    Error::Other(anyhow::anyhow!("no such parameter: {name}"))
+}
+
+/// Validate the index of the parameter.
+fn parameter_index(parameters: &Parameters, index: usize) -> Result<'static, u32> {
+    if index == 0 || index > parameters.len() {
+        return Err(parameter_out_of_range(index));
+    }
+    Ok(index as u32)
+}
+
+/// Look up the index of the named parameter.
+fn named_parameter_index(parameters: &Parameters, name: &str) -> Result<'static, u32> {
+    parameters
+        .index(name.as_bytes())
+        .ok_or_else(|| parameter_not_found(name))
+}
+
+/// BEGIN, COMMIT, ROLLBACK, SAVEPOINT, RELEASE and ROLLBACK TO.
+enum TransactionControl {
+    Begin(TransactionType),
+    Commit,
+    Rollback,
+    Savepoint(Vec<u8>),
+    Release(Vec<u8>),
+    RollbackTo(Vec<u8>),
+}
+
+pub struct TransactionStatement<'conn> {
+    conn: &'conn Connection,
+    control: TransactionControl,
+}
+
+impl ExecutionStatement for TransactionStatement<'_> {
+    fn execute(&self) -> Result<'_, u64> {
+        if self.conn.ref_count.get() != 0 {
+            return Err(Error::Other(anyhow::anyhow!("other statments running")));
+        }
+        match &self.control {
+            TransactionControl::Begin(transaction_type) => {
+                self.conn.begin_transaction(*transaction_type)?
+            }
+            TransactionControl::Commit => self.conn.commit_transaction()?,
+            TransactionControl::Rollback => self.conn.rollback_transaction()?,
+            TransactionControl::Savepoint(name) => self.conn.savepoint(name),
+            TransactionControl::Release(name) => self.conn.release_savepoint(name)?,
+            TransactionControl::RollbackTo(name) => self.conn.rollback_to_savepoint(name)?,
+        }
+        Ok(0)
+    }
+}
+
 pub struct SelectStatement<'conn> {
     conn: &'conn Connection,
     table_page_id: PageId,
     columns: Vec<Expression>,
     filter: Expression,
     query_plan: QueryPlan,
+    /// None if the statement is not an aggregate query.
+    aggregation: Option<Box<Aggregation>>,
+    /// Empty if the rows from the query plan do not need to be sorted.
+    sort_keys: Vec<(Expression, SortOrder)>,
+    limit: Option<Expression>,
+    offset: Option<Expression>,
+    sorter_memory_limit: usize,
+    parameters: Parameters,
+    /// None if the query plan does not depend on the bound values.
+    plan_inputs: Option<Box<PlanInputs>>,
+}
+
+/// The inputs to plan the query again with new bound values.
+struct PlanInputs {
+    table_name: Vec<u8>,
+    referenced_columns: Vec<ColumnNumber>,
+    /// The sort keys before the query plan satisfies them.
+    order_by: Vec<(Expression, SortOrder)>,
+}
+
+/// Generate the query plan for the SELECT statement.
+///
+/// `sort_keys` are cleared if the query plan returns the rows in the order.
+fn plan_select(
+    table: &Table,
+    filter: &Expression,
+    referenced_columns: &[ColumnNumber],
+    sort_keys: &mut Vec<(Expression, SortOrder)>,
+    aggregation: Option<&mut Aggregation>,
+) -> QueryPlan {
+    let mut query_plan = QueryPlan::generate(table, filter, Some(referenced_columns));
+    if let Some(aggregation) = aggregation {
+        aggregation.group_by_sorted = query_plan.is_sorted_by(table, &aggregation.group_by);
+    } else if query_plan.sort_by(table, sort_keys) {
+        sort_keys.clear();
+    }
+    query_plan.use_covering_index(table, referenced_columns);
+    query_plan
 }
 
 impl<'conn> SelectStatement<'conn> {
@@ -492,14 +2069,135 @@ impl<'conn> SelectStatement<'conn> {
             columns,
             filter,
             query_plan,
+            aggregation: None,
+            sort_keys: Vec::new(),
+            limit: None,
+            offset: None,
+            sorter_memory_limit: DEFAULT_SORTER_MEMORY_LIMIT,
+            parameters: Parameters::default(),
+            plan_inputs: None,
         }
     }
 
+    /// Bind the value to the parameter at the index starting from 1. None
+    /// binds NULL.
+    pub fn bind(&mut self, index: usize, value: Option<Value>) -> Result<'static, ()> {
+        let index = parameter_index(&self.parameters, index)?;
+        self.bind_parameter(index, value)
+    }
+
+    /// Bind the value to the named parameter (e.g. ":name").
+    pub fn bind_named(&mut self, name: &str, value: Option<Value>) -> Result<'static, ()> {
+        let index = named_parameter_index(&self.parameters, name)?;
+        self.bind_parameter(index, value)
+    }
+
+    fn bind_parameter(&mut self, index: u32, value: Option<Value>) -> Result<'static, ()> {
+        let value = value.map(ConstantValue::copy_from);
+        let aggregation = self.aggregation.as_deref_mut();
+        let plan_inputs = self.plan_inputs.as_deref_mut();
+        for expr in self
+            .columns
+            .iter_mut()
+            .chain(std::iter::once(&mut self.filter))
+            .chain(self.sort_keys.iter_mut().map(|(expr, _)| expr))
+            .chain(self.limit.iter_mut())
+            .chain(self.offset.iter_mut())
+            .chain(aggregation.into_iter().flat_map(|aggregation| {
+                aggregation
+                    .group_by
+                    .iter_mut()
+                    .map(|(expr, _)| expr)
+                    .chain(aggregation.having.iter_mut())
+                    .chain(
+                        aggregation
+                            .aggregates
+                            .iter_mut()
+                            .filter_map(|aggregate| aggregate.arg.as_mut()),
+                    )
+            }))
+            .chain(
+                plan_inputs
+                    .into_iter()
+                    .flat_map(|inputs| inputs.order_by.iter_mut().map(|(expr, _)| expr)),
+            )
+        {
+            expr.bind_parameter(index, &value);
+        }
+        self.replan()
+    }
+
+    /// Plan the query again since the bound values may allow a better plan
+    /// (e.g. "WHERE id = ?").
+    fn replan(&mut self) -> Result<'static, ()> {
+        let Some(plan_inputs) = self.plan_inputs.as_deref() else {
+            return Ok(());
+        };
+        let conn = self.conn;
+        if conn.schema.borrow().is_none() {
+            conn.load_schema()?;
+        }
+        let schema_cell = conn.schema.borrow();
+        let schema = schema_cell.as_ref().unwrap();
+        let table = schema
+            .get_table(&plan_inputs.table_name)
+            .ok_or(anyhow::anyhow!(
+                "table not found: {:?}",
+                std::str::from_utf8(&plan_inputs.table_name).unwrap_or_default()
+            ))?;
+        let mut sort_keys = plan_inputs.order_by.clone();
+        self.query_plan = plan_select(
+            table,
+            &self.filter,
+            &plan_inputs.referenced_columns,
+            &mut sort_keys,
+            self.aggregation.as_deref_mut(),
+        );
+        self.sort_keys = sort_keys;
+        Ok(())
+    }
+
+    fn explain(&self) -> Result<'static, Vec<String>> {
+        let mut details = vec![self
+            .conn
+            .explain_query_plan(self.table_page_id, &self.query_plan)?];
+        if let Some(aggregation) = self.aggregation.as_deref() {
+            if !aggregation.group_by.is_empty() && !aggregation.group_by_sorted {
+                details.push("USE TEMP B-TREE FOR GROUP BY".to_string());
+            }
+        }
+        if !self.sort_keys.is_empty() {
+            details.push("USE TEMP B-TREE FOR ORDER BY".to_string());
+        }
+        Ok(details)
+    }
+
     pub fn query(&'conn self) -> anyhow::Result<Rows<'conn>> {
         let read_txn = self.conn.start_read()?;
+        self.rows(Some(read_txn))
+    }
+
+    /// Start the query. The caller holds the write transaction if `read_txn`
+    /// is None (e.g. INSERT ... SELECT).
+    fn rows(&'conn self, read_txn: Option<ReadTransaction<'conn>>) -> anyhow::Result<Rows<'conn>> {
         // TODO: check schema version.
 
-        let query = Query::new(
+        // Negative limit means no limit.
+        let n_remaining = self
+            .limit
+            .as_ref()
+            .map(evaluate_integer)
+            .transpose()?
+            .and_then(|limit| u64::try_from(limit).ok());
+        let n_skip = self
+            .offset
+            .as_ref()
+            .map(evaluate_integer)
+            .transpose()?
+            .map(|offset| offset.max(0) as u64)
+            .unwrap_or(0);
+
+        let mut query = Query::new(
             self.table_page_id,
             &self.conn.pager,
             &self.conn.btree_ctx,
@@ -507,46 +2205,347 @@ impl<'conn> SelectStatement<'conn> {
             &self.filter,
         )?;
 
-        Ok(Rows {
-            _read_txn: read_txn,
-            stmt: self,
-            query,
-        })
-    }
+        let sorted_rows = if let Some(aggregation) = self.aggregation.as_deref() {
+            Some(self.aggregate(aggregation, &mut query)?)
+        } else if self.sort_keys.is_empty() {
+            None
+        } else {
+            let mut sorter = Sorter::new(
+                self.sort_keys
+                    .iter()
+                    .map(|(_, order)| order.clone())
+                    .collect(),
+                self.sorter_memory_limit,
+            );
+            while let Some(data) = query.next()? {
+                let mut row = Vec::with_capacity(self.sort_keys.len() + self.columns.len());
+                for expr in self
+                    .sort_keys
+                    .iter()
+                    .map(|(expr, _)| expr)
+                    .chain(self.columns.iter())
+                {
+                    let (value, _, _) = expr.execute(Some(&data))?;
+                    row.push(value.map(ConstantValue::copy_from));
+                }
+                sorter.add(row)?;
+            }
+            Some(sorter.finish()?)
+        };
+
+        Ok(Rows {
+            _read_txn: read_txn,
+            stmt: self,
+            query,
+            sorted_rows,
+            n_skip,
+            n_remaining,
+        })
+    }
+
+    /// Group the rows from the query and return the result rows sorted by the
+    /// sort keys.
+    ///
+    /// Each result row consists of the sort keys followed by the result
+    /// columns.
+    fn aggregate(
+        &self,
+        aggregation: &Aggregation,
+        query: &mut Query,
+    ) -> anyhow::Result<SortedRows> {
+        let mut output = Sorter::new(
+            self.sort_keys
+                .iter()
+                .map(|(_, order)| order.clone())
+                .collect(),
+            self.sorter_memory_limit,
+        );
+        let orders = aggregation
+            .group_by
+            .iter()
+            .map(|(_, order)| order.clone())
+            .collect::<Vec<_>>();
+        let mut group = None;
+        if aggregation.group_by.is_empty() || aggregation.group_by_sorted {
+            while let Some(data) = query.next()? {
+                let row = aggregation.input_row(&data)?;
+                self.add_to_group(aggregation, &orders, &mut group, &row, &mut output)?;
+            }
+        } else {
+            let mut sorter = Sorter::new(orders.clone(), self.sorter_memory_limit);
+            while let Some(data) = query.next()? {
+                sorter.add(aggregation.input_row(&data)?)?;
+            }
+            let mut rows = sorter.finish()?;
+            while let Some(row) = rows.next()? {
+                self.add_to_group(aggregation, &orders, &mut group, row, &mut output)?;
+            }
+        }
+        match group {
+            Some(group) => self.output_group(aggregation, group, &mut output)?,
+            // An aggregate query without GROUP BY returns a row even if there
+            // are no rows.
+            None if aggregation.group_by.is_empty() => self.output_group(
+                aggregation,
+                Group::new(aggregation, Vec::new()),
+                &mut output,
+            )?,
+            None => {}
+        }
+        output.finish()
+    }
+
+    /// Accumulate an input row built by [Aggregation::input_row()].
+    ///
+    /// The current group is output if the row belongs to a new group.
+    fn add_to_group(
+        &self,
+        aggregation: &Aggregation,
+        orders: &[SortOrder],
+        group: &mut Option<Group>,
+        row: &SortRow,
+        output: &mut Sorter,
+    ) -> anyhow::Result<()> {
+        let n_keys = aggregation.group_by.len();
+        if let Some(current) = group {
+            if compare_rows(orders, &current.key, row) != Ordering::Equal {
+                let current = group.take().unwrap();
+                self.output_group(aggregation, current, output)?;
+            }
+        }
+        let group = group.get_or_insert_with(|| Group::new(aggregation, row[..n_keys].to_vec()));
+
+        let mut load = true;
+        for (i, (aggregator, call)) in group
+            .aggregators
+            .iter_mut()
+            .zip(aggregation.aggregates.iter())
+            .enumerate()
+        {
+            let loaded = aggregator.step(row[n_keys + i].as_ref().map(|v| v.as_value()));
+            // Bare columns follow the last min() or max() as SQLite does.
+            if matches!(
+                call.function,
+                AggregateFunction::Min | AggregateFunction::Max
+            ) {
+                load = loaded;
+            }
+        }
+        if load {
+            group
+                .bare_values
+                .clone_from_slice(&row[n_keys + aggregation.aggregates.len()..]);
+        }
+        Ok(())
+    }
+
+    fn output_group(
+        &self,
+        aggregation: &Aggregation,
+        group: Group,
+        output: &mut Sorter,
+    ) -> anyhow::Result<()> {
+        let aggregates = group
+            .aggregators
+            .iter()
+            .map(|aggregator| aggregator.finalize())
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let data = GroupData {
+            columns: &aggregation.bare_columns,
+            values: &group.bare_values,
+            aggregates: &aggregates,
+        };
+        if let Some(having) = &aggregation.having {
+            if !having.execute(Some(&data))?.0.is_some_and(|v| v.as_bool()) {
+                return Ok(());
+            }
+        }
+        let mut row = Vec::with_capacity(self.sort_keys.len() + self.columns.len());
+        for expr in self
+            .sort_keys
+            .iter()
+            .map(|(expr, _)| expr)
+            .chain(self.columns.iter())
+        {
+            let (value, _, _) = expr.execute(Some(&data))?;
+            row.push(value.map(ConstantValue::copy_from));
+        }
+        output.add(row)
+    }
+}
+
+/// The grouping and aggregate functions of an aggregate query.
+struct Aggregation {
+    group_by: Vec<(Expression, SortOrder)>,
+    /// Whether the rows from the query plan are already sorted by the GROUP BY
+    /// terms.
+    group_by_sorted: bool,
+    aggregates: Vec<AggregateCall>,
+    /// The columns referenced out of the aggregate functions.
+    bare_columns: Vec<ColumnNumber>,
+    having: Option<Expression>,
+}
+
+impl Aggregation {
+    /// Build a row which consists of the GROUP BY terms, the arguments of the
+    /// aggregate functions and the bare columns.
+    fn input_row(&self, data: &RowData) -> anyhow::Result<SortRow> {
+        let mut row = Vec::with_capacity(
+            self.group_by.len() + self.aggregates.len() + self.bare_columns.len(),
+        );
+        for (expr, _) in self.group_by.iter() {
+            let (value, _, _) = expr.execute(Some(data))?;
+            row.push(value.map(ConstantValue::copy_from));
+        }
+        for call in self.aggregates.iter() {
+            let value = match &call.arg {
+                Some(arg) => arg.execute(Some(data))?.0,
+                None => None,
+            };
+            row.push(value.map(ConstantValue::copy_from));
+        }
+        for column in self.bare_columns.iter() {
+            let value = data
+                .get_column_value(column)
+                .map_err(|e| anyhow::anyhow!(e))?;
+            row.push(value.map(ConstantValue::copy_from));
+        }
+        Ok(row)
+    }
+}
+
+struct Group {
+    /// The values of the GROUP BY terms.
+    key: SortRow,
+    aggregators: Vec<Aggregator>,
+    bare_values: Vec<Option<ConstantValue>>,
+}
+
+impl Group {
+    fn new(aggregation: &Aggregation, key: SortRow) -> Self {
+        Self {
+            key,
+            aggregators: aggregation
+                .aggregates
+                .iter()
+                .map(|call| call.aggregator())
+                .collect(),
+            bare_values: vec![None; aggregation.bare_columns.len()],
+        }
+    }
+}
+
+/// The context to evaluate expressions of an aggregate query for a group.
+struct GroupData<'a> {
+    columns: &'a [ColumnNumber],
+    values: &'a [Option<ConstantValue>],
+    aggregates: &'a [Option<ConstantValue>],
+}
+
+impl DataContext for GroupData<'_> {
+    fn get_column_value(
+        &self,
+        column_idx: &ColumnNumber,
+    ) -> std::result::Result<Option<Value<'_>>, Box<dyn std::error::Error + Sync + Send>> {
+        let Some(i) = self.columns.iter().position(|column| column == column_idx) else {
+            return Err("column not in group".into());
+        };
+        Ok(self.values[i].as_ref().map(|v| v.as_value()))
+    }
+
+    fn get_aggregate_value(
+        &self,
+        aggregate_idx: usize,
+    ) -> std::result::Result<Option<Value<'_>>, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(self.aggregates[aggregate_idx]
+            .as_ref()
+            .map(|v| v.as_value()))
+    }
+}
+
+/// Evaluate a constant expression of LIMIT or OFFSET.
+fn evaluate_integer(expr: &Expression) -> anyhow::Result<i64> {
+    let (value, _, _) = expr.execute::<RowData>(None)?;
+    match value.map(|v| v.apply_numeric_affinity()) {
+        Some(Value::Integer(i)) => Ok(i),
+        _ => bail!("datatype mismatch"),
+    }
 }
 
 pub struct Rows<'conn> {
-    _read_txn: ReadTransaction<'conn>,
+    _read_txn: Option<ReadTransaction<'conn>>,
     stmt: &'conn SelectStatement<'conn>,
     query: Query<'conn>,
+    sorted_rows: Option<SortedRows>,
+    /// The number of rows to skip for OFFSET.
+    n_skip: u64,
+    /// The number of rows to return for LIMIT.
+    n_remaining: Option<u64>,
 }
 
 impl<'conn> Rows<'conn> {
-    pub fn next_row(&mut self) -> Result<Option<Row<'_>>> {
-        if let Some(data) = self.query.next()? {
-            Ok(Some(Row {
-                stmt: self.stmt,
-                data,
-            }))
-        } else {
-            Ok(None)
+    pub fn next_row(&mut self) -> Result<'_, Option<Row<'_>>> {
+        while self.n_skip > 0 {
+            self.n_skip -= 1;
+            let exists = if let Some(sorted_rows) = &mut self.sorted_rows {
+                sorted_rows.next()?.is_some()
+            } else {
+                self.query.next()?.is_some()
+            };
+            if !exists {
+                return Ok(None);
+            }
+        }
+        if let Some(n_remaining) = &mut self.n_remaining {
+            if *n_remaining == 0 {
+                return Ok(None);
+            }
+            *n_remaining -= 1;
         }
+
+        let content = if let Some(sorted_rows) = &mut self.sorted_rows {
+            sorted_rows
+                .next()?
+                .map(|row| RowContent::Sorted(&row[self.stmt.sort_keys.len()..]))
+        } else {
+            self.query.next()?.map(RowContent::Data)
+        };
+        Ok(content.map(|content| Row {
+            stmt: self.stmt,
+            content,
+        }))
     }
 }
 
+enum RowContent<'a> {
+    Data(RowData<'a>),
+    /// The values of the result columns.
+    Sorted(&'a [Option<ConstantValue>]),
+}
+
 pub struct Row<'a> {
     stmt: &'a SelectStatement<'a>,
-    data: RowData<'a>,
+    content: RowContent<'a>,
 }
 
 impl<'a> Row<'a> {
-    pub fn parse(&self) -> Result<Columns<'_>> {
-        let mut columns = Vec::with_capacity(self.stmt.columns.len());
-        for expr in self.stmt.columns.iter() {
-            let (value, _, _) = expr.execute(Some(&self.data))?;
-            columns.push(value);
+    pub fn parse(&self) -> Result<'_, Columns<'_>> {
+        match &self.content {
+            RowContent::Data(data) => {
+                let mut columns = Vec::with_capacity(self.stmt.columns.len());
+                for expr in self.stmt.columns.iter() {
+                    let (value, _, _) = expr.execute(Some(data))?;
+                    columns.push(value);
+                }
+                Ok(Columns(columns))
+            }
+            RowContent::Sorted(values) => Ok(Columns(
+                values
+                    .iter()
+                    .map(|v| v.as_ref().map(|v| v.as_value()))
+                    .collect(),
+            )),
         }
-        Ok(Columns(columns))
     }
 }
 
@@ -582,6 +2581,7 @@ struct InsertRecord {
 struct IndexSchema {
     root_page_id: PageId,
     columns: Vec<(ColumnNumber, Collation)>,
+    unique: bool,
 }
 
 impl IndexSchema {
@@ -603,188 +2603,5088 @@ impl IndexSchema {
         IndexSchema {
             root_page_id: index.root_page_id,
             columns,
+            unique: index.unique,
         }
     }
-}
-
-pub struct InsertStatement<'conn> {
-    conn: &'conn Connection,
-    table_page_id: PageId,
-    records: Vec<InsertRecord>,
-    indexes: Vec<IndexSchema>,
-}
 
-impl<'conn> ExecutionStatement for InsertStatement<'conn> {
-    fn execute(&self) -> Result<u64> {
-        let write_txn = self.conn.start_write()?;
+    /// Insert a key to the index.
+    ///
+    /// The last value of the key is the rowid. If the index is unique, this
+    /// fails when another key with the same values except the rowid exists.
+    /// Keys containing NULL never conflict.
+    fn insert<'a>(&self, conn: &Connection, key: &[Option<&Value>]) -> Result<'a, ()> {
+        let mut index_cursor = BtreeCursor::new(self.root_page_id, &conn.pager, &conn.btree_ctx)?;
+        self.insert_with_cursor(&mut index_cursor, key)
+    }
 
-        let mut cursor =
-            BtreeCursor::new(self.table_page_id, &self.conn.pager, &self.conn.btree_ctx)?;
-        let mut n = 0;
-        for record in self.records.iter() {
-            let mut rowid = None;
-            if let Some(rowid_expr) = &record.rowid {
-                let (rowid_value, _, _) = rowid_expr.execute::<RowData>(None)?;
-                // NULL then fallback to generate new rowid.
-                if let Some(rowid_value) = rowid_value {
-                    match rowid_value.apply_numeric_affinity() {
-                        Value::Integer(rowid_value) => {
-                            rowid = Some(rowid_value);
-                        }
-                        _ => return Err(Error::DataTypeMismatch),
-                    }
-                }
+    /// Insert a key to the index with the cursor of the index.
+    fn insert_with_cursor<'a>(
+        &self,
+        index_cursor: &mut BtreeCursor,
+        key: &[Option<&Value>],
+    ) -> Result<'a, ()> {
+        let comparators = self
+            .columns
+            .iter()
+            .zip(key.iter())
+            .map(|((_, collation), v)| v.map(|v| ValueCmp::new(v, collation)))
+            .collect::<Vec<_>>();
+        if self.unique {
+            let unique_comparators = &comparators[..comparators.len() - 1];
+            if unique_comparators.iter().all(|c| c.is_some())
+                && index_cursor.index_move_to(unique_comparators)?
+            {
+                return Err(Error::UniqueConstraintViolation);
             }
-            let rowid = if let Some(rowid) = rowid {
-                rowid
-            } else {
-                cursor.move_to_last()?;
-                let last_rowid = cursor.get_table_key()?.unwrap_or(0);
-                // TODO: 32-bit rowid support.
-                if last_rowid == MAX_ROWID {
-                    todo!("find unused rowid randomly");
-                } else {
-                    last_rowid + 1
+        }
+        index_cursor.index_insert(&comparators, &RecordPayload::new(key)?)?;
+        Ok(())
+    }
+
+    /// Compare 2 keys in the order of the index.
+    ///
+    /// NULL is smaller than any other value.
+    fn compare_keys(
+        &self,
+        key1: &[Option<ConstantValue>],
+        key2: &[Option<ConstantValue>],
+    ) -> Ordering {
+        for (((_, collation), v1), v2) in self.columns.iter().zip(key1).zip(key2) {
+            let ordering = match (v1, v2) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Less,
+                (Some(_), None) => Ordering::Greater,
+                (Some(v1), Some(v2)) => {
+                    ValueCmp::new(&v1.as_value(), collation).compare(&v2.as_value())
                 }
             };
-
-            // Check rowid conflict
-            let current_rowid = cursor.table_move_to(rowid)?;
-            if current_rowid.is_some() && current_rowid.unwrap() == rowid {
-                return Err(Error::UniqueConstraintViolation);
+            if ordering != Ordering::Equal {
+                return ordering;
             }
+        }
+        Ordering::Equal
+    }
 
-            let mut columns = Vec::with_capacity(record.columns.len());
-            for (expr, type_affinity) in record.columns.iter() {
-                let (value, _, _) = expr.execute::<RowData>(None)?;
-                let value = value.map(|v| v.apply_affinity(*type_affinity));
-                columns.push(value);
-            }
+    /// Find the existing key which has the same values as the key except the
+    /// rowid, and return its rowid.
+    ///
+    /// Keys containing NULL never conflict.
+    fn find_conflict(
+        &self,
+        conn: &Connection,
+        key: &[Option<&Value>],
+    ) -> Result<'static, Option<i64>> {
+        let n_key_columns = self.columns.len() - 1;
+        let Some(comparators) = self
+            .columns
+            .iter()
+            .zip(key.iter())
+            .take(n_key_columns)
+            .map(|((_, collation), v)| v.map(|v| Some(ValueCmp::new(v, collation))))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return Ok(None);
+        };
+        let mut index_cursor = BtreeCursor::new(self.root_page_id, &conn.pager, &conn.btree_ctx)?;
+        if !index_cursor.index_move_to(&comparators)? {
+            return Ok(None);
+        }
+        let Some(payload) = index_cursor.get_index_payload()? else {
+            return Err(Error::Other(anyhow::anyhow!("index entry not found")));
+        };
+        let mut record = parse_record(&payload)?;
+        match record.get(n_key_columns)? {
+            Some(Value::Integer(rowid)) => Ok(Some(rowid)),
+            _ => Err(Error::Other(anyhow::anyhow!("invalid rowid in index"))),
+        }
+    }
 
-            cursor.table_insert(
-                rowid,
-                &RecordPayload::new(&columns.iter().map(|v| v.as_ref()).collect::<Vec<_>>())?,
-            )?;
+    /// Build the key of the row. The last value of the key is the rowid.
+    fn key<'a, 'b>(
+        &self,
+        rowid: &'a Value<'b>,
+        columns: &'a [Option<Value<'b>>],
+    ) -> Vec<Option<&'a Value<'b>>> {
+        self.columns
+            .iter()
+            .map(|(column_number, _)| match column_number {
+                ColumnNumber::RowId => Some(rowid),
+                ColumnNumber::Column(column_idx) => columns[*column_idx].as_ref(),
+            })
+            .collect()
+    }
 
-            let row_id = Value::Integer(rowid);
-            for index in self.indexes.iter() {
-                let index_columns = index
-                    .columns
+    /// Whether the key columns except the rowid are the columns in any order.
+    fn has_key_columns(&self, columns: &[ColumnNumber]) -> bool {
+        let key_columns = &self.columns[..self.columns.len() - 1];
+        key_columns.len() == columns.len()
+            && key_columns
+                .iter()
+                .all(|(column_number, _)| columns.contains(column_number))
+    }
+
+    /// Delete a key from the index.
+    ///
+    /// The last value of the key is the rowid.
+    fn delete<'a>(&self, conn: &Connection, key: &[Option<&Value>]) -> Result<'a, ()> {
+        let comparators = self
+            .columns
+            .iter()
+            .zip(key.iter())
+            .map(|((_, collation), v)| v.map(|v| ValueCmp::new(v, collation)))
+            .collect::<Vec<_>>();
+        let mut index_cursor = BtreeCursor::new(self.root_page_id, &conn.pager, &conn.btree_ctx)?;
+        if !index_cursor.index_move_to(&comparators)? {
+            return Err(Error::Other(anyhow::anyhow!("index entry not found")));
+        }
+        index_cursor.delete()?;
+        Ok(())
+    }
+
+    /// Scan the index and compute the statistics in the format of
+    /// sqlite_stat1.
+    ///
+    /// NULLs are counted as the same value as sqlite3 does. None if the index is
+    /// empty.
+    fn analyze<'a>(&self, conn: &Connection) -> Result<'a, Option<IndexStats>> {
+        // Except the rowid.
+        let key_columns = &self.columns[..self.columns.len() - 1];
+        let mut n_rows = 0;
+        let mut n_distinct = vec![0; key_columns.len()];
+        let mut prev_values: Option<Vec<Option<ConstantValue>>> = None;
+        let mut cursor = BtreeCursor::new(self.root_page_id, &conn.pager, &conn.btree_ctx)?;
+        cursor.move_to_first()?;
+        loop {
+            let Some(payload) = cursor.get_index_payload()? else {
+                break;
+            };
+            let mut record = parse_record(&payload)?;
+            let mut values = Vec::with_capacity(key_columns.len());
+            for i in 0..key_columns.len() {
+                values.push(record.get(i)?.map(ConstantValue::copy_from));
+            }
+            drop(record);
+            drop(payload);
+
+            // The leading columns before the first different one share the
+            // values with the previous entry.
+            let n_same = prev_values.as_ref().map_or(0, |prev_values| {
+                prev_values
                     .iter()
-                    .map(|(column_number, _)| match column_number {
-                        ColumnNumber::RowId => Some(&row_id),
-                        ColumnNumber::Column(column_idx) => columns[*column_idx].as_ref(),
+                    .zip(values.iter())
+                    .zip(key_columns.iter())
+                    .take_while(|((prev, value), (_, collation))| match (prev, value) {
+                        (None, None) => true,
+                        (Some(prev), Some(value)) => {
+                            ValueCmp::new(&prev.as_value(), collation).compare(&value.as_value())
+                                == Ordering::Equal
+                        }
+                        _ => false,
                     })
-                    .collect::<Vec<_>>();
-                let comparators = index
-                    .columns
-                    .iter()
-                    .zip(index_columns.iter())
-                    .map(|((_, collation), v)| v.map(|v| ValueCmp::new(v, collation)))
-                    .collect::<Vec<_>>();
-                let mut index_cursor =
-                    BtreeCursor::new(index.root_page_id, &self.conn.pager, &self.conn.btree_ctx)?;
-                index_cursor.index_insert(&comparators, &RecordPayload::new(&index_columns)?)?;
+                    .count()
+            });
+            for n in n_distinct[n_same..].iter_mut() {
+                *n += 1;
             }
-
-            n += 1;
+            n_rows += 1;
+            prev_values = Some(values);
+            cursor.move_next()?;
         }
+        if n_rows == 0 {
+            return Ok(None);
+        }
+        Ok(Some(IndexStats {
+            n_rows,
+            avg_eq: n_distinct
+                .iter()
+                .map(|n_distinct| n_rows.div_ceil(*n_distinct))
+                .collect(),
+        }))
+    }
 
-        write_txn.commit()?;
-
-        Ok(n)
+    /// Whether the index contains any of the columns.
+    fn contains_any(&self, columns: &[ColumnNumber]) -> bool {
+        self.columns
+            .iter()
+            .any(|(column_number, _)| columns.contains(column_number))
     }
 }
 
-pub struct ClearStatement<'conn> {
+pub struct CreateTableStatement<'conn> {
     conn: &'conn Connection,
-    table_page_id: PageId,
-    index_page_ids: Vec<PageId>,
+    table_name: Vec<u8>,
+    if_not_exists: bool,
+    sql: Vec<u8>,
+    /// The name of the index for the PRIMARY KEY which is not the rowid.
+    autoindex_name: Option<Vec<u8>>,
 }
 
-impl<'conn> ExecutionStatement for ClearStatement<'conn> {
-    fn execute(&self) -> Result<u64> {
+impl<'conn> ExecutionStatement for CreateTableStatement<'conn> {
+    fn execute(&self) -> Result<'_, u64> {
+        // The schema may be changed after the statement is prepared.
+        if self.conn.schema.borrow().is_none() {
+            self.conn.load_schema()?;
+        }
         let write_txn = self.conn.start_write()?;
 
-        let mut cursor =
-            BtreeCursor::new(self.table_page_id, &self.conn.pager, &self.conn.btree_ctx)?;
-
-        let n_deleted = cursor.clear()?;
-
-        for index_page_id in self.index_page_ids.iter() {
-            let mut cursor =
-                BtreeCursor::new(*index_page_id, &self.conn.pager, &self.conn.btree_ctx)?;
-            let n = cursor.clear()?;
-            if n != n_deleted {
-                return Err(Error::Other(anyhow::anyhow!(
-                    "number of deleted rows in table and index does not match"
-                )));
+        let schema_cell = self.conn.schema.borrow();
+        let schema = schema_cell.as_ref().unwrap();
+        if schema.get_table(&self.table_name).is_some()
+            || schema.get_index(&self.table_name).is_some()
+        {
+            if self.if_not_exists {
+                return Ok(0);
             }
+            return Err(Error::Other(anyhow::anyhow!(
+                "table {:?} already exists",
+                std::str::from_utf8(&self.table_name).unwrap_or_default()
+            )));
+        }
+        drop(schema_cell);
+
+        let root_page_id = allocate_btree(&self.conn.pager, &self.conn.btree_ctx, true)?;
+        self.conn.insert_schema_record(
+            b"table",
+            &self.table_name,
+            &self.table_name,
+            root_page_id,
+            Some(&self.sql),
+        )?;
+        if let Some(autoindex_name) = &self.autoindex_name {
+            let root_page_id = allocate_btree(&self.conn.pager, &self.conn.btree_ctx, false)?;
+            self.conn.insert_schema_record(
+                b"index",
+                autoindex_name,
+                &self.table_name,
+                root_page_id,
+                None,
+            )?;
         }
+        self.conn.increment_schema_cookie()?;
 
         write_txn.commit()?;
+        *self.conn.schema.borrow_mut() = None;
 
-        Ok(n_deleted)
+        Ok(0)
     }
 }
 
-pub struct DeleteStatement<'conn> {
+pub struct CreateIndexStatement<'conn> {
     conn: &'conn Connection,
-    table_page_id: PageId,
-    indexes: Vec<IndexSchema>,
-    filter: Expression,
-    query_plan: QueryPlan,
+    index_name: Vec<u8>,
+    table_name: Vec<u8>,
+    if_not_exists: bool,
+    sql: Vec<u8>,
 }
 
-impl<'conn> ExecutionStatement for DeleteStatement<'conn> {
-    fn execute(&self) -> Result<u64> {
+impl<'conn> ExecutionStatement for CreateIndexStatement<'conn> {
+    fn execute(&self) -> Result<'_, u64> {
+        // The schema may be changed after the statement is prepared.
+        if self.conn.schema.borrow().is_none() {
+            self.conn.load_schema()?;
+        }
         let write_txn = self.conn.start_write()?;
 
+        let schema_cell = self.conn.schema.borrow();
+        let schema = schema_cell.as_ref().unwrap();
+        if schema.get_table(&self.index_name).is_some()
+            || schema.get_index(&self.index_name).is_some()
+        {
+            if self.if_not_exists {
+                return Ok(0);
+            }
+            return Err(Error::Other(anyhow::anyhow!(
+                "index {:?} already exists",
+                std::str::from_utf8(&self.index_name).unwrap_or_default()
+            )));
+        }
+        let table = schema.get_table(&self.table_name).ok_or(anyhow::anyhow!(
+            "table not found: {:?}",
+            std::str::from_utf8(&self.table_name).unwrap_or_default()
+        ))?;
+
+        let root_page_id = allocate_btree(&self.conn.pager, &self.conn.btree_ctx, false)?;
+        let (_, _, index) = Index::parse(&self.sql, root_page_id, table)?;
+        let index = IndexSchema::create(table, &index);
+        let table_page_id = table.root_page_id;
+        drop(schema_cell);
+
+        // Collect the keys of existing rows and insert them in the key order,
+        // which keeps the index cursor moving forward.
+        let query_plan = QueryPlan::FullScan;
+        let filter = Expression::one();
         let mut query = Query::new(
-            self.table_page_id,
+            table_page_id,
             &self.conn.pager,
             &self.conn.btree_ctx,
-            &self.query_plan,
-            &self.filter,
+            &query_plan,
+            &filter,
         )?;
+        let mut keys = Vec::new();
+        while let Some(data) = query.next()? {
+            let key = index
+                .columns
+                .iter()
+                .map(|(column_idx, _)| {
+                    data.get_column_value(column_idx)
+                        .map(|value| value.map(ConstantValue::copy_from))
+                        .map_err(expression::Error::FailGetColumn)
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            keys.push(key);
+        }
+        drop(query);
+        keys.sort_by(|key1, key2| index.compare_keys(key1, key2));
 
-        let mut n_deleted = 0;
+        let mut index_cursor =
+            BtreeCursor::new(root_page_id, &self.conn.pager, &self.conn.btree_ctx)?;
+        for key in keys.iter() {
+            let values = key
+                .iter()
+                .map(|value| value.as_ref().map(ConstantValue::as_value))
+                .collect::<Vec<_>>();
+            let key = values.iter().map(|v| v.as_ref()).collect::<Vec<_>>();
+            index.insert_with_cursor(&mut index_cursor, &key)?;
+        }
+        drop(index_cursor);
 
-        loop {
-            let Some(data) = query.next()? else {
-                break;
-            };
+        self.conn.insert_schema_record(
+            b"index",
+            &self.index_name,
+            &self.table_name,
+            root_page_id,
+            Some(&self.sql),
+        )?;
+        self.conn.increment_schema_cookie()?;
 
-            // Delete from index
-            for index in &self.indexes {
-                let tmp_keys = index
-                    .columns
-                    .iter()
-                    .map(|(column_idx, collation)| {
-                        let value = data
-                            .get_column_value(column_idx)
-                            .map_err(expression::Error::FailGetColumn)?;
-                        Ok((value, collation))
-                    })
-                    .collect::<Result<Vec<_>>>()?;
-                let comparators = tmp_keys
-                    .iter()
-                    .map(|(v, c)| v.as_ref().map(|v| ValueCmp::new(v, c)))
-                    .collect::<Vec<_>>();
-                let mut index_cursor =
-                    BtreeCursor::new(index.root_page_id, &self.conn.pager, &self.conn.btree_ctx)?;
-                index_cursor.index_move_to(&comparators)?;
-                index_cursor.delete()?;
-            }
+        write_txn.commit()?;
+        *self.conn.schema.borrow_mut() = None;
 
-            drop(data);
-            query.delete()?;
+        Ok(0)
+    }
+}
 
-            n_deleted += 1;
+pub struct DropTableStatement<'conn> {
+    conn: &'conn Connection,
+    table_name: Vec<u8>,
+    if_exists: bool,
+}
+
+impl<'conn> ExecutionStatement for DropTableStatement<'conn> {
+    fn execute(&self) -> Result<'_, u64> {
+        // The schema may be changed after the statement is prepared.
+        if self.conn.schema.borrow().is_none() {
+            self.conn.load_schema()?;
         }
+        let write_txn = self.conn.start_write()?;
+
+        let schema_cell = self.conn.schema.borrow();
+        let schema = schema_cell.as_ref().unwrap();
+        if schema.get_table(&self.table_name).is_none() {
+            if self.if_exists {
+                return Ok(0);
+            }
+            return Err(Error::Other(anyhow::anyhow!(
+                "no such table: {:?}",
+                std::str::from_utf8(&self.table_name).unwrap_or_default()
+            )));
+        }
+        // Dropping sqlite_stat1 itself leaves no statistics to delete.
+        let stat_page_id = schema
+            .get_table(STAT1_TABLE_NAME)
+            .filter(|_| !self.table_name.eq_ignore_ascii_case(STAT1_TABLE_NAME))
+            .map(|table| table.root_page_id);
+        drop(schema_cell);
+
+        // The table and all the indexes of the table share tbl_name.
+        let root_page_ids = self.conn.delete_schema_records(2, &self.table_name)?;
+        for root_page_id in root_page_ids {
+            drop_btree(&self.conn.pager, &self.conn.btree_ctx, root_page_id)?;
+        }
+        self.conn
+            .delete_stat_records(stat_page_id, 0, &self.table_name)?;
+        self.conn.increment_schema_cookie()?;
 
         write_txn.commit()?;
+        *self.conn.schema.borrow_mut() = None;
 
-        Ok(n_deleted)
+        Ok(0)
+    }
+}
+
+pub struct DropIndexStatement<'conn> {
+    conn: &'conn Connection,
+    index_name: Vec<u8>,
+    if_exists: bool,
+}
+
+impl<'conn> ExecutionStatement for DropIndexStatement<'conn> {
+    fn execute(&self) -> Result<'_, u64> {
+        // The schema may be changed after the statement is prepared.
+        if self.conn.schema.borrow().is_none() {
+            self.conn.load_schema()?;
+        }
+        let write_txn = self.conn.start_write()?;
+
+        let schema_cell = self.conn.schema.borrow();
+        let schema = schema_cell.as_ref().unwrap();
+        if schema.get_index(&self.index_name).is_none() {
+            if self.if_exists {
+                return Ok(0);
+            }
+            return Err(Error::Other(anyhow::anyhow!(
+                "no such index: {:?}",
+                std::str::from_utf8(&self.index_name).unwrap_or_default()
+            )));
+        }
+        let stat_page_id = schema
+            .get_table(STAT1_TABLE_NAME)
+            .map(|table| table.root_page_id);
+        drop(schema_cell);
+
+        let root_page_ids = self.conn.delete_schema_records(1, &self.index_name)?;
+        for root_page_id in root_page_ids {
+            drop_btree(&self.conn.pager, &self.conn.btree_ctx, root_page_id)?;
+        }
+        self.conn
+            .delete_stat_records(stat_page_id, 1, &self.index_name)?;
+        self.conn.increment_schema_cookie()?;
+
+        write_txn.commit()?;
+        *self.conn.schema.borrow_mut() = None;
+
+        Ok(0)
+    }
+}
+
+pub struct AnalyzeStatement<'conn> {
+    conn: &'conn Connection,
+    /// The name of the table or the index. All tables if None.
+    name: Option<Vec<u8>>,
+}
+
+/// A table and its indexes to analyze.
+struct AnalyzeTarget {
+    table_name: Vec<u8>,
+    table_page_id: PageId,
+    /// The lowercased names and the schemas of the indexes.
+    indexes: Vec<(Vec<u8>, IndexSchema)>,
+}
+
+impl AnalyzeTarget {
+    fn new(
+        schema: &Schema,
+        table_name: &[u8],
+        table: &Table,
+        filter: impl Fn(&[u8]) -> bool,
+    ) -> Self {
+        let mut indexes = Vec::new();
+        let mut next_index = table.indexes.as_ref();
+        while let Some(index) = next_index {
+            if let Some((index_name, _)) = schema
+                .indexes()
+                .find(|(index_name, i)| Rc::ptr_eq(i, index) && filter(index_name))
+            {
+                indexes.push((index_name.to_vec(), IndexSchema::create(table, index)));
+            }
+            next_index = index.next.as_ref();
+        }
+        Self {
+            table_name: table_name.to_vec(),
+            table_page_id: table.root_page_id,
+            indexes,
+        }
+    }
+}
+
+impl<'conn> ExecutionStatement for AnalyzeStatement<'conn> {
+    fn execute(&self) -> Result<'_, u64> {
+        // The schema may be changed after the statement is prepared.
+        if self.conn.schema.borrow().is_none() {
+            self.conn.load_schema()?;
+        }
+        let write_txn = self.conn.start_write()?;
+
+        let schema_cell = self.conn.schema.borrow();
+        let schema = schema_cell.as_ref().unwrap();
+        // The rows to delete from sqlite_stat1 by the column index and the name.
+        let mut stale_stats = Vec::new();
+        let mut targets = Vec::new();
+        match &self.name {
+            None => {
+                let mut tables = schema.tables().collect::<Vec<_>>();
+                tables.sort_by_key(|(table_name, _)| *table_name);
+                for (table_name, table) in tables {
+                    // sqlite3 does not analyze the internal tables.
+                    if is_reserved_name(table_name) {
+                        continue;
+                    }
+                    stale_stats.push((0, table_name.to_vec()));
+                    targets.push(AnalyzeTarget::new(schema, table_name, table, |_| true));
+                }
+            }
+            Some(name) => {
+                let mut name = name.clone();
+                upper_to_lower(&mut name);
+                if let Some(table) = schema.get_table(&name) {
+                    if !is_reserved_name(&name) {
+                        stale_stats.push((0, name.clone()));
+                        targets.push(AnalyzeTarget::new(schema, &name, table, |_| true));
+                    }
+                } else if schema.get_index(&name).is_some() {
+                    stale_stats.push((1, name.clone()));
+                    targets.extend(
+                        schema
+                            .tables()
+                            .map(|(table_name, table)| {
+                                AnalyzeTarget::new(schema, table_name, table, |index_name| {
+                                    index_name == name
+                                })
+                            })
+                            .filter(|target| !target.indexes.is_empty()),
+                    );
+                } else {
+                    return Err(Error::Other(anyhow::anyhow!(
+                        "no such table or index: {:?}",
+                        std::str::from_utf8(&name).unwrap_or_default()
+                    )));
+                }
+            }
+        }
+        let stat_page_id = schema
+            .get_table(STAT1_TABLE_NAME)
+            .map(|table| table.root_page_id);
+        drop(schema_cell);
+
+        let stat_page_id = if let Some(stat_page_id) = stat_page_id {
+            stat_page_id
+        } else {
+            let root_page_id = allocate_btree(&self.conn.pager, &self.conn.btree_ctx, true)?;
+            self.conn.insert_schema_record(
+                b"table",
+                STAT1_TABLE_NAME,
+                STAT1_TABLE_NAME,
+                root_page_id,
+                Some(STAT1_TABLE_SQL),
+            )?;
+            self.conn.increment_schema_cookie()?;
+            root_page_id
+        };
+        for (column_idx, name) in stale_stats {
+            self.conn
+                .delete_stat_records(Some(stat_page_id), column_idx, &name)?;
+        }
+
+        let mut stats = Vec::new();
+        for target in targets {
+            if target.indexes.is_empty() {
+                // The table without indexes records only the number of rows.
+                let mut cursor =
+                    BtreeCursor::new(target.table_page_id, &self.conn.pager, &self.conn.btree_ctx)?;
+                cursor.move_to_first()?;
+                let mut n_rows = 0;
+                while cursor.get_table_key()?.is_some() {
+                    n_rows += 1;
+                    cursor.move_next()?;
+                }
+                if n_rows > 0 {
+                    let index_stats = IndexStats {
+                        n_rows,
+                        avg_eq: Vec::new(),
+                    };
+                    stats.push((target.table_name.clone(), None, index_stats));
+                }
+            }
+            for (index_name, index) in target.indexes {
+                if let Some(index_stats) = index.analyze(self.conn)? {
+                    stats.push((target.table_name.clone(), Some(index_name), index_stats));
+                }
+            }
+        }
+
+        let mut cursor = BtreeCursor::new(stat_page_id, &self.conn.pager, &self.conn.btree_ctx)?;
+        cursor.move_to_last()?;
+        let mut rowid = cursor.get_table_key()?.unwrap_or(0);
+        for (table_name, index_name, index_stats) in stats {
+            rowid += 1;
+            let table_name = Value::Text(Buffer::Ref(&table_name));
+            let index_name = index_name
+                .as_ref()
+                .map(|index_name| Value::Text(Buffer::Ref(index_name)));
+            let stat = index_stats.format();
+            let stat = Value::Text(Buffer::Ref(&stat));
+            cursor.table_insert(
+                rowid,
+                &RecordPayload::new(&[Some(&table_name), index_name.as_ref(), Some(&stat)])?,
+            )?;
+        }
+        drop(cursor);
+
+        write_txn.commit()?;
+        // Reload the statistics.
+        *self.conn.schema.borrow_mut() = None;
+
+        Ok(0)
+    }
+}
+
+pub struct InsertStatement<'conn> {
+    conn: &'conn Connection,
+    table_page_id: PageId,
+    n_columns: usize,
+    records: Vec<InsertRecord>,
+    /// The query generating the rows for INSERT ... SELECT.
+    query: Option<Box<InsertQuery<'conn>>>,
+    indexes: Vec<IndexSchema>,
+    /// The resolution of the conflicts which the upsert does not handle.
+    conflict: ConflictResolution,
+    upsert: Option<UpsertClause>,
+    parameters: Parameters,
+}
+
+struct InsertQuery<'conn> {
+    select: SelectStatement<'conn>,
+    /// The target column of each result column of the query.
+    columns: Vec<ColumnNumber>,
+    /// The values of the columns not in the target columns.
+    default_columns: Vec<(Expression, TypeAffinity)>,
+}
+
+/// The uniqueness constraint of the table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Constraint {
+    RowId,
+    /// The unique index at the position in [InsertStatement::indexes].
+    Index(usize),
+}
+
+/// ON CONFLICT clause of INSERT statement.
+struct UpsertClause {
+    /// None if the clause handles the conflicts of any constraint.
+    target: Option<Constraint>,
+    /// None for DO NOTHING.
+    action: Option<UpsertUpdate>,
+}
+
+struct UpsertUpdate {
+    assignments: Vec<(ColumnNumber, Expression, TypeAffinity)>,
+    filter: Option<Expression>,
+}
+
+/// The context to evaluate DO UPDATE clause of an upsert.
+struct UpsertData<'a> {
+    rowid: Value<'a>,
+    columns: &'a [Option<Value<'a>>],
+    /// The rowid of the row proposed for insertion.
+    excluded_rowid: Value<'a>,
+    excluded: &'a [Option<Value<'a>>],
+}
+
+impl DataContext for UpsertData<'_> {
+    fn get_column_value(
+        &self,
+        column_idx: &ColumnNumber,
+    ) -> std::result::Result<Option<Value<'_>>, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(match column_idx {
+            ColumnNumber::RowId => Some(self.rowid.clone()),
+            ColumnNumber::Column(idx) => self.columns[*idx].clone(),
+        })
+    }
+
+    fn get_excluded_value(
+        &self,
+        column_idx: &ColumnNumber,
+    ) -> std::result::Result<Option<Value<'_>>, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(match column_idx {
+            ColumnNumber::RowId => Some(self.excluded_rowid.clone()),
+            ColumnNumber::Column(idx) => self.excluded[*idx].clone(),
+        })
+    }
+}
+
+impl InsertStatement<'_> {
+    fn bind_parameter(&mut self, index: u32, value: Option<Value>) -> Result<'static, ()> {
+        if let Some(query) = self.query.as_deref_mut() {
+            query.select.bind_parameter(index, value.clone())?;
+        }
+        let value = value.map(ConstantValue::copy_from);
+        for record in self.records.iter_mut() {
+            for expr in record
+                .rowid
+                .iter_mut()
+                .chain(record.columns.iter_mut().map(|(expr, _)| expr))
+            {
+                expr.bind_parameter(index, &value);
+            }
+        }
+        if let Some(update) = self
+            .upsert
+            .as_mut()
+            .and_then(|upsert| upsert.action.as_mut())
+        {
+            for expr in update
+                .assignments
+                .iter_mut()
+                .map(|(_, expr, _)| expr)
+                .chain(update.filter.iter_mut())
+            {
+                expr.bind_parameter(index, &value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Insert the rows from the query of INSERT ... SELECT.
+    fn insert_query_rows(
+        &self,
+        query: &InsertQuery,
+        cursor: &mut BtreeCursor,
+    ) -> Result<'static, u64> {
+        let mut defaults = Vec::with_capacity(query.default_columns.len());
+        for (expr, _) in query.default_columns.iter() {
+            let (value, _, _) = expr.execute::<RowData>(None)?;
+            defaults.push(value);
+        }
+        let mut insert = |values: &mut dyn Iterator<Item = Option<Value>>| {
+            let mut rowid = None;
+            let mut columns = defaults.clone();
+            for (column, value) in query.columns.iter().zip(values) {
+                match column {
+                    ColumnNumber::RowId => rowid = value,
+                    ColumnNumber::Column(column_idx) => columns[*column_idx] = value,
+                }
+            }
+            let columns = columns
+                .into_iter()
+                .zip(query.default_columns.iter())
+                .map(|(value, (_, type_affinity))| value.map(|v| v.apply_affinity(*type_affinity)))
+                .collect::<Vec<_>>();
+            self.insert_row(cursor, rowid, &columns)
+        };
+
+        let mut rows = query.select.rows(None)?;
+        let mut n = 0;
+        if query.select.table_page_id == self.table_page_id {
+            // Scanning the table while inserting to it would see the new rows.
+            // Collect the rows beforehand as SQLite does with a temporary
+            // table.
+            let mut buffered = Vec::new();
+            while let Some(row) = rows
+                .next_row()
+                .map_err(|e| anyhow::anyhow!("next row: {}", e))?
+            {
+                let values = row.parse().map_err(|e| anyhow::anyhow!("parse: {}", e))?;
+                buffered.push(
+                    values
+                        .iter()
+                        .map(|v| v.clone().map(ConstantValue::copy_from))
+                        .collect::<Vec<_>>(),
+                );
+            }
+            drop(rows);
+            for values in buffered.iter() {
+                if insert(&mut values.iter().map(|v| v.as_ref().map(|v| v.as_value())))? {
+                    n += 1;
+                }
+            }
+        } else {
+            while let Some(row) = rows
+                .next_row()
+                .map_err(|e| anyhow::anyhow!("next row: {}", e))?
+            {
+                let values = row.parse().map_err(|e| anyhow::anyhow!("parse: {}", e))?;
+                if insert(&mut values.iter().cloned())? {
+                    n += 1;
+                }
+            }
+        }
+        Ok(n)
+    }
+
+    /// Insert the row resolving the conflicts with the existing rows.
+    ///
+    /// The new rowid is generated if `rowid` is NULL. `columns` must have the
+    /// type affinities applied. Returns whether a row is inserted or updated.
+    fn insert_row(
+        &self,
+        cursor: &mut BtreeCursor,
+        rowid: Option<Value>,
+        columns: &[Option<Value>],
+    ) -> Result<'static, bool> {
+        let rowid = match rowid.map(|v| v.apply_numeric_affinity()) {
+            Some(Value::Integer(rowid)) => rowid,
+            Some(_) => return Err(Error::DataTypeMismatch),
+            None => {
+                cursor.move_to_last()?;
+                let last_rowid = cursor.get_table_key()?.unwrap_or(0);
+                // TODO: 32-bit rowid support.
+                if last_rowid == MAX_ROWID {
+                    todo!("find unused rowid randomly");
+                } else {
+                    last_rowid + 1
+                }
+            }
+        };
+
+        let conflicts = self.find_conflicts(cursor, rowid, columns)?;
+        if conflicts.is_empty() {
+            self.write_row(cursor, rowid, columns)?;
+            return Ok(true);
+        }
+        if let Some(upsert) = &self.upsert {
+            if let Some((_, conflict_rowid)) = conflicts
+                .iter()
+                .find(|(constraint, _)| upsert.target.is_none_or(|target| target == *constraint))
+            {
+                return match &upsert.action {
+                    Some(update) => {
+                        self.upsert_row(cursor, update, *conflict_rowid, rowid, columns)
+                    }
+                    None => Ok(false),
+                };
+            }
+        }
+        match self.conflict {
+            ConflictResolution::Abort => Err(Error::UniqueConstraintViolation),
+            ConflictResolution::Ignore => Ok(false),
+            ConflictResolution::Replace => {
+                let mut conflict_rowids = conflicts
+                    .iter()
+                    .map(|(_, conflict_rowid)| *conflict_rowid)
+                    .collect::<Vec<_>>();
+                conflict_rowids.sort_unstable();
+                conflict_rowids.dedup();
+                for conflict_rowid in conflict_rowids {
+                    let old_columns = self.load_row(conflict_rowid)?;
+                    self.delete_row(cursor, conflict_rowid, &old_columns)?;
+                }
+                self.write_row(cursor, rowid, columns)?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Update the existing row by DO UPDATE clause of the upsert.
+    ///
+    /// Returns false if the WHERE clause rejects the row.
+    fn upsert_row(
+        &self,
+        cursor: &mut BtreeCursor,
+        update: &UpsertUpdate,
+        rowid: i64,
+        excluded_rowid: i64,
+        excluded: &[Option<Value>],
+    ) -> Result<'static, bool> {
+        let old_columns = self.load_row(rowid)?;
+        let old_values = old_columns
+            .iter()
+            .map(|v| v.as_ref().map(|v| v.as_value()))
+            .collect::<Vec<_>>();
+        let data = UpsertData {
+            rowid: Value::Integer(rowid),
+            columns: &old_values,
+            excluded_rowid: Value::Integer(excluded_rowid),
+            excluded,
+        };
+        if let Some(filter) = &update.filter {
+            if !filter.execute(Some(&data))?.0.is_some_and(|v| v.as_bool()) {
+                return Ok(false);
+            }
+        }
+        let mut new_rowid = rowid;
+        let mut new_values = old_values.clone();
+        // All the expressions are evaluated against the original row.
+        for (column_number, expr, type_affinity) in update.assignments.iter() {
+            let (value, _, _) = expr.execute(Some(&data))?;
+            match column_number {
+                ColumnNumber::RowId => match value.map(|v| v.apply_numeric_affinity()) {
+                    Some(Value::Integer(rowid)) => new_rowid = rowid,
+                    _ => return Err(Error::DataTypeMismatch),
+                },
+                ColumnNumber::Column(column_idx) => {
+                    new_values[*column_idx] = value.map(|v| v.apply_affinity(*type_affinity));
+                }
+            }
+        }
+
+        self.delete_row(cursor, rowid, &old_columns)?;
+        // The updated row must not conflict with other rows.
+        if !self
+            .find_conflicts(cursor, new_rowid, &new_values)?
+            .is_empty()
+        {
+            return Err(Error::UniqueConstraintViolation);
+        }
+        self.write_row(cursor, new_rowid, &new_values)?;
+        Ok(true)
+    }
+
+    /// Find the existing rows conflicting with the row on each constraint.
+    ///
+    /// Returns the constraints and the rowids of the conflicting rows.
+    fn find_conflicts(
+        &self,
+        cursor: &mut BtreeCursor,
+        rowid: i64,
+        columns: &[Option<Value>],
+    ) -> Result<'static, Vec<(Constraint, i64)>> {
+        let mut conflicts = Vec::new();
+        if cursor.table_move_to(rowid)? == Some(rowid) {
+            conflicts.push((Constraint::RowId, rowid));
+        }
+        let row_id = Value::Integer(rowid);
+        for (i, index) in self.indexes.iter().enumerate() {
+            if !index.unique {
+                continue;
+            }
+            if let Some(conflict_rowid) =
+                index.find_conflict(self.conn, &index.key(&row_id, columns))?
+            {
+                conflicts.push((Constraint::Index(i), conflict_rowid));
+            }
+        }
+        Ok(conflicts)
+    }
+
+    /// Load the values of the columns of the existing row.
+    fn load_row(&self, rowid: i64) -> Result<'static, Vec<Option<ConstantValue>>> {
+        let query_plan = QueryPlan::RowId(rowid);
+        let filter = Expression::one();
+        let mut query = Query::new(
+            self.table_page_id,
+            &self.conn.pager,
+            &self.conn.btree_ctx,
+            &query_plan,
+            &filter,
+        )?;
+        let Some(data) = query.next()? else {
+            unreachable!("the row must exist");
+        };
+        let columns = (0..self.n_columns)
+            .map(|column_idx| {
+                data.get_column_value(&ColumnNumber::Column(column_idx))
+                    .map(|v| v.map(ConstantValue::copy_from))
+                    .map_err(expression::Error::FailGetColumn)
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(columns)
+    }
+
+    /// Delete the existing row and its keys in the indexes.
+    fn delete_row(
+        &self,
+        cursor: &mut BtreeCursor,
+        rowid: i64,
+        columns: &[Option<ConstantValue>],
+    ) -> Result<'static, ()> {
+        let values = columns
+            .iter()
+            .map(|v| v.as_ref().map(|v| v.as_value()))
+            .collect::<Vec<_>>();
+        let row_id = Value::Integer(rowid);
+        for index in self.indexes.iter() {
+            index.delete(self.conn, &index.key(&row_id, &values))?;
+        }
+        if cursor.table_move_to(rowid)? != Some(rowid) {
+            unreachable!("the row must exist");
+        }
+        cursor.delete()?;
+        Ok(())
+    }
+
+    /// Insert the row not conflicting with the existing rows to the table
+    /// and the indexes.
+    fn write_row(
+        &self,
+        cursor: &mut BtreeCursor,
+        rowid: i64,
+        columns: &[Option<Value>],
+    ) -> Result<'static, ()> {
+        cursor.table_insert(
+            rowid,
+            &RecordPayload::new(&columns.iter().map(|v| v.as_ref()).collect::<Vec<_>>())?,
+        )?;
+
+        let row_id = Value::Integer(rowid);
+        for index in self.indexes.iter() {
+            index.insert(self.conn, &index.key(&row_id, columns))?;
+        }
+        Ok(())
+    }
+}
+
+impl<'conn> ExecutionStatement for InsertStatement<'conn> {
+    fn execute(&self) -> Result<'_, u64> {
+        let write_txn = self.conn.start_write()?;
+
+        let mut cursor =
+            BtreeCursor::new(self.table_page_id, &self.conn.pager, &self.conn.btree_ctx)?;
+        let mut n = 0;
+        for record in self.records.iter() {
+            let rowid = match &record.rowid {
+                Some(rowid_expr) => rowid_expr.execute::<RowData>(None)?.0,
+                None => None,
+            };
+            let mut columns = Vec::with_capacity(record.columns.len());
+            for (expr, type_affinity) in record.columns.iter() {
+                let (value, _, _) = expr.execute::<RowData>(None)?;
+                let value = value.map(|v| v.apply_affinity(*type_affinity));
+                columns.push(value);
+            }
+            if self.insert_row(&mut cursor, rowid, &columns)? {
+                n += 1;
+            }
+        }
+        if let Some(query) = self.query.as_deref() {
+            n += self.insert_query_rows(query, &mut cursor)?;
+        }
+
+        write_txn.commit()?;
+
+        Ok(n)
+    }
+
+    fn bind(&mut self, index: usize, value: Option<Value>) -> Result<'static, ()> {
+        let index = parameter_index(&self.parameters, index)?;
+        self.bind_parameter(index, value)
+    }
+
+    fn bind_named(&mut self, name: &str, value: Option<Value>) -> Result<'static, ()> {
+        let index = named_parameter_index(&self.parameters, name)?;
+        self.bind_parameter(index, value)
+    }
+}
+
+pub struct UpdateStatement<'conn> {
+    conn: &'conn Connection,
+    table_page_id: PageId,
+    n_columns: usize,
+    assignments: Vec<(ColumnNumber, Expression, TypeAffinity)>,
+    indexes: Vec<IndexSchema>,
+    filter: Expression,
+    query_plan: QueryPlan,
+}
+
+impl<'conn> ExecutionStatement for UpdateStatement<'conn> {
+    fn execute(&self) -> Result<'_, u64> {
+        let write_txn = self.conn.start_write()?;
+
+        // Collect the target rowids beforehand because updating a row may move
+        // it in the table or in the index used by the query plan.
+        let mut rowids = Vec::new();
+        {
+            let mut query = Query::new(
+                self.table_page_id,
+                &self.conn.pager,
+                &self.conn.btree_ctx,
+                &self.query_plan,
+                &self.filter,
+            )?;
+            while let Some(data) = query.next()? {
+                let Some(Value::Integer(rowid)) = data
+                    .get_column_value(&ColumnNumber::RowId)
+                    .map_err(expression::Error::FailGetColumn)?
+                else {
+                    unreachable!("rowid must be integer");
+                };
+                rowids.push(rowid);
+            }
+        }
+
+        let assigned_columns = self
+            .assignments
+            .iter()
+            .map(|(column_number, _, _)| *column_number)
+            .collect::<Vec<_>>();
+        let mut n = 0;
+        for rowid in rowids {
+            let query_plan = QueryPlan::RowId(rowid);
+            let filter = Expression::one();
+            let mut query = Query::new(
+                self.table_page_id,
+                &self.conn.pager,
+                &self.conn.btree_ctx,
+                &query_plan,
+                &filter,
+            )?;
+            let Some(data) = query.next()? else {
+                unreachable!("the row must exist");
+            };
+            let old_columns = (0..self.n_columns)
+                .map(|column_idx| {
+                    data.get_column_value(&ColumnNumber::Column(column_idx))
+                        .map(|v| v.map(ConstantValue::copy_from))
+                        .map_err(expression::Error::FailGetColumn)
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            let mut new_columns = old_columns.clone();
+            let mut new_rowid = rowid;
+            // All the expressions are evaluated against the original row.
+            for (column_number, expr, type_affinity) in self.assignments.iter() {
+                let (value, _, _) = expr.execute(Some(&data))?;
+                match column_number {
+                    ColumnNumber::RowId => match value.map(|v| v.apply_numeric_affinity()) {
+                        Some(Value::Integer(rowid)) => new_rowid = rowid,
+                        _ => return Err(Error::DataTypeMismatch),
+                    },
+                    ColumnNumber::Column(column_idx) => {
+                        new_columns[*column_idx] = value
+                            .map(|v| ConstantValue::copy_from(v.apply_affinity(*type_affinity)));
+                    }
+                }
+            }
+            drop(data);
+            drop(query);
+
+            let rowid_changed = new_rowid != rowid;
+            let old_values = old_columns
+                .iter()
+                .map(|v| v.as_ref().map(|v| v.as_value()))
+                .collect::<Vec<_>>();
+            let new_values = new_columns
+                .iter()
+                .map(|v| v.as_ref().map(|v| v.as_value()))
+                .collect::<Vec<_>>();
+            let old_rowid = Value::Integer(rowid);
+            let new_rowid_value = Value::Integer(new_rowid);
+
+            let changed_indexes = self
+                .indexes
+                .iter()
+                // Every index contains the rowid.
+                .filter(|index| rowid_changed || index.contains_any(&assigned_columns))
+                .collect::<Vec<_>>();
+            for index in changed_indexes.iter() {
+                let old_key = index
+                    .columns
+                    .iter()
+                    .map(|(column_number, _)| match column_number {
+                        ColumnNumber::RowId => Some(&old_rowid),
+                        ColumnNumber::Column(column_idx) => old_values[*column_idx].as_ref(),
+                    })
+                    .collect::<Vec<_>>();
+                index.delete(self.conn, &old_key)?;
+            }
+
+            let mut cursor =
+                BtreeCursor::new(self.table_page_id, &self.conn.pager, &self.conn.btree_ctx)?;
+            if rowid_changed {
+                // Check rowid conflict
+                if cursor.table_move_to(new_rowid)? == Some(new_rowid) {
+                    return Err(Error::UniqueConstraintViolation);
+                }
+                cursor.table_move_to(rowid)?;
+                cursor.delete()?;
+            }
+            cursor.table_insert(
+                new_rowid,
+                &RecordPayload::new(&new_values.iter().map(|v| v.as_ref()).collect::<Vec<_>>())?,
+            )?;
+
+            for index in changed_indexes {
+                let new_key = index
+                    .columns
+                    .iter()
+                    .map(|(column_number, _)| match column_number {
+                        ColumnNumber::RowId => Some(&new_rowid_value),
+                        ColumnNumber::Column(column_idx) => new_values[*column_idx].as_ref(),
+                    })
+                    .collect::<Vec<_>>();
+                index.insert(self.conn, &new_key)?;
+            }
+
+            n += 1;
+        }
+
+        write_txn.commit()?;
+
+        Ok(n)
+    }
+
+    fn explain(&self) -> Result<'static, Vec<String>> {
+        Ok(vec![self.conn.explain_query_plan(
+            self.table_page_id,
+            &self.query_plan,
+        )?])
+    }
+}
+
+pub struct ClearStatement<'conn> {
+    conn: &'conn Connection,
+    table_page_id: PageId,
+    index_page_ids: Vec<PageId>,
+}
+
+impl<'conn> ExecutionStatement for ClearStatement<'conn> {
+    fn execute(&self) -> Result<'_, u64> {
+        let write_txn = self.conn.start_write()?;
+
+        let mut cursor =
+            BtreeCursor::new(self.table_page_id, &self.conn.pager, &self.conn.btree_ctx)?;
+
+        let n_deleted = cursor.clear()?;
+
+        for index_page_id in self.index_page_ids.iter() {
+            let mut cursor =
+                BtreeCursor::new(*index_page_id, &self.conn.pager, &self.conn.btree_ctx)?;
+            let n = cursor.clear()?;
+            if n != n_deleted {
+                return Err(Error::Other(anyhow::anyhow!(
+                    "number of deleted rows in table and index does not match"
+                )));
+            }
+        }
+
+        write_txn.commit()?;
+
+        Ok(n_deleted)
+    }
+}
+
+pub struct DeleteStatement<'conn> {
+    conn: &'conn Connection,
+    table_page_id: PageId,
+    indexes: Vec<IndexSchema>,
+    filter: Expression,
+    query_plan: QueryPlan,
+}
+
+impl<'conn> ExecutionStatement for DeleteStatement<'conn> {
+    fn execute(&self) -> Result<'_, u64> {
+        let write_txn = self.conn.start_write()?;
+
+        let mut query = Query::new(
+            self.table_page_id,
+            &self.conn.pager,
+            &self.conn.btree_ctx,
+            &self.query_plan,
+            &self.filter,
+        )?;
+
+        let mut n_deleted = 0;
+
+        loop {
+            let Some(data) = query.next()? else {
+                break;
+            };
+
+            // Delete from index
+            for index in &self.indexes {
+                let values = index
+                    .columns
+                    .iter()
+                    .map(|(column_idx, _)| {
+                        data.get_column_value(column_idx)
+                            .map_err(expression::Error::FailGetColumn)
+                    })
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+                let key = values.iter().map(|v| v.as_ref()).collect::<Vec<_>>();
+                index.delete(self.conn, &key)?;
+            }
+
+            drop(data);
+            query.delete()?;
+
+            n_deleted += 1;
+        }
+
+        write_txn.commit()?;
+
+        Ok(n_deleted)
+    }
+
+    fn explain(&self) -> Result<'static, Vec<String>> {
+        Ok(vec![self.conn.explain_query_plan(
+            self.table_page_id,
+            &self.query_plan,
+        )?])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::journal::set_crash_point;
+    use crate::interpreter::test_utils::*;
+
+    #[test]
+    fn test_create_table() {
+        let file = create_sqlite_database(&["CREATE TABLE example(col);"]);
+        {
+            let conn = Connection::open(file.path()).unwrap();
+
+            let stmt = conn
+                .prepare("CREATE TABLE example2(id INTEGER PRIMARY KEY, col TEXT COLLATE NOCASE);")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 0);
+
+            let stmt = conn
+                .prepare("INSERT INTO example2(id, col) VALUES (1, 'hello'), (2, 'world');")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 2);
+
+            let stmt = conn
+                .prepare("SELECT id, col FROM example2 WHERE col = 'WORLD';")
+                .unwrap();
+            let mut rows = stmt.query().unwrap();
+            let row = rows.next_row().unwrap().unwrap();
+            let columns = row.parse().unwrap();
+            assert_eq!(columns.get(0), Some(&Value::Integer(2)));
+            assert_eq!(
+                columns.get(1),
+                Some(&Value::Text(b"world".as_slice().into()))
+            );
+            drop(row);
+            assert!(rows.next_row().unwrap().is_none());
+        }
+
+        // SQLite can read the new table.
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        let sql: String = conn
+            .query_row(
+                "SELECT sql FROM sqlite_schema WHERE name = 'example2';",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(
+            sql,
+            "CREATE TABLE example2(id INTEGER PRIMARY KEY, col TEXT COLLATE NOCASE)"
+        );
+        let n: i64 = conn
+            .query_row("SELECT count(*) FROM example2;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(n, 2);
+        let result: String = conn
+            .query_row("PRAGMA integrity_check;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(result, "ok");
+    }
+
+    #[test]
+    fn test_create_table_autoindex() {
+        let file = create_sqlite_database(&["CREATE TABLE example(col);"]);
+        {
+            let conn = Connection::open(file.path()).unwrap();
+            let stmt = conn
+                .prepare("CREATE TABLE example2(name TEXT PRIMARY KEY, col);")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 0);
+            let stmt = conn
+                .prepare("INSERT INTO example2(name, col) VALUES ('b', 2), ('a', 1), ('c', 3);")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 3);
+            let stmt = conn
+                .prepare("INSERT INTO example2(name, col) VALUES ('b', 4);")
+                .unwrap();
+            assert!(matches!(
+                stmt.execute(),
+                Err(Error::UniqueConstraintViolation)
+            ));
+            let stmt = conn
+                .prepare("UPDATE example2 SET name = 'a' WHERE col = 3;")
+                .unwrap();
+            assert!(matches!(
+                stmt.execute(),
+                Err(Error::UniqueConstraintViolation)
+            ));
+            let stmt = conn
+                .prepare("DELETE FROM example2 WHERE name = 'a';")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 1);
+            let stmt = conn
+                .prepare("INSERT INTO example2(name, col) VALUES ('a', 5);")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 1);
+
+            assert_eq!(
+                explain(&conn, "SELECT col FROM example2 WHERE name = 'c';"),
+                vec!["SEARCH example2 USING INDEX sqlite_autoindex_example2_1 (name=?)"]
+            );
+        }
+
+        // SQLite can read the new table and its autoindex.
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        let (name, root_page_id, sql): (String, i64, Option<String>) = conn
+            .query_row(
+                "SELECT name, rootpage, sql FROM sqlite_schema WHERE type = 'index' AND tbl_name = 'example2';",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(name, "sqlite_autoindex_example2_1");
+        assert!(root_page_id > 0);
+        assert_eq!(sql, None);
+        let result: String = conn
+            .query_row("PRAGMA integrity_check;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(result, "ok");
+        let names = conn
+            .prepare(
+                "SELECT name FROM example2 INDEXED BY sqlite_autoindex_example2_1 WHERE name > '';",
+            )
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<String>>>()
+            .unwrap();
+        assert_eq!(names, vec!["a", "b", "c"]);
+        assert!(conn
+            .execute("INSERT INTO example2(name, col) VALUES ('c', 6);", [])
+            .is_err());
+    }
+
+    #[test]
+    fn test_sqlite_autoindex() {
+        let file = create_sqlite_database(&["CREATE TABLE example(name TEXT PRIMARY KEY, col);"]);
+        {
+            let conn = Connection::open(file.path()).unwrap();
+            let stmt = conn
+                .prepare("INSERT INTO example(name, col) VALUES ('a', 1), ('b', 2);")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 2);
+            let stmt = conn
+                .prepare("INSERT INTO example(name, col) VALUES ('a', 3);")
+                .unwrap();
+            assert!(matches!(
+                stmt.execute(),
+                Err(Error::UniqueConstraintViolation)
+            ));
+        }
+
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        let result: String = conn
+            .query_row("PRAGMA integrity_check;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(result, "ok");
+    }
+
+    #[test]
+    fn test_create_table_int_primary_key() {
+        let file = create_sqlite_database(&["CREATE TABLE example(col);"]);
+        {
+            let conn = Connection::open(file.path()).unwrap();
+            // Only INTEGER PRIMARY KEY is the alias of the rowid.
+            let stmt = conn
+                .prepare("CREATE TABLE example2(id INT PRIMARY KEY, col);")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 0);
+            let stmt = conn
+                .prepare("INSERT INTO example2(id, col) VALUES (10, 'a'), (20, 'b');")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 2);
+            let stmt = conn
+                .prepare("INSERT INTO example2(id, col) VALUES (10, 'c');")
+                .unwrap();
+            assert!(matches!(
+                stmt.execute(),
+                Err(Error::UniqueConstraintViolation)
+            ));
+        }
+
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        let result: String = conn
+            .query_row("PRAGMA integrity_check;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(result, "ok");
+        let rows = conn
+            .prepare("SELECT rowid, id, col FROM example2 ORDER BY id;")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<(i64, i64, String)>>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![(1, 10, "a".to_string()), (2, 20, "b".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_sqlite_bigint_primary_key() {
+        let file = create_sqlite_database(&[
+            "CREATE TABLE example(id BIGINT PRIMARY KEY, col);",
+            "INSERT INTO example(id, col) VALUES (10, 'a'), (20, 'b');",
+        ]);
+        {
+            let conn = Connection::open(file.path()).unwrap();
+            let stmt = conn
+                .prepare("SELECT rowid, id, col FROM example WHERE id = 20;")
+                .unwrap();
+            let mut rows = stmt.query().unwrap();
+            let row = rows.next_row().unwrap().unwrap();
+            let columns = row.parse().unwrap();
+            assert_eq!(columns.get(0), Some(&Value::Integer(2)));
+            assert_eq!(columns.get(1), Some(&Value::Integer(20)));
+            assert_eq!(columns.get(2), Some(&Value::Text(b"b".as_slice().into())));
+            drop(row);
+            assert!(rows.next_row().unwrap().is_none());
+            drop(rows);
+
+            let stmt = conn
+                .prepare("INSERT INTO example(id, col) VALUES (10, 'c');")
+                .unwrap();
+            assert!(matches!(
+                stmt.execute(),
+                Err(Error::UniqueConstraintViolation)
+            ));
+            let stmt = conn
+                .prepare("INSERT INTO example(id, col) VALUES (30, 'c');")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 1);
+        }
+
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        let result: String = conn
+            .query_row("PRAGMA integrity_check;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(result, "ok");
+    }
+
+    #[test]
+    fn test_create_table_schema_cookie() {
+        let file = create_sqlite_database(&["CREATE TABLE example(col);"]);
+        let conn = Connection::open(file.path()).unwrap();
+
+        conn.prepare("CREATE TABLE example2(col);")
+            .unwrap()
+            .execute()
+            .unwrap();
+        drop(conn);
+
+        let buf = std::fs::read(file.path()).unwrap();
+        let header = DatabaseHeader::from(buf[..DATABASE_HEADER_SIZE].try_into().unwrap());
+        assert_eq!(header.schema_cookie(), 2);
+        assert_eq!(header.n_pages(), 3);
+    }
+
+    #[test]
+    fn test_create_table_if_not_exists() {
+        let file = create_sqlite_database(&["CREATE TABLE example(col);"]);
+        {
+            let conn = Connection::open(file.path()).unwrap();
+
+            let stmt = conn.prepare("CREATE TABLE example(col);").unwrap();
+            assert!(stmt.execute().is_err());
+            let stmt = conn.prepare("CREATE TABLE Example(col2);").unwrap();
+            assert!(stmt.execute().is_err());
+            let stmt = conn
+                .prepare("CREATE TABLE IF NOT EXISTS example(col);")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 0);
+
+            let stmt = conn
+                .prepare("CREATE TABLE IF NOT EXISTS example2(col);")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 0);
+            // The second execution is a no-op.
+            assert_eq!(stmt.execute().unwrap(), 0);
+        }
+
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        let sql: String = conn
+            .query_row(
+                "SELECT sql FROM sqlite_schema WHERE name = 'example2';",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(sql, "CREATE TABLE example2(col)");
+        let n: i64 = conn
+            .query_row("SELECT count(*) FROM sqlite_schema;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(n, 2);
+    }
+
+    #[test]
+    fn test_create_table_fail() {
+        let file = create_sqlite_database(&["CREATE TABLE example(col);"]);
+        let conn = Connection::open(file.path()).unwrap();
+
+        // Reserved name.
+        assert!(conn.prepare("CREATE TABLE sqlite_foo(col);").is_err());
+        assert!(conn.prepare("CREATE TABLE SQLITE_SCHEMA(col);").is_err());
+        // Duplicated column name.
+        assert!(conn.prepare("CREATE TABLE foo(col, COL);").is_err());
+        // Multiple primary keys.
+        assert!(conn
+            .prepare("CREATE TABLE foo(id primary key, id2 primary key);")
+            .is_err());
+        // Unknown collation.
+        assert!(conn
+            .prepare("CREATE TABLE foo(col collate unknown);")
+            .is_err());
+    }
+
+    #[test]
+    fn test_create_index() {
+        let mut stmts = vec!["PRAGMA page_size = 512;", "CREATE TABLE example(id, col);"];
+        let inserts = (0..500)
+            .map(|i| {
+                format!(
+                    "INSERT INTO example(id, col) VALUES ({i}, 'value{}');",
+                    i % 100
+                )
+            })
+            .collect::<Vec<_>>();
+        stmts.extend(inserts.iter().map(|s| s.as_str()));
+        let file = create_sqlite_database(&stmts);
+        {
+            let conn = Connection::open(file.path()).unwrap();
+
+            let stmt = conn
+                .prepare("CREATE INDEX index1 ON example(col, id);")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 0);
+
+            let index_page_id = find_index_page_id("index1", file.path());
+            let stmt = conn
+                .prepare("SELECT id FROM example WHERE col = 'value7';")
+                .unwrap();
+            let Statement::Query(select) = &stmt else {
+                unreachable!()
+            };
+            assert_eq!(select.query_plan.index_page_id(), Some(index_page_id));
+            let mut rows = stmt.query().unwrap();
+            let mut ids = Vec::new();
+            while let Some(row) = rows.next_row().unwrap() {
+                let columns = row.parse().unwrap();
+                let Some(Value::Integer(id)) = columns.get(0) else {
+                    panic!("unexpected id");
+                };
+                ids.push(*id);
+            }
+            assert_eq!(ids, vec![7, 107, 207, 307, 407]);
+        }
+
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        let sql: String = conn
+            .query_row(
+                "SELECT sql FROM sqlite_schema WHERE name = 'index1';",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(sql, "CREATE INDEX index1 ON example(col, id)");
+        let n: i64 = conn
+            .query_row(
+                "SELECT count(*) FROM example INDEXED BY index1 WHERE col = 'value7';",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(n, 5);
+        let result: String = conn
+            .query_row("PRAGMA integrity_check;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(result, "ok");
+    }
+
+    #[test]
+    fn test_create_index_maintained_by_insert() {
+        let file = create_sqlite_database(&["CREATE TABLE example(id INTEGER PRIMARY KEY, col);"]);
+        {
+            let conn = Connection::open(file.path()).unwrap();
+            let stmt = conn
+                .prepare("CREATE INDEX IF NOT EXISTS index1 ON example(col);")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 0);
+            // The second execution is a no-op.
+            assert_eq!(stmt.execute().unwrap(), 0);
+            let stmt = conn.prepare("CREATE INDEX index1 ON example(id);").unwrap();
+            assert!(stmt.execute().is_err());
+            let stmt = conn
+                .prepare("CREATE INDEX example ON example(id);")
+                .unwrap();
+            assert!(stmt.execute().is_err());
+
+            let stmt = conn
+                .prepare("INSERT INTO example(id, col) VALUES (1, 10), (2, 20);")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 2);
+        }
+
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        let id: i64 = conn
+            .query_row(
+                "SELECT id FROM example INDEXED BY index1 WHERE col = 20;",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(id, 2);
+        let result: String = conn
+            .query_row("PRAGMA integrity_check;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(result, "ok");
+    }
+
+    #[test]
+    fn test_create_unique_index() {
+        let file = create_sqlite_database(&[
+            "CREATE TABLE example(col1, col2);",
+            "INSERT INTO example(col1, col2) VALUES (1, 'a'), (2, 'a'), (NULL, 'b'), (NULL, 'b');",
+        ]);
+        {
+            let conn = Connection::open(file.path()).unwrap();
+
+            // Duplicated keys in existing rows.
+            let stmt = conn
+                .prepare("CREATE UNIQUE INDEX index1 ON example(col2);")
+                .unwrap();
+            assert!(matches!(
+                stmt.execute(),
+                Err(Error::UniqueConstraintViolation)
+            ));
+
+            // NULLs are distinct.
+            let stmt = conn
+                .prepare("CREATE UNIQUE INDEX index2 ON example(col1);")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 0);
+
+            let stmt = conn
+                .prepare("INSERT INTO example(col1, col2) VALUES (2, 'c');")
+                .unwrap();
+            assert!(matches!(
+                stmt.execute(),
+                Err(Error::UniqueConstraintViolation)
+            ));
+            let stmt = conn
+                .prepare("INSERT INTO example(col1, col2) VALUES (3, 'c'), (NULL, 'c');")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 2);
+        }
+
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        let names = conn
+            .prepare("SELECT name FROM sqlite_schema WHERE type = 'index';")
+            .unwrap()
+            .query_map([], |row| row.get::<_, String>(0))
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(names, vec!["index2".to_string()]);
+        let sql: String = conn
+            .query_row(
+                "SELECT sql FROM sqlite_schema WHERE name = 'index2';",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(sql, "CREATE UNIQUE INDEX index2 ON example(col1)");
+        assert!(conn
+            .execute("INSERT INTO example(col1) VALUES (3);", [])
+            .is_err());
+        let result: String = conn
+            .query_row("PRAGMA integrity_check;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(result, "ok");
+    }
+
+    #[test]
+    fn test_create_index_fail() {
+        let file = create_sqlite_database(&["CREATE TABLE example(col);"]);
+        let conn = Connection::open(file.path()).unwrap();
+
+        // Reserved name.
+        assert!(conn
+            .prepare("CREATE INDEX sqlite_index ON example(col);")
+            .is_err());
+        assert!(conn
+            .prepare("CREATE INDEX index1 ON sqlite_schema(name);")
+            .is_err());
+        // Unknown table.
+        assert!(conn
+            .prepare("CREATE INDEX index1 ON invalid(col);")
+            .is_err());
+        // Unknown column.
+        assert!(conn
+            .prepare("CREATE INDEX index1 ON example(invalid);")
+            .is_err());
+    }
+
+    #[test]
+    fn test_insert_delete_with_index_integrity() {
+        let file = create_sqlite_database(&[
+            "PRAGMA page_size = 512;",
+            "CREATE TABLE example(id, col);",
+            "CREATE INDEX index1 ON example(col, id);",
+        ]);
+        {
+            let conn = Connection::open(file.path()).unwrap();
+            for i in 0..500 {
+                let sql = format!(
+                    "INSERT INTO example(id, col) VALUES ({i}, 'value{}');",
+                    i % 100
+                );
+                assert_eq!(conn.prepare(&sql).unwrap().execute().unwrap(), 1);
+            }
+            for i in 0..50 {
+                let sql = format!("DELETE FROM example WHERE col = 'value{}';", i * 2);
+                assert_eq!(conn.prepare(&sql).unwrap().execute().unwrap(), 5);
+            }
+        }
+
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        let n: i64 = conn
+            .query_row("SELECT count(*) FROM example;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(n, 250);
+        let result: String = conn
+            .query_row("PRAGMA integrity_check;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(result, "ok");
+    }
+
+    #[test]
+    fn test_delete_rebalance_integrity() {
+        let mut stmts = vec![
+            "PRAGMA page_size = 512;",
+            "CREATE TABLE example(id, col);",
+            "CREATE INDEX index1 ON example(col, id);",
+        ];
+        let inserts = (0..1000)
+            .map(|i| {
+                format!(
+                    "INSERT INTO example(id, col) VALUES ({i}, '{}{i}');",
+                    "a".repeat(i % 50)
+                )
+            })
+            .collect::<Vec<_>>();
+        stmts.extend(inserts.iter().map(|s| s.as_str()));
+        let file = create_sqlite_database(&stmts);
+        let used_pages = |conn: &rusqlite::Connection| {
+            let n_pages: i64 = conn
+                .query_row("PRAGMA page_count;", [], |row| row.get(0))
+                .unwrap();
+            let n_free: i64 = conn
+                .query_row("PRAGMA freelist_count;", [], |row| row.get(0))
+                .unwrap();
+            n_pages - n_free
+        };
+        let used_before = used_pages(&rusqlite::Connection::open(file.path()).unwrap());
+
+        {
+            let conn = Connection::open(file.path()).unwrap();
+            let stmt = conn
+                .prepare("DELETE FROM example WHERE id >= 50 AND id < 950;")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 900);
+        }
+
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        let n: i64 = conn
+            .query_row("SELECT count(*) FROM example;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(n, 100);
+        let result: String = conn
+            .query_row("PRAGMA integrity_check;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(result, "ok");
+        // The emptied pages are returned to the freelist.
+        let used_after = used_pages(&conn);
+        assert!(used_after * 4 < used_before, "{used_after} {used_before}");
+    }
+
+    #[test]
+    fn test_commit_crash_recovery() {
+        let mut stmts = vec![
+            "PRAGMA page_size = 512;",
+            "CREATE TABLE example(id, col);",
+            "CREATE INDEX index1 ON example(col);",
+        ];
+        let inserts = (0..200)
+            .map(|i| format!("INSERT INTO example(id, col) VALUES ({i}, 'value{i:040}');"))
+            .collect::<Vec<_>>();
+        stmts.extend(inserts.iter().map(|s| s.as_str()));
+        let file = create_sqlite_database(&stmts);
+        let original = std::fs::read(file.path()).unwrap();
+        let load_rows = |path: &Path| {
+            let conn = rusqlite::Connection::open(path).unwrap();
+            let result: String = conn
+                .query_row("PRAGMA integrity_check;", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(result, "ok");
+            let mut stmt = conn
+                .prepare("SELECT id, col FROM example ORDER BY id;")
+                .unwrap();
+            let rows = stmt
+                .query_map([], |row| {
+                    Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+                })
+                .unwrap()
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .unwrap();
+            rows
+        };
+        let original_rows = load_rows(file.path());
+        assert_eq!(original_rows.len(), 200);
+
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let journal = journal_path(&db_path);
+        let copy_path = dir.path().join("copy.db");
+        let mut n_hot_journals = 0;
+        // Crash at each write operation to the database file and the journal.
+        for crash_point in 0.. {
+            std::fs::write(&db_path, &original).unwrap();
+            let conn = Connection::open(&db_path).unwrap();
+            set_crash_point(Some(crash_point));
+            let is_committed = {
+                let stmt = conn
+                    .prepare("DELETE FROM example WHERE id % 3 != 0 OR id > 100;")
+                    .unwrap();
+                stmt.execute().is_ok()
+            };
+            // Rollback on abort also fails after the crash.
+            drop(conn);
+            set_crash_point(None);
+            // Deleting the journal commits the transaction even if syncing the
+            // directory fails after that.
+            let is_committed =
+                is_committed || (!journal.exists() && std::fs::read(&db_path).unwrap() != original);
+
+            if is_committed {
+                assert!(!journal.exists());
+                let rows = load_rows(&db_path);
+                assert_eq!(rows.len(), 34);
+                assert!(rows.iter().all(|(id, _)| id % 3 == 0 && *id <= 100));
+                break;
+            }
+
+            let journal_content = std::fs::read(&journal).unwrap_or_default();
+            if journal_content.first().is_some_and(|b| *b != 0) {
+                n_hot_journals += 1;
+                // SQLite rolls back the hot journal as well.
+                std::fs::copy(&db_path, &copy_path).unwrap();
+                std::fs::copy(&journal, journal_path(&copy_path)).unwrap();
+                assert_eq!(load_rows(&copy_path), original_rows);
+
+                // Crash while rolling back the journal.
+                set_crash_point(Some(1));
+                assert!(Connection::open(&db_path).is_err());
+                set_crash_point(None);
+                assert!(journal.exists());
+            }
+
+            let conn = Connection::open(&db_path).unwrap();
+            assert!(!journal.exists());
+            let stmt = conn.prepare("SELECT count(*) FROM example;").unwrap();
+            let mut rows = stmt.query().unwrap();
+            let row = rows.next_row().unwrap().unwrap();
+            assert_eq!(row.parse().unwrap().get(0), Some(&Value::Integer(200)));
+            drop(row);
+            drop(rows);
+            drop(stmt);
+            drop(conn);
+            assert_eq!(load_rows(&db_path), original_rows);
+        }
+        assert!(n_hot_journals > 10);
+    }
+
+    fn create_wal_database(queries: &[&str]) -> tempfile::NamedTempFile {
+        let file = create_sqlite_database(queries);
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        conn.execute_batch("PRAGMA journal_mode = WAL;").unwrap();
+        conn.close().unwrap();
+        file
+    }
+
+    fn count_rows_by_sqlite(path: &Path) -> i64 {
+        let conn = rusqlite::Connection::open(path).unwrap();
+        let result: String = conn
+            .query_row("PRAGMA integrity_check;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(result, "ok");
+        conn.query_row("SELECT count(*) FROM example;", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    fn count_rows(conn: &Connection) -> i64 {
+        let stmt = conn.prepare("SELECT count(*) FROM example;").unwrap();
+        let mut rows = stmt.query().unwrap();
+        let row = rows.next_row().unwrap().unwrap();
+        let columns = row.parse().unwrap();
+        match columns.get(0) {
+            Some(Value::Integer(n)) => *n,
+            v => panic!("unexpected count: {v:?}"),
+        }
+    }
+
+    #[test]
+    fn test_wal_commit() {
+        let file = create_wal_database(&[
+            "PRAGMA page_size = 512;",
+            "CREATE TABLE example(id, col);",
+            "CREATE INDEX index1 ON example(col);",
+        ]);
+        let wal = wal_path(file.path());
+        assert!(!wal.exists());
+        let original = std::fs::read(file.path()).unwrap();
+
+        {
+            let conn = Connection::open(file.path()).unwrap();
+            assert_eq!(conn.journal_mode(), JournalMode::Wal);
+            for i in 0..100 {
+                let sql = format!("INSERT INTO example(id, col) VALUES ({i}, 'value{i:020}');");
+                assert_eq!(conn.prepare(&sql).unwrap().execute().unwrap(), 1);
+            }
+            assert_eq!(count_rows(&conn), 100);
+        }
+        // Commits are appended to the WAL file only.
+        assert_eq!(std::fs::read(file.path()).unwrap(), original);
+        assert!(std::fs::metadata(&wal).unwrap().len() > 0);
+        assert!(!journal_path(file.path()).exists());
+
+        let conn = Connection::open(file.path()).unwrap();
+        assert_eq!(count_rows(&conn), 100);
+        drop(conn);
+
+        // SQLite reads the frames written by nexusdb.
+        assert_eq!(count_rows_by_sqlite(file.path()), 100);
+    }
+
+    #[test]
+    fn test_wal_read_sqlite_frames() {
+        let file = create_wal_database(&["CREATE TABLE example(id, col);"]);
+        let sqlite_conn = rusqlite::Connection::open(file.path()).unwrap();
+        sqlite_conn
+            .execute_batch("PRAGMA wal_autocheckpoint = 0;")
+            .unwrap();
+        for i in 0..50 {
+            sqlite_conn
+                .execute(
+                    "INSERT INTO example(id, col) VALUES (?, ?);",
+                    (i, "a".repeat(i * 10)),
+                )
+                .unwrap();
+        }
+        sqlite_conn
+            .execute("DELETE FROM example WHERE id < 10;", [])
+            .unwrap();
+        assert!(std::fs::metadata(wal_path(file.path())).unwrap().len() > 0);
+
+        let conn = Connection::open(file.path()).unwrap();
+        assert_eq!(conn.journal_mode(), JournalMode::Wal);
+        assert_eq!(count_rows(&conn), 40);
+        let stmt = conn
+            .prepare("SELECT col FROM example WHERE id = 49;")
+            .unwrap();
+        let mut rows = stmt.query().unwrap();
+        let row = rows.next_row().unwrap().unwrap();
+        assert_eq!(
+            row.parse().unwrap().get(0),
+            Some(&Value::Text("a".repeat(490).as_bytes().into()))
+        );
+    }
+
+    #[test]
+    fn test_wal_checkpoint() {
+        let file = create_wal_database(&["CREATE TABLE example(id, col);"]);
+        let wal = wal_path(file.path());
+        let conn = Connection::open(file.path()).unwrap();
+        for i in 0..10 {
+            let sql = format!("INSERT INTO example(id, col) VALUES ({i}, 'value{i}');");
+            assert_eq!(conn.prepare(&sql).unwrap().execute().unwrap(), 1);
+        }
+        let original = std::fs::read(file.path()).unwrap();
+        conn.checkpoint().unwrap();
+        assert_ne!(std::fs::read(file.path()).unwrap(), original);
+        // Only the WAL header is left.
+        assert_eq!(std::fs::metadata(&wal).unwrap().len(), 32);
+        assert_eq!(count_rows(&conn), 10);
+
+        // The WAL restarts after the checkpoint.
+        let stmt = conn.prepare("DELETE FROM example WHERE id < 5;").unwrap();
+        assert_eq!(stmt.execute().unwrap(), 5);
+        drop(stmt);
+        assert!(std::fs::metadata(&wal).unwrap().len() > 0);
+        drop(conn);
+
+        // Check the database file without the WAL.
+        let dir = tempfile::tempdir().unwrap();
+        let copy_path = dir.path().join("copy.db");
+        std::fs::copy(file.path(), &copy_path).unwrap();
+        assert_eq!(count_rows_by_sqlite(&copy_path), 10);
+        assert_eq!(count_rows_by_sqlite(file.path()), 5);
+    }
+
+    #[test]
+    fn test_wal_auto_checkpoint() {
+        let file = create_wal_database(&["CREATE TABLE example(id, col);"]);
+        let wal = wal_path(file.path());
+        let conn = Connection::open(file.path()).unwrap();
+        let mut wal_size = 0;
+        let mut n_inserts = 0;
+        loop {
+            let sql = format!("INSERT INTO example(id, col) VALUES ({n_inserts}, 'value');");
+            assert_eq!(conn.prepare(&sql).unwrap().execute().unwrap(), 1);
+            n_inserts += 1;
+            let size = std::fs::metadata(&wal).unwrap().len();
+            if size < wal_size {
+                break;
+            }
+            wal_size = size;
+            assert!(n_inserts < 1000);
+        }
+        // Only the WAL header is left.
+        assert_eq!(std::fs::metadata(&wal).unwrap().len(), 32);
+        drop(conn);
+        assert_eq!(count_rows_by_sqlite(file.path()), n_inserts);
+    }
+
+    #[test]
+    fn test_wal_multiple_connections() {
+        let file = create_wal_database(&["CREATE TABLE example(id, col);"]);
+        let writer = Connection::open(file.path()).unwrap();
+        let reader = Connection::open(file.path()).unwrap();
+        assert_eq!(count_rows(&reader), 0);
+
+        let stmt = writer
+            .prepare("INSERT INTO example(id, col) VALUES (1, 'value');")
+            .unwrap();
+        assert_eq!(stmt.execute().unwrap(), 1);
+        drop(stmt);
+        assert_eq!(count_rows(&reader), 1);
+
+        // The schema changes are loaded as well.
+        let stmt = writer.prepare("CREATE TABLE example2(id, col);").unwrap();
+        stmt.execute().unwrap();
+        drop(stmt);
+        let stmt = writer
+            .prepare("INSERT INTO example(id, col) VALUES (2, 'value');")
+            .unwrap();
+        assert_eq!(stmt.execute().unwrap(), 1);
+        drop(stmt);
+        assert_eq!(count_rows(&reader), 2);
+        let stmt = reader
+            .prepare("INSERT INTO example2(id, col) VALUES (1, 'value');")
+            .unwrap();
+        assert_eq!(stmt.execute().unwrap(), 1);
+        drop(stmt);
+
+        writer.checkpoint().unwrap();
+        assert_eq!(count_rows(&reader), 2);
+        let stmt = writer.prepare("DELETE FROM example WHERE id = 1;").unwrap();
+        assert_eq!(stmt.execute().unwrap(), 1);
+        drop(stmt);
+        assert_eq!(count_rows(&reader), 1);
+    }
+
+    #[test]
+    fn test_set_journal_mode() {
+        let file = create_sqlite_database(&["CREATE TABLE example(id, col);"]);
+        let wal = wal_path(file.path());
+        let mut conn = Connection::open(file.path()).unwrap();
+        assert_eq!(conn.journal_mode(), JournalMode::Delete);
+        conn.set_journal_mode(JournalMode::Wal).unwrap();
+        assert_eq!(conn.journal_mode(), JournalMode::Wal);
+        let stmt = conn
+            .prepare("INSERT INTO example(id, col) VALUES (1, 'value');")
+            .unwrap();
+        assert_eq!(stmt.execute().unwrap(), 1);
+        drop(stmt);
+        assert!(std::fs::metadata(&wal).unwrap().len() > 0);
+        drop(conn);
+
+        let mut conn = Connection::open(file.path()).unwrap();
+        assert_eq!(conn.journal_mode(), JournalMode::Wal);
+        assert_eq!(count_rows(&conn), 1);
+        conn.set_journal_mode(JournalMode::Delete).unwrap();
+        assert_eq!(conn.journal_mode(), JournalMode::Delete);
+        assert!(!wal.exists());
+        let stmt = conn
+            .prepare("INSERT INTO example(id, col) VALUES (2, 'value');")
+            .unwrap();
+        assert_eq!(stmt.execute().unwrap(), 1);
+        drop(stmt);
+        assert!(!wal.exists());
+        drop(conn);
+
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        let mode: String = conn
+            .query_row("PRAGMA journal_mode;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "delete");
+        drop(conn);
+        assert_eq!(count_rows_by_sqlite(file.path()), 2);
+    }
+
+    #[test]
+    fn test_wal_crash_recovery() {
+        let mut stmts = vec!["PRAGMA page_size = 512;", "CREATE TABLE example(id, col);"];
+        let inserts = (0..100)
+            .map(|i| format!("INSERT INTO example(id, col) VALUES ({i}, 'value{i:040}');"))
+            .collect::<Vec<_>>();
+        stmts.extend(inserts.iter().map(|s| s.as_str()));
+        let file = create_wal_database(&stmts);
+        let original = std::fs::read(file.path()).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let wal = wal_path(&db_path);
+        // Crash at each write operation of a commit and the following checkpoint.
+        for crash_point in 0.. {
+            std::fs::write(&db_path, &original).unwrap();
+            let _ = std::fs::remove_file(&wal);
+            let conn = Connection::open(&db_path).unwrap();
+            set_crash_point(Some(crash_point));
+            let is_committed = {
+                let stmt = conn
+                    .prepare("DELETE FROM example WHERE id % 3 != 0;")
+                    .unwrap();
+                stmt.execute().is_ok()
+            };
+            let is_checkpointed = is_committed && conn.checkpoint().is_ok();
+            drop(conn);
+            set_crash_point(None);
+
+            let conn = Connection::open(&db_path).unwrap();
+            let n_rows = count_rows(&conn);
+            drop(conn);
+            assert_eq!(count_rows_by_sqlite(&db_path), n_rows);
+            if is_committed {
+                assert_eq!(n_rows, 34);
+            } else {
+                // The commit frame may be written before the crash.
+                assert!(n_rows == 100 || n_rows == 34, "{n_rows}");
+            }
+            if is_checkpointed {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn test_drop_table() {
+        let mut stmts = vec![
+            "PRAGMA page_size = 512;",
+            "CREATE TABLE example(id, col);",
+            "CREATE INDEX index1 ON example(col);",
+            "CREATE INDEX index2 ON example(id);",
+            "CREATE TABLE example2(col);",
+            "INSERT INTO example2(col) VALUES (1);",
+        ];
+        // The payloads overflow to overflow pages.
+        let inserts = (0..100)
+            .map(|i| {
+                format!(
+                    "INSERT INTO example(id, col) VALUES ({i}, x'{:04x}{}');",
+                    i,
+                    "11".repeat(600)
+                )
+            })
+            .collect::<Vec<_>>();
+        stmts.extend(inserts.iter().map(|s| s.as_str()));
+        let file = create_sqlite_database(&stmts);
+        let n_pages = {
+            let conn = rusqlite::Connection::open(file.path()).unwrap();
+            conn.query_row("PRAGMA page_count;", [], |row| row.get::<_, i64>(0))
+                .unwrap()
+        };
+
+        {
+            let conn = Connection::open(file.path()).unwrap();
+            let stmt = conn.prepare("DROP TABLE example;").unwrap();
+            assert_eq!(stmt.execute().unwrap(), 0);
+
+            assert!(conn.prepare("SELECT * FROM example;").is_err());
+            let stmt = conn.prepare("SELECT col FROM example2;").unwrap();
+            let mut rows = stmt.query().unwrap();
+            let row = rows.next_row().unwrap().unwrap();
+            assert_eq!(row.parse().unwrap().get(0), Some(&Value::Integer(1)));
+        }
+
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        let names = conn
+            .prepare("SELECT name FROM sqlite_schema;")
+            .unwrap()
+            .query_map([], |row| row.get::<_, String>(0))
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(names, vec!["example2"]);
+        // All the pages except page 1 and the example2 table are reclaimed.
+        let n_free_pages: i64 = conn
+            .query_row("PRAGMA freelist_count;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(n_free_pages, n_pages - 2);
+        let result: String = conn
+            .query_row("PRAGMA integrity_check;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(result, "ok");
+        drop(conn);
+
+        // Freed pages are reused.
+        let conn = Connection::open(file.path()).unwrap();
+        let stmt = conn.prepare("CREATE TABLE example(id, col);").unwrap();
+        assert_eq!(stmt.execute().unwrap(), 0);
+        let stmt = conn
+            .prepare("INSERT INTO example(id, col) VALUES (1, 2);")
+            .unwrap();
+        assert_eq!(stmt.execute().unwrap(), 1);
+        assert_eq!(conn.pager.num_pages() as i64, n_pages);
+    }
+
+    #[test]
+    fn test_drop_index() {
+        let mut stmts = vec![
+            "PRAGMA page_size = 512;",
+            "CREATE TABLE example(id, col);",
+            "CREATE INDEX index1 ON example(col);",
+            "CREATE INDEX index2 ON example(id);",
+        ];
+        let inserts = (0..500)
+            .map(|i| format!("INSERT INTO example(id, col) VALUES ({i}, 'value{i}');"))
+            .collect::<Vec<_>>();
+        stmts.extend(inserts.iter().map(|s| s.as_str()));
+        let file = create_sqlite_database(&stmts);
+
+        {
+            let conn = Connection::open(file.path()).unwrap();
+            let stmt = conn.prepare("DROP INDEX IF EXISTS Index1;").unwrap();
+            assert_eq!(stmt.execute().unwrap(), 0);
+
+            let stmt = conn
+                .prepare("SELECT id FROM example WHERE col = 'value7';")
+                .unwrap();
+            let Statement::Query(select) = &stmt else {
+                unreachable!()
+            };
+            assert_eq!(select.query_plan.index_page_id(), None);
+            {
+                let mut rows = stmt.query().unwrap();
+                let row = rows.next_row().unwrap().unwrap();
+                assert_eq!(row.parse().unwrap().get(0), Some(&Value::Integer(7)));
+            }
+
+            // The remaining index is still maintained.
+            let stmt = conn
+                .prepare("INSERT INTO example(id, col) VALUES (500, 'value500');")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 1);
+        }
+
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        let names = conn
+            .prepare("SELECT name FROM sqlite_schema;")
+            .unwrap()
+            .query_map([], |row| row.get::<_, String>(0))
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(names, vec!["example", "index2"]);
+        let n_free_pages: i64 = conn
+            .query_row("PRAGMA freelist_count;", [], |row| row.get(0))
+            .unwrap();
+        assert!(n_free_pages > 1);
+        let result: String = conn
+            .query_row("PRAGMA integrity_check;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(result, "ok");
+    }
+
+    #[test]
+    fn test_drop_fail() {
+        let file = create_sqlite_database(&[
+            "CREATE TABLE example(col);",
+            "CREATE INDEX index1 ON example(col);",
+        ]);
+        let conn = Connection::open(file.path()).unwrap();
+
+        // Reserved name.
+        assert!(conn.prepare("DROP TABLE sqlite_schema;").is_err());
+        assert!(conn.prepare("DROP TABLE sqlite_master;").is_err());
+        assert!(conn.prepare("DROP TABLE sqlite_sequence;").is_err());
+        assert!(conn
+            .prepare("DROP INDEX sqlite_autoindex_example_1;")
+            .is_err());
+        // Unknown table or index.
+        let stmt = conn.prepare("DROP TABLE invalid;").unwrap();
+        assert!(stmt.execute().is_err());
+        let stmt = conn.prepare("DROP INDEX invalid;").unwrap();
+        assert!(stmt.execute().is_err());
+        // Index is not a table and vice versa.
+        let stmt = conn.prepare("DROP TABLE index1;").unwrap();
+        assert!(stmt.execute().is_err());
+        let stmt = conn.prepare("DROP INDEX example;").unwrap();
+        assert!(stmt.execute().is_err());
+        // IF EXISTS
+        let stmt = conn.prepare("DROP TABLE IF EXISTS invalid;").unwrap();
+        assert_eq!(stmt.execute().unwrap(), 0);
+        let stmt = conn.prepare("DROP INDEX IF EXISTS invalid;").unwrap();
+        assert_eq!(stmt.execute().unwrap(), 0);
+        // Dropped twice.
+        let stmt = conn.prepare("DROP TABLE example;").unwrap();
+        assert_eq!(stmt.execute().unwrap(), 0);
+        assert!(stmt.execute().is_err());
+    }
+
+    #[test]
+    fn test_update() {
+        let file = create_sqlite_database(&[
+            "CREATE TABLE example(id INTEGER PRIMARY KEY, col1, col2 INTEGER);",
+            "INSERT INTO example(id, col1, col2) VALUES (1, 'a', 10), (2, 'b', 20), (3, 'c', 30);",
+        ]);
+        {
+            let conn = Connection::open(file.path()).unwrap();
+
+            let stmt = conn
+                .prepare("UPDATE example SET col1 = 'x', col2 = '99' WHERE id = 2;")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 1);
+            // Expressions are evaluated against the original row.
+            let stmt = conn
+                .prepare("UPDATE example SET col1 = col2, col2 = col1;")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 3);
+            let stmt = conn
+                .prepare("UPDATE example SET col1 = NULL WHERE col1 = 100;")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 0);
+            // Rowid changes.
+            let stmt = conn
+                .prepare("UPDATE example SET id = 10 WHERE id = 3;")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 1);
+        }
+
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        let rows = conn
+            .prepare("SELECT id, col1, col2 FROM example;")
+            .unwrap()
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, rusqlite::types::Value>(1)?,
+                    row.get::<_, rusqlite::types::Value>(2)?,
+                ))
+            })
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        use rusqlite::types::Value as V;
+        assert_eq!(
+            rows,
+            vec![
+                (1, V::Integer(10), V::Text("a".to_string())),
+                (2, V::Integer(99), V::Text("x".to_string())),
+                (10, V::Integer(30), V::Text("c".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_with_index() {
+        let mut stmts = vec![
+            "PRAGMA page_size = 512;",
+            "CREATE TABLE example(id, col);",
+            "CREATE INDEX index1 ON example(col);",
+            "CREATE INDEX index2 ON example(id);",
+        ];
+        let inserts = (0..500)
+            .map(|i| {
+                format!(
+                    "INSERT INTO example(id, col) VALUES ({i}, 'value{}');",
+                    i % 100
+                )
+            })
+            .collect::<Vec<_>>();
+        stmts.extend(inserts.iter().map(|s| s.as_str()));
+        let file = create_sqlite_database(&stmts);
+        {
+            let conn = Connection::open(file.path()).unwrap();
+
+            // The rows are selected by index1 which is updated.
+            let stmt = conn
+                .prepare("UPDATE example SET col = 'value8' WHERE col = 'value7';")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 5);
+            let stmt = conn
+                .prepare("UPDATE example SET rowid = 1009 WHERE id = 8;")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 1);
+
+            let stmt = conn
+                .prepare("SELECT rowid, id FROM example WHERE col = 'value8';")
+                .unwrap();
+            let mut rows = stmt.query().unwrap();
+            let mut ids = Vec::new();
+            while let Some(row) = rows.next_row().unwrap() {
+                let columns = row.parse().unwrap();
+                let Some(Value::Integer(id)) = columns.get(1) else {
+                    panic!("unexpected id");
+                };
+                ids.push(*id);
+            }
+            ids.sort();
+            assert_eq!(ids, vec![7, 8, 107, 108, 207, 208, 307, 308, 407, 408]);
+        }
+
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        let n: i64 = conn
+            .query_row(
+                "SELECT count(*) FROM example INDEXED BY index1 WHERE col = 'value7';",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(n, 0);
+        let rowid: i64 = conn
+            .query_row(
+                "SELECT rowid FROM example INDEXED BY index2 WHERE id = 8;",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(rowid, 1009);
+        let result: String = conn
+            .query_row("PRAGMA integrity_check;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(result, "ok");
+    }
+
+    #[test]
+    fn test_update_unique_constraint() {
+        let file = create_sqlite_database(&[
+            "CREATE TABLE example(col1, col2);",
+            "CREATE UNIQUE INDEX index1 ON example(col1);",
+            "INSERT INTO example(col1, col2) VALUES (1, 'a'), (2, 'b'), (NULL, 'c'), (NULL, 'd');",
+        ]);
+        {
+            let conn = Connection::open(file.path()).unwrap();
+
+            let stmt = conn
+                .prepare("UPDATE example SET col1 = 1 WHERE col2 = 'b';")
+                .unwrap();
+            assert!(matches!(
+                stmt.execute(),
+                Err(Error::UniqueConstraintViolation)
+            ));
+            let stmt = conn
+                .prepare("UPDATE example SET rowid = 1 WHERE col2 = 'b';")
+                .unwrap();
+            assert!(matches!(
+                stmt.execute(),
+                Err(Error::UniqueConstraintViolation)
+            ));
+            let stmt = conn
+                .prepare("UPDATE example SET rowid = 'a' WHERE col2 = 'b';")
+                .unwrap();
+            assert!(matches!(stmt.execute(), Err(Error::DataTypeMismatch)));
+
+            // Updating a row to the same key does not conflict with itself.
+            let stmt = conn
+                .prepare("UPDATE example SET col1 = 2 WHERE col2 = 'b';")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 1);
+            // NULLs are distinct.
+            let stmt = conn
+                .prepare("UPDATE example SET col1 = NULL WHERE col2 = 'a';")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 1);
+        }
+
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        let values = conn
+            .prepare("SELECT col1 FROM example ORDER BY rowid;")
+            .unwrap()
+            .query_map([], |row| row.get::<_, Option<i64>>(0))
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(values, vec![None, Some(2), None, None]);
+        let result: String = conn
+            .query_row("PRAGMA integrity_check;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(result, "ok");
+    }
+
+    #[test]
+    fn test_update_fail() {
+        let file = create_sqlite_database(&["CREATE TABLE example(col);"]);
+        let conn = Connection::open(file.path()).unwrap();
+
+        // Unknown table.
+        assert!(conn.prepare("UPDATE invalid SET col = 1;").is_err());
+        // Unknown column.
+        assert!(conn.prepare("UPDATE example SET invalid = 1;").is_err());
+        assert!(conn
+            .prepare("UPDATE example SET col = 1 WHERE invalid = 1;")
+            .is_err());
+    }
+
+    fn collect_rows(stmt: &Statement) -> Vec<Vec<Option<String>>> {
+        let mut rows = stmt.query().unwrap();
+        let mut result = Vec::new();
+        while let Some(row) = rows.next_row().unwrap() {
+            let columns = row.parse().unwrap();
+            result.push(
+                columns
+                    .iter()
+                    .map(|v| {
+                        v.as_ref().map(|v| {
+                            let mut buf = Vec::new();
+                            v.display(&mut buf).unwrap();
+                            String::from_utf8(buf).unwrap()
+                        })
+                    })
+                    .collect(),
+            );
+        }
+        result
+    }
+
+    fn collect_sqlite_rows(file: &tempfile::NamedTempFile, sql: &str) -> Vec<Vec<Option<String>>> {
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        let mut stmt = conn.prepare(sql).unwrap();
+        let n_columns = stmt.column_count();
+        let rows = stmt
+            .query_map([], |row| {
+                (0..n_columns)
+                    .map(|i| {
+                        Ok(match row.get::<_, rusqlite::types::Value>(i)? {
+                            rusqlite::types::Value::Null => None,
+                            rusqlite::types::Value::Integer(i) => Some(i.to_string()),
+                            rusqlite::types::Value::Real(f) => Some(f.to_string()),
+                            rusqlite::types::Value::Text(t) => Some(t),
+                            rusqlite::types::Value::Blob(b) => Some(String::from_utf8(b).unwrap()),
+                        })
+                    })
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .unwrap();
+        rows
+    }
+
+    #[test]
+    fn test_select_logical_operators() {
+        let file = create_sqlite_database(&[
+            "CREATE TABLE example(id, col1, col2);",
+            "INSERT INTO example(id, col1, col2) VALUES (1, 1, 1), (2, 1, 0), (3, 0, NULL), (4, NULL, NULL), (5, 1, NULL), (6, 0.5, '1abc'), (7, 'abc', 0.0);",
+        ]);
+        let conn = Connection::open(file.path()).unwrap();
+        for sql in [
+            "SELECT id, col1 AND col2, col1 OR col2, NOT col1, NOT col2 FROM example;",
+            "SELECT id FROM example WHERE col1 AND col2;",
+            "SELECT id FROM example WHERE col1 OR col2;",
+            "SELECT id FROM example WHERE NOT col1;",
+            "SELECT id FROM example WHERE NOT col1 = 1 OR col2 = 1 AND id > 1;",
+            "SELECT id FROM example WHERE (col1 = 1) = NOT NOT col1;",
+            "SELECT id FROM example WHERE col1 = 1 AND id != 2 AND (col2 = 1 OR col2 = '1abc');",
+            "SELECT id FROM example WHERE NOT (col1 AND col2);",
+            "SELECT NULL AND 0, NULL AND 1, NULL OR 1, NULL OR 0, NOT NULL FROM example WHERE id = 1;",
+        ] {
+            let stmt = conn.prepare(sql).unwrap();
+            assert_eq!(
+                collect_rows(&stmt),
+                collect_sqlite_rows(&file, sql),
+                "sql: {sql}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_select_arithmetic() {
+        let file = create_sqlite_database(&[
+            "CREATE TABLE example(id, value, ts);",
+            "INSERT INTO example(id, value, ts) VALUES (1, 2, 125), (2, 0.5, 3600), (3, NULL, 59), (4, '3.0', -61), (5, '1abc', 0), (6, x'31', 9223372036854775807), (7, 'abc', -9223372036854775808);",
+        ]);
+        let conn = Connection::open(file.path()).unwrap();
+        for sql in [
+            "SELECT id, value * 1000, ts / 60, ts % 60, value + ts, value - ts FROM example;",
+            "SELECT id, ts + 1, ts - 1, ts * 2, ts / -1, ts % -1, -ts FROM example;",
+            "SELECT id, ts / 0, ts % 0, ts / 0.0, value / 0, 7 % 3.5, -7 % 3, 7.9 % 2 FROM example;",
+            "SELECT id, ts & 7, ts | 1, ts << 1, ts >> 1, ts << -1, ts >> 64, ts << 64, ~ts FROM example;",
+            "SELECT id, 1 + 2 * 3 - 4 / 2, (1 + 2) * 3, 1 << 2 + 1, 6 & 3 | 8, 2 * 3 < 7, '1' || 2 + 3 FROM example;",
+            "SELECT id FROM example WHERE ts / 60 = 1 OR value * 2 = 1;",
+            "SELECT id, value FROM example ORDER BY ts % 10, id;",
+        ] {
+            let stmt = conn.prepare(sql).unwrap();
+            assert_eq!(
+                collect_rows(&stmt),
+                collect_sqlite_rows(&file, sql),
+                "sql: {sql}"
+            );
+        }
+
+        // Integer/real promotion.
+        let stmt = conn
+            .prepare("SELECT 5 / 2, 5.0 / 2, '3.0' + 1, 9223372036854775807 + 1, 2.5 % 1, 1 / 0, 1e308 * 10 - 1e308 * 10 FROM example WHERE id = 1;")
+            .unwrap();
+        let mut rows = stmt.query().unwrap();
+        let row = rows.next_row().unwrap().unwrap();
+        let columns = row.parse().unwrap();
+        assert_eq!(columns.get(0), Some(&Value::Integer(2)));
+        assert_eq!(columns.get(1), Some(&Value::Real(2.5)));
+        assert_eq!(columns.get(2), Some(&Value::Real(4.0)));
+        assert_eq!(columns.get(3), Some(&Value::Real(9223372036854775808.0)));
+        assert_eq!(columns.get(4), Some(&Value::Real(0.0)));
+        assert_eq!(columns.get(5), None);
+        assert_eq!(columns.get(6), None);
+    }
+
+    #[test]
+    fn test_select_predicates() {
+        let file = create_sqlite_database(&[
+            "CREATE TABLE example(id, name TEXT, tag COLLATE NOCASE, ts INTEGER);",
+            "INSERT INTO example(id, name, tag, ts) VALUES (1, 'cpu_user', 'a', 10), (2, 'CPU_system', 'B', 20), (3, 'mem%used', NULL, 30), (4, NULL, 'c', NULL), (5, 'disk', 'b', '25'), (6, 'éclair', 'A', 15), (7, 123, 'ab', 5);",
+        ]);
+        let conn = Connection::open(file.path()).unwrap();
+        for sql in [
+            "SELECT id, tag IS NULL, tag IS NOT NULL, tag ISNULL, tag NOTNULL, tag NOT NULL, ts IS 20, name IS tag FROM example;",
+            "SELECT id FROM example WHERE ts BETWEEN 10 AND 25;",
+            "SELECT id FROM example WHERE ts NOT BETWEEN 10 AND '25';",
+            "SELECT id, ts BETWEEN NULL AND 20, ts BETWEEN 20 AND NULL, tag BETWEEN 'a' AND 'b' FROM example;",
+            "SELECT id FROM example WHERE tag IN ('a', 'b');",
+            "SELECT id FROM example WHERE tag NOT IN ('a', 'b');",
+            "SELECT id, ts IN (10, '20', 30.0), ts IN (10, NULL), ts NOT IN (10, NULL), ts IN (), NULL IN (), NULL IN (1) FROM example;",
+            "SELECT id FROM example WHERE name LIKE 'cpu%';",
+            "SELECT id FROM example WHERE name LIKE 'CPU_S%' OR name LIKE '_clair' OR name LIKE '1%';",
+            "SELECT id FROM example WHERE name NOT LIKE '%u%';",
+            "SELECT id FROM example WHERE name LIKE 'mem!%%' ESCAPE '!';",
+            "SELECT id, name LIKE NULL, name LIKE 'a' ESCAPE NULL FROM example;",
+            "SELECT id FROM example WHERE name GLOB 'cpu*';",
+            "SELECT id FROM example WHERE name GLOB '[a-d]*' OR name GLOB '?PU_*';",
+            "SELECT id FROM example WHERE name GLOB '*[^a-z_]*';",
+            "SELECT id FROM example WHERE tag IN ('a', 'b') AND ts BETWEEN 10 AND 20 AND name LIKE '%u%';",
+        ] {
+            let stmt = conn.prepare(sql).unwrap();
+            assert_eq!(
+                collect_rows(&stmt),
+                collect_sqlite_rows(&file, sql),
+                "sql: {sql}"
+            );
+        }
+
+        let stmt = conn
+            .prepare("SELECT id FROM example WHERE name LIKE 'a' ESCAPE '!!';")
+            .unwrap();
+        assert_eq!(
+            stmt.query().unwrap().next_row().err().unwrap().to_string(),
+            "query error: expression: ESCAPE expression must be a single character"
+        );
+        assert_eq!(
+            conn.prepare("SELECT id FROM example WHERE name GLOB 'a' ESCAPE '!';")
+                .err()
+                .unwrap()
+                .to_string(),
+            "expression error: wrong number of arguments to function glob()"
+        );
+    }
+
+    #[test]
+    fn test_query_plan_in_list() {
+        let mut stmts = vec![
+            "CREATE TABLE example(id, col1, col2 TEXT COLLATE NOCASE);",
+            "CREATE INDEX index1 ON example(col1);",
+            "CREATE INDEX index2 ON example(col2);",
+        ];
+        let inserts = (0..100)
+            .map(|i| {
+                format!(
+                    "INSERT INTO example(id, col1, col2) VALUES ({i}, {}, '{}');",
+                    i % 7,
+                    ["a", "B", "c", "D"][i % 4]
+                )
+            })
+            .collect::<Vec<_>>();
+        stmts.extend(inserts.iter().map(|s| s.as_str()));
+        let file = create_sqlite_database(&stmts);
+        let index1_page_id = find_index_page_id("index1", file.path());
+        let index2_page_id = find_index_page_id("index2", file.path());
+        let conn = Connection::open(file.path()).unwrap();
+
+        for (sql, expected_index_page_id) in [
+            (
+                "SELECT id FROM example WHERE col1 IN (5, 1, 3, 1);",
+                Some(index1_page_id),
+            ),
+            (
+                "SELECT id FROM example WHERE col1 IN (NULL, 2, 100);",
+                Some(index1_page_id),
+            ),
+            (
+                "SELECT id FROM example WHERE col1 IN ();",
+                Some(index1_page_id),
+            ),
+            (
+                "SELECT id FROM example WHERE col2 IN ('b', 'A', 'B');",
+                Some(index2_page_id),
+            ),
+            (
+                "SELECT id FROM example WHERE col1 IN (1, 2) AND col2 = 'c';",
+                Some(index2_page_id),
+            ),
+            (
+                "SELECT id FROM example WHERE col1 IN (1, 2) AND id IN (8, 9, 15);",
+                Some(index1_page_id),
+            ),
+            ("SELECT id FROM example WHERE col1 IN (1, id);", None),
+            ("SELECT id FROM example WHERE col1 NOT IN (1, 2);", None),
+        ] {
+            let stmt = conn.prepare(sql).unwrap();
+            let Statement::Query(select) = &stmt else {
+                unreachable!()
+            };
+            assert_eq!(
+                select.query_plan.index_page_id(),
+                expected_index_page_id,
+                "sql: {sql}"
+            );
+            assert_eq!(
+                collect_rows(&stmt),
+                collect_sqlite_rows(&file, sql),
+                "sql: {sql}"
+            );
+        }
+
+        // The seeks are in the index order.
+        let sql = "SELECT col1, id FROM example WHERE col1 IN (4, 2) ORDER BY col1;";
+        let stmt = conn.prepare(sql).unwrap();
+        let Statement::Query(select) = &stmt else {
+            unreachable!()
+        };
+        assert!(select.sort_keys.is_empty());
+        assert_eq!(collect_rows(&stmt), collect_sqlite_rows(&file, sql));
+
+        let stmt = conn
+            .prepare("DELETE FROM example WHERE col1 IN (3, 6);")
+            .unwrap();
+        assert_eq!(stmt.execute().unwrap(), 28);
+        let sql = "SELECT id FROM example WHERE col1 IN (2, 3, 4, 6);";
+        let stmt = conn.prepare(sql).unwrap();
+        assert_eq!(collect_rows(&stmt), collect_sqlite_rows(&file, sql));
+    }
+
+    #[test]
+    fn test_query_plan_rowid_in_list() {
+        let mut stmts = vec![
+            "CREATE TABLE example(id INTEGER PRIMARY KEY, col1, col2);",
+            "CREATE INDEX index1 ON example(col2);",
+        ];
+        let inserts = (0..100)
+            .map(|i| {
+                format!(
+                    "INSERT INTO example(id, col1, col2) VALUES ({}, {i}, {});",
+                    i * 2,
+                    i % 7
+                )
+            })
+            .collect::<Vec<_>>();
+        stmts.extend(inserts.iter().map(|s| s.as_str()));
+        let file = create_sqlite_database(&stmts);
+        let conn = Connection::open(file.path()).unwrap();
+
+        for (sql, expected_rowids) in [
+            (
+                "SELECT * FROM example WHERE rowid IN (10, 2, 6, 6);",
+                vec![2, 6, 10],
+            ),
+            // Missing rowids and values other than integers are skipped.
+            (
+                "SELECT * FROM example WHERE id IN (3, 198, 1000, '8', 'x', 4.0, 4.5, NULL);",
+                vec![3, 4, 8, 198, 1000],
+            ),
+            ("SELECT * FROM example WHERE id IN ();", vec![]),
+            (
+                "SELECT * FROM example WHERE col2 = 3 AND id IN (6, 20, 48);",
+                vec![6, 20, 48],
+            ),
+            (
+                "SELECT * FROM example WHERE id IN (6, 20, 48) AND id IN (20, 30);",
+                vec![20, 30],
+            ),
+        ] {
+            let stmt = conn.prepare(sql).unwrap();
+            let Statement::Query(select) = &stmt else {
+                unreachable!()
+            };
+            assert!(
+                matches!(
+                    &select.query_plan,
+                    QueryPlan::RowIds { rowids, reverse: false } if *rowids == expected_rowids
+                ),
+                "sql: {sql}"
+            );
+            assert_eq!(
+                collect_rows(&stmt),
+                collect_sqlite_rows(&file, sql),
+                "sql: {sql}"
+            );
+        }
+
+        // The rowids are seeked backward instead of sorting.
+        let sql = "SELECT id FROM example WHERE id IN (4, 2, 8) ORDER BY id DESC;";
+        let stmt = conn.prepare(sql).unwrap();
+        let Statement::Query(select) = &stmt else {
+            unreachable!()
+        };
+        assert!(matches!(
+            select.query_plan,
+            QueryPlan::RowIds { reverse: true, .. }
+        ));
+        assert!(select.sort_keys.is_empty());
+        assert_eq!(collect_rows(&stmt), collect_sqlite_rows(&file, sql));
+        assert_eq!(
+            explain(&conn, "SELECT * FROM example WHERE rowid IN (1, 5, 9);"),
+            vec!["SEARCH example USING INTEGER PRIMARY KEY (rowid=?)"]
+        );
+
+        let stmt = conn
+            .prepare("UPDATE example SET id = id + 1 WHERE id IN (10, 12, 14);")
+            .unwrap();
+        assert_eq!(stmt.execute().unwrap(), 3);
+        let stmt = conn
+            .prepare("DELETE FROM example WHERE id IN (11, 13, 16, 17);")
+            .unwrap();
+        assert_eq!(stmt.execute().unwrap(), 3);
+        let sql = "SELECT id FROM example WHERE id < 20;";
+        assert_eq!(
+            collect_rows(&conn.prepare(sql).unwrap()),
+            vec![0, 2, 4, 6, 8, 15, 18]
+                .into_iter()
+                .map(|id| vec![Some(id.to_string())])
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_query_plan_conjunction() {
+        let mut stmts = vec![
+            "CREATE TABLE example(id, col1, col2);",
+            "CREATE INDEX index1 ON example(col2);",
+        ];
+        let inserts = (0..100)
+            .map(|i| {
+                format!(
+                    "INSERT INTO example(id, col1, col2) VALUES ({i}, {}, {});",
+                    i % 3,
+                    i % 7
+                )
+            })
+            .collect::<Vec<_>>();
+        stmts.extend(inserts.iter().map(|s| s.as_str()));
+        let file = create_sqlite_database(&stmts);
+        let index_page_id = find_index_page_id("index1", file.path());
+        let conn = Connection::open(file.path()).unwrap();
+
+        for (sql, expected_index_page_id) in [
+            (
+                "SELECT id FROM example WHERE col1 = 1 AND col2 = 3;",
+                Some(index_page_id),
+            ),
+            (
+                "SELECT id FROM example WHERE col2 = 3 AND col1 = 1 AND id > 50;",
+                Some(index_page_id),
+            ),
+            ("SELECT id FROM example WHERE col1 = 1 OR col2 = 3;", None),
+            ("SELECT id FROM example WHERE NOT col2 = 3;", None),
+            (
+                "SELECT id FROM example WHERE col2 = 3 AND rowid = 10;",
+                None,
+            ),
+        ] {
+            let stmt = conn.prepare(sql).unwrap();
+            let Statement::Query(select) = &stmt else {
+                unreachable!()
+            };
+            assert_eq!(
+                select.query_plan.index_page_id(),
+                expected_index_page_id,
+                "sql: {sql}"
+            );
+            assert_eq!(
+                collect_rows(&stmt),
+                collect_sqlite_rows(&file, sql),
+                "sql: {sql}"
+            );
+        }
+
+        let stmt = conn
+            .prepare("SELECT id FROM example WHERE col2 = 3 AND rowid = 11 AND col1 = 1;")
+            .unwrap();
+        let Statement::Query(select) = &stmt else {
+            unreachable!()
+        };
+        assert!(matches!(select.query_plan, QueryPlan::RowId(11)));
+        assert_eq!(collect_rows(&stmt), vec![vec![Some("10".to_string())]]);
+
+        // DELETE with a conjunction uses the index as well.
+        let stmt = conn
+            .prepare("DELETE FROM example WHERE col2 = 3 AND col1 = 0;")
+            .unwrap();
+        assert_eq!(stmt.execute().unwrap(), 5);
+        let sql = "SELECT id FROM example WHERE col2 = 3;";
+        let stmt = conn.prepare(sql).unwrap();
+        assert_eq!(collect_rows(&stmt), collect_sqlite_rows(&file, sql));
+    }
+
+    #[test]
+    fn test_query_plan_range() {
+        let mut stmts = vec![
+            "CREATE TABLE example(id INTEGER PRIMARY KEY, host TEXT, ts INTEGER, value);",
+            "CREATE INDEX index_ts ON example(ts);",
+            "CREATE INDEX index_host_ts ON example(host, ts);",
+        ];
+        let inserts = (1..=1000)
+            .map(|i| {
+                let ts = match i % 50 {
+                    0 => "NULL".to_string(),
+                    1 => "'text'".to_string(),
+                    2 => format!("{}.5", i / 10),
+                    _ => (i / 10).to_string(),
+                };
+                format!(
+                    "INSERT INTO example(id, host, ts, value) VALUES ({i}, '{}', {ts}, {});",
+                    ["a", "b", "c"][i % 3],
+                    i % 7
+                )
+            })
+            .collect::<Vec<_>>();
+        stmts.extend(inserts.iter().map(|s| s.as_str()));
+        let file = create_sqlite_database(&stmts);
+        let index_ts_page_id = find_index_page_id("index_ts", file.path());
+        let index_host_ts_page_id = find_index_page_id("index_host_ts", file.path());
+        let conn = Connection::open(file.path()).unwrap();
+
+        for (sql, expected_index_page_id) in [
+            (
+                "SELECT id FROM example WHERE ts > 50 AND ts < 60;",
+                Some(index_ts_page_id),
+            ),
+            (
+                "SELECT id FROM example WHERE ts >= 95;",
+                Some(index_ts_page_id),
+            ),
+            (
+                "SELECT id FROM example WHERE ts <= 3 AND value = 1;",
+                Some(index_ts_page_id),
+            ),
+            (
+                "SELECT id FROM example WHERE 10 < ts AND 12.5 >= ts;",
+                Some(index_ts_page_id),
+            ),
+            (
+                "SELECT id FROM example WHERE ts BETWEEN '20' AND 21 AND ts > 20;",
+                Some(index_ts_page_id),
+            ),
+            (
+                "SELECT id FROM example WHERE ts > 'a';",
+                Some(index_ts_page_id),
+            ),
+            (
+                "SELECT id FROM example WHERE ts > 90 AND ts < 10;",
+                Some(index_ts_page_id),
+            ),
+            (
+                "SELECT id FROM example WHERE host = 'b' AND ts > 30 AND ts <= 40;",
+                Some(index_host_ts_page_id),
+            ),
+            (
+                "SELECT id FROM example WHERE ts = 30 AND host > 'a';",
+                Some(index_ts_page_id),
+            ),
+            (
+                "SELECT id FROM example WHERE host IN ('c', 'a') AND ts BETWEEN 40 AND 41;",
+                Some(index_host_ts_page_id),
+            ),
+            ("SELECT id FROM example WHERE id > 10 AND id <= 20;", None),
+            ("SELECT id FROM example WHERE ts != 10 ORDER BY id;", None),
+        ] {
+            let stmt = conn.prepare(sql).unwrap();
+            let Statement::Query(select) = &stmt else {
+                unreachable!()
+            };
+            assert_eq!(
+                select.query_plan.index_page_id(),
+                expected_index_page_id,
+                "sql: {sql}"
+            );
+            assert_eq!(
+                collect_rows(&stmt),
+                collect_sqlite_rows(&file, sql),
+                "sql: {sql}"
+            );
+        }
+
+        for (sql, expected_min, expected_max) in [
+            (
+                "SELECT id FROM example WHERE id > 10 AND id <= 20;",
+                Some(11),
+                Some(20),
+            ),
+            (
+                "SELECT id FROM example WHERE rowid BETWEEN 500 AND 505;",
+                Some(500),
+                Some(505),
+            ),
+            (
+                "SELECT id FROM example WHERE 990 < id AND id > 980;",
+                Some(991),
+                None,
+            ),
+            (
+                "SELECT id FROM example WHERE id < 5.5 AND id < '7';",
+                None,
+                Some(6),
+            ),
+            (
+                "SELECT id FROM example WHERE id >= 9223372036854775807;",
+                Some(i64::MAX),
+                None,
+            ),
+            (
+                "SELECT id FROM example WHERE id > 9223372036854775807;",
+                Some(i64::MAX),
+                None,
+            ),
+            (
+                "SELECT id FROM example WHERE id < 3 AND ts > 90;",
+                None,
+                Some(2),
+            ),
+        ] {
+            let stmt = conn.prepare(sql).unwrap();
+            let Statement::Query(select) = &stmt else {
+                unreachable!()
+            };
+            let QueryPlan::RowIdRange { min, max, .. } = select.query_plan else {
+                panic!("not rowid range: {sql}");
+            };
+            assert_eq!((min, max), (expected_min, expected_max), "sql: {sql}");
+            assert_eq!(
+                collect_rows(&stmt),
+                collect_sqlite_rows(&file, sql),
+                "sql: {sql}"
+            );
+        }
+
+        // An index equality is preferred to a rowid range.
+        let stmt = conn
+            .prepare("SELECT id FROM example WHERE id > 10 AND host = 'a' AND ts = 30;")
+            .unwrap();
+        let Statement::Query(select) = &stmt else {
+            unreachable!()
+        };
+        assert_eq!(
+            select.query_plan.index_page_id(),
+            Some(index_host_ts_page_id)
+        );
+
+        // A non-numeric bound does not narrow the rowid range.
+        let stmt = conn
+            .prepare("SELECT id FROM example WHERE id > 'abc';")
+            .unwrap();
+        let Statement::Query(select) = &stmt else {
+            unreachable!()
+        };
+        assert!(matches!(select.query_plan, QueryPlan::FullScan));
+
+        // The rows from a range scan are sorted.
+        let sql = "SELECT ts, id FROM example WHERE ts > 50 AND ts < 53 ORDER BY ts;";
+        let stmt = conn.prepare(sql).unwrap();
+        let Statement::Query(select) = &stmt else {
+            unreachable!()
+        };
+        assert!(select.sort_keys.is_empty());
+        assert_eq!(collect_rows(&stmt), collect_sqlite_rows(&file, sql));
+
+        for sql in [
+            "DELETE FROM example WHERE ts > 10 AND ts <= 20;",
+            "DELETE FROM example WHERE id BETWEEN 700 AND 800;",
+            "DELETE FROM example WHERE host = 'c' AND ts < 40;",
+        ] {
+            let expected = collect_sqlite_rows(&file, &sql.replace("DELETE", "SELECT count(*)"));
+            let stmt = conn.prepare(sql).unwrap();
+            assert_eq!(
+                vec![vec![Some(stmt.execute().unwrap().to_string())]],
+                expected,
+                "sql: {sql}"
+            );
+        }
+        let sql = "SELECT id, host, ts FROM example ORDER BY id;";
+        let stmt = conn.prepare(sql).unwrap();
+        assert_eq!(collect_rows(&stmt), collect_sqlite_rows(&file, sql));
+    }
+
+    #[test]
+    fn test_query_plan_multi_column_index() {
+        let mut stmts = vec![
+            "CREATE TABLE example(id, host TEXT, metric TEXT COLLATE NOCASE, ts INTEGER);",
+            "CREATE INDEX index_host ON example(host);",
+            "CREATE INDEX index_host_metric_ts ON example(host, metric, ts);",
+            "CREATE INDEX index_metric ON example(metric);",
+        ];
+        let inserts = (0..600)
+            .map(|i| {
+                format!(
+                    "INSERT INTO example(id, host, metric, ts) VALUES ({i}, '{}', '{}', {});",
+                    ["a", "b", "c"][i % 3],
+                    ["cpu", "MEM", "disk", "net"][i % 4],
+                    i / 10
+                )
+            })
+            .collect::<Vec<_>>();
+        stmts.extend(inserts.iter().map(|s| s.as_str()));
+        let file = create_sqlite_database(&stmts);
+        let index_host_page_id = find_index_page_id("index_host", file.path());
+        let index_host_metric_ts_page_id = find_index_page_id("index_host_metric_ts", file.path());
+        let index_metric_page_id = find_index_page_id("index_metric", file.path());
+        let conn = Connection::open(file.path()).unwrap();
+
+        for (sql, expected_index_page_id) in [
+            (
+                "SELECT id FROM example WHERE host = 'a' AND metric = 'cpu' ORDER BY id;",
+                index_host_metric_ts_page_id,
+            ),
+            (
+                "SELECT id FROM example WHERE metric = 'mem' AND ts = 12 AND host = 'b' ORDER BY id;",
+                index_host_metric_ts_page_id,
+            ),
+            (
+                "SELECT id FROM example WHERE host = 'c' AND metric = 'disk' AND ts > 50 ORDER BY id;",
+                index_host_metric_ts_page_id,
+            ),
+            (
+                "SELECT id FROM example WHERE host IN ('c', 'a') AND metric IN ('net', 'CPU', 'cpu') AND ts BETWEEN 10 AND 20 ORDER BY id;",
+                index_host_metric_ts_page_id,
+            ),
+            (
+                "SELECT id FROM example WHERE host = 'a' AND metric IN ('mem', NULL) ORDER BY id;",
+                index_host_metric_ts_page_id,
+            ),
+            // The equality on the first column is preferred to the IN list.
+            (
+                "SELECT id FROM example WHERE host IN ('a', 'b') AND metric = 'cpu' AND ts = 3 ORDER BY id;",
+                index_host_metric_ts_page_id,
+            ),
+            (
+                "SELECT id FROM example WHERE host IN ('a', 'b') AND metric = 'cpu' ORDER BY id;",
+                index_host_metric_ts_page_id,
+            ),
+            (
+                "SELECT id FROM example WHERE host IN ('a', 'b') AND metric = 'cpu' AND id = 4;",
+                index_host_metric_ts_page_id,
+            ),
+            // Both bind 1 column. The narrower index is chosen.
+            (
+                "SELECT id FROM example WHERE host = 'b' AND ts = 10 ORDER BY id;",
+                index_host_page_id,
+            ),
+            (
+                "SELECT id FROM example WHERE metric = 'net' AND ts < 10 ORDER BY id;",
+                index_metric_page_id,
+            ),
+        ] {
+            let stmt = conn.prepare(sql).unwrap();
+            let Statement::Query(select) = &stmt else {
+                unreachable!()
+            };
+            assert_eq!(
+                select.query_plan.index_page_id(),
+                Some(expected_index_page_id),
+                "sql: {sql}"
+            );
+            assert_eq!(
+                collect_rows(&stmt),
+                collect_sqlite_rows(&file, sql),
+                "sql: {sql}"
+            );
+        }
+
+        // The rows with the bound prefix are sorted by the following columns.
+        let sql = "SELECT ts, id FROM example WHERE metric = 'cpu' AND host = 'a' ORDER BY ts;";
+        let stmt = conn.prepare(sql).unwrap();
+        let Statement::Query(select) = &stmt else {
+            unreachable!()
+        };
+        assert!(select.sort_keys.is_empty());
+        assert_eq!(collect_rows(&stmt), collect_sqlite_rows(&file, sql));
+
+        let stmt = conn
+            .prepare("DELETE FROM example WHERE host IN ('a', 'c') AND metric = 'net';")
+            .unwrap();
+        assert_eq!(stmt.execute().unwrap(), 100);
+        let sql = "SELECT id FROM example WHERE metric = 'net' ORDER BY id;";
+        let stmt = conn.prepare(sql).unwrap();
+        assert_eq!(collect_rows(&stmt), collect_sqlite_rows(&file, sql));
+    }
+
+    #[test]
+    fn test_query_plan_covering_index() {
+        let long_host = "h".repeat(1000);
+        let mut stmts = vec![
+            "PRAGMA page_size = 512;".to_string(),
+            "CREATE TABLE example(id INTEGER PRIMARY KEY, host TEXT, metric TEXT, ts INTEGER, payload BLOB);".to_string(),
+            "CREATE INDEX index_host_ts ON example(host, ts);".to_string(),
+        ];
+        stmts.extend((0..300).map(|i| {
+            format!(
+                "INSERT INTO example(host, metric, ts, payload) VALUES ('{}', 'metric{}', {}, zeroblob(300));",
+                ["a", "b", long_host.as_str()][i % 3],
+                i % 4,
+                i / 3
+            )
+        }));
+        let stmts = stmts.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+        let file = create_sqlite_database(&stmts);
+        let index_page_id = find_index_page_id("index_host_ts", file.path());
+        let conn = Connection::open(file.path()).unwrap();
+
+        for (sql, covering) in [
+            ("SELECT ts, id FROM example WHERE host = 'a' ORDER BY ts;", true),
+            (
+                "SELECT host, ts FROM example WHERE host IN ('b', 'c') AND ts > 40 ORDER BY ts;",
+                true,
+            ),
+            (
+                &format!("SELECT host, ts, rowid FROM example WHERE host = '{long_host}' AND ts < 20 ORDER BY ts;"),
+                true,
+            ),
+            (
+                "SELECT count(*), max(ts), sum(id) FROM example WHERE host = 'b' GROUP BY host HAVING min(ts) >= 0;",
+                true,
+            ),
+            ("SELECT metric FROM example WHERE host = 'a' ORDER BY ts;", false),
+            (
+                "SELECT ts FROM example WHERE host = 'a' AND metric = 'metric1' ORDER BY ts;",
+                false,
+            ),
+            ("SELECT * FROM example WHERE host = 'b' ORDER BY ts;", false),
+        ] {
+            let stmt = conn.prepare(sql).unwrap();
+            let Statement::Query(select) = &stmt else {
+                unreachable!()
+            };
+            assert_eq!(
+                select.query_plan.index_page_id(),
+                Some(index_page_id),
+                "sql: {sql}"
+            );
+            assert_eq!(select.query_plan.is_covering(), covering, "sql: {sql}");
+            assert_eq!(
+                collect_rows(&stmt),
+                collect_sqlite_rows(&file, sql),
+                "sql: {sql}"
+            );
+        }
+
+        // DELETE reads the full rows to maintain the indexes.
+        let stmt = conn
+            .prepare("DELETE FROM example WHERE host = 'a' AND ts < 50;")
+            .unwrap();
+        assert_eq!(stmt.execute().unwrap(), 50);
+        let sql = "SELECT ts, id FROM example WHERE host = 'a' ORDER BY ts;";
+        assert_eq!(
+            collect_rows(&conn.prepare(sql).unwrap()),
+            collect_sqlite_rows(&file, sql)
+        );
+    }
+
+    #[test]
+    fn test_query_plan_prefer_covering_index() {
+        let mut stmts = vec![
+            "CREATE TABLE example(id INTEGER PRIMARY KEY, a, b, c);".to_string(),
+            "CREATE INDEX ia ON example(a);".to_string(),
+            "CREATE INDEX iab ON example(a, b);".to_string(),
+        ];
+        stmts.extend((0..200).map(|i| {
+            format!(
+                "INSERT INTO example(a, b, c) VALUES ({}, {}, {});",
+                i % 50,
+                i % 7,
+                i
+            )
+        }));
+        let stmts = stmts.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+        let file = create_sqlite_database(&stmts);
+        let ia_page_id = find_index_page_id("ia", file.path());
+        let iab_page_id = find_index_page_id("iab", file.path());
+
+        for analyze in [false, true] {
+            if analyze {
+                let conn = rusqlite::Connection::open(file.path()).unwrap();
+                conn.execute("ANALYZE;", []).unwrap();
+            }
+            let conn = Connection::open(file.path()).unwrap();
+            for (sql, index_page_id, covering) in [
+                ("SELECT a, b FROM example WHERE a = 1;", iab_page_id, true),
+                (
+                    "SELECT id, b FROM example WHERE a IN (1, 2);",
+                    iab_page_id,
+                    true,
+                ),
+                ("SELECT a, c FROM example WHERE a = 1;", ia_page_id, false),
+            ] {
+                let stmt = conn.prepare(sql).unwrap();
+                let Statement::Query(select) = &stmt else {
+                    unreachable!()
+                };
+                // The costs of both indexes are the same if they do not cover
+                // the query.
+                if covering || !analyze {
+                    assert_eq!(
+                        select.query_plan.index_page_id(),
+                        Some(index_page_id),
+                        "sql: {sql}, analyze: {analyze}"
+                    );
+                }
+                assert_eq!(select.query_plan.is_covering(), covering, "sql: {sql}");
+                // SQLite may scan another index in a different order.
+                let mut rows = collect_rows(&stmt);
+                rows.sort();
+                let mut expected = collect_sqlite_rows(&file, sql);
+                expected.sort();
+                assert_eq!(rows, expected, "sql: {sql}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_query_plan_descending() {
+        let mut stmts = vec![
+            "CREATE TABLE example(id INTEGER PRIMARY KEY, host TEXT, ts INTEGER, value);",
+            "CREATE INDEX index_host_ts ON example(host, ts);",
+        ];
+        let inserts = (1..=1000)
+            .map(|i| {
+                let ts = if i % 13 == 0 {
+                    "NULL".to_string()
+                } else {
+                    (i / 20).to_string()
+                };
+                format!(
+                    "INSERT INTO example(id, host, ts, value) VALUES ({i}, '{}', {ts}, {});",
+                    ["a", "b", "c"][i % 3],
+                    i * 7 % 100
+                )
+            })
+            .collect::<Vec<_>>();
+        stmts.extend(inserts.iter().map(|s| s.as_str()));
+        let file = create_sqlite_database(&stmts);
+        let index_page_id = find_index_page_id("index_host_ts", file.path());
+        let conn = Connection::open(file.path()).unwrap();
+
+        for (sql, expected_index_page_id) in [
+            ("SELECT id, value FROM example ORDER BY id DESC;", None),
+            ("SELECT id FROM example ORDER BY rowid DESC LIMIT 5;", None),
+            (
+                "SELECT id FROM example WHERE id BETWEEN 100 AND 200 ORDER BY id DESC;",
+                None,
+            ),
+            ("SELECT id FROM example WHERE id < 50 ORDER BY id DESC;", None),
+            ("SELECT id FROM example WHERE id > 990 ORDER BY id DESC;", None),
+            ("SELECT id FROM example WHERE id > 2000 ORDER BY id DESC;", None),
+            (
+                "SELECT id, ts FROM example WHERE host = 'a' ORDER BY ts DESC, id DESC;",
+                Some(index_page_id),
+            ),
+            (
+                "SELECT id, ts FROM example WHERE host = 'a' AND ts > 10 AND ts <= 40 ORDER BY ts DESC, id DESC;",
+                Some(index_page_id),
+            ),
+            (
+                "SELECT id, ts FROM example WHERE host = 'b' AND ts < 30 ORDER BY ts DESC, id DESC;",
+                Some(index_page_id),
+            ),
+            (
+                "SELECT id, ts FROM example WHERE host = 'c' AND ts >= 45 ORDER BY ts DESC, id DESC;",
+                Some(index_page_id),
+            ),
+            (
+                "SELECT host, ts, id FROM example WHERE host IN ('a', 'c') AND ts BETWEEN 5 AND 8 ORDER BY host DESC, ts DESC;",
+                Some(index_page_id),
+            ),
+            (
+                "SELECT id, value FROM example WHERE host = 'b' AND ts = 12 ORDER BY host DESC, ts DESC, id DESC;",
+                Some(index_page_id),
+            ),
+            // The index is scanned only to avoid sorting.
+            (
+                "SELECT * FROM example ORDER BY host DESC, ts DESC LIMIT 30;",
+                Some(index_page_id),
+            ),
+            (
+                "SELECT host, ts, id FROM example ORDER BY host DESC;",
+                Some(index_page_id),
+            ),
+            (
+                "SELECT * FROM example WHERE value < 10 ORDER BY host, ts, id;",
+                Some(index_page_id),
+            ),
+        ] {
+            let stmt = conn.prepare(sql).unwrap();
+            let Statement::Query(select) = &stmt else {
+                unreachable!()
+            };
+            assert_eq!(
+                select.query_plan.index_page_id(),
+                expected_index_page_id,
+                "sql: {sql}"
+            );
+            assert!(select.sort_keys.is_empty(), "sql: {sql}");
+            assert_eq!(
+                collect_rows(&stmt),
+                collect_sqlite_rows(&file, sql),
+                "sql: {sql}"
+            );
+        }
+
+        assert_eq!(
+            explain(&conn, "SELECT * FROM example ORDER BY host DESC, ts DESC;"),
+            vec!["SCAN example USING INDEX index_host_ts"]
+        );
+
+        // Mixed directions need sorting.
+        let sql = "SELECT id FROM example WHERE host IN ('a', 'b') ORDER BY host DESC, ts, id;";
+        let stmt = conn.prepare(sql).unwrap();
+        let Statement::Query(select) = &stmt else {
+            unreachable!()
+        };
+        assert!(!select.sort_keys.is_empty());
+        assert_eq!(collect_rows(&stmt), collect_sqlite_rows(&file, sql));
+
+        let sql = "SELECT id FROM example ORDER BY id DESC;";
+        let stmt = conn.prepare(sql).unwrap();
+        let Statement::Query(select) = &stmt else {
+            unreachable!()
+        };
+        assert!(matches!(
+            select.query_plan,
+            QueryPlan::RowIdRange {
+                min: None,
+                max: None,
+                reverse: true
+            }
+        ));
+    }
+
+    #[test]
+    fn test_analyze() {
+        let mut stmts = vec![
+            "CREATE TABLE example(id INTEGER PRIMARY KEY, host TEXT COLLATE NOCASE, metric, ts);",
+            "CREATE INDEX index_host ON example(host);",
+            "CREATE INDEX index_host_metric ON example(host, metric);",
+            "CREATE UNIQUE INDEX index_ts ON example(ts);",
+            "CREATE TABLE no_index(col);",
+            "CREATE TABLE empty(col);",
+            "CREATE INDEX index_empty ON empty(col);",
+        ];
+        let inserts = (0..300)
+            .map(|i| {
+                let metric = if i < 100 {
+                    "NULL".to_string()
+                } else {
+                    format!("{}", i % 7)
+                };
+                format!(
+                    "INSERT INTO example(host, metric, ts) VALUES ('{}', {metric}, {i});",
+                    ["a", "A", "b", "c", "d"][i % 5],
+                )
+            })
+            .collect::<Vec<_>>();
+        stmts.extend(inserts.iter().map(|s| s.as_str()));
+        stmts.push("INSERT INTO no_index(col) VALUES (1), (2), (3);");
+        let file = create_sqlite_database(&stmts);
+        stmts.push("ANALYZE;");
+        let expected_file = create_sqlite_database(&stmts);
+
+        let sql = "SELECT tbl, idx, stat FROM sqlite_stat1 ORDER BY tbl, idx;";
+        let conn = Connection::open(file.path()).unwrap();
+        let stmt = conn.prepare("ANALYZE;").unwrap();
+        assert_eq!(stmt.execute().unwrap(), 0);
+        let expected = collect_sqlite_rows(&expected_file, sql);
+        assert_eq!(collect_sqlite_rows(&file, sql), expected);
+        assert_eq!(collect_rows(&conn.prepare(sql).unwrap()), expected);
+
+        // Analyzing again replaces the stale statistics.
+        let stmt = conn
+            .prepare("DELETE FROM example WHERE ts >= 100;")
+            .unwrap();
+        assert_eq!(stmt.execute().unwrap(), 200);
+        let stmt = conn.prepare("ANALYZE Index_Host;").unwrap();
+        assert_eq!(stmt.execute().unwrap(), 0);
+        assert_eq!(
+            collect_sqlite_rows(&file, sql),
+            vec![
+                vec![
+                    Some("example".into()),
+                    Some("index_host".into()),
+                    Some("100 25".into())
+                ],
+                vec![
+                    Some("example".into()),
+                    Some("index_host_metric".into()),
+                    Some("300 75 10".into())
+                ],
+                vec![
+                    Some("example".into()),
+                    Some("index_ts".into()),
+                    Some("300 1".into())
+                ],
+                vec![Some("no_index".into()), None, Some("3".into())],
+            ]
+        );
+        let stmt = conn.prepare("ANALYZE example;").unwrap();
+        assert_eq!(stmt.execute().unwrap(), 0);
+        assert_eq!(
+            collect_sqlite_rows(&file, sql)[..3],
+            [
+                vec![
+                    Some("example".into()),
+                    Some("index_host".into()),
+                    Some("100 25".into())
+                ],
+                vec![
+                    Some("example".into()),
+                    Some("index_host_metric".into()),
+                    Some("100 25 25".into())
+                ],
+                vec![
+                    Some("example".into()),
+                    Some("index_ts".into()),
+                    Some("100 1".into())
+                ],
+            ]
+        );
+
+        // Dropping removes the statistics.
+        let stmt = conn.prepare("DROP INDEX index_ts;").unwrap();
+        assert_eq!(stmt.execute().unwrap(), 0);
+        let stmt = conn.prepare("DROP TABLE no_index;").unwrap();
+        assert_eq!(stmt.execute().unwrap(), 0);
+        assert_eq!(
+            collect_rows(
+                &conn
+                    .prepare("SELECT idx FROM sqlite_stat1 ORDER BY idx;")
+                    .unwrap()
+            ),
+            vec![
+                vec![Some("index_host".into())],
+                vec![Some("index_host_metric".into())],
+            ]
+        );
+
+        let stmt = conn.prepare("ANALYZE unknown;").unwrap();
+        assert!(stmt.execute().is_err());
+
+        // sqlite_stat1 can be dropped like sqlite3.
+        let stmt = conn.prepare("DROP TABLE sqlite_stat1;").unwrap();
+        assert_eq!(stmt.execute().unwrap(), 0);
+        assert_eq!(
+            collect_sqlite_rows(&file, "SELECT name FROM sqlite_schema ORDER BY name;"),
+            vec![
+                vec![Some("empty".into())],
+                vec![Some("example".into())],
+                vec![Some("index_empty".into())],
+                vec![Some("index_host".into())],
+                vec![Some("index_host_metric".into())],
+            ]
+        );
+
+        let conn = rusqlite::Connection::open(file.path()).unwrap();
+        let result: String = conn
+            .query_row("PRAGMA integrity_check;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(result, "ok");
+    }
+
+    #[test]
+    fn test_query_plan_stats() {
+        let mut stmts = vec![
+            "CREATE TABLE example(id INTEGER PRIMARY KEY, flag INTEGER, kind INTEGER, host TEXT);",
+            "CREATE INDEX index_flag_kind ON example(flag, kind);",
+            "CREATE INDEX index_host ON example(host);",
+        ];
+        let inserts = (0..1000)
+            .map(|i| {
+                format!(
+                    "INSERT INTO example(flag, kind, host) VALUES ({}, {}, 'host{}');",
+                    i % 2,
+                    i % 5,
+                    i % 500
+                )
+            })
+            .collect::<Vec<_>>();
+        stmts.extend(inserts.iter().map(|s| s.as_str()));
+        let file = create_sqlite_database(&stmts);
+        let index_flag_kind_page_id = find_index_page_id("index_flag_kind", file.path());
+        let index_host_page_id = find_index_page_id("index_host", file.path());
+
+        let cases = [
+            (
+                "SELECT id FROM example WHERE flag = 1 AND kind = 3 AND host = 'host3' ORDER BY id;",
+                Some(index_flag_kind_page_id),
+                Some(index_host_page_id),
+            ),
+            // The index matching half of the rows is more expensive than the
+            // full scan unless it covers the query.
+            (
+                "SELECT id, host FROM example WHERE flag = 0 ORDER BY id;",
+                Some(index_flag_kind_page_id),
+                None,
+            ),
+            (
+                "SELECT id FROM example WHERE flag = 0;",
+                Some(index_flag_kind_page_id),
+                Some(index_flag_kind_page_id),
+            ),
+            (
+                "SELECT id FROM example WHERE host = 'host10' AND id > 100 ORDER BY id;",
+                Some(index_host_page_id),
+                Some(index_host_page_id),
+            ),
+            (
+                "SELECT id FROM example WHERE flag = 0 AND id BETWEEN 10 AND 20 ORDER BY id;",
+                Some(index_flag_kind_page_id),
+                None,
+            ),
+        ];
+        let check_plans = |conn: &Connection, analyzed: bool| {
+            for (sql, index_page_id, analyzed_index_page_id) in cases {
+                let stmt = conn.prepare(sql).unwrap();
+                let Statement::Query(select) = &stmt else {
+                    unreachable!()
+                };
+                let expected = if analyzed {
+                    analyzed_index_page_id
+                } else {
+                    index_page_id
+                };
+                assert_eq!(select.query_plan.index_page_id(), expected, "sql: {sql}");
+                assert_eq!(
+                    collect_rows(&stmt),
+                    collect_sqlite_rows(&file, sql),
+                    "sql: {sql}"
+                );
+            }
+        };
+
+        let conn = Connection::open(file.path()).unwrap();
+        check_plans(&conn, false);
+        let stmt = conn.prepare("ANALYZE;").unwrap();
+        assert_eq!(stmt.execute().unwrap(), 0);
+        check_plans(&conn, true);
+
+        let stmt = conn
+            .prepare("SELECT id FROM example WHERE flag = 0 AND id BETWEEN 10 AND 20;")
+            .unwrap();
+        let Statement::Query(select) = &stmt else {
+            unreachable!()
+        };
+        assert!(matches!(
+            select.query_plan,
+            QueryPlan::RowIdRange {
+                min: Some(10),
+                max: Some(20),
+                ..
+            }
+        ));
+
+        // The statistics are loaded by another connection.
+        let conn = Connection::open(file.path()).unwrap();
+        check_plans(&conn, true);
+    }
+
+    #[test]
+    fn test_select_order_by() {
+        let file = create_sqlite_database(&[
+            "CREATE TABLE example(id, col1, col2 COLLATE NOCASE);",
+            "INSERT INTO example(id, col1, col2) VALUES (1, 3, 'b'), (2, NULL, 'A'), (3, 1, 'a'), (4, 3, 'B'), (5, 2.5, 'c'), (6, 'x', NULL), (7, 1, 'C');",
+        ]);
+        let conn = Connection::open(file.path()).unwrap();
+        for sql in [
+            "SELECT id FROM example ORDER BY col1;",
+            "SELECT id FROM example ORDER BY col1 DESC;",
+            "SELECT id, col2 FROM example ORDER BY col2;",
+            "SELECT id, col2 FROM example ORDER BY col2 COLLATE BINARY DESC;",
+            "SELECT id FROM example ORDER BY col1 ASC, col2 DESC;",
+            "SELECT col2, id FROM example ORDER BY 1, 2 DESC;",
+            "SELECT * FROM example WHERE col1 = 3 ORDER BY col2;",
+            "SELECT id FROM example ORDER BY id DESC;",
+            "SELECT id FROM example ORDER BY col1 LIMIT 3;",
+            "SELECT id FROM example ORDER BY col1 LIMIT 3 OFFSET 2;",
+            "SELECT id FROM example ORDER BY col1 LIMIT 2, 3;",
+            "SELECT id FROM example LIMIT 2;",
+            "SELECT id FROM example LIMIT -1 OFFSET 5;",
+            "SELECT id FROM example LIMIT 0;",
+            "SELECT id FROM example LIMIT 3 OFFSET 10;",
+            "SELECT id FROM example LIMIT '2' OFFSET -1;",
+        ] {
+            let stmt = conn.prepare(sql).unwrap();
+            assert_eq!(
+                collect_rows(&stmt),
+                collect_sqlite_rows(&file, sql),
+                "sql: {sql}"
+            );
+        }
+
+        // The order of rows is the rowid order.
+        let stmt = conn
+            .prepare("SELECT id FROM example ORDER BY rowid;")
+            .unwrap();
+        let Statement::Query(select) = &stmt else {
+            unreachable!()
+        };
+        assert!(select.sort_keys.is_empty());
+
+        assert!(conn.prepare("SELECT id FROM example ORDER BY 2;").is_err());
+        assert!(conn
+            .prepare("SELECT id FROM example ORDER BY invalid;")
+            .is_err());
+        let stmt = conn.prepare("SELECT id FROM example LIMIT 'a';").unwrap();
+        assert!(stmt.query().is_err());
+    }
+
+    #[test]
+    fn test_select_aggregate() {
+        let file = create_sqlite_database(&[
+            "CREATE TABLE example(id, col1, col2 COLLATE NOCASE, col3 INTEGER);",
+            "INSERT INTO example(id, col1, col2, col3) VALUES (1, 3, 'b', 10), (2, NULL, 'A', '20'), (3, 1, 'a', 2.5), (4, 3, 'B', NULL), (5, 2.5, 'c', 'x'), (6, 'x', NULL, 7), (7, 1, 'C', -3);",
+            "CREATE TABLE empty(col);",
+        ]);
+        let conn = Connection::open(file.path()).unwrap();
+        for sql in [
+            "SELECT count(*), count(col1), COUNT(), sum(id), avg(id), total(id) FROM example;",
+            "SELECT sum(col1), avg(col1), total(col1), min(col1), max(col1) FROM example;",
+            "SELECT sum(col3), avg(col3), min(col3), max(col3) FROM example;",
+            "SELECT min(col2), max(col2), max(col2 COLLATE BINARY) FROM example;",
+            "SELECT count(*), sum(col1) FROM example WHERE col1 = 3;",
+            "SELECT count(*), sum(col), avg(col), total(col), min(col), max(col) FROM empty;",
+            "SELECT col FROM empty GROUP BY col;",
+            "SELECT id, max(col3) FROM example;",
+            "SELECT id, min(col1) FROM example;",
+            "SELECT col1, count(*), sum(col3) FROM example GROUP BY col1;",
+            "SELECT col2, count(*), max(id) FROM example GROUP BY col2;",
+            "SELECT count(*) FROM example GROUP BY col2 COLLATE BINARY;",
+            "SELECT col1, id, count(*) FROM example GROUP BY col1, id;",
+            "SELECT col1, count(*) FROM example GROUP BY 1 HAVING count(*) > 1;",
+            "SELECT col1, count(*) FROM example GROUP BY col1 HAVING max(id) >= 5;",
+            "SELECT col1, count(*) FROM example GROUP BY col1 HAVING max(col2);",
+            "SELECT col1, count(*) FROM example GROUP BY col1 HAVING avg(col3);",
+            "SELECT col1, count(*) AS c FROM example GROUP BY col1 ORDER BY count(*) DESC, col1;",
+            "SELECT col1, sum(id) FROM example GROUP BY col1 ORDER BY 2 LIMIT 2 OFFSET 1;",
+            "SELECT count(*) FROM example HAVING count(*) > 100;",
+            "SELECT max(id) = 7, count(*) || '-' || min(id) FROM example;",
+        ] {
+            let stmt = conn.prepare(sql).unwrap();
+            assert_eq!(
+                collect_rows(&stmt),
+                collect_sqlite_rows(&file, sql),
+                "sql: {sql}"
+            );
+        }
+
+        // Real values in a REAL column are stored as integers if possible.
+        let file = create_sqlite_database(&[
+            "CREATE TABLE example(id, col REAL);",
+            "INSERT INTO example(id, col) VALUES (1, 3), (2, -1.0), (3, 0), (4, 0.5);",
+        ]);
+        let conn = Connection::open(file.path()).unwrap();
+        let sql =
+            "SELECT sum(col), min(col), max(col), total(col), avg(col) FROM example WHERE id < 4;";
+        let stmt = conn.prepare(sql).unwrap();
+        let mut rows = stmt.query().unwrap();
+        let row = rows.next_row().unwrap().unwrap();
+        let columns = row.parse().unwrap();
+        let expected: (f64, f64, f64, f64, f64) = rusqlite::Connection::open(file.path())
+            .unwrap()
+            .query_row(sql, [], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            })
+            .unwrap();
+        assert_eq!(expected, (2.0, -1.0, 3.0, 2.0, 2.0 / 3.0));
+        assert_eq!(columns.get(0), Some(&Value::Real(expected.0)));
+        assert_eq!(columns.get(1), Some(&Value::Real(expected.1)));
+        assert_eq!(columns.get(2), Some(&Value::Real(expected.2)));
+        assert_eq!(columns.get(3), Some(&Value::Real(expected.3)));
+        assert_eq!(columns.get(4), Some(&Value::Real(expected.4)));
+        drop(row);
+        drop(rows);
+        for sql in [
+            "SELECT col, count(*) FROM example GROUP BY col;",
+            "SELECT id, col FROM example WHERE col = 3;",
+        ] {
+            let stmt = conn.prepare(sql).unwrap();
+            assert_eq!(
+                collect_rows(&stmt),
+                collect_sqlite_rows(&file, sql),
+                "sql: {sql}"
+            );
+        }
+
+        // Integer overflow of sum().
+        let file = create_sqlite_database(&[
+            "CREATE TABLE example(col);",
+            "INSERT INTO example(col) VALUES (9223372036854775807), (1);",
+        ]);
+        let conn = Connection::open(file.path()).unwrap();
+        let stmt = conn.prepare("SELECT sum(col) FROM example;").unwrap();
+        assert_eq!(stmt.query().err().unwrap().to_string(), "integer overflow");
+        let sql = "SELECT total(col), avg(col) FROM example;";
+        let stmt = conn.prepare(sql).unwrap();
+        assert_eq!(collect_rows(&stmt), collect_sqlite_rows(&file, sql));
+    }
+
+    #[test]
+    fn test_select_group_by_index() {
+        let mut stmts = vec![
+            "CREATE TABLE example(id, col, col2);",
+            "CREATE INDEX index1 ON example(col, col2);",
+        ];
+        let inserts = (0..200)
+            .map(|i| {
+                format!(
+                    "INSERT INTO example(id, col, col2) VALUES ({i}, {}, {});",
+                    i % 10,
+                    (i * 7) % 13
+                )
+            })
+            .collect::<Vec<_>>();
+        stmts.extend(inserts.iter().map(|s| s.as_str()));
+        let file = create_sqlite_database(&stmts);
+        let conn = Connection::open(file.path()).unwrap();
+
+        for (sql, group_by_sorted) in [
+            (
+                "SELECT col2, count(*), sum(id) FROM example WHERE col = 3 GROUP BY col2;",
+                true,
+            ),
+            (
+                "SELECT col, col2, min(id) FROM example WHERE col = 3 GROUP BY col, col2;",
+                true,
+            ),
+            (
+                "SELECT col2, count(*), max(id) FROM example GROUP BY col2;",
+                false,
+            ),
+            (
+                "SELECT col, avg(col2) FROM example GROUP BY col HAVING avg(col2) > 6;",
+                false,
+            ),
+        ] {
+            let mut stmt = conn.prepare(sql).unwrap();
+            let Statement::Query(select) = &mut stmt else {
+                unreachable!()
+            };
+            assert_eq!(
+                select.aggregation.as_ref().unwrap().group_by_sorted,
+                group_by_sorted,
+                "sql: {sql}"
+            );
+            assert_eq!(
+                collect_rows(&stmt),
+                collect_sqlite_rows(&file, sql),
+                "sql: {sql}"
+            );
+
+            // Spill the rows to group to temporary files.
+            let Statement::Query(select) = &mut stmt else {
+                unreachable!()
+            };
+            select.sorter_memory_limit = 1000;
+            assert_eq!(
+                collect_rows(&stmt),
+                collect_sqlite_rows(&file, sql),
+                "sql: {sql}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_select_aggregate_fail() {
+        let file = create_sqlite_database(&["CREATE TABLE example(col1, col2);"]);
+        let conn = Connection::open(file.path()).unwrap();
+
+        for (sql, message) in [
+            (
+                "SELECT col1 FROM example WHERE count(*) > 1;",
+                "expression error: misuse of aggregate function count()",
+            ),
+            (
+                "SELECT sum(max(col1)) FROM example;",
+                "expression error: misuse of aggregate function max()",
+            ),
+            (
+                "SELECT col1 FROM example GROUP BY sum(col2);",
+                "expression error: misuse of aggregate function sum()",
+            ),
+            (
+                "SELECT unknown(col1) FROM example;",
+                "expression error: no such function: unknown",
+            ),
+            (
+                "SELECT sum(col1, col2) FROM example;",
+                "expression error: wrong number of arguments to function sum()",
+            ),
+            (
+                "SELECT avg(*) FROM example;",
+                "expression error: wrong number of arguments to function avg()",
+            ),
+            (
+                "SELECT col1 FROM example GROUP BY 2;",
+                "GROUP BY term 1 out of range - should be between 1 and 1",
+            ),
+        ] {
+            assert_eq!(
+                conn.prepare(sql).err().unwrap().to_string(),
+                message,
+                "sql: {sql}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_select_order_by_index() {
+        let mut stmts = vec![
+            "CREATE TABLE example(id, col, col2);",
+            "CREATE INDEX index1 ON example(col, col2);",
+        ];
+        let inserts = (0..200)
+            .map(|i| {
+                format!(
+                    "INSERT INTO example(id, col, col2) VALUES ({i}, {}, {});",
+                    i % 10,
+                    (i * 7) % 13
+                )
+            })
+            .collect::<Vec<_>>();
+        stmts.extend(inserts.iter().map(|s| s.as_str()));
+        let file = create_sqlite_database(&stmts);
+        let conn = Connection::open(file.path()).unwrap();
+
+        for (sql, sorted_by_index) in [
+            ("SELECT id FROM example WHERE col = 3 ORDER BY col2;", true),
+            (
+                "SELECT id FROM example WHERE col = 3 ORDER BY col, col2, rowid;",
+                true,
+            ),
+            (
+                "SELECT id FROM example WHERE col = 3 ORDER BY col2, rowid, id;",
+                true,
+            ),
+            (
+                "SELECT id FROM example WHERE col = 3 ORDER BY col2, id;",
+                false,
+            ),
+            (
+                "SELECT id FROM example WHERE col = 3 ORDER BY col2 DESC, id;",
+                false,
+            ),
+            ("SELECT id FROM example WHERE col = 3 ORDER BY id;", false),
+            ("SELECT id FROM example ORDER BY col, col2;", true),
+            ("SELECT id FROM example ORDER BY col, col2, rowid;", true),
+            ("SELECT id FROM example ORDER BY col, id;", false),
+        ] {
+            let stmt = conn.prepare(sql).unwrap();
+            let Statement::Query(select) = &stmt else {
+                unreachable!()
+            };
+            assert_eq!(select.sort_keys.is_empty(), sorted_by_index, "sql: {sql}");
+            assert_eq!(
+                collect_rows(&stmt),
+                collect_sqlite_rows(&file, sql),
+                "sql: {sql}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_select_order_by_spill() {
+        let mut stmts = vec!["CREATE TABLE example(id, col);"];
+        let inserts = (0..1000)
+            .map(|i| {
+                format!(
+                    "INSERT INTO example(id, col) VALUES ({i}, 'value{:03}');",
+                    (i * 7919) % 1000
+                )
+            })
+            .collect::<Vec<_>>();
+        stmts.extend(inserts.iter().map(|s| s.as_str()));
+        let file = create_sqlite_database(&stmts);
+        let conn = Connection::open(file.path()).unwrap();
+
+        let sql = "SELECT id, col FROM example ORDER BY col DESC LIMIT 900 OFFSET 50;";
+        let mut stmt = conn.prepare(sql).unwrap();
+        let Statement::Query(select) = &mut stmt else {
+            unreachable!()
+        };
+        select.sorter_memory_limit = 1000;
+        assert_eq!(collect_rows(&stmt), collect_sqlite_rows(&file, sql));
+    }
+
+    #[test]
+    fn test_lock_with_sqlite() {
+        let file = create_sqlite_database(&["CREATE TABLE example(id, col);"]);
+        let conn = Connection::open(file.path()).unwrap();
+        let sqlite_conn = rusqlite::Connection::open(file.path()).unwrap();
+        sqlite_conn.busy_timeout(Duration::ZERO).unwrap();
+        let insert = conn
+            .prepare("INSERT INTO example(id, col) VALUES (1, 'value');")
+            .unwrap();
+
+        // SQLite holds the reserved lock.
+        sqlite_conn
+            .execute_batch("BEGIN IMMEDIATE; INSERT INTO example(id, col) VALUES (2, 'value');")
+            .unwrap();
+        assert!(matches!(insert.execute(), Err(Error::Busy)));
+        // The journal of the live writer is not rolled back.
+        assert!(journal_path(file.path()).exists());
+        assert_eq!(count_rows(&conn), 0);
+        sqlite_conn.execute_batch("COMMIT;").unwrap();
+        // The change by SQLite is loaded.
+        assert_eq!(count_rows(&conn), 1);
+        assert_eq!(insert.execute().unwrap(), 1);
+        assert_eq!(count_rows_by_sqlite(file.path()), 2);
+
+        // Reading blocks SQLite from committing.
+        let select = conn.prepare("SELECT * FROM example;").unwrap();
+        let rows = select.query().unwrap();
+        assert!(sqlite_conn
+            .execute("INSERT INTO example(id, col) VALUES (3, 'value');", [])
+            .is_err());
+        drop(rows);
+        sqlite_conn
+            .execute("INSERT INTO example(id, col) VALUES (3, 'value');", [])
+            .unwrap();
+        assert_eq!(count_rows(&conn), 3);
+
+        // SQLite holds the exclusive lock.
+        sqlite_conn.execute_batch("BEGIN EXCLUSIVE;").unwrap();
+        let err = select.query().err().unwrap();
+        assert!(matches!(err.downcast_ref::<Error>(), Some(Error::Busy)));
+        sqlite_conn.execute_batch("COMMIT;").unwrap();
+        assert_eq!(count_rows(&conn), 3);
+    }
+
+    #[test]
+    fn test_lock_multiple_connections() {
+        let file = create_sqlite_database(&["CREATE TABLE example(id, col);"]);
+        let conn1 = Connection::open(file.path()).unwrap();
+        let conn2 = Connection::open(file.path()).unwrap();
+
+        let select = conn1.prepare("SELECT * FROM example;").unwrap();
+        let rows = select.query().unwrap();
+        // Reading does not block other readers.
+        assert_eq!(count_rows(&conn2), 0);
+        let insert = conn2
+            .prepare("INSERT INTO example(id, col) VALUES (1, 'value');")
+            .unwrap();
+        assert!(matches!(insert.execute(), Err(Error::Busy)));
+
+        conn2.set_busy_timeout(Duration::from_millis(100));
+        let start = std::time::Instant::now();
+        assert!(matches!(insert.execute(), Err(Error::Busy)));
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        drop(rows);
+        assert_eq!(insert.execute().unwrap(), 1);
+        assert_eq!(count_rows(&conn1), 1);
+        assert_eq!(count_rows_by_sqlite(file.path()), 1);
+    }
+
+    #[test]
+    fn test_busy_timeout() {
+        let file = create_sqlite_database(&["CREATE TABLE example(id, col);"]);
+        let path = file.path().to_path_buf();
+        let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+        let reader = std::thread::spawn(move || {
+            let conn = Connection::open(&path).unwrap();
+            let select = conn.prepare("SELECT * FROM example;").unwrap();
+            let rows = select.query().unwrap();
+            locked_tx.send(()).unwrap();
+            std::thread::sleep(Duration::from_millis(200));
+            drop(rows);
+        });
+        locked_rx.recv().unwrap();
+
+        let conn = Connection::open(file.path()).unwrap();
+        conn.set_busy_timeout(Duration::from_secs(10));
+        let insert = conn
+            .prepare("INSERT INTO example(id, col) VALUES (1, 'value');")
+            .unwrap();
+        // The commit waits for the reader to finish.
+        assert_eq!(insert.execute().unwrap(), 1);
+        reader.join().unwrap();
+        assert_eq!(count_rows_by_sqlite(file.path()), 1);
+    }
+
+    #[test]
+    fn test_wal_lock() {
+        let file = create_wal_database(&["CREATE TABLE example(id, col);"]);
+        let conn1 = Connection::open(file.path()).unwrap();
+        let conn2 = Connection::open(file.path()).unwrap();
+
+        // Readers do not block the writer in the WAL mode.
+        let select = conn1.prepare("SELECT * FROM example;").unwrap();
+        let rows = select.query().unwrap();
+        let insert = conn2
+            .prepare("INSERT INTO example(id, col) VALUES (1, 'value');")
+            .unwrap();
+        assert_eq!(insert.execute().unwrap(), 1);
+        // The checkpoint waits for the readers.
+        assert!(conn2
+            .checkpoint()
+            .unwrap_err()
+            .downcast_ref::<Error>()
+            .is_some_and(|e| matches!(e, Error::Busy)));
+        drop(rows);
+        conn2.checkpoint().unwrap();
+        assert_eq!(count_rows(&conn1), 1);
+    }
+
+    #[test]
+    fn test_open_create() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        assert!(Connection::open(&path).is_err());
+        assert!(!path.exists());
+
+        for pagesize in [512, 4096, 65536] {
+            std::fs::remove_file(&path).ok();
+            let conn = Connection::open_with_flags(&path, &OpenFlags::create().pagesize(pagesize))
+                .unwrap();
+            let stmt = conn.prepare("CREATE TABLE example(id, col);").unwrap();
+            stmt.execute().unwrap();
+            drop(stmt);
+            let stmt = conn
+                .prepare("INSERT INTO example(id, col) VALUES (1, 'value');")
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 1);
+            drop(stmt);
+            drop(conn);
+
+            assert_eq!(count_rows_by_sqlite(&path), 1);
+            let sqlite_conn = rusqlite::Connection::open(&path).unwrap();
+            let actual: u32 = sqlite_conn
+                .query_row("PRAGMA page_size;", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(actual, pagesize);
+        }
+
+        // The existing database is opened as is.
+        let conn = Connection::open_with_flags(&path, &OpenFlags::create().pagesize(1024)).unwrap();
+        assert_eq!(conn.pager.pagesize(), 65536);
+        assert_eq!(count_rows(&conn), 1);
+
+        // The empty file is initialized.
+        let file = tempfile::NamedTempFile::new().unwrap();
+        assert!(Connection::open(file.path()).is_err());
+        let conn = Connection::open_with_flags(file.path(), &OpenFlags::create()).unwrap();
+        let stmt = conn.prepare("SELECT * FROM sqlite_schema;").unwrap();
+        assert!(stmt.query().unwrap().next_row().unwrap().is_none());
+        drop(stmt);
+        drop(conn);
+        let sqlite_conn = rusqlite::Connection::open(file.path()).unwrap();
+        let result: String = sqlite_conn
+            .query_row("PRAGMA integrity_check;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(result, "ok");
+
+        for pagesize in [0, 256, 1000, 131072] {
+            assert!(
+                Connection::open_with_flags(&path, &OpenFlags::create().pagesize(pagesize))
+                    .is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn test_open_read_only() {
+        let file = create_sqlite_database(&[
+            "CREATE TABLE example(id, col);",
+            "INSERT INTO example(id, col) VALUES (1, 'value');",
+        ]);
+        let conn = Connection::open_with_flags(file.path(), &OpenFlags::read_only()).unwrap();
+        assert_eq!(count_rows(&conn), 1);
+
+        for sql in [
+            "INSERT INTO example(id, col) VALUES (2, 'value');",
+            "UPDATE example SET col = 'updated';",
+            "DELETE FROM example;",
+            "CREATE TABLE example2(col);",
+            "CREATE INDEX index1 ON example(col);",
+            "DROP TABLE example;",
+            "ANALYZE;",
+        ] {
+            assert!(
+                matches!(conn.prepare(sql), Err(Error::ReadOnly)),
+                "sql: {sql}"
+            );
+        }
+        assert!(conn.checkpoint().is_err());
+        drop(conn);
+        assert_eq!(count_rows_by_sqlite(file.path()), 1);
+    }
+
+    #[test]
+    fn test_open_read_only_with_writer() {
+        let file = create_wal_database(&["CREATE TABLE example(id, col);"]);
+        let writer = Connection::open(file.path()).unwrap();
+        let reader = Connection::open_with_flags(file.path(), &OpenFlags::read_only()).unwrap();
+        assert_eq!(reader.journal_mode(), JournalMode::Wal);
+        assert_eq!(count_rows(&reader), 0);
+
+        let stmt = writer
+            .prepare("INSERT INTO example(id, col) VALUES (1, 'value');")
+            .unwrap();
+        assert_eq!(stmt.execute().unwrap(), 1);
+        assert_eq!(count_rows(&reader), 1);
+        writer.checkpoint().unwrap();
+        assert_eq!(stmt.execute().unwrap(), 1);
+        assert_eq!(count_rows(&reader), 2);
+    }
+
+    #[test]
+    fn test_cache_size() {
+        let file = create_sqlite_database(&[
+            "PRAGMA page_size = 512;",
+            "CREATE TABLE example(id INTEGER PRIMARY KEY, col);",
+        ]);
+        let flags = OpenFlags::read_write().cache_size(CacheSize::Pages(8));
+        let conn = Connection::open_with_flags(file.path(), &flags).unwrap();
+        for i in 0..1000 {
+            let sql = format!("INSERT INTO example(id, col) VALUES ({i}, '{i:0100}');");
+            assert_eq!(conn.prepare(&sql).unwrap().execute().unwrap(), 1);
+        }
+        assert!(conn.cache_stats().evictions > 0);
+        // The transaction modifies more pages than the capacity.
+        let stmt = conn.prepare("UPDATE example SET col = 'updated';").unwrap();
+        assert_eq!(stmt.execute().unwrap(), 1000);
+        drop(stmt);
+        let sql = "SELECT id, col FROM example WHERE id >= 100;";
+        assert_eq!(
+            collect_rows(&conn.prepare(sql).unwrap()),
+            collect_sqlite_rows(&file, sql)
+        );
+        assert_eq!(count_rows_by_sqlite(file.path()), 1000);
+
+        conn.set_cache_size(CacheSize::Bytes(512 * 1024));
+        assert_eq!(count_rows(&conn), 1000);
+        let stats = conn.cache_stats();
+        assert_eq!(count_rows(&conn), 1000);
+        let new_stats = conn.cache_stats();
+        // All the pages are cached.
+        assert_eq!(new_stats.misses, stats.misses);
+        assert_eq!(new_stats.evictions, stats.evictions);
+        assert!(new_stats.hits > stats.hits);
+    }
+
+    fn execute(conn: &Connection, sql: &str) -> u64 {
+        conn.prepare(sql).unwrap().execute().unwrap()
+    }
+
+    #[test]
+    fn test_transaction() {
+        let file = create_sqlite_database(&[
+            "PRAGMA page_size = 512;",
+            "CREATE TABLE example(id INTEGER PRIMARY KEY, col);",
+        ]);
+        let conn = Connection::open(file.path()).unwrap();
+        let other = Connection::open(file.path()).unwrap();
+        assert!(conn.is_autocommit());
+
+        execute(&conn, "BEGIN;");
+        assert!(!conn.is_autocommit());
+        for i in 0..1000 {
+            let sql = format!("INSERT INTO example(id, col) VALUES ({i}, '{i:050}');");
+            assert_eq!(execute(&conn, &sql), 1);
+        }
+        assert_eq!(count_rows(&conn), 1000);
+        // The changes are not visible until the transaction is committed.
+        assert_eq!(count_rows(&other), 0);
+        execute(&conn, "COMMIT;");
+        assert!(conn.is_autocommit());
+        assert_eq!(count_rows(&other), 1000);
+        assert_eq!(count_rows_by_sqlite(file.path()), 1000);
+
+        execute(&conn, "BEGIN TRANSACTION;");
+        execute(&conn, "DELETE FROM example WHERE id >= 10;");
+        execute(&conn, "CREATE TABLE example2(col);");
+        assert_eq!(count_rows(&conn), 10);
+        execute(&conn, "ROLLBACK;");
+        assert_eq!(count_rows(&conn), 1000);
+        assert!(conn.prepare("SELECT * FROM example2;").is_err());
+        assert_eq!(count_rows_by_sqlite(file.path()), 1000);
+
+        assert!(conn.prepare("COMMIT;").unwrap().execute().is_err());
+        assert!(conn.prepare("ROLLBACK;").unwrap().execute().is_err());
+        execute(&conn, "BEGIN;");
+        assert!(conn.prepare("BEGIN;").unwrap().execute().is_err());
+        assert!(conn.checkpoint().is_err());
+        execute(&conn, "COMMIT TRANSACTION;");
+        assert!(conn.is_autocommit());
+    }
+
+    #[test]
+    fn test_transaction_failed_statement() {
+        let file = create_sqlite_database(&["CREATE TABLE example(id INTEGER PRIMARY KEY, col);"]);
+        let conn = Connection::open(file.path()).unwrap();
+
+        execute(&conn, "BEGIN;");
+        execute(&conn, "INSERT INTO example(id, col) VALUES (1, 'a');");
+        let insert = conn
+            .prepare("INSERT INTO example(id, col) VALUES (2, 'b'), (1, 'c');")
+            .unwrap();
+        assert!(matches!(
+            insert.execute(),
+            Err(Error::UniqueConstraintViolation)
+        ));
+        drop(insert);
+        // Only the failed statement is rolled back.
+        assert!(!conn.is_autocommit());
+        assert_eq!(count_rows(&conn), 1);
+        execute(&conn, "INSERT INTO example(id, col) VALUES (3, 'd');");
+        execute(&conn, "COMMIT;");
+
+        let sql = "SELECT id, col FROM example;";
+        assert_eq!(
+            collect_rows(&conn.prepare(sql).unwrap()),
+            collect_sqlite_rows(&file, sql)
+        );
+        assert_eq!(count_rows_by_sqlite(file.path()), 2);
+    }
+
+    #[test]
+    fn test_savepoint() {
+        let file = create_sqlite_database(&[
+            "PRAGMA page_size = 512;",
+            "CREATE TABLE example(id INTEGER PRIMARY KEY, col);",
+        ]);
+        let conn = Connection::open(file.path()).unwrap();
+        let insert = |start: i64, end: i64| {
+            for i in start..end {
+                let sql = format!("INSERT INTO example(id, col) VALUES ({i}, '{i:050}');");
+                execute(&conn, &sql);
+            }
+        };
+
+        execute(&conn, "BEGIN;");
+        insert(0, 100);
+        execute(&conn, "SAVEPOINT a;");
+        insert(100, 200);
+        execute(&conn, "SAVEPOINT b;");
+        execute(&conn, "DELETE FROM example WHERE id < 50;");
+        execute(&conn, "CREATE TABLE example2(col);");
+        assert_eq!(count_rows(&conn), 150);
+        // Savepoint names are case insensitive.
+        execute(&conn, "ROLLBACK TO SAVEPOINT B;");
+        assert_eq!(count_rows(&conn), 200);
+        assert!(conn.prepare("SELECT * FROM example2;").is_err());
+        execute(&conn, "ROLLBACK TO a;");
+        assert_eq!(count_rows(&conn), 100);
+        // b is released by rolling back to a.
+        assert!(conn.prepare("RELEASE b;").unwrap().execute().is_err());
+
+        insert(300, 400);
+        execute(&conn, "RELEASE a;");
+        // The transaction started by BEGIN continues after releasing all the
+        // savepoints.
+        assert!(!conn.is_autocommit());
+        execute(&conn, "COMMIT;");
+        assert_eq!(count_rows(&conn), 200);
+        assert_eq!(count_rows_by_sqlite(file.path()), 200);
+
+        // SAVEPOINT starts a transaction which is committed by RELEASE.
+        execute(&conn, "SAVEPOINT outer;");
+        assert!(!conn.is_autocommit());
+        execute(&conn, "SAVEPOINT inner;");
+        execute(&conn, "DELETE FROM example;");
+        execute(&conn, "RELEASE inner;");
+        execute(&conn, "ROLLBACK TO outer;");
+        insert(1000, 1010);
+        execute(&conn, "RELEASE outer;");
+        assert!(conn.is_autocommit());
+        assert_eq!(count_rows_by_sqlite(file.path()), 210);
+
+        let sql = "SELECT id, col FROM example;";
+        assert_eq!(
+            collect_rows(&conn.prepare(sql).unwrap()),
+            collect_sqlite_rows(&file, sql)
+        );
+    }
+
+    #[test]
+    fn test_transaction_lock() {
+        let file = create_sqlite_database(&["CREATE TABLE example(id, col);"]);
+        let conn1 = Connection::open(file.path()).unwrap();
+        let conn2 = Connection::open(file.path()).unwrap();
+        let insert = "INSERT INTO example(id, col) VALUES (1, 'value');";
+
+        execute(&conn1, "BEGIN IMMEDIATE;");
+        assert!(matches!(
+            conn2.prepare(insert).unwrap().execute(),
+            Err(Error::Busy)
+        ));
+        assert_eq!(count_rows(&conn2), 0);
+        execute(&conn1, insert);
+        execute(&conn1, "COMMIT;");
+        assert_eq!(count_rows(&conn2), 1);
+
+        execute(&conn1, "BEGIN EXCLUSIVE;");
+        assert!(conn2
+            .prepare("SELECT * FROM example;")
+            .unwrap()
+            .query()
+            .is_err());
+        execute(&conn1, "ROLLBACK;");
+
+        // The deferred transaction holds the shared lock after reading.
+        execute(&conn1, "BEGIN;");
+        assert_eq!(count_rows(&conn1), 1);
+        assert!(matches!(
+            conn2.prepare(insert).unwrap().execute(),
+            Err(Error::Busy)
+        ));
+        // COMMIT waits for the readers.
+        execute(&conn2, "BEGIN;");
+        execute(&conn2, insert);
+        assert!(matches!(
+            conn2.prepare("COMMIT;").unwrap().execute(),
+            Err(Error::Busy)
+        ));
+        execute(&conn1, "COMMIT;");
+        execute(&conn2, "COMMIT;");
+        assert_eq!(count_rows(&conn1), 2);
+        assert_eq!(count_rows_by_sqlite(file.path()), 2);
+    }
+
+    #[test]
+    fn test_wal_transaction() {
+        let file = create_wal_database(&["CREATE TABLE example(id, col);"]);
+        let conn1 = Connection::open(file.path()).unwrap();
+        let conn2 = Connection::open(file.path()).unwrap();
+        let insert = "INSERT INTO example(id, col) VALUES (1, 'value');";
+
+        execute(&conn1, "BEGIN;");
+        assert_eq!(count_rows(&conn1), 0);
+        execute(&conn2, insert);
+        // The snapshot of the transaction is kept.
+        assert_eq!(count_rows(&conn1), 0);
+        // Writing on the stale snapshot fails.
+        assert!(matches!(
+            conn1.prepare(insert).unwrap().execute(),
+            Err(Error::Busy)
+        ));
+        execute(&conn1, "ROLLBACK;");
+
+        execute(&conn1, "BEGIN EXCLUSIVE;");
+        execute(&conn1, insert);
+        // Readers are not blocked in the WAL mode.
+        assert_eq!(count_rows(&conn2), 1);
+        execute(&conn1, "COMMIT;");
+        assert_eq!(count_rows(&conn2), 2);
+        assert_eq!(count_rows_by_sqlite(file.path()), 2);
+    }
+
+    #[test]
+    fn test_transaction_read_only() {
+        let file = create_sqlite_database(&["CREATE TABLE example(id, col);"]);
+        let conn = Connection::open_with_flags(file.path(), &OpenFlags::read_only()).unwrap();
+        execute(&conn, "BEGIN;");
+        assert_eq!(count_rows(&conn), 0);
+        execute(&conn, "COMMIT;");
+        assert!(matches!(
+            conn.prepare("BEGIN IMMEDIATE;").unwrap().execute(),
+            Err(Error::ReadOnly)
+        ));
+        assert!(conn.is_autocommit());
+    }
+
+    #[test]
+    fn test_transaction_drop_connection() {
+        let file = create_sqlite_database(&["CREATE TABLE example(id, col);"]);
+        let conn = Connection::open(file.path()).unwrap();
+        execute(&conn, "BEGIN;");
+        execute(&conn, "INSERT INTO example(id, col) VALUES (1, 'value');");
+        drop(conn);
+        assert!(!journal_path(file.path()).exists());
+        assert_eq!(count_rows_by_sqlite(file.path()), 0);
+    }
+
+    #[test]
+    fn test_bind_select() {
+        let mut stmts = vec![
+            "CREATE TABLE example(id INTEGER PRIMARY KEY, col1, col2);",
+            "CREATE INDEX index1 ON example(col2);",
+        ];
+        let inserts = (0..100)
+            .map(|i| {
+                format!(
+                    "INSERT INTO example(id, col1, col2) VALUES ({i}, {}, {});",
+                    i % 3,
+                    i % 7
+                )
+            })
+            .collect::<Vec<_>>();
+        stmts.extend(inserts.iter().map(|s| s.as_str()));
+        let file = create_sqlite_database(&stmts);
+        let index_page_id = find_index_page_id("index1", file.path());
+        let conn = Connection::open(file.path()).unwrap();
+
+        // Unbound parameters are NULL.
+        let mut stmt = conn
+            .prepare("SELECT col1 FROM example WHERE id = ?;")
+            .unwrap();
+        assert!(collect_rows(&stmt).is_empty());
+        for id in [10, 20] {
+            stmt.bind(1, Some(Value::Integer(id))).unwrap();
+            let Statement::Query(select) = &stmt else {
+                unreachable!()
+            };
+            assert!(matches!(select.query_plan, QueryPlan::RowId(rowid) if rowid == id));
+            assert_eq!(
+                collect_rows(&stmt),
+                collect_sqlite_rows(&file, &format!("SELECT col1 FROM example WHERE id = {id};"))
+            );
+        }
+        // The value is converted as a literal is.
+        stmt.bind(1, Some(Value::Text(b"30".as_slice().into())))
+            .unwrap();
+        assert_eq!(
+            collect_rows(&stmt),
+            collect_sqlite_rows(&file, "SELECT col1 FROM example WHERE id = 30;")
+        );
+        stmt.bind(1, None).unwrap();
+        assert!(collect_rows(&stmt).is_empty());
+
+        let mut stmt = conn
+            .prepare("SELECT id, :b FROM example WHERE col2 = :a AND col1 != :b ORDER BY col2;")
+            .unwrap();
+        for (a, b) in [(3, 1), (5, 0)] {
+            stmt.bind_named(":a", Some(Value::Integer(a))).unwrap();
+            stmt.bind_named(":b", Some(Value::Integer(b))).unwrap();
+            let Statement::Query(select) = &stmt else {
+                unreachable!()
+            };
+            assert_eq!(select.query_plan.index_page_id(), Some(index_page_id));
+            assert!(select.sort_keys.is_empty());
+            assert_eq!(
+                collect_rows(&stmt),
+                collect_sqlite_rows(
+                    &file,
+                    &format!(
+                        "SELECT id, {b} FROM example WHERE col2 = {a} AND col1 != {b} ORDER BY col2;"
+                    )
+                )
+            );
+        }
+
+        let mut stmt = conn
+            .prepare("SELECT count(*) FROM example WHERE id BETWEEN ?1 AND ?2 AND col1 != ?1;")
+            .unwrap();
+        stmt.bind(1, Some(Value::Integer(1))).unwrap();
+        stmt.bind(2, Some(Value::Integer(50))).unwrap();
+        assert_eq!(
+            collect_rows(&stmt),
+            collect_sqlite_rows(
+                &file,
+                "SELECT count(*) FROM example WHERE id BETWEEN 1 AND 50 AND col1 != 1;"
+            )
+        );
+
+        let mut stmt = conn
+            .prepare("SELECT id FROM example LIMIT ? OFFSET ?;")
+            .unwrap();
+        stmt.bind(1, Some(Value::Integer(2))).unwrap();
+        stmt.bind(2, Some(Value::Integer(5))).unwrap();
+        assert_eq!(
+            collect_rows(&stmt),
+            vec![vec![Some("5".to_string())], vec![Some("6".to_string())]]
+        );
+
+        assert!(stmt.bind(0, None).is_err());
+        assert!(stmt.bind(3, None).is_err());
+        assert!(stmt.bind_named(":a", None).is_err());
+    }
+
+    #[test]
+    fn test_bind_insert() {
+        let file = create_sqlite_database(&["CREATE TABLE example(id INTEGER PRIMARY KEY, col);"]);
+        let conn = Connection::open(file.path()).unwrap();
+        let mut stmt = conn
+            .prepare("INSERT INTO example(id, col) VALUES (?, @col), (?, @col || 'x');")
+            .unwrap();
+        for i in 0..3 {
+            stmt.bind(1, Some(Value::Integer(i * 2))).unwrap();
+            stmt.bind(3, Some(Value::Integer(i * 2 + 1))).unwrap();
+            stmt.bind_named("@col", Some(Value::Text(format!("v{i}").as_bytes().into())))
+                .unwrap();
+            assert_eq!(stmt.execute().unwrap(), 2);
+        }
+        // NULL rowid generates a new rowid.
+        stmt.bind(1, None).unwrap();
+        stmt.bind(3, Some(Value::Integer(10))).unwrap();
+        stmt.bind_named("@col", None).unwrap();
+        assert_eq!(stmt.execute().unwrap(), 2);
+        assert!(stmt.bind(4, None).is_err());
+        assert!(stmt.bind_named(":col", None).is_err());
+        drop(stmt);
+
+        assert_eq!(
+            collect_sqlite_rows(&file, "SELECT * FROM example;"),
+            vec![
+                vec![Some("0".to_string()), Some("v0".to_string())],
+                vec![Some("1".to_string()), Some("v0x".to_string())],
+                vec![Some("2".to_string()), Some("v1".to_string())],
+                vec![Some("3".to_string()), Some("v1x".to_string())],
+                vec![Some("4".to_string()), Some("v2".to_string())],
+                vec![Some("5".to_string()), Some("v2x".to_string())],
+                vec![Some("6".to_string()), None],
+                vec![Some("10".to_string()), None],
+            ]
+        );
+
+        assert!(matches!(
+            conn.prepare("UPDATE example SET col = ?;"),
+            Err(Error::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_insert_default_values() {
+        let schema = [
+            "CREATE TABLE example(id INTEGER PRIMARY KEY DEFAULT 5, col1 DEFAULT (1 + 2), col2 TEXT DEFAULT 12, col3 DEFAULT -1.5, col4 DEFAULT x'6162', col5);",
+            "CREATE INDEX index1 ON example(col2, col1);",
+        ];
+        let inserts = [
+            "INSERT INTO example DEFAULT VALUES;",
+            "INSERT INTO example(col1, col5) VALUES (10, 'a'), (NULL, 'b');",
+            "INSERT INTO example VALUES (10, 1, 2, 3, 4, 5);",
+            "INSERT INTO example(col2) VALUES ('x');",
+        ];
+        let file = create_sqlite_database(&schema);
+        let expected = create_sqlite_database(&[&schema[..], &inserts[..]].concat());
+        let conn = Connection::open(file.path()).unwrap();
+        for sql in inserts {
+            assert!(execute(&conn, sql) > 0);
+        }
+        let query = "SELECT id, col1, col2, typeof(col2), col3, col4, col5 FROM example;";
+        assert_eq!(
+            collect_sqlite_rows(&file, query),
+            collect_sqlite_rows(&expected, query)
+        );
+        assert_eq!(
+            collect_sqlite_rows(&file, "PRAGMA integrity_check;"),
+            vec![vec![Some("ok".to_string())]]
+        );
+
+        assert_eq!(
+            conn.prepare("INSERT INTO example VALUES (1);")
+                .err()
+                .unwrap()
+                .to_string(),
+            "table example has 6 columns but 1 values were supplied"
+        );
+        assert_eq!(
+            conn.prepare("INSERT INTO example(col1) VALUES (1, 2);")
+                .err()
+                .unwrap()
+                .to_string(),
+            "2 values for 1 columns"
+        );
+        assert!(conn
+            .prepare("CREATE TABLE invalid(col DEFAULT (col2));")
+            .is_err());
+    }
+
+    #[test]
+    fn test_insert_default_keywords() {
+        let schema = [
+            "CREATE TABLE example(id INTEGER PRIMARY KEY, col1 DEFAULT TRUE, col2 DEFAULT false, col3 DEFAULT abc, col4 DEFAULT \"quoted\", col5 DEFAULT CURRENT_DATE, col6 DEFAULT CURRENT_TIME, col7 DEFAULT CURRENT_TIMESTAMP);",
+        ];
+        let inserts = [
+            "INSERT INTO example DEFAULT VALUES;",
+            "INSERT INTO example(col1) VALUES (5);",
+        ];
+        let file = create_sqlite_database(&schema);
+        let expected = create_sqlite_database(&[&schema[..], &inserts[..]].concat());
+        let conn = Connection::open(file.path()).unwrap();
+        for sql in inserts {
+            assert_eq!(execute(&conn, sql), 1);
+        }
+        let query = "SELECT id, col1, col2, col3, col4, typeof(col5), length(col5), length(col6), length(col7), col5 = date(col7), col6 = time(col7) FROM example;";
+        assert_eq!(
+            collect_sqlite_rows(&file, query),
+            collect_sqlite_rows(&expected, query)
+        );
+        // The time is when the row is inserted.
+        assert_eq!(
+            collect_sqlite_rows(
+                &file,
+                "SELECT abs(strftime('%s', col7) - strftime('%s', 'now')) < 60 FROM example;"
+            ),
+            vec![vec![Some("1".to_string())]; 2]
+        );
+    }
+
+    #[test]
+    fn test_insert_select() {
+        let schema = [
+            "PRAGMA page_size = 512;",
+            "CREATE TABLE source(id INTEGER PRIMARY KEY, col1, col2);",
+            "CREATE TABLE example(id INTEGER PRIMARY KEY, col1 INTEGER, col2 TEXT DEFAULT 'default');",
+            "CREATE INDEX index1 ON example(col2);",
+        ];
+        let values = (0..200)
+            .map(|i| format!("INSERT INTO source VALUES ({i}, '{}', {});", i % 7, i * 3))
+            .collect::<Vec<_>>();
+        let inserts = [
+            "INSERT INTO example SELECT * FROM source WHERE id < 100;",
+            "INSERT INTO example(col2, col1) SELECT col1, col2 FROM source WHERE id >= 150 ORDER BY col2 DESC LIMIT 10;",
+            "INSERT INTO example(col1) SELECT count(*) FROM source GROUP BY col1;",
+            // Inserting to the scanned table does not see the new rows.
+            "INSERT INTO example(col1, col2) SELECT col1 + 1000, col2 FROM example WHERE id < 50;",
+        ];
+        let setup = schema
+            .iter()
+            .copied()
+            .chain(values.iter().map(|s| s.as_str()))
+            .collect::<Vec<_>>();
+        let file = create_sqlite_database(&setup);
+        let expected = create_sqlite_database(&[&setup[..], &inserts[..]].concat());
+        let conn = Connection::open(file.path()).unwrap();
+        let n_rows = inserts
+            .iter()
+            .map(|sql| execute(&conn, sql))
+            .collect::<Vec<_>>();
+        assert_eq!(n_rows, vec![100, 10, 7, 50]);
+        let query = "SELECT id, col1, typeof(col1), col2, typeof(col2) FROM example;";
+        assert_eq!(
+            collect_sqlite_rows(&file, query),
+            collect_sqlite_rows(&expected, query)
+        );
+        assert_eq!(
+            collect_sqlite_rows(&file, "PRAGMA integrity_check;"),
+            vec![vec![Some("ok".to_string())]]
+        );
+
+        // The rowid conflicts.
+        assert!(matches!(
+            conn.prepare("INSERT INTO example SELECT * FROM source WHERE id = 1;")
+                .unwrap()
+                .execute(),
+            Err(Error::UniqueConstraintViolation)
+        ));
+        assert_eq!(
+            conn.prepare("INSERT INTO example(col1) SELECT id, col1 FROM source;")
+                .err()
+                .unwrap()
+                .to_string(),
+            "2 values for 1 columns"
+        );
+
+        let mut stmt = conn
+            .prepare("INSERT INTO example(col1, col2) SELECT id, ? FROM source WHERE id = ?;")
+            .unwrap();
+        stmt.bind(1, Some(Value::Text(b"bound".as_slice().into())))
+            .unwrap();
+        stmt.bind(2, Some(Value::Integer(3))).unwrap();
+        assert_eq!(stmt.execute().unwrap(), 1);
+        drop(stmt);
+        assert_eq!(
+            collect_sqlite_rows(
+                &file,
+                "SELECT col1, col2 FROM example WHERE col2 = 'bound';"
+            ),
+            vec![vec![Some("3".to_string()), Some("bound".to_string())]]
+        );
+    }
+
+    #[test]
+    fn test_insert_conflict() {
+        let mut schema = vec![
+            "PRAGMA page_size = 512;",
+            "CREATE TABLE example(id INTEGER PRIMARY KEY, key1, key2, col);",
+            "CREATE UNIQUE INDEX index1 ON example(key1);",
+            "CREATE UNIQUE INDEX index2 ON example(key2, key1);",
+            "CREATE INDEX index3 ON example(col);",
+        ];
+        let values = (0..200)
+            .map(|i| {
+                format!(
+                    "INSERT INTO example VALUES ({i}, 'k{i}', {}, {});",
+                    i % 10,
+                    i % 3
+                )
+            })
+            .collect::<Vec<_>>();
+        schema.extend(values.iter().map(|s| s.as_str()));
+        let inserts = [
+            // The rowid conflicts.
+            "INSERT OR IGNORE INTO example VALUES (1, 'new1', 1, 1), (300, 'k300', 0, 0);",
+            "INSERT OR REPLACE INTO example VALUES (2, 'new2', 2, 2);",
+            // The unique indexes conflict.
+            "INSERT OR IGNORE INTO example VALUES (301, 'k3', 9, 9);",
+            "INSERT OR REPLACE INTO example VALUES (302, 'k4', 5, 'k5');",
+            // Replace the different rows conflicting on the rowid and the index.
+            "INSERT OR REPLACE INTO example VALUES (10, 'k11', 0, 'x');",
+            // Keys containing NULL do not conflict.
+            "INSERT OR ABORT INTO example VALUES (303, NULL, 0, 0), (304, NULL, 0, 0);",
+            "INSERT INTO example VALUES (20, 'k20', 0, 0), (305, 'k30', 0, 0) ON CONFLICT DO NOTHING;",
+            "INSERT INTO example(id, key1, col) VALUES (21, 'k21', 'new') ON CONFLICT (id) DO UPDATE SET col = excluded.col || col, key2 = NULL;",
+            "INSERT INTO example(id, key1, col) VALUES (400, 'k22', 'a'), (401, 'k23', 'b') ON CONFLICT (key1) DO UPDATE SET col = excluded.col WHERE id = 22;",
+            // Change the rowid of the existing row.
+            "INSERT INTO example(id, key1) VALUES (402, 'k24') ON CONFLICT (key1) DO UPDATE SET id = excluded.id + 100;",
+            "INSERT INTO example SELECT id + 1000, key1, key2, 'selected' FROM example WHERE id < 50 ON CONFLICT (key1) DO UPDATE SET col = excluded.col;",
+        ];
+        let file = create_sqlite_database(&schema);
+        let expected = create_sqlite_database(&schema);
+        let conn = Connection::open(file.path()).unwrap();
+        let expected_conn = rusqlite::Connection::open(expected.path()).unwrap();
+        for sql in inserts {
+            let n_changes = expected_conn.execute(sql, []).unwrap();
+            assert_eq!(execute(&conn, sql), n_changes as u64, "{sql}");
+        }
+        drop(expected_conn);
+        let query = "SELECT * FROM example ORDER BY id;";
+        assert_eq!(
+            collect_sqlite_rows(&file, query),
+            collect_sqlite_rows(&expected, query)
+        );
+        assert_eq!(
+            collect_sqlite_rows(&file, "PRAGMA integrity_check;"),
+            vec![vec![Some("ok".to_string())]]
+        );
+
+        // The conflicts out of the conflict target abort the statement.
+        for sql in [
+            "INSERT INTO example VALUES (1, 'k1', 0, 0);",
+            "INSERT OR ABORT INTO example VALUES (500, 'k1', 0, 0);",
+            "INSERT INTO example VALUES (1, 'k500', 0, 0) ON CONFLICT (key1) DO NOTHING;",
+            // The updated row conflicts with another row.
+            "INSERT INTO example(id, key1) VALUES (1, 'k500') ON CONFLICT (id) DO UPDATE SET key1 = 'k3';",
+        ] {
+            assert!(
+                matches!(
+                    conn.prepare(sql).unwrap().execute(),
+                    Err(Error::UniqueConstraintViolation)
+                ),
+                "{sql}"
+            );
+        }
+        assert_eq!(
+            collect_sqlite_rows(&file, query),
+            collect_sqlite_rows(&expected, query)
+        );
+
+        assert_eq!(
+            conn.prepare(
+                "INSERT INTO example VALUES (1, 'k1', 0, 0) ON CONFLICT (col) DO NOTHING;"
+            )
+            .err()
+            .unwrap()
+            .to_string(),
+            "ON CONFLICT clause does not match any PRIMARY KEY or UNIQUE constraint"
+        );
+        assert!(conn.prepare("SELECT excluded.col FROM example;").is_err());
+
+        // Upsert with bound parameters.
+        let mut stmt = conn
+            .prepare("INSERT INTO example(id, col) VALUES (?, ?) ON CONFLICT (id) DO UPDATE SET col = col || ?;")
+            .unwrap();
+        stmt.bind(1, Some(Value::Integer(1))).unwrap();
+        stmt.bind(2, Some(Value::Integer(0))).unwrap();
+        stmt.bind(3, Some(Value::Text(b"-bound".as_slice().into())))
+            .unwrap();
+        assert_eq!(stmt.execute().unwrap(), 1);
+        drop(stmt);
+        assert_eq!(
+            collect_sqlite_rows(&file, "SELECT col FROM example WHERE id = 1;"),
+            vec![vec![Some("selected-bound".to_string())]]
+        );
+    }
+
+    fn explain(conn: &Connection, sql: &str) -> Vec<String> {
+        let Statement::Explain(stmt) = conn.prepare(&format!("EXPLAIN QUERY PLAN {sql}")).unwrap()
+        else {
+            unreachable!()
+        };
+        stmt.rows().iter().map(|row| row.detail.clone()).collect()
+    }
+
+    #[test]
+    fn test_explain_query_plan() {
+        let mut stmts = vec![
+            "CREATE TABLE example(id INTEGER PRIMARY KEY, col1, col2, col3);",
+            "CREATE INDEX index1 ON example(col2);",
+            "CREATE INDEX index2 ON example(col1, col3);",
+        ];
+        let inserts = (0..100)
+            .map(|i| {
+                format!(
+                    "INSERT INTO example(id, col1, col2, col3) VALUES ({i}, {}, {}, {});",
+                    i % 3,
+                    i % 7,
+                    i % 11
+                )
+            })
+            .collect::<Vec<_>>();
+        stmts.extend(inserts.iter().map(|s| s.as_str()));
+        let file = create_sqlite_database(&stmts);
+        let conn = Connection::open(file.path()).unwrap();
+
+        for sql in [
+            "SELECT * FROM example;",
+            "SELECT * FROM example WHERE id = 5;",
+            "SELECT * FROM example WHERE id IN (1, 5, 9);",
+            "SELECT * FROM example WHERE id > 5 AND id < 10;",
+            "SELECT * FROM example WHERE id >= 5;",
+            "SELECT * FROM example WHERE col2 = 3;",
+            "SELECT * FROM example WHERE col2 IN (1, 2);",
+            "SELECT id FROM example WHERE col2 > 3;",
+            "SELECT * FROM example WHERE col1 = 1 AND col3 < 5;",
+            "SELECT * FROM example WHERE col1 = 1 AND col3 = 5;",
+            "SELECT * FROM example WHERE col2 > 3 ORDER BY col2;",
+            "SELECT * FROM example ORDER BY col3;",
+            "SELECT * FROM example ORDER BY id DESC;",
+            "SELECT * FROM example ORDER BY col2;",
+            "SELECT * FROM example ORDER BY col2 DESC;",
+            "SELECT col2 FROM example ORDER BY col2 DESC LIMIT 3;",
+            "SELECT * FROM example ORDER BY col1 DESC, col3 DESC;",
+            "SELECT col3, count(*) FROM example WHERE col1 = 1 GROUP BY col3;",
+            "SELECT count(*) FROM example WHERE col2 = 1;",
+            "DELETE FROM example WHERE col2 = 3;",
+            "DELETE FROM example WHERE id = 3;",
+            "UPDATE example SET col3 = 1 WHERE col1 = 2;",
+            "INSERT INTO example(id) VALUES (1000);",
+        ] {
+            let expected = collect_sqlite_rows(&file, &format!("EXPLAIN QUERY PLAN {sql}"))
+                .into_iter()
+                .map(|row| row[3].clone().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(explain(&conn, sql), expected, "sql: {sql}");
+        }
+
+        // Unlike SQLite, an index is not scanned only to avoid sorting for
+        // GROUP BY.
+        assert_eq!(
+            explain(&conn, "SELECT col2, count(*) FROM example GROUP BY col2;"),
+            vec!["SCAN example", "USE TEMP B-TREE FOR GROUP BY"]
+        );
+        assert_eq!(
+            explain(&conn, "SELECT * FROM sqlite_schema;"),
+            vec!["SCAN sqlite_schema"]
+        );
+
+        let stmt = conn
+            .prepare("EXPLAIN QUERY PLAN SELECT * FROM example;")
+            .unwrap();
+        assert!(stmt.query().is_err());
+        assert!(stmt.execute().is_err());
+        assert!(matches!(
+            conn.prepare("EXPLAIN QUERY PLAN SELECT * FROM example WHERE id = ?;"),
+            Err(Error::Unsupported(_))
+        ));
     }
 }
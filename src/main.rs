@@ -4,8 +4,23 @@ use std::io::Write;
 use std::path::Path;
 
 use nexusdb::Connection;
+use nexusdb::QueryPlanRow;
 use nexusdb::Statement;
 
+/// Print the rows of EXPLAIN QUERY PLAN as a tree like the sqlite3 shell.
+fn print_query_plan(rows: &[QueryPlanRow], parent: u32, indent: &str) {
+    let children = rows
+        .iter()
+        .filter(|row| row.parent == parent)
+        .collect::<Vec<_>>();
+    for (i, row) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        println!("{indent}{}{}", if last { "`--" } else { "|--" }, row.detail);
+        let indent = format!("{indent}{}", if last { "   " } else { "|  " });
+        print_query_plan(rows, row.id, &indent);
+    }
+}
+
 fn main() {
     let mut args = env::args();
     if args.len() != 2 {
@@ -68,6 +83,10 @@ fn main() {
                         }
                         println!("-------nexusdb Executing----- insert delete ");
                     }
+                    Statement::Explain(stmt) => {
+                        println!("QUERY PLAN");
+                        print_query_plan(stmt.rows(), 0, "");
+                    }
                 }
             }
         }
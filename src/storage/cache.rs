@@ -0,0 +1,334 @@
+//! A map evicting the least recently used entries.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Counters of cache lookups, for tuning the capacity of the cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The number of lookups finding the entry.
+    pub hits: u64,
+    /// The number of lookups not finding the entry.
+    pub misses: u64,
+    /// The number of entries evicted.
+    pub evictions: u64,
+}
+
+/// What [LruCache::evict()] does with an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eviction {
+    /// Remove the entry.
+    Evict,
+    /// Keep the entry and move it to the most recently used end.
+    Skip,
+    /// Keep the entry out of the eviction order until [LruCache::unpin_all()].
+    Pin,
+}
+
+/// A map which remembers the order of uses of the entries.
+///
+/// The cache does not evict entries by itself. The owner calls [Self::evict()]
+/// to choose an entry to drop, which allows it to pin entries in use.
+pub struct LruCache<K, V> {
+    /// The values and the last use of them.
+    entries: HashMap<K, (V, u64)>,
+    /// The keys ordered by the last use.
+    order: BTreeMap<u64, K>,
+    /// The pinned keys ordered by the last use. They are not in `order`.
+    pinned: BTreeMap<u64, K>,
+    clock: u64,
+    stats: CacheStats,
+}
+
+impl<K: Hash + Eq + Copy, V> LruCache<K, V> {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            pinned: BTreeMap::new(),
+            clock: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Look up the value and mark it as the most recently used.
+    ///
+    /// This is counted as a hit or a miss.
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let Some((_, last_use)) = self.entries.get_mut(key) else {
+            self.stats.misses += 1;
+            return None;
+        };
+        self.stats.hits += 1;
+        self.clock += 1;
+        if self.order.remove(last_use).is_some() {
+            self.order.insert(self.clock, *key);
+        } else {
+            self.pinned.remove(last_use);
+            self.pinned.insert(self.clock, *key);
+        }
+        *last_use = self.clock;
+        self.entries.get(key).map(|(value, _)| value)
+    }
+
+    /// Look up the value without updating the order or the counters.
+    pub fn peek(&self, key: &K) -> Option<&V> {
+        self.entries.get(key).map(|(value, _)| value)
+    }
+
+    /// Insert the value as the most recently used.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let old = self.remove(&key);
+        self.clock += 1;
+        self.order.insert(self.clock, key);
+        self.entries.insert(key, (value, self.clock));
+        old
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let (value, last_use) = self.entries.remove(key)?;
+        if self.order.remove(&last_use).is_none() {
+            self.pinned.remove(&last_use);
+        }
+        Some(value)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.pinned.clear();
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&K, &V) -> bool) {
+        let order = &mut self.order;
+        let pinned = &mut self.pinned;
+        self.entries.retain(|key, (value, last_use)| {
+            let retain = f(key, value);
+            if !retain && order.remove(last_use).is_none() {
+                pinned.remove(last_use);
+            }
+            retain
+        });
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|(key, (value, _))| (key, value))
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.values().map(|(value, _)| value)
+    }
+
+    /// Remove the least recently used entry for which `eviction` returns
+    /// [Eviction::Evict].
+    ///
+    /// Skipped entries are moved to the most recently used end and pinned
+    /// entries are taken out of the order, so that following evictions do not
+    /// scan them again.
+    pub fn evict(&mut self, mut eviction: impl FnMut(&K, &V) -> Eviction) -> Option<(K, V)> {
+        let mut skipped = Vec::new();
+        let mut pinned = Vec::new();
+        let mut evicted = None;
+        for (last_use, key) in self.order.iter() {
+            match eviction(key, &self.entries[key].0) {
+                Eviction::Evict => {
+                    evicted = Some(*last_use);
+                    break;
+                }
+                Eviction::Skip => skipped.push(*last_use),
+                Eviction::Pin => pinned.push(*last_use),
+            }
+        }
+        for last_use in skipped {
+            let key = self.order.remove(&last_use).unwrap();
+            self.clock += 1;
+            self.order.insert(self.clock, key);
+            self.entries.get_mut(&key).unwrap().1 = self.clock;
+        }
+        for last_use in pinned {
+            let key = self.order.remove(&last_use).unwrap();
+            self.pinned.insert(last_use, key);
+        }
+        let key = self.order.remove(&evicted?).unwrap();
+        let (value, _) = self.entries.remove(&key).unwrap();
+        self.stats.evictions += 1;
+        Some((key, value))
+    }
+
+    /// Put all the pinned entries back to the eviction order.
+    pub fn unpin_all(&mut self) {
+        self.order.append(&mut self.pinned);
+    }
+}
+
+impl<K: Hash + Eq + Copy, V> Default for LruCache<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evict_unless(skip: bool) -> Eviction {
+        if skip {
+            Eviction::Skip
+        } else {
+            Eviction::Evict
+        }
+    }
+
+    #[test]
+    fn test_get_and_insert() {
+        let mut cache = LruCache::new();
+        assert!(cache.is_empty());
+        assert_eq!(cache.insert(1, "a"), None);
+        assert_eq!(cache.insert(2, "b"), None);
+        assert_eq!(cache.insert(1, "c"), Some("a"));
+        assert_eq!(cache.len(), 2);
+
+        assert_eq!(cache.get(&1), Some(&"c"));
+        assert_eq!(cache.get(&3), None);
+        assert_eq!(cache.peek(&2), Some(&"b"));
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 1,
+                evictions: 0,
+            }
+        );
+
+        assert_eq!(cache.remove(&1), Some("c"));
+        assert_eq!(cache.remove(&1), None);
+        assert_eq!(cache.iter().collect::<Vec<_>>(), vec![(&2, &"b")]);
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_evict() {
+        let mut cache = LruCache::new();
+        for i in 0..5 {
+            cache.insert(i, i * 10);
+        }
+        cache.get(&0);
+        cache.peek(&1);
+        assert_eq!(cache.evict(|_, _| Eviction::Evict), Some((1, 10)));
+        assert_eq!(cache.evict(|_, _| Eviction::Evict), Some((2, 20)));
+        // Pinned entries are skipped.
+        assert_eq!(cache.evict(|key, _| evict_unless(*key == 3)), Some((4, 40)));
+        assert_eq!(cache.evict(|key, _| evict_unless(*key == 3)), Some((0, 0)));
+        assert_eq!(cache.evict(|_, _| Eviction::Skip), None);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.stats().evictions, 4);
+        assert_eq!(cache.evict(|_, _| Eviction::Evict), Some((3, 30)));
+        assert_eq!(cache.evict(|_, _| Eviction::Evict), None);
+    }
+
+    #[test]
+    fn test_evict_moves_pinned_entries() {
+        let mut cache = LruCache::new();
+        for i in 0..4 {
+            cache.insert(i, ());
+        }
+        assert_eq!(cache.evict(|key, _| evict_unless(*key < 2)), Some((2, ())));
+        // 0 and 1 are moved after 3.
+        assert_eq!(cache.evict(|_, _| Eviction::Evict), Some((3, ())));
+        assert_eq!(cache.evict(|_, _| Eviction::Evict), Some((0, ())));
+    }
+
+    #[test]
+    fn test_evict_pinned_entries() {
+        let mut cache = LruCache::new();
+        for i in 0..4 {
+            cache.insert(i, ());
+        }
+        let pin_even = |key: &i32, _: &()| {
+            if key % 2 == 0 {
+                Eviction::Pin
+            } else {
+                Eviction::Evict
+            }
+        };
+        assert_eq!(cache.evict(pin_even), Some((1, ())));
+        // Pinned entries are not passed to the closure again.
+        let pin_all = |key: &i32, _: &()| {
+            assert_ne!(*key, 0);
+            Eviction::Pin
+        };
+        assert_eq!(cache.evict(pin_all), None);
+        // Pinned entries are still looked up.
+        assert_eq!(cache.get(&2), Some(&()));
+        assert_eq!(cache.len(), 3);
+
+        cache.unpin_all();
+        assert_eq!(cache.evict(|_, _| Eviction::Evict), Some((0, ())));
+        assert_eq!(cache.evict(|_, _| Eviction::Evict), Some((3, ())));
+        assert_eq!(cache.evict(|_, _| Eviction::Evict), Some((2, ())));
+
+        // Pinned entries can be removed.
+        cache.insert(5, ());
+        cache.insert(6, ());
+        cache.insert(7, ());
+        assert_eq!(cache.evict(|_, _| Eviction::Pin), None);
+        assert_eq!(cache.remove(&5), Some(()));
+        cache.retain(|key, _| *key != 6);
+        cache.unpin_all();
+        assert_eq!(cache.evict(|_, _| Eviction::Evict), Some((7, ())));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_evict_cost_with_many_pinned_entries() {
+        // Evicting with more pinned entries than the capacity must not rescan
+        // them on every insertion.
+        let capacity = 10;
+        let mut cache = LruCache::new();
+        let mut n_checked = 0;
+        for i in 0..10000 {
+            cache.insert(i, ());
+            while cache.len() > capacity {
+                let evicted = cache.evict(|_, _| {
+                    n_checked += 1;
+                    Eviction::Pin
+                });
+                if evicted.is_none() {
+                    break;
+                }
+            }
+        }
+        assert_eq!(cache.len(), 10000);
+        assert_eq!(n_checked, 10000);
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut cache = LruCache::new();
+        for i in 0..6 {
+            cache.insert(i, i);
+        }
+        cache.retain(|key, _| key % 2 == 0);
+        assert_eq!(cache.len(), 3);
+        let mut values = cache.values().copied().collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, vec![0, 2, 4]);
+        assert_eq!(cache.evict(|_, _| Eviction::Evict), Some((0, 0)));
+        assert_eq!(cache.evict(|_, _| Eviction::Evict), Some((2, 2)));
+        assert_eq!(cache.evict(|_, _| Eviction::Evict), Some((4, 4)));
+    }
+}
@@ -48,8 +48,7 @@ impl NFFile {
 
         // 使用局部计算的半个间隔来四舍五入
         let index = (delta_ts + half_interval) / self.header.interval as i64;
-        let position = index as usize * self.header.data_length as usize;
-        position
+        index as usize * self.header.data_length as usize
     }
 
     pub fn add_data<T>(&mut self, timestamp: i64, value: &T) {
@@ -95,7 +94,7 @@ impl NFFile {
         let value_size = mem::size_of::<T>();
 
         // 确保有足够的空间
-        let required_size = current_position + values.len() * value_size;
+        let required_size = current_position + std::mem::size_of_val(values);
         if required_size > self.data.len() {
             self.data.resize(required_size, 0);
         }
@@ -139,6 +138,7 @@ pub fn flush_nffile(nf_file: &mut NFFile) -> io::Result<()> {
     let mut file = OpenOptions::new()
         .write(true)
         .create(true)
+        .truncate(false)
         .open(&nf_file.file_path)?;
 
     // 如何解决同时写的问题，可以只flush修改的那部分数据，不去修改别的部分
@@ -181,7 +181,7 @@ pub fn load_nffile(nf_file: &mut NFFile) -> io::Result<()> {
 
 #[cfg(test)]
 mod tests {
-    use crate::utils::abs_path::{get_absolute_path, get_absolute_path_for_data_file};
+    use crate::utils::abs_path::get_absolute_path_for_data_file;
 
     use super::*;
 
@@ -216,14 +216,14 @@ mod tests {
             4,
             Some(get_absolute_path_for_data_file(&file_path)),
         );
-        let _ = load_nffile(&mut loaded_nf_file).expect("Failed to load data from file");
+        load_nffile(&mut loaded_nf_file).expect("Failed to load data from file");
 
         // 重新查询并验证加载的数据
         let loaded_result = loaded_nf_file.query_data::<i32>(1000);
         assert!(loaded_result.is_some());
         assert_eq!(*loaded_result.unwrap(), 12345);
 
-        let mut loaded_nf_file = NFFile::new(
+        let _loaded_nf_file = NFFile::new(
             0,
             1000,
             4,
@@ -265,7 +265,7 @@ mod tests {
             4,
             Some(get_absolute_path_for_data_file(&file_path)),
         );
-        let _ = load_nffile(&mut loaded_nf_file).expect("Failed to load data from file");
+        load_nffile(&mut loaded_nf_file).expect("Failed to load data from file");
 
         // 重新读取并验证加载的数据
         let loaded_result: Vec<&i32> = loaded_nf_file.range_read(1000, 3);
@@ -279,6 +279,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_range_write_and_read_f64() {
         // let header = Header {
         //     start_ts: 0,